@@ -0,0 +1,4 @@
+ALTER TABLE quotes RENAME COLUMN source TO source_reference;
+ALTER TABLE quotes ADD COLUMN source_medium TEXT DEFAULT NULL
+    CHECK (source_medium IN ('in_person', 'chat', 'meeting', 'stream', 'email'));
+ALTER TABLE quotes ADD COLUMN source_location TEXT DEFAULT NULL;
@@ -0,0 +1,16 @@
+ALTER TABLE authors ADD COLUMN avatar TEXT DEFAULT NULL;
+
+CREATE TABLE author_aliases (
+    id                  UUID NOT NULL UNIQUE PRIMARY KEY,
+    author_id           UUID NOT NULL REFERENCES authors(id),
+    alias               TEXT NOT NULL
+);
+CREATE UNIQUE INDEX author_aliases_alias_key ON author_aliases (lower(alias));
+CREATE INDEX author_aliases_author_id_idx ON author_aliases (author_id);
+
+CREATE TABLE author_avatars (
+    author_id           UUID NOT NULL UNIQUE PRIMARY KEY REFERENCES authors(id),
+    content_type        TEXT NOT NULL,
+    data                BYTEA NOT NULL,
+    uploaded            TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
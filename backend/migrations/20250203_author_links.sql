@@ -0,0 +1,40 @@
+ALTER TABLE authors ADD COLUMN user_id UUID DEFAULT NULL UNIQUE REFERENCES users(id);
+
+CREATE TABLE author_claims (
+    id                  UUID NOT NULL UNIQUE PRIMARY KEY,
+    author_id           UUID NOT NULL REFERENCES authors(id),
+    user_id             UUID NOT NULL REFERENCES users(id),
+    status              TEXT NOT NULL DEFAULT 'pending'
+                        CHECK (status IN ('pending', 'approved', 'rejected')),
+    created             TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    decided             TIMESTAMPTZ DEFAULT NULL,
+    decided_by          UUID DEFAULT NULL REFERENCES users(id)
+);
+CREATE UNIQUE INDEX author_claims_pending_key ON author_claims (author_id, user_id)
+    WHERE status = 'pending';
+
+CREATE TABLE notifications (
+    id                  UUID NOT NULL UNIQUE PRIMARY KEY,
+    user_id             UUID NOT NULL REFERENCES users(id),
+    kind                TEXT NOT NULL,
+    quote_id            UUID DEFAULT NULL,
+    details             JSONB NOT NULL DEFAULT '{}',
+    created             TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    read_at             TIMESTAMPTZ DEFAULT NULL
+);
+CREATE INDEX notifications_user_id_idx ON notifications (user_id, created DESC);
+
+CREATE TABLE quote_flags (
+    id                  UUID NOT NULL UNIQUE PRIMARY KEY,
+    quote_id            UUID NOT NULL REFERENCES quotes(id),
+    user_id             UUID NOT NULL REFERENCES users(id),
+    kind                TEXT NOT NULL CHECK (kind IN ('review', 'hide')),
+    reason              TEXT DEFAULT NULL,
+    status              TEXT NOT NULL DEFAULT 'open'
+                        CHECK (status IN ('open', 'accepted', 'dismissed')),
+    created             TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    resolved            TIMESTAMPTZ DEFAULT NULL,
+    resolved_by         UUID DEFAULT NULL REFERENCES users(id)
+);
+CREATE UNIQUE INDEX quote_flags_open_key ON quote_flags (quote_id, user_id)
+    WHERE status = 'open';
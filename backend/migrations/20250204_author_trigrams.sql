@@ -0,0 +1,7 @@
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX authors_fullname_trgm_idx ON authors USING GIN (fullname gin_trgm_ops);
+CREATE INDEX authors_codename_trgm_idx ON authors USING GIN (codename gin_trgm_ops);
+CREATE INDEX author_aliases_alias_trgm_idx ON author_aliases USING GIN (alias gin_trgm_ops);
+
+CREATE INDEX lines_author_id_idx ON lines (author_id);
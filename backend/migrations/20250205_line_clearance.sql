@@ -0,0 +1 @@
+ALTER TABLE lines ADD COLUMN clearance BIGINT NOT NULL DEFAULT 0;
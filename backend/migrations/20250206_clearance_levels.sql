@@ -0,0 +1,22 @@
+CREATE TABLE clearance_levels (
+    level               SMALLINT NOT NULL UNIQUE PRIMARY KEY CHECK (level BETWEEN 0 AND 255),
+    name                TEXT NOT NULL,
+    description         TEXT DEFAULT NULL,
+    colour              TEXT DEFAULT NULL
+);
+CREATE UNIQUE INDEX clearance_levels_name_key ON clearance_levels (lower(name));
+
+INSERT INTO clearance_levels (level, name, description) VALUES
+    (0, 'Public', 'Visible to everyone, including visitors who are not logged in.'),
+    (1, 'Member', 'The clearance new accounts start with.'),
+    (255, 'Infradmin', 'Reserved for the infrastructure administrator.');
+
+-- levels already in use stay valid until an admin names them
+INSERT INTO clearance_levels (level, name)
+SELECT DISTINCT level, 'Level ' || level FROM (
+    SELECT clearance AS level FROM quotes
+    UNION SELECT clearance FROM lines
+    UNION SELECT clearance FROM users
+) AS used
+WHERE level BETWEEN 0 AND 255
+ON CONFLICT (level) DO NOTHING;
@@ -0,0 +1,57 @@
+CREATE TABLE user_groups (
+    id                  UUID NOT NULL UNIQUE PRIMARY KEY,
+    name                TEXT NOT NULL,
+    description         TEXT DEFAULT NULL,
+    created             TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE UNIQUE INDEX user_groups_name_key ON user_groups (lower(name));
+
+CREATE TABLE group_members (
+    group_id            UUID NOT NULL REFERENCES user_groups(id),
+    user_id             UUID NOT NULL REFERENCES users(id),
+    PRIMARY KEY (group_id, user_id)
+);
+CREATE INDEX group_members_user_id_idx ON group_members (user_id);
+
+CREATE TABLE quote_groups (
+    quote_id            UUID NOT NULL REFERENCES quotes(id),
+    group_id            UUID NOT NULL REFERENCES user_groups(id),
+    PRIMARY KEY (quote_id, group_id)
+);
+CREATE INDEX quote_groups_group_id_idx ON quote_groups (group_id);
+
+-- a quote without groups is shared with everyone; otherwise the viewer must be in
+-- one of its groups, or be linked to one of its authors. clearance is checked separately.
+CREATE FUNCTION quote_shared_with(quote UUID, viewer UUID) RETURNS BOOLEAN
+LANGUAGE SQL STABLE AS $$
+    SELECT NOT EXISTS (SELECT 1 FROM quote_groups WHERE quote_id = quote)
+        OR EXISTS (
+            SELECT 1 FROM quote_groups
+            INNER JOIN group_members ON group_members.group_id = quote_groups.group_id
+            WHERE quote_groups.quote_id = quote AND group_members.user_id = viewer
+        )
+        OR EXISTS (
+            SELECT 1 FROM lines
+            INNER JOIN authors ON authors.id = lines.author_id
+            WHERE lines.quote_id = quote AND authors.user_id = viewer
+        )
+$$;
+
+-- whether a quote shows up in listings for the viewer: shared with them, and either
+-- within their clearance or with a line by their linked author. anyone not logged in
+-- is a NULL viewer with clearance 0.
+CREATE FUNCTION quote_visible_to(quote UUID, viewer_clearance BIGINT, viewer UUID) RETURNS BOOLEAN
+LANGUAGE SQL STABLE AS $$
+    SELECT EXISTS (
+        SELECT 1 FROM quotes
+        WHERE quotes.id = quote
+        AND (
+            quotes.clearance <= viewer_clearance
+            OR EXISTS (
+                SELECT 1 FROM lines
+                INNER JOIN authors ON authors.id = lines.author_id
+                WHERE lines.quote_id = quote AND authors.user_id = viewer
+            )
+        )
+    ) AND quote_shared_with(quote, viewer)
+$$;
@@ -0,0 +1,25 @@
+-- quotes that already exist were published instantly
+ALTER TABLE quotes
+    ADD COLUMN status TEXT NOT NULL DEFAULT 'published'
+        CHECK (status IN ('draft', 'pending', 'published', 'rejected', 'archived')),
+    ADD COLUMN submitted_by UUID DEFAULT NULL REFERENCES users(id),
+    ADD COLUMN review_note TEXT DEFAULT NULL;
+CREATE INDEX quotes_status_idx ON quotes (status);
+CREATE INDEX quotes_submitted_by_idx ON quotes (submitted_by);
+
+-- only published quotes show up in listings
+CREATE OR REPLACE FUNCTION quote_visible_to(quote UUID, viewer_clearance BIGINT, viewer UUID) RETURNS BOOLEAN
+LANGUAGE SQL STABLE AS $$
+    SELECT EXISTS (
+        SELECT 1 FROM quotes
+        WHERE quotes.id = quote AND quotes.status = 'published'
+        AND (
+            quotes.clearance <= viewer_clearance
+            OR EXISTS (
+                SELECT 1 FROM lines
+                INNER JOIN authors ON authors.id = lines.author_id
+                WHERE lines.quote_id = quote AND authors.user_id = viewer
+            )
+        )
+    ) AND quote_shared_with(quote, viewer)
+$$;
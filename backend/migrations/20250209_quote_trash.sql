@@ -0,0 +1,21 @@
+ALTER TABLE quotes
+    ADD COLUMN deleted_at TIMESTAMPTZ DEFAULT NULL,
+    ADD COLUMN deleted_by UUID DEFAULT NULL REFERENCES users(id);
+CREATE INDEX quotes_deleted_at_idx ON quotes (deleted_at) WHERE deleted_at IS NOT NULL;
+
+-- trashed quotes don't show up in listings
+CREATE OR REPLACE FUNCTION quote_visible_to(quote UUID, viewer_clearance BIGINT, viewer UUID) RETURNS BOOLEAN
+LANGUAGE SQL STABLE AS $$
+    SELECT EXISTS (
+        SELECT 1 FROM quotes
+        WHERE quotes.id = quote AND quotes.status = 'published' AND quotes.deleted_at IS NULL
+        AND (
+            quotes.clearance <= viewer_clearance
+            OR EXISTS (
+                SELECT 1 FROM lines
+                INNER JOIN authors ON authors.id = lines.author_id
+                WHERE lines.quote_id = quote AND authors.user_id = viewer
+            )
+        )
+    ) AND quote_shared_with(quote, viewer)
+$$;
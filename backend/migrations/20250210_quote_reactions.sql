@@ -0,0 +1,12 @@
+-- one row per user and quote, holding both their emoji reaction and their vote
+CREATE TABLE quote_reactions (
+    quote_id            UUID NOT NULL REFERENCES quotes(id),
+    user_id             UUID NOT NULL REFERENCES users(id),
+    reaction            TEXT DEFAULT NULL
+        CHECK (reaction IN ('laugh', 'heart', 'fire', 'skull', 'eyes', 'clap')),
+    vote                SMALLINT DEFAULT NULL CHECK (vote IN (-1, 1)),
+    voted_at            TIMESTAMPTZ DEFAULT NULL,
+    PRIMARY KEY (quote_id, user_id)
+);
+CREATE INDEX quote_reactions_user_id_idx ON quote_reactions (user_id);
+CREATE INDEX quote_reactions_voted_at_idx ON quote_reactions (voted_at) WHERE vote IS NOT NULL;
@@ -0,0 +1,14 @@
+-- replies point at a top-level comment of the same quote; deeper nesting is refused by the app
+CREATE TABLE quote_comments (
+    id                  UUID NOT NULL UNIQUE PRIMARY KEY,
+    quote_id            UUID NOT NULL REFERENCES quotes(id),
+    parent_id           UUID DEFAULT NULL REFERENCES quote_comments(id),
+    user_id             UUID NOT NULL REFERENCES users(id),
+    content             TEXT NOT NULL,
+    mentions            UUID[] NOT NULL DEFAULT '{}',
+    created             TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    edited              TIMESTAMPTZ DEFAULT NULL
+);
+CREATE INDEX quote_comments_quote_id_idx ON quote_comments (quote_id, created) WHERE parent_id IS NULL;
+CREATE INDEX quote_comments_parent_id_idx ON quote_comments (parent_id);
+CREATE INDEX quote_comments_user_id_idx ON quote_comments (user_id);
@@ -0,0 +1,37 @@
+CREATE TABLE quote_favourites (
+    user_id             UUID NOT NULL REFERENCES users(id),
+    quote_id            UUID NOT NULL REFERENCES quotes(id),
+    created             TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, quote_id)
+);
+CREATE INDEX quote_favourites_quote_id_idx ON quote_favourites (quote_id);
+
+CREATE TABLE collections (
+    id                  UUID NOT NULL UNIQUE PRIMARY KEY,
+    owner_id            UUID NOT NULL REFERENCES users(id),
+    name                TEXT NOT NULL,
+    description         TEXT DEFAULT NULL,
+    visibility          TEXT NOT NULL DEFAULT 'private'
+        CHECK (visibility IN ('private', 'shared', 'public_link')),
+    -- only set while the visibility is public_link
+    link_token          TEXT DEFAULT NULL UNIQUE,
+    created             TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated             TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE UNIQUE INDEX collections_name_key ON collections (owner_id, lower(name));
+
+CREATE TABLE collection_items (
+    collection_id       UUID NOT NULL REFERENCES collections(id),
+    quote_id            UUID NOT NULL REFERENCES quotes(id),
+    position            INTEGER NOT NULL,
+    added               TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (collection_id, quote_id)
+);
+CREATE INDEX collection_items_quote_id_idx ON collection_items (quote_id);
+
+CREATE TABLE collection_shares (
+    collection_id       UUID NOT NULL REFERENCES collections(id),
+    user_id             UUID NOT NULL REFERENCES users(id),
+    PRIMARY KEY (collection_id, user_id)
+);
+CREATE INDEX collection_shares_user_id_idx ON collection_shares (user_id);
@@ -0,0 +1,7 @@
+-- one row per calendar day in the configured timezone, chosen on first request
+CREATE TABLE quote_of_the_day (
+    day                 DATE NOT NULL UNIQUE PRIMARY KEY,
+    quote_id            UUID NOT NULL REFERENCES quotes(id),
+    chosen              TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE INDEX quote_of_the_day_quote_id_idx ON quote_of_the_day (quote_id);
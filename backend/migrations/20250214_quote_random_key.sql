@@ -0,0 +1,5 @@
+-- a fixed random position per quote, so a random pick can seek the index
+-- instead of sorting the whole table
+ALTER TABLE quotes ADD COLUMN random_key DOUBLE PRECISION NOT NULL DEFAULT random();
+CREATE INDEX quotes_random_key_idx ON quotes (random_key)
+    WHERE status = 'published' AND deleted_at IS NULL;
@@ -0,0 +1,18 @@
+CREATE TABLE quote_share_links (
+    id                  UUID NOT NULL UNIQUE PRIMARY KEY,
+    quote_id            UUID NOT NULL REFERENCES quotes(id),
+    created_by          UUID NOT NULL REFERENCES users(id),
+    -- hashed like session tokens; the plain token is only shown once on creation
+    token               TEXT NOT NULL UNIQUE,
+    created             TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expiry              TIMESTAMPTZ DEFAULT NULL,
+    revoked             TIMESTAMPTZ DEFAULT NULL
+);
+CREATE INDEX quote_share_links_quote_id_idx ON quote_share_links (quote_id);
+CREATE INDEX quote_share_links_created_by_idx ON quote_share_links (created_by);
+
+CREATE TABLE quote_share_link_views (
+    link_id             UUID NOT NULL REFERENCES quote_share_links(id),
+    viewed              TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE INDEX quote_share_link_views_link_id_idx ON quote_share_link_views (link_id);
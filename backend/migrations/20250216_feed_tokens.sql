@@ -0,0 +1,6 @@
+CREATE TABLE feed_tokens (
+    user_id             UUID NOT NULL UNIQUE PRIMARY KEY REFERENCES users(id),
+    -- hashed like session tokens; the plain token is only shown once on creation
+    token               TEXT NOT NULL UNIQUE,
+    created             TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,12 @@
+-- free-form labels such as "work" or "dnd", stored trimmed and lowercase
+CREATE TABLE quote_tags (
+    quote_id            UUID NOT NULL REFERENCES quotes(id),
+    tag                 TEXT NOT NULL,
+    PRIMARY KEY (quote_id, tag)
+);
+CREATE INDEX quote_tags_tag_idx ON quote_tags (tag);
+
+-- a quote of the day is kept per tag as well; '' is the pick among all public quotes
+ALTER TABLE quote_of_the_day ADD COLUMN tag TEXT NOT NULL DEFAULT '';
+ALTER TABLE quote_of_the_day DROP CONSTRAINT quote_of_the_day_pkey;
+ALTER TABLE quote_of_the_day ADD PRIMARY KEY (day, tag);
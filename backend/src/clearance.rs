@@ -0,0 +1,374 @@
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use utoipa::ToSchema;
+
+use crate::omnierror::OmniError;
+
+const NAME_LEN_BOUND_UPPER: usize = 32;
+const DESCRIPTION_LEN_BOUND_UPPER: usize = 256;
+
+/// A name for a clearance number; quotes, lines and users may only use defined levels.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ClearanceLevel {
+    pub level: u8,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// A `#rrggbb` colour for badges
+    #[serde(default)]
+    pub colour: Option<String>,
+}
+
+/// Empty `description` or `colour` strings clear the field.
+#[derive(Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ClearanceLevelPatch {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub colour: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ClearanceValidityError {
+    #[error("Clearance level {0} is not defined.")]
+    Undefined(u8),
+    #[error("Clearance level names must not be empty and at most {NAME_LEN_BOUND_UPPER} characters long.")]
+    NameLengthInvalid,
+    #[error("Clearance level descriptions must be at most {DESCRIPTION_LEN_BOUND_UPPER} characters long.")]
+    DescriptionTooLong,
+    #[error("Clearance level colours must look like #rrggbb.")]
+    ColourInvalid,
+    #[error("Clearance level {0} is still used by quotes, lines or users.")]
+    InUse(u8),
+    #[error("The public clearance level cannot be deleted.")]
+    PublicUndeletable,
+}
+
+impl ClearanceValidityError {
+    pub fn code(&self) -> &'static str {
+        use ClearanceValidityError as CV;
+        match self {
+            CV::Undefined(_) => "clearance_undefined",
+            CV::NameLengthInvalid => "clearance_name_length_invalid",
+            CV::DescriptionTooLong => "clearance_description_too_long",
+            CV::ColourInvalid => "clearance_colour_invalid",
+            CV::InUse(_) => "clearance_in_use",
+            CV::PublicUndeletable => "clearance_public_undeletable",
+        }
+    }
+    pub fn field(&self) -> &'static str {
+        use ClearanceValidityError as CV;
+        match self {
+            CV::Undefined(_) => "clearance",
+            CV::NameLengthInvalid => "name",
+            CV::DescriptionTooLong => "description",
+            CV::ColourInvalid => "colour",
+            CV::InUse(_) | CV::PublicUndeletable => "level",
+        }
+    }
+}
+
+impl ClearanceLevel {
+    pub fn is_valid(&self) -> Result<(), ClearanceValidityError> {
+        is_valid_name(&self.name)?;
+        if let Some(description) = &self.description {
+            is_valid_description(description)?;
+        }
+        if let Some(colour) = &self.colour {
+            is_valid_colour(colour)?;
+        }
+        Ok(())
+    }
+    pub async fn get_all(pool: &PgPool) -> Result<Vec<ClearanceLevel>, OmniError> {
+        match sqlx::query!(
+            "SELECT level, name, description, colour FROM clearance_levels ORDER BY level"
+        )
+        .fetch_all(pool)
+        .await
+        {
+            Ok(rows) => Ok(rows
+                .into_iter()
+                .map(|r| ClearanceLevel {
+                    level: r.level as u8,
+                    name: r.name,
+                    description: r.description,
+                    colour: r.colour,
+                })
+                .collect()),
+            Err(e) => Err(e)?,
+        }
+    }
+    pub async fn get_by_level(
+        level: u8,
+        pool: &PgPool,
+    ) -> Result<Option<ClearanceLevel>, OmniError> {
+        match sqlx::query!(
+            "SELECT level, name, description, colour FROM clearance_levels WHERE level = $1",
+            level as i16
+        )
+        .fetch_optional(pool)
+        .await
+        {
+            Ok(row) => Ok(row.map(|r| ClearanceLevel {
+                level: r.level as u8,
+                name: r.name,
+                description: r.description,
+                colour: r.colour,
+            })),
+            Err(e) => Err(e)?,
+        }
+    }
+    /// Fails with the first level that has no definition.
+    pub async fn ensure_defined(levels: &[u8], pool: &PgPool) -> Result<(), OmniError> {
+        let levels: Vec<i16> = levels.iter().map(|l| *l as i16).collect();
+        match sqlx::query_scalar!(
+            r#"
+            SELECT wanted AS "level!" FROM UNNEST($1::smallint[]) AS wanted
+            WHERE wanted NOT IN (SELECT level FROM clearance_levels)
+            ORDER BY wanted LIMIT 1
+            "#,
+            &levels
+        )
+        .fetch_optional(pool)
+        .await
+        {
+            Ok(Some(level)) => Err(ClearanceValidityError::Undefined(level as u8))?,
+            Ok(None) => Ok(()),
+            Err(e) => Err(e)?,
+        }
+    }
+    pub async fn name_of(level: u8, pool: &PgPool) -> Result<Option<String>, OmniError> {
+        match sqlx::query_scalar!(
+            "SELECT name FROM clearance_levels WHERE level = $1",
+            level as i16
+        )
+        .fetch_optional(pool)
+        .await
+        {
+            Ok(name) => Ok(name),
+            Err(e) => Err(e)?,
+        }
+    }
+    pub async fn create(level: ClearanceLevel, pool: &PgPool) -> Result<ClearanceLevel, OmniError> {
+        match sqlx::query!(
+            "INSERT INTO clearance_levels (level, name, description, colour) VALUES ($1, $2, $3, $4)",
+            level.level as i16,
+            level.name,
+            level.description,
+            level.colour
+        )
+        .execute(pool)
+        .await
+        {
+            Ok(_) => Ok(level),
+            Err(e) => Err(e)?,
+        }
+    }
+    pub async fn patch(
+        self,
+        patch: ClearanceLevelPatch,
+        pool: &PgPool,
+    ) -> Result<ClearanceLevel, OmniError> {
+        let level = ClearanceLevel {
+            level: self.level,
+            name: patch.name.unwrap_or(self.name),
+            description: match patch.description {
+                Some(d) => Some(d).filter(|d| !d.is_empty()),
+                None => self.description,
+            },
+            colour: match patch.colour {
+                Some(c) => Some(c).filter(|c| !c.is_empty()),
+                None => self.colour,
+            },
+        };
+        level.is_valid()?;
+        match sqlx::query!(
+            "UPDATE clearance_levels SET name = $1, description = $2, colour = $3 WHERE level = $4",
+            level.name,
+            level.description,
+            level.colour,
+            level.level as i16
+        )
+        .execute(pool)
+        .await
+        {
+            Ok(_) => Ok(level),
+            Err(e) => Err(e)?,
+        }
+    }
+    pub async fn destroy(self, pool: &PgPool) -> Result<(), OmniError> {
+        if self.level == 0 {
+            return Err(ClearanceValidityError::PublicUndeletable)?;
+        }
+        let in_use = sqlx::query_scalar!(
+            r#"
+            SELECT
+                EXISTS (SELECT 1 FROM quotes WHERE clearance = $1)
+                OR EXISTS (SELECT 1 FROM lines WHERE clearance = $1)
+                OR EXISTS (SELECT 1 FROM users WHERE clearance = $1) AS "in_use!"
+            "#,
+            self.level as i16
+        )
+        .fetch_one(pool)
+        .await?;
+        if in_use {
+            return Err(ClearanceValidityError::InUse(self.level))?;
+        }
+        match sqlx::query!(
+            "DELETE FROM clearance_levels WHERE level = $1",
+            self.level as i16
+        )
+        .execute(pool)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e)?,
+        }
+    }
+}
+
+fn is_valid_name(name: &str) -> Result<(), ClearanceValidityError> {
+    match name.trim().is_empty() || name.chars().count() > NAME_LEN_BOUND_UPPER {
+        true => Err(ClearanceValidityError::NameLengthInvalid),
+        false => Ok(()),
+    }
+}
+
+fn is_valid_description(description: &str) -> Result<(), ClearanceValidityError> {
+    match description.chars().count() > DESCRIPTION_LEN_BOUND_UPPER {
+        true => Err(ClearanceValidityError::DescriptionTooLong),
+        false => Ok(()),
+    }
+}
+
+fn is_valid_colour(colour: &str) -> Result<(), ClearanceValidityError> {
+    let hex = colour.strip_prefix('#').unwrap_or_default();
+    match hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        true => Ok(()),
+        false => Err(ClearanceValidityError::ColourInvalid),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn level(level: u8, name: &str) -> ClearanceLevel {
+        ClearanceLevel {
+            level,
+            name: name.to_string(),
+            description: None,
+            colour: None,
+        }
+    }
+
+    fn patch(description: Option<&str>, colour: Option<&str>) -> ClearanceLevelPatch {
+        ClearanceLevelPatch {
+            name: None,
+            description: description.map(str::to_string),
+            colour: colour.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn names_descriptions_and_colours_are_checked() {
+        assert!(level(3, "Friends").is_valid().is_ok());
+        assert!(matches!(
+            level(3, " ").is_valid(),
+            Err(ClearanceValidityError::NameLengthInvalid)
+        ));
+        assert!(matches!(
+            level(3, &"x".repeat(NAME_LEN_BOUND_UPPER + 1)).is_valid(),
+            Err(ClearanceValidityError::NameLengthInvalid)
+        ));
+        let mut described = level(3, "Friends");
+        described.description = Some("x".repeat(DESCRIPTION_LEN_BOUND_UPPER + 1));
+        assert!(matches!(
+            described.is_valid(),
+            Err(ClearanceValidityError::DescriptionTooLong)
+        ));
+        for (colour, valid) in [
+            ("#a0B1c2", true),
+            ("a0b1c2", false),
+            ("#a0b1c", false),
+            ("#a0b1cg", false),
+        ] {
+            assert_eq!(is_valid_colour(colour).is_ok(), valid, "{colour}");
+        }
+    }
+
+    #[sqlx::test]
+    async fn levels_must_be_defined(pool: PgPool) {
+        assert_eq!(
+            ClearanceLevel::name_of(1, &pool).await.unwrap().as_deref(),
+            Some("Member")
+        );
+        assert_eq!(ClearanceLevel::name_of(7, &pool).await.unwrap(), None);
+        assert!(ClearanceLevel::ensure_defined(&[0, 1, 255], &pool)
+            .await
+            .is_ok());
+        let res = ClearanceLevel::ensure_defined(&[9, 1, 7], &pool).await;
+        assert!(matches!(
+            res,
+            Err(OmniError::ClearanceValidityError(
+                ClearanceValidityError::Undefined(7)
+            ))
+        ));
+    }
+
+    #[sqlx::test]
+    async fn empty_patch_values_clear_the_field(pool: PgPool) {
+        let mut friends = level(3, "Friends");
+        friends.description = Some("Close friends".into());
+        let friends = ClearanceLevel::create(friends, &pool).await.unwrap();
+        let friends = friends
+            .patch(patch(Some(""), Some("#00ff00")), &pool)
+            .await
+            .unwrap();
+        let stored = ClearanceLevel::get_by_level(3, &pool)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.description, None);
+        assert_eq!(stored.colour.as_deref(), Some("#00ff00"));
+        assert!(friends
+            .patch(patch(None, Some("green")), &pool)
+            .await
+            .is_err());
+    }
+
+    #[sqlx::test]
+    async fn only_unused_levels_can_be_deleted(pool: PgPool) {
+        let public = ClearanceLevel::get_by_level(0, &pool)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(matches!(
+            public.destroy(&pool).await,
+            Err(OmniError::ClearanceValidityError(
+                ClearanceValidityError::PublicUndeletable
+            ))
+        ));
+
+        let friends = ClearanceLevel::create(level(3, "Friends"), &pool)
+            .await
+            .unwrap();
+        testing::user("friend", 3, &[], &pool).await;
+        assert!(matches!(
+            friends.clone().destroy(&pool).await,
+            Err(OmniError::ClearanceValidityError(
+                ClearanceValidityError::InUse(3)
+            ))
+        ));
+        let family = ClearanceLevel::create(level(4, "Family"), &pool)
+            .await
+            .unwrap();
+        family.destroy(&pool).await.unwrap();
+        assert!(ClearanceLevel::get_by_level(4, &pool)
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
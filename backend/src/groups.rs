@@ -0,0 +1,330 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{omnierror::OmniError, user::User};
+
+const NAME_LEN_BOUND_UPPER: usize = 48;
+const DESCRIPTION_LEN_BOUND_UPPER: usize = 256;
+
+/// A circle of users; quotes restricted to groups are only shown to their members.
+#[derive(Serialize, ToSchema)]
+pub struct Group {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub created: DateTime<Utc>,
+    pub member_count: u32,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct NewGroup {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// An empty `description` string clears it.
+#[derive(Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct GroupPatch {
+    pub name: Option<String>,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum GroupValidityError {
+    #[error("Group names must not be empty and at most {NAME_LEN_BOUND_UPPER} characters long.")]
+    NameLengthInvalid,
+    #[error("Group descriptions must be at most {DESCRIPTION_LEN_BOUND_UPPER} characters long.")]
+    DescriptionTooLong,
+    #[error("Group {0} does not exist.")]
+    Undefined(Uuid),
+}
+
+impl GroupValidityError {
+    pub fn code(&self) -> &'static str {
+        use GroupValidityError as GV;
+        match self {
+            GV::NameLengthInvalid => "group_name_length_invalid",
+            GV::DescriptionTooLong => "group_description_too_long",
+            GV::Undefined(_) => "group_undefined",
+        }
+    }
+    pub fn field(&self) -> &'static str {
+        use GroupValidityError as GV;
+        match self {
+            GV::NameLengthInvalid => "name",
+            GV::DescriptionTooLong => "description",
+            GV::Undefined(_) => "groups",
+        }
+    }
+}
+
+struct GroupRow {
+    id: Uuid,
+    name: String,
+    description: Option<String>,
+    created: DateTime<Utc>,
+    member_count: i64,
+}
+
+impl From<GroupRow> for Group {
+    fn from(row: GroupRow) -> Self {
+        Group {
+            id: row.id,
+            name: row.name,
+            description: row.description,
+            created: row.created,
+            member_count: row.member_count as u32,
+        }
+    }
+}
+
+impl NewGroup {
+    pub fn is_valid(&self) -> Result<(), GroupValidityError> {
+        is_valid_name(&self.name)?;
+        if let Some(description) = &self.description {
+            is_valid_description(description)?;
+        }
+        Ok(())
+    }
+}
+
+impl Group {
+    pub async fn get_all(pool: &PgPool) -> Result<Vec<Group>, OmniError> {
+        match sqlx::query_as!(
+            GroupRow,
+            r#"
+            SELECT
+                user_groups.id, user_groups.name, user_groups.description, user_groups.created,
+                COUNT(group_members.user_id) AS "member_count!"
+            FROM user_groups
+            LEFT JOIN group_members ON group_members.group_id = user_groups.id
+            GROUP BY user_groups.id ORDER BY user_groups.name
+            "#
+        )
+        .fetch_all(pool)
+        .await
+        {
+            Ok(rows) => Ok(rows.into_iter().map(Group::from).collect()),
+            Err(e) => Err(e)?,
+        }
+    }
+    pub async fn get_joined_by(user_id: &Uuid, pool: &PgPool) -> Result<Vec<Group>, OmniError> {
+        match sqlx::query_as!(
+            GroupRow,
+            r#"
+            SELECT
+                user_groups.id, user_groups.name, user_groups.description, user_groups.created,
+                COUNT(group_members.user_id) AS "member_count!"
+            FROM user_groups
+            LEFT JOIN group_members ON group_members.group_id = user_groups.id
+            WHERE user_groups.id IN (SELECT group_id FROM group_members WHERE user_id = $1)
+            GROUP BY user_groups.id ORDER BY user_groups.name
+            "#,
+            user_id
+        )
+        .fetch_all(pool)
+        .await
+        {
+            Ok(rows) => Ok(rows.into_iter().map(Group::from).collect()),
+            Err(e) => Err(e)?,
+        }
+    }
+    pub async fn get_by_id(id: &Uuid, pool: &PgPool) -> Result<Option<Group>, OmniError> {
+        match sqlx::query_as!(
+            GroupRow,
+            r#"
+            SELECT
+                user_groups.id, user_groups.name, user_groups.description, user_groups.created,
+                COUNT(group_members.user_id) AS "member_count!"
+            FROM user_groups
+            LEFT JOIN group_members ON group_members.group_id = user_groups.id
+            WHERE user_groups.id = $1
+            GROUP BY user_groups.id
+            "#,
+            id
+        )
+        .fetch_optional(pool)
+        .await
+        {
+            Ok(row) => Ok(row.map(Group::from)),
+            Err(e) => Err(e)?,
+        }
+    }
+    /// Ids of the groups the viewer belongs to; none for anonymous viewers.
+    pub async fn ids_of(viewer: Option<&User>, pool: &PgPool) -> Result<Vec<Uuid>, OmniError> {
+        let user = match viewer {
+            Some(user) => user,
+            None => return Ok(vec![]),
+        };
+        match sqlx::query_scalar!(
+            "SELECT group_id FROM group_members WHERE user_id = $1",
+            user.id
+        )
+        .fetch_all(pool)
+        .await
+        {
+            Ok(ids) => Ok(ids),
+            Err(e) => Err(e)?,
+        }
+    }
+    /// Fails with the first id that is not a group.
+    pub async fn ensure_exist(ids: &[Uuid], pool: &PgPool) -> Result<(), OmniError> {
+        match sqlx::query_scalar!(
+            r#"
+            SELECT wanted AS "id!" FROM UNNEST($1::uuid[]) AS wanted
+            WHERE wanted NOT IN (SELECT id FROM user_groups)
+            LIMIT 1
+            "#,
+            ids
+        )
+        .fetch_optional(pool)
+        .await
+        {
+            Ok(Some(id)) => Err(GroupValidityError::Undefined(id))?,
+            Ok(None) => Ok(()),
+            Err(e) => Err(e)?,
+        }
+    }
+    pub async fn create(group: NewGroup, pool: &PgPool) -> Result<Group, OmniError> {
+        let description = group.description.filter(|d| !d.is_empty());
+        match sqlx::query_as!(
+            GroupRow,
+            r#"
+            INSERT INTO user_groups (id, name, description) VALUES ($1, $2, $3)
+            RETURNING id, name, description, created, 0::BIGINT AS "member_count!"
+            "#,
+            Uuid::now_v7(),
+            group.name,
+            description
+        )
+        .fetch_one(pool)
+        .await
+        {
+            Ok(row) => Ok(row.into()),
+            Err(e) => Err(e)?,
+        }
+    }
+    pub async fn patch(mut self, patch: GroupPatch, pool: &PgPool) -> Result<Group, OmniError> {
+        if let Some(name) = patch.name {
+            is_valid_name(&name)?;
+            self.name = name;
+        }
+        if let Some(description) = patch.description {
+            is_valid_description(&description)?;
+            self.description = Some(description).filter(|d| !d.is_empty());
+        }
+        match sqlx::query!(
+            "UPDATE user_groups SET name = $1, description = $2 WHERE id = $3",
+            self.name,
+            self.description,
+            self.id
+        )
+        .execute(pool)
+        .await
+        {
+            Ok(_) => Ok(self),
+            Err(e) => Err(e)?,
+        }
+    }
+    /// Refused while quotes are restricted to the group, since dropping it
+    /// could make them visible to everyone.
+    pub async fn destroy(self, pool: &PgPool) -> Result<(), OmniError> {
+        let quotes = sqlx::query_scalar!(
+            "SELECT quote_id FROM quote_groups WHERE group_id = $1 ORDER BY quote_id",
+            self.id
+        )
+        .fetch_all(pool)
+        .await?;
+        if !quotes.is_empty() {
+            return Err(OmniError::GroupInUse(quotes));
+        }
+
+        let mut tr = pool.begin().await?;
+        let destroyed = async {
+            sqlx::query!("DELETE FROM group_members WHERE group_id = $1", self.id)
+                .execute(&mut *tr)
+                .await?;
+            sqlx::query!("DELETE FROM user_groups WHERE id = $1", self.id)
+                .execute(&mut *tr)
+                .await?;
+            Ok::<(), OmniError>(())
+        }
+        .await;
+        match destroyed {
+            Ok(()) => Ok(tr.commit().await?),
+            Err(e) => {
+                tr.rollback().await?;
+                Err(e)
+            }
+        }
+    }
+    pub async fn get_members(&self, pool: &PgPool) -> Result<Vec<User>, OmniError> {
+        User::get_in_group(&self.id, pool).await
+    }
+    pub async fn has_member(&self, user_id: &Uuid, pool: &PgPool) -> Result<bool, OmniError> {
+        match sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM group_members WHERE group_id = $1 AND user_id = $2
+            ) AS "exists!"
+            "#,
+            self.id,
+            user_id
+        )
+        .fetch_one(pool)
+        .await
+        {
+            Ok(exists) => Ok(exists),
+            Err(e) => Err(e)?,
+        }
+    }
+    pub async fn add_member(&self, user_id: &Uuid, pool: &PgPool) -> Result<(), OmniError> {
+        match sqlx::query!(
+            r#"
+            INSERT INTO group_members (group_id, user_id) VALUES ($1, $2)
+            ON CONFLICT (group_id, user_id) DO NOTHING
+            "#,
+            self.id,
+            user_id
+        )
+        .execute(pool)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e)?,
+        }
+    }
+    pub async fn remove_member(&self, user_id: &Uuid, pool: &PgPool) -> Result<(), OmniError> {
+        match sqlx::query!(
+            "DELETE FROM group_members WHERE group_id = $1 AND user_id = $2",
+            self.id,
+            user_id
+        )
+        .execute(pool)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e)?,
+        }
+    }
+}
+
+fn is_valid_name(name: &str) -> Result<(), GroupValidityError> {
+    match name.trim().is_empty() || name.chars().count() > NAME_LEN_BOUND_UPPER {
+        true => Err(GroupValidityError::NameLengthInvalid),
+        false => Ok(()),
+    }
+}
+
+fn is_valid_description(description: &str) -> Result<(), GroupValidityError> {
+    match description.chars().count() > DESCRIPTION_LEN_BOUND_UPPER {
+        true => Err(GroupValidityError::DescriptionTooLong),
+        false => Ok(()),
+    }
+}
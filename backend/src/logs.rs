@@ -0,0 +1,87 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::{PgExecutor, PgPool};
+use strum::AsRefStr;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::omnierror::OmniError;
+
+/// An audit log entry; `details` is free-form per action.
+#[derive(Serialize, ToSchema)]
+pub struct Log {
+    pub id: Uuid,
+    pub timestamp: DateTime<Utc>,
+    pub actor_id: Uuid,
+    pub subject_id: Uuid,
+    pub action: String,
+    pub details: Value,
+}
+
+#[derive(Debug, Clone, Copy, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum LogAction {
+    AuthorsMerged,
+    AuthorsDeleted,
+    AuthorsLinked,
+    AuthorsUnlinked,
+    AuthorsClaimRejected,
+    QuotesHidden,
+    QuotesGroupsChanged,
+    QuotesTagsChanged,
+    QuotesStatusChanged,
+    QuotesTrashed,
+    QuotesRestored,
+    QuotesPurged,
+    CommentsDeleted,
+}
+
+const RECENT_LOGS_LIMIT: i64 = 100;
+
+impl Log {
+    /// Takes any executor so that entries can be written inside the transaction they describe.
+    pub async fn record<'e, E: PgExecutor<'e>>(
+        actor_id: &Uuid,
+        subject_id: &Uuid,
+        action: LogAction,
+        details: Value,
+        executor: E,
+    ) -> Result<(), OmniError> {
+        match sqlx::query!(
+            "INSERT INTO logs (id, actor_id, subject_id, action, details) VALUES ($1, $2, $3, $4, $5)",
+            Uuid::now_v7(),
+            actor_id,
+            subject_id,
+            action.as_ref(),
+            details
+        )
+        .execute(executor)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e)?,
+        }
+    }
+    pub async fn get_recent(
+        subject_id: Option<Uuid>,
+        pool: &PgPool,
+    ) -> Result<Vec<Log>, OmniError> {
+        match sqlx::query_as!(
+            Log,
+            r#"
+            SELECT id, timestamp, actor_id, subject_id, action, details FROM logs
+            WHERE ($1::uuid IS NULL OR subject_id = $1)
+            ORDER BY timestamp DESC LIMIT $2
+            "#,
+            subject_id,
+            RECENT_LOGS_LIMIT
+        )
+        .fetch_all(pool)
+        .await
+        {
+            Ok(logs) => Ok(logs),
+            Err(e) => Err(e)?,
+        }
+    }
+}
@@ -1,13 +1,20 @@
 use tracing::{error, info};
 
+mod clearance;
 mod database;
+mod groups;
+mod logs;
 mod omnierror;
+mod pagination;
 mod quotes;
 mod router;
 mod setup;
 mod state;
+#[cfg(test)]
+mod testing;
 mod user;
 mod workers;
+mod xml;
 
 #[tokio::main]
 async fn main() {
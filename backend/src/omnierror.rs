@@ -1,17 +1,50 @@
 use axum::{
-    http::StatusCode,
+    http::{header::CONTENT_TYPE, StatusCode},
     response::{IntoResponse, Response},
 };
+use serde::Serialize;
 use tracing::error;
+use utoipa::ToSchema;
+use uuid::Uuid;
 
-use crate::user::{auth::error::AuthError, validity::ValidityError};
+use crate::{
+    clearance::ClearanceValidityError,
+    groups::GroupValidityError,
+    quotes::{
+        authors::validity::AuthorValidityError, collections::CollectionValidityError,
+        comments::CommentValidityError, share_links::ShareLinkValidityError,
+        validity::QuoteValidityError,
+    },
+    user::{auth::error::AuthError, validity::ValidityError},
+};
 
 #[derive(thiserror::Error, Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum OmniError {
     #[error("{0}")]
     AuthError(#[from] AuthError),
     #[error("{0}")]
     UserValidityError(#[from] ValidityError),
+    #[error("{0}")]
+    QuoteValidityError(#[from] QuoteValidityError),
+    #[error("{0}")]
+    AuthorValidityError(#[from] AuthorValidityError),
+    #[error("{0}")]
+    ClearanceValidityError(#[from] ClearanceValidityError),
+    #[error("{0}")]
+    GroupValidityError(#[from] GroupValidityError),
+    #[error("{0}")]
+    CommentValidityError(#[from] CommentValidityError),
+    #[error("{0}")]
+    CollectionValidityError(#[from] CollectionValidityError),
+    #[error("{0}")]
+    ShareLinkValidityError(#[from] ShareLinkValidityError),
+    #[error("No such {0} found")]
+    NotFoundError(&'static str),
+    #[error("The author still has lines in {} quote(s)", .0.len())]
+    AuthorInUse(Vec<Uuid>),
+    #[error("The group still restricts {} quote(s)", .0.len())]
+    GroupInUse(Vec<Uuid>),
 
     #[error("sqlx::Error => {0}")]
     SqlxError(#[from] sqlx::Error),
@@ -22,6 +55,64 @@ pub enum OmniError {
 
     #[error("passwordhash error: {0}")]
     PassHashError(String),
+    #[error("cookie extraction error: {0}")]
+    CookieExtractionError(&'static str),
+    #[error("quote card rendering error: {0}")]
+    CardRenderError(String),
+    #[error("feed rendering error: {0}")]
+    FeedRenderError(String),
+}
+
+/// RFC 7807 problem details body; `code` is stable and meant for machines,
+/// `detail` is meant for humans and may change wording at any time.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Problem {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub title: &'static str,
+    pub status: u16,
+    pub code: &'static str,
+    pub detail: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<FieldIssue>,
+    /// Ids of the resources that prevent the operation, when there are any
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub references: Vec<Uuid>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FieldIssue {
+    pub field: String,
+    pub message: String,
+}
+
+impl Problem {
+    pub fn new(status: StatusCode, code: &'static str, detail: impl Into<String>) -> Problem {
+        Problem {
+            kind: format!("urn:quote-engine:problem:{code}"),
+            title: status.canonical_reason().unwrap_or("Error"),
+            status: status.as_u16(),
+            code,
+            detail: detail.into(),
+            fields: vec![],
+            references: vec![],
+        }
+    }
+    pub fn with_field(mut self, field: impl Into<String>, message: impl Into<String>) -> Problem {
+        self.fields.push(FieldIssue {
+            field: field.into(),
+            message: message.into(),
+        });
+        self
+    }
+}
+
+impl IntoResponse for Problem {
+    fn into_response(self) -> Response {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let body = serde_json::to_string(&self).unwrap_or_default();
+        (status, [(CONTENT_TYPE, "application/problem+json")], body).into_response()
+    }
 }
 
 impl IntoResponse for OmniError {
@@ -32,38 +123,258 @@ impl IntoResponse for OmniError {
 
 impl OmniError {
     pub fn respond(&self) -> Response {
+        self.problem().into_response()
+    }
+    pub fn problem(&self) -> Problem {
         const ISE: StatusCode = StatusCode::INTERNAL_SERVER_ERROR;
         const BAD: StatusCode = StatusCode::BAD_REQUEST;
-        const NOC: StatusCode = StatusCode::NO_CONTENT;
         use OmniError as E;
         match self {
-            E::AuthError(e) => (e.status_code(), e.to_string()).into_response(),
-            E::UserValidityError(e) => (BAD, e.to_string()).into_response(),
-            E::SqlxError(e) => {
-                use sqlx::Error as SE;
-                match e {
-                    SE::RowNotFound => (NOC, "SQLx Error; No rows returned").into_response(),
-                    SE::Database(e) => {
-                        if e.is_unique_violation() {
-                            return (BAD, "SQLx Error; Unique Violation").into_response();
-                        }
-                        if e.is_foreign_key_violation() {
-                            return (BAD, "SQLx Error; Foreign Key Violation").into_response();
-                        }
-                        (ISE, "SQLx Error; Database error").into_response()
+            E::AuthError(e) => Problem::new(e.status_code(), e.code(), e.to_string()),
+            E::UserValidityError(e) => {
+                Problem::new(BAD, e.code(), e.to_string()).with_field(e.field(), e.to_string())
+            }
+            E::QuoteValidityError(e) => {
+                Problem::new(BAD, e.code(), e.to_string()).with_field(e.field(), e.to_string())
+            }
+            E::AuthorValidityError(e) => {
+                let status = match e {
+                    AuthorValidityError::MergeBothLinked
+                    | AuthorValidityError::NameTaken
+                    | AuthorValidityError::AlreadyLinked
+                    | AuthorValidityError::UserAlreadyLinked
+                    | AuthorValidityError::ClaimAlreadyDecided => StatusCode::CONFLICT,
+                    _ => BAD,
+                };
+                Problem::new(status, e.code(), e.to_string()).with_field(e.field(), e.to_string())
+            }
+            E::ClearanceValidityError(e) => {
+                let status = match e {
+                    ClearanceValidityError::InUse(_) => StatusCode::CONFLICT,
+                    _ => BAD,
+                };
+                Problem::new(status, e.code(), e.to_string()).with_field(e.field(), e.to_string())
+            }
+            E::GroupValidityError(e) => {
+                Problem::new(BAD, e.code(), e.to_string()).with_field(e.field(), e.to_string())
+            }
+            E::CommentValidityError(e) => {
+                Problem::new(BAD, e.code(), e.to_string()).with_field(e.field(), e.to_string())
+            }
+            E::CollectionValidityError(e) => {
+                Problem::new(BAD, e.code(), e.to_string()).with_field(e.field(), e.to_string())
+            }
+            E::ShareLinkValidityError(e) => {
+                Problem::new(BAD, e.code(), e.to_string()).with_field(e.field(), e.to_string())
+            }
+            E::NotFoundError(_) => {
+                Problem::new(StatusCode::NOT_FOUND, "not_found", self.to_string())
+            }
+            E::AuthorInUse(quotes) => Problem {
+                references: quotes.clone(),
+                ..Problem::new(StatusCode::CONFLICT, "author_in_use", self.to_string())
+            },
+            E::GroupInUse(quotes) => Problem {
+                references: quotes.clone(),
+                ..Problem::new(StatusCode::CONFLICT, "group_in_use", self.to_string())
+            },
+            E::SqlxError(e) => sqlx_problem(e),
+            E::B64DecodeError(_) => {
+                Problem::new(BAD, "malformed_base64", "Could not decode Base64")
+            }
+            E::FromUtf8Error(_) => Problem::new(BAD, "malformed_utf8", "Data is not valid UTF-8"),
+            E::PassHashError(e) => {
+                error!("PasswordHash Error: {e}");
+                Problem::new(ISE, "internal", "PasswordHash Error")
+            }
+            E::CookieExtractionError(e) => {
+                error!("Cookie Extraction Error: {e}");
+                Problem::new(ISE, "internal", "Cookie Extraction Error")
+            }
+            E::CardRenderError(e) => {
+                error!("Card Render Error: {e}");
+                Problem::new(ISE, "internal", "Card Render Error")
+            }
+            E::FeedRenderError(e) => {
+                error!("Feed Render Error: {e}");
+                Problem::new(ISE, "internal", "Feed Render Error")
+            }
+        }
+    }
+}
+
+fn sqlx_problem(e: &sqlx::Error) -> Problem {
+    const ISE: StatusCode = StatusCode::INTERNAL_SERVER_ERROR;
+    const CONFLICT: StatusCode = StatusCode::CONFLICT;
+    use sqlx::Error as SE;
+    match e {
+        SE::RowNotFound => Problem::new(StatusCode::NOT_FOUND, "not_found", "No rows returned"),
+        SE::Database(dbe) => {
+            let field = constraint_field(dbe.table(), dbe.constraint());
+            if dbe.is_unique_violation() {
+                let code = match dbe.constraint() {
+                    Some("users_handle_key") => "duplicate_handle",
+                    Some("authors_fullname_key") => "duplicate_fullname",
+                    Some("authors_codename_key") => "duplicate_codename",
+                    Some("author_aliases_alias_key") => "duplicate_alias",
+                    Some("author_claims_pending_key") => "duplicate_claim",
+                    Some("authors_user_id_key") => "user_already_linked",
+                    Some("quote_flags_open_key") => "duplicate_flag",
+                    Some("clearance_levels_pkey" | "clearance_levels_level_key") => {
+                        "duplicate_clearance_level"
                     }
-                    _ => (ISE, "SQLx Error; Logged").into_response(),
-                }
+                    Some("clearance_levels_name_key") => "duplicate_clearance_name",
+                    Some("user_groups_name_key") => "duplicate_group_name",
+                    Some("collections_name_key") => "duplicate_collection_name",
+                    _ => "duplicate",
+                };
+                let problem =
+                    Problem::new(CONFLICT, code, "A resource with this value already exists");
+                return match field {
+                    Some(f) => problem.with_field(f, "Already taken"),
+                    None => problem,
+                };
+            }
+            if dbe.is_foreign_key_violation() {
+                let problem = Problem::new(
+                    CONFLICT,
+                    "foreign_key_violation",
+                    "The resource references, or is referenced by, another resource",
+                );
+                return match field {
+                    Some(f) => problem.with_field(f, "Broken reference"),
+                    None => problem,
+                };
             }
-            E::B64DecodeError(_) => (ISE, "Base64Decode Error").into_response(),
-            E::FromUtf8Error(_) => (ISE, "FromUtf8 Error").into_response(),
-            E::PassHashError(_) => (ISE, "PasswordHash Error").into_response(),
+            error!("SQLx Database Error: {dbe}");
+            Problem::new(ISE, "database", "Database error")
+        }
+        _ => {
+            error!("SQLx Error: {e}");
+            Problem::new(ISE, "database", "Database error; Logged")
         }
     }
 }
 
+/// `users_handle_key` on `users` => `handle`
+fn constraint_field(table: Option<&str>, constraint: Option<&str>) -> Option<String> {
+    let constraint = constraint?;
+    let column = match table {
+        Some(t) => constraint.strip_prefix(t)?.strip_prefix('_')?,
+        None => constraint,
+    };
+    let column = column
+        .strip_suffix("_key")
+        .or_else(|| column.strip_suffix("_fkey"))
+        .unwrap_or(column);
+    Some(column.to_string())
+}
+
 impl From<argon2::password_hash::Error> for OmniError {
     fn from(e: argon2::password_hash::Error) -> Self {
         OmniError::PassHashError(e.to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use axum::body::to_bytes;
+    use serde_json::{json, Value};
+    use sqlx::PgPool;
+    use uuid::Uuid;
+
+    use crate::testing;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn errors_are_served_as_problem_json() {
+        let res = OmniError::NotFoundError("author").respond();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        assert_eq!(res.headers()[CONTENT_TYPE], "application/problem+json");
+        let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            body,
+            json!({
+                "type": "urn:quote-engine:problem:not_found",
+                "title": "Not Found",
+                "status": 404,
+                "code": "not_found",
+                "detail": "No such author found",
+            })
+        );
+    }
+
+    #[test]
+    fn validity_errors_name_their_field() {
+        let problem = OmniError::from(ValidityError::PasswordLengthInvalid).problem();
+        assert_eq!(
+            (problem.status, problem.code),
+            (400, "password_length_invalid")
+        );
+        assert_eq!(problem.fields[0].field, "password");
+
+        let problem = OmniError::from(AuthorValidityError::AliasDuplicated).problem();
+        assert_eq!((problem.status, problem.code), (400, "alias_duplicated"));
+        assert_eq!(problem.fields[0].field, "aliases");
+
+        let problem = OmniError::from(AuthorValidityError::MergeBothLinked).problem();
+        assert_eq!((problem.status, problem.code), (409, "merge_both_linked"));
+        assert_eq!(problem.fields[0].field, "target");
+    }
+
+    #[test]
+    fn conflicts_list_what_is_in_the_way() {
+        let quotes = vec![Uuid::now_v7(), Uuid::now_v7()];
+        let problem = OmniError::AuthorInUse(quotes.clone()).problem();
+        assert_eq!((problem.status, problem.code), (409, "author_in_use"));
+        assert_eq!(problem.references, quotes);
+    }
+
+    #[test]
+    fn internal_errors_keep_their_details_to_the_log() {
+        for e in [
+            OmniError::PassHashError("secret salt".into()),
+            OmniError::CardRenderError("/srv/secret.svg".into()),
+            OmniError::SqlxError(sqlx::Error::PoolTimedOut),
+        ] {
+            let problem = e.problem();
+            assert_eq!(problem.status, 500);
+            assert!(!problem.detail.contains("secret") && !problem.detail.contains("pool"));
+        }
+    }
+
+    #[sqlx::test]
+    async fn constraint_violations_get_their_own_codes(pool: PgPool) {
+        testing::user("taken", 1, &[], &pool).await;
+        let duplicate = sqlx::query!(
+            r#"
+            INSERT INTO users (id, handle, clearance, attributes, joindate, password_hash)
+            VALUES ($1, 'taken', 1, 0, NOW(), '')
+            "#,
+            Uuid::now_v7()
+        )
+        .execute(&pool)
+        .await
+        .unwrap_err();
+        let problem = OmniError::from(duplicate).problem();
+        assert_eq!((problem.status, problem.code), (409, "duplicate_handle"));
+        assert_eq!(problem.fields[0].field, "handle");
+
+        let dangling = sqlx::query!(
+            "INSERT INTO sessions (id, token, user_id) VALUES ($1, 'x', $2)",
+            Uuid::now_v7(),
+            Uuid::now_v7()
+        )
+        .execute(&pool)
+        .await
+        .unwrap_err();
+        let problem = OmniError::from(dangling).problem();
+        assert_eq!(
+            (problem.status, problem.code),
+            (409, "foreign_key_violation")
+        );
+        assert_eq!(problem.fields[0].field, "user_id");
+    }
+}
@@ -0,0 +1,60 @@
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+const PER_PAGE_DEFAULT: u32 = 20;
+const PER_PAGE_BOUND_UPPER: u32 = 100;
+
+/// Pages are 1-indexed; `per_page` is clamped to 1..=100.
+#[derive(Debug, Clone, Copy, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PageQuery {
+    #[serde(default = "first_page")]
+    pub page: u32,
+    #[serde(default = "per_page_default")]
+    pub per_page: u32,
+}
+
+fn first_page() -> u32 {
+    1
+}
+fn per_page_default() -> u32 {
+    PER_PAGE_DEFAULT
+}
+
+impl Default for PageQuery {
+    fn default() -> Self {
+        PageQuery {
+            page: first_page(),
+            per_page: per_page_default(),
+        }
+    }
+}
+
+impl PageQuery {
+    pub fn limit(&self) -> i64 {
+        self.per_page.clamp(1, PER_PAGE_BOUND_UPPER) as i64
+    }
+    pub fn offset(&self) -> i64 {
+        (self.page.max(1) as i64 - 1) * self.limit()
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub page: u32,
+    pub per_page: u32,
+    /// Number of items across all pages
+    pub total: u64,
+}
+
+impl<T> Page<T> {
+    pub fn new(items: Vec<T>, query: &PageQuery, total: i64) -> Page<T> {
+        Page {
+            items,
+            page: query.page.max(1),
+            per_page: query.limit() as u32,
+            total: total.max(0) as u64,
+        }
+    }
+}
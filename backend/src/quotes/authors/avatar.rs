@@ -0,0 +1,91 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{omnierror::OmniError, router::API_VERSION_PREFIX};
+
+use super::Author;
+
+pub struct AvatarImage {
+    pub content_type: String,
+    pub data: Vec<u8>,
+}
+
+impl Author {
+    /// Path under which an uploaded avatar is served; stored in `authors.avatar`.
+    pub fn uploaded_avatar_path(id: &Uuid) -> String {
+        format!("{API_VERSION_PREFIX}/authors/{id}/avatar")
+    }
+    pub async fn get_avatar(id: &Uuid, pool: &PgPool) -> Result<Option<AvatarImage>, OmniError> {
+        match sqlx::query_as!(
+            AvatarImage,
+            "SELECT content_type, data FROM author_avatars WHERE author_id = $1",
+            id
+        )
+        .fetch_optional(pool)
+        .await
+        {
+            Ok(opt) => Ok(opt),
+            Err(e) => Err(OmniError::from(e)),
+        }
+    }
+    pub async fn set_avatar(
+        mut self,
+        image: AvatarImage,
+        pool: &PgPool,
+    ) -> Result<Author, OmniError> {
+        let path = Author::uploaded_avatar_path(&self.id);
+        let mut tr = pool.begin().await?;
+        match sqlx::query!(
+            r#"
+            INSERT INTO author_avatars (author_id, content_type, data) VALUES ($1, $2, $3)
+            ON CONFLICT (author_id)
+            DO UPDATE SET content_type = $2, data = $3, uploaded = NOW()
+            "#,
+            self.id,
+            image.content_type,
+            image.data
+        )
+        .execute(&mut *tr)
+        .await
+        {
+            Ok(_) => (),
+            Err(e) => {
+                tr.rollback().await?;
+                return Err(OmniError::from(e));
+            }
+        }
+        match sqlx::query!(
+            "UPDATE authors SET avatar = $1 WHERE id = $2",
+            path,
+            self.id
+        )
+        .execute(&mut *tr)
+        .await
+        {
+            Ok(_) => (),
+            Err(e) => {
+                tr.rollback().await?;
+                return Err(OmniError::from(e));
+            }
+        }
+        tr.commit().await?;
+        self.avatar = Some(path);
+        Ok(self)
+    }
+    /// Removes an uploaded avatar; external avatar URLs are cleared via `AuthorPatch`.
+    pub async fn clear_avatar(mut self, pool: &PgPool) -> Result<Author, OmniError> {
+        let mut tr = pool.begin().await?;
+        for query in [
+            sqlx::query!("DELETE FROM author_avatars WHERE author_id = $1", self.id),
+            sqlx::query!("UPDATE authors SET avatar = NULL WHERE id = $1", self.id),
+        ] {
+            if let Err(e) = query.execute(&mut *tr).await {
+                tr.rollback().await?;
+                return Err(OmniError::from(e));
+            }
+        }
+        tr.commit().await?;
+        self.avatar = None;
+        Ok(self)
+    }
+}
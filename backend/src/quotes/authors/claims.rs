@@ -0,0 +1,394 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::json;
+use sqlx::PgPool;
+use strum::{AsRefStr, EnumString};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    logs::{Log, LogAction},
+    omnierror::OmniError,
+    user::{
+        notifications::{Notification, NotificationKind},
+        User,
+    },
+};
+
+use super::{validity::AuthorValidityError, Author};
+
+/// A user's request to be linked to an author; an admin approves or rejects it.
+#[derive(Serialize, ToSchema)]
+pub struct AuthorClaim {
+    pub id: Uuid,
+    pub author_id: Uuid,
+    pub user_id: Uuid,
+    pub status: ClaimStatus,
+    pub created: DateTime<Utc>,
+    pub decided: Option<DateTime<Utc>>,
+    pub decided_by: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ToSchema, AsRefStr, EnumString)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum ClaimStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+struct ClaimRow {
+    id: Uuid,
+    author_id: Uuid,
+    user_id: Uuid,
+    status: String,
+    created: DateTime<Utc>,
+    decided: Option<DateTime<Utc>>,
+    decided_by: Option<Uuid>,
+}
+
+impl From<ClaimRow> for AuthorClaim {
+    fn from(row: ClaimRow) -> Self {
+        AuthorClaim {
+            id: row.id,
+            author_id: row.author_id,
+            user_id: row.user_id,
+            // guarded by a CHECK constraint
+            status: row.status.parse().unwrap_or(ClaimStatus::Pending),
+            created: row.created,
+            decided: row.decided,
+            decided_by: row.decided_by,
+        }
+    }
+}
+
+impl Author {
+    pub async fn claim(&self, user: &User, pool: &PgPool) -> Result<AuthorClaim, OmniError> {
+        if self.user_id.is_some() {
+            return Err(AuthorValidityError::AlreadyLinked)?;
+        }
+        if Author::get_linked_to(&user.id, pool).await?.is_some() {
+            return Err(AuthorValidityError::UserAlreadyLinked)?;
+        }
+        match sqlx::query_as!(
+            ClaimRow,
+            r#"
+            INSERT INTO author_claims (id, author_id, user_id) VALUES ($1, $2, $3)
+            RETURNING id, author_id, user_id, status, created, decided, decided_by
+            "#,
+            Uuid::now_v7(),
+            self.id,
+            user.id
+        )
+        .fetch_one(pool)
+        .await
+        {
+            Ok(row) => Ok(row.into()),
+            Err(e) => Err(e)?,
+        }
+    }
+    pub async fn get_linked_to(user_id: &Uuid, pool: &PgPool) -> Result<Option<Author>, OmniError> {
+        match sqlx::query_scalar!("SELECT id FROM authors WHERE user_id = $1", user_id)
+            .fetch_optional(pool)
+            .await
+        {
+            Ok(Some(id)) => Author::get_by_id(&id, pool).await,
+            Ok(None) => Ok(None),
+            Err(e) => Err(e)?,
+        }
+    }
+    /// Removes the link to a user account; recorded in the audit log.
+    pub async fn unlink(mut self, actor_id: &Uuid, pool: &PgPool) -> Result<Author, OmniError> {
+        let user_id = match self.user_id {
+            Some(user_id) => user_id,
+            None => return Ok(self),
+        };
+        let mut tr = pool.begin().await?;
+        let unlinked = async {
+            sqlx::query!("UPDATE authors SET user_id = NULL WHERE id = $1", self.id)
+                .execute(&mut *tr)
+                .await?;
+            Log::record(
+                actor_id,
+                &self.id,
+                LogAction::AuthorsUnlinked,
+                json!({ "user_id": user_id }),
+                &mut *tr,
+            )
+            .await
+        }
+        .await;
+        match unlinked {
+            Ok(()) => {
+                tr.commit().await?;
+                self.user_id = None;
+                Ok(self)
+            }
+            Err(e) => {
+                tr.rollback().await?;
+                Err(e)
+            }
+        }
+    }
+}
+
+impl AuthorClaim {
+    pub async fn get_by_id(id: &Uuid, pool: &PgPool) -> Result<Option<AuthorClaim>, OmniError> {
+        match sqlx::query_as!(
+            ClaimRow,
+            r#"
+            SELECT id, author_id, user_id, status, created, decided, decided_by
+            FROM author_claims WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(pool)
+        .await
+        {
+            Ok(row) => Ok(row.map(AuthorClaim::from)),
+            Err(e) => Err(e)?,
+        }
+    }
+    pub async fn get_pending(pool: &PgPool) -> Result<Vec<AuthorClaim>, OmniError> {
+        match sqlx::query_as!(
+            ClaimRow,
+            r#"
+            SELECT id, author_id, user_id, status, created, decided, decided_by
+            FROM author_claims WHERE status = 'pending' ORDER BY created
+            "#
+        )
+        .fetch_all(pool)
+        .await
+        {
+            Ok(rows) => Ok(rows.into_iter().map(AuthorClaim::from).collect()),
+            Err(e) => Err(e)?,
+        }
+    }
+    /// Approving links the author to the claimant and rejects other pending claims on it;
+    /// the claimant is notified either way, and the decision is recorded in the audit log.
+    pub async fn decide(
+        mut self,
+        approve: bool,
+        actor_id: &Uuid,
+        pool: &PgPool,
+    ) -> Result<AuthorClaim, OmniError> {
+        if self.status != ClaimStatus::Pending {
+            return Err(AuthorValidityError::ClaimAlreadyDecided)?;
+        }
+        let status = match approve {
+            true => ClaimStatus::Approved,
+            false => ClaimStatus::Rejected,
+        };
+
+        let mut tr = pool.begin().await?;
+        let decided = async {
+            // another moderator may have decided it since it was read
+            let decided = match sqlx::query_scalar!(
+                r#"
+                UPDATE author_claims SET status = $1, decided = NOW(), decided_by = $2
+                WHERE id = $3 AND status = 'pending' RETURNING decided AS "decided!"
+                "#,
+                status.as_ref(),
+                actor_id,
+                self.id
+            )
+            .fetch_optional(&mut *tr)
+            .await?
+            {
+                Some(decided) => decided,
+                None => return Err(AuthorValidityError::ClaimAlreadyDecided)?,
+            };
+            if approve {
+                // the author may have been linked some other way meanwhile
+                let linked = sqlx::query!(
+                    "UPDATE authors SET user_id = $1 WHERE id = $2 AND user_id IS NULL",
+                    self.user_id,
+                    self.author_id
+                )
+                .execute(&mut *tr)
+                .await?;
+                if linked.rows_affected() == 0 {
+                    return Err(AuthorValidityError::AlreadyLinked)?;
+                }
+                let rejected = sqlx::query_scalar!(
+                    r#"
+                    UPDATE author_claims SET status = 'rejected', decided = NOW(), decided_by = $1
+                    WHERE author_id = $2 AND status = 'pending' RETURNING id
+                    "#,
+                    actor_id,
+                    self.author_id
+                )
+                .fetch_all(&mut *tr)
+                .await?;
+                Log::record(
+                    actor_id,
+                    &self.author_id,
+                    LogAction::AuthorsLinked,
+                    json!({
+                        "user_id": self.user_id,
+                        "claim_id": self.id,
+                        "rejected_claims": rejected,
+                    }),
+                    &mut *tr,
+                )
+                .await?;
+            } else {
+                Log::record(
+                    actor_id,
+                    &self.author_id,
+                    LogAction::AuthorsClaimRejected,
+                    json!({ "user_id": self.user_id, "claim_id": self.id }),
+                    &mut *tr,
+                )
+                .await?;
+            }
+            let kind = match approve {
+                true => NotificationKind::ClaimApproved,
+                false => NotificationKind::ClaimRejected,
+            };
+            Notification::send(
+                &self.user_id,
+                kind,
+                None,
+                json!({ "author_id": self.author_id, "claim_id": self.id }),
+                &mut *tr,
+            )
+            .await?;
+            Ok::<DateTime<Utc>, OmniError>(decided)
+        }
+        .await;
+
+        match decided {
+            Ok(decided) => {
+                tr.commit().await?;
+                self.status = status;
+                self.decided = Some(decided);
+                self.decided_by = Some(*actor_id);
+                Ok(self)
+            }
+            Err(e) => {
+                tr.rollback().await?;
+                Err(e)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    async fn notifications(user: &User, pool: &PgPool) -> i64 {
+        sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS "count!" FROM notifications WHERE user_id = $1"#,
+            user.id
+        )
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn approving_a_claim_links_and_rejects_the_rest(pool: PgPool) {
+        let admin = testing::user("admin", 0, &[], &pool).await;
+        let first = testing::user("first", 0, &[], &pool).await;
+        let second = testing::user("second", 0, &[], &pool).await;
+        let author = testing::author("jk", &pool).await;
+        let claim = author.claim(&first, &pool).await.unwrap();
+        let other = author.claim(&second, &pool).await.unwrap();
+        assert_eq!(claim.status, ClaimStatus::Pending);
+
+        let claim = claim.decide(true, &admin.id, &pool).await.unwrap();
+        assert_eq!(claim.status, ClaimStatus::Approved);
+        assert_eq!(claim.decided_by, Some(admin.id));
+        let author = Author::get_linked_to(&first.id, &pool).await.unwrap();
+        assert_eq!(author.unwrap().user_id, Some(first.id));
+        let other = AuthorClaim::get_by_id(&other.id, &pool).await.unwrap();
+        assert_eq!(other.unwrap().status, ClaimStatus::Rejected);
+        assert!(AuthorClaim::get_pending(&pool).await.unwrap().is_empty());
+        assert_eq!(notifications(&first, &pool).await, 1);
+
+        let res = claim.decide(false, &admin.id, &pool).await;
+        assert!(matches!(
+            res,
+            Err(OmniError::AuthorValidityError(
+                AuthorValidityError::ClaimAlreadyDecided
+            ))
+        ));
+    }
+
+    #[sqlx::test]
+    async fn claims_are_decided_once_and_never_relink_an_author(pool: PgPool) {
+        let admin = testing::user("admin", 0, &[], &pool).await;
+        let claimant = testing::user("claimant", 0, &[], &pool).await;
+        let author = testing::author("jk", &pool).await;
+        let claim = author.claim(&claimant, &pool).await.unwrap();
+
+        // a copy read before the claim was rejected
+        let stale = AuthorClaim::get_by_id(&claim.id, &pool)
+            .await
+            .unwrap()
+            .unwrap();
+        claim.decide(false, &admin.id, &pool).await.unwrap();
+        let res = stale.decide(true, &admin.id, &pool).await;
+        assert!(matches!(
+            res,
+            Err(OmniError::AuthorValidityError(
+                AuthorValidityError::ClaimAlreadyDecided
+            ))
+        ));
+        let logs = Log::get_recent(Some(author.id), &pool).await.unwrap();
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].action, "authors_claim_rejected");
+
+        let claim = author.claim(&claimant, &pool).await.unwrap();
+        let owner = testing::user("owner", 0, &[], &pool).await;
+        testing::link(&author, &owner, &pool).await;
+        let id = claim.id;
+        let res = claim.decide(true, &admin.id, &pool).await;
+        assert!(matches!(
+            res,
+            Err(OmniError::AuthorValidityError(
+                AuthorValidityError::AlreadyLinked
+            ))
+        ));
+        let author = Author::get_by_id(&author.id, &pool).await.unwrap().unwrap();
+        assert_eq!(author.user_id, Some(owner.id));
+        let claim = AuthorClaim::get_by_id(&id, &pool).await.unwrap();
+        assert_eq!(claim.unwrap().status, ClaimStatus::Pending);
+    }
+
+    #[sqlx::test]
+    async fn linked_authors_and_users_cannot_be_claimed(pool: PgPool) {
+        let owner = testing::user("owner", 0, &[], &pool).await;
+        let other = testing::user("other", 0, &[], &pool).await;
+        let linked = testing::author("jk", &pool).await;
+        let free = testing::author("jan", &pool).await;
+        testing::link(&linked, &owner, &pool).await;
+        let linked = Author::get_by_id(&linked.id, &pool).await.unwrap().unwrap();
+
+        let res = linked.claim(&other, &pool).await;
+        assert!(matches!(
+            res,
+            Err(OmniError::AuthorValidityError(
+                AuthorValidityError::AlreadyLinked
+            ))
+        ));
+        let res = free.claim(&owner, &pool).await;
+        assert!(matches!(
+            res,
+            Err(OmniError::AuthorValidityError(
+                AuthorValidityError::UserAlreadyLinked
+            ))
+        ));
+
+        let unlinked = linked.unlink(&owner.id, &pool).await.unwrap();
+        assert_eq!(unlinked.user_id, None);
+        assert!(Author::get_linked_to(&owner.id, &pool)
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,408 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{PgPool, Postgres, Transaction};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    logs::{Log, LogAction},
+    omnierror::OmniError,
+    user::{auth::error::AuthError, User},
+};
+
+use super::{validity::AuthorValidityError, Author};
+
+/// What happens to the quotes an author has lines in when the author is deleted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthorDeletePolicy {
+    /// Refuse to delete an author that still has lines
+    #[default]
+    Forbid,
+    /// Move the author's lines to the placeholder "Unknown" author
+    Reassign,
+    /// Move every quote the author has a line in to the trash, crediting the
+    /// author's lines to "Unknown" so the quotes can still be restored
+    Cascade,
+}
+
+pub const UNKNOWN_NAME: &str = "Unknown";
+pub const UNKNOWN_CODENAME: &str = "unknown";
+
+#[derive(Serialize, ToSchema)]
+pub struct AuthorDeletion {
+    pub author: Author,
+    pub policy: AuthorDeletePolicy,
+    /// Quotes that were reassigned or trashed, depending on the policy
+    pub affected_quotes: Vec<Uuid>,
+}
+
+impl Author {
+    /// Lines whose author is no longer known are reassigned to this author.
+    pub fn unknown_id() -> Uuid {
+        Uuid::nil()
+    }
+    pub fn is_unknown(&self) -> bool {
+        self.id.is_nil()
+    }
+
+    /// Locks the author so no lines can be added for it meanwhile, then the quotes it has lines in;
+    /// each with its clearance and whether it is shared with `actor`.
+    async fn lock_quotes(
+        &self,
+        actor: &User,
+        tr: &mut Transaction<'_, Postgres>,
+    ) -> Result<Vec<(Uuid, i64, bool)>, OmniError> {
+        sqlx::query!("SELECT id FROM authors WHERE id = $1 FOR UPDATE", self.id)
+            .fetch_optional(&mut **tr)
+            .await?;
+        match sqlx::query!(
+            r#"
+            SELECT id, clearance, quote_shared_with(id, $2) AS "shared!" FROM quotes
+            WHERE id IN (SELECT quote_id FROM lines WHERE author_id = $1)
+            ORDER BY id FOR UPDATE
+            "#,
+            self.id,
+            actor.id
+        )
+        .fetch_all(&mut **tr)
+        .await
+        {
+            Ok(rows) => Ok(rows
+                .into_iter()
+                .map(|r| (r.id, r.clearance, r.shared))
+                .collect()),
+            Err(e) => Err(e)?,
+        }
+    }
+
+    /// Deletes the author along with its aliases and avatar, dealing with the quotes
+    /// it appears in according to `policy`; recorded in the audit log.
+    pub async fn destroy(
+        self,
+        policy: AuthorDeletePolicy,
+        actor: &User,
+        pool: &PgPool,
+    ) -> Result<AuthorDeletion, OmniError> {
+        let mut tr = pool.begin().await?;
+        let deleted = async {
+            let quotes = self.lock_quotes(actor, &mut tr).await?;
+            let affected_quotes: Vec<Uuid> = quotes.iter().map(|(id, ..)| *id).collect();
+            match policy {
+                AuthorDeletePolicy::Forbid if !quotes.is_empty() => {
+                    return Err(OmniError::AuthorInUse(affected_quotes));
+                }
+                AuthorDeletePolicy::Reassign | AuthorDeletePolicy::Cascade if self.is_unknown() => {
+                    return Err(AuthorValidityError::UnknownPolicyInvalid)?;
+                }
+                // the same the actor would need to trash each quote on its own
+                AuthorDeletePolicy::Cascade
+                    if quotes
+                        .iter()
+                        .any(|(_, c, shared)| !shared || *c >= actor.clearance as i64) =>
+                {
+                    return Err(AuthError::InsufficientClearance)?;
+                }
+                _ => (),
+            }
+
+            if policy == AuthorDeletePolicy::Cascade {
+                let trashed = sqlx::query_scalar!(
+                    r#"
+                    UPDATE quotes SET deleted_at = NOW(), deleted_by = $1
+                    WHERE id = ANY($2) AND deleted_at IS NULL RETURNING id
+                    "#,
+                    actor.id,
+                    &affected_quotes
+                )
+                .fetch_all(&mut *tr)
+                .await?;
+                for id in trashed {
+                    Log::record(
+                        &actor.id,
+                        &id,
+                        LogAction::QuotesTrashed,
+                        json!({ "author": self.id }),
+                        &mut *tr,
+                    )
+                    .await?;
+                }
+            }
+            if policy != AuthorDeletePolicy::Forbid {
+                guarantee_unknown_exists(&mut tr).await?;
+                sqlx::query!(
+                    "UPDATE lines SET author_id = $1 WHERE author_id = $2",
+                    Author::unknown_id(),
+                    self.id
+                )
+                .execute(&mut *tr)
+                .await?;
+            }
+            for query in [
+                sqlx::query!("DELETE FROM author_claims WHERE author_id = $1", self.id),
+                sqlx::query!("DELETE FROM author_aliases WHERE author_id = $1", self.id),
+                sqlx::query!("DELETE FROM author_avatars WHERE author_id = $1", self.id),
+                sqlx::query!("DELETE FROM authors WHERE id = $1", self.id),
+            ] {
+                query.execute(&mut *tr).await?;
+            }
+            Log::record(
+                &actor.id,
+                &self.id,
+                LogAction::AuthorsDeleted,
+                json!({
+                    "author": self,
+                    "policy": policy,
+                    "affected_quotes": affected_quotes,
+                }),
+                &mut *tr,
+            )
+            .await?;
+            Ok::<Vec<Uuid>, OmniError>(affected_quotes)
+        }
+        .await;
+
+        match deleted {
+            Ok(affected_quotes) => {
+                tr.commit().await?;
+                Ok(AuthorDeletion {
+                    author: self,
+                    policy,
+                    affected_quotes,
+                })
+            }
+            Err(e) => {
+                tr.rollback().await?;
+                Err(e)
+            }
+        }
+    }
+}
+
+/// Creates the placeholder unless it exists. Its names are reserved, but an author created before
+/// they were may already hold one; the placeholder then goes by its id instead.
+async fn guarantee_unknown_exists(tr: &mut Transaction<'_, Postgres>) -> Result<(), OmniError> {
+    let id = Author::unknown_id();
+    let exists = sqlx::query_scalar!("SELECT 1 FROM authors WHERE id = $1", id)
+        .fetch_optional(&mut **tr)
+        .await?;
+    if exists.is_some() {
+        return Ok(());
+    }
+    let taken = sqlx::query_scalar!(
+        "SELECT 1 FROM authors WHERE lower(fullname) = lower($1) OR lower(codename) = $2",
+        UNKNOWN_NAME,
+        UNKNOWN_CODENAME
+    )
+    .fetch_optional(&mut **tr)
+    .await?;
+    let (fullname, codename) = match taken {
+        Some(_) => (
+            format!("{UNKNOWN_NAME} ({id})"),
+            format!("{UNKNOWN_CODENAME}-{id}"),
+        ),
+        None => (UNKNOWN_NAME.to_string(), UNKNOWN_CODENAME.to_string()),
+    };
+    match sqlx::query!(
+        r#"
+        INSERT INTO authors (id, fullname, codename, bio) VALUES ($1, $2, $3, $4)
+        ON CONFLICT DO NOTHING
+        "#,
+        id,
+        fullname,
+        codename,
+        "Placeholder for lines whose author has been deleted."
+    )
+    .execute(&mut **tr)
+    .await
+    {
+        Ok(_) => Ok(()),
+        Err(e) => Err(e)?,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        groups::{Group, NewGroup},
+        quotes::Quote,
+        testing,
+    };
+
+    async fn setup(pool: &PgPool) -> (User, Author, Quote) {
+        let admin = testing::user("admin", 2, &[], pool).await;
+        let author = testing::author("jk", pool).await;
+        let other = testing::author("jan", pool).await;
+        let quote = testing::quote(&[(&author, "one"), (&other, "two")], 0);
+        let quote = testing::save(quote, &admin, pool).await;
+        (admin, author, quote)
+    }
+
+    async fn line_authors(quote: &Quote, trashed: bool, pool: &PgPool) -> Vec<Option<Uuid>> {
+        let quote = Quote::fetch_by_id(&quote.id, trashed, pool).await.unwrap();
+        quote.unwrap().lines.iter().map(|l| l.author_id).collect()
+    }
+
+    #[sqlx::test]
+    async fn forbid_refuses_authors_in_use(pool: PgPool) {
+        let (admin, author, quote) = setup(&pool).await;
+        let res = author
+            .clone()
+            .destroy(AuthorDeletePolicy::Forbid, &admin, &pool)
+            .await;
+        assert!(matches!(res, Err(OmniError::AuthorInUse(ids)) if ids == vec![quote.id]));
+        assert!(Author::get_by_id(&author.id, &pool)
+            .await
+            .unwrap()
+            .is_some());
+
+        let unused = testing::author("unused", &pool).await;
+        let deletion = unused
+            .destroy(AuthorDeletePolicy::Forbid, &admin, &pool)
+            .await
+            .unwrap();
+        assert!(deletion.affected_quotes.is_empty());
+    }
+
+    #[sqlx::test]
+    async fn reassign_credits_the_lines_to_unknown(pool: PgPool) {
+        let (admin, author, quote) = setup(&pool).await;
+        let before = line_authors(&quote, false, &pool).await;
+        let deletion = author
+            .clone()
+            .destroy(AuthorDeletePolicy::Reassign, &admin, &pool)
+            .await
+            .unwrap();
+        assert_eq!(deletion.affected_quotes, vec![quote.id]);
+        assert!(Author::get_by_id(&author.id, &pool)
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            line_authors(&quote, false, &pool).await,
+            vec![Some(Author::unknown_id()), before[1]]
+        );
+    }
+
+    #[sqlx::test]
+    async fn cascade_moves_the_quotes_to_the_trash(pool: PgPool) {
+        let (admin, author, quote) = setup(&pool).await;
+        author
+            .destroy(AuthorDeletePolicy::Cascade, &admin, &pool)
+            .await
+            .unwrap();
+        assert!(Quote::get_by_id(&quote.id, &pool).await.unwrap().is_none());
+        let lines = line_authors(&quote, true, &pool).await;
+        assert_eq!(lines[0], Some(Author::unknown_id()));
+    }
+
+    #[sqlx::test]
+    async fn cascade_needs_clearance_for_every_quote(pool: PgPool) {
+        let (admin, author, _) = setup(&pool).await;
+        let secret = testing::quote(&[(&author, "secret")], 3);
+        testing::save(secret, &admin, &pool).await;
+        let res = author
+            .clone()
+            .destroy(AuthorDeletePolicy::Cascade, &admin, &pool)
+            .await;
+        assert!(matches!(
+            res,
+            Err(OmniError::AuthError(AuthError::InsufficientClearance))
+        ));
+        assert!(Author::get_by_id(&author.id, &pool)
+            .await
+            .unwrap()
+            .is_some());
+    }
+
+    #[sqlx::test]
+    async fn cascade_needs_each_quote_to_be_deletable_by_the_actor(pool: PgPool) {
+        let (admin, author, _) = setup(&pool).await;
+        let level = testing::quote(&[(&author, "as high as the actor")], 2);
+        let level = testing::save(level, &admin, &pool).await;
+        let res = author
+            .clone()
+            .destroy(AuthorDeletePolicy::Cascade, &admin, &pool)
+            .await;
+        assert!(matches!(
+            res,
+            Err(OmniError::AuthError(AuthError::InsufficientClearance))
+        ));
+
+        sqlx::query!("UPDATE quotes SET clearance = 0 WHERE id = $1", level.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let group = Group::create(
+            NewGroup {
+                name: "Team".into(),
+                description: None,
+            },
+            &pool,
+        )
+        .await
+        .unwrap();
+        let other = testing::user("other", 2, &[], &pool).await;
+        level
+            .set_groups(vec![group.id], &admin.id, &pool)
+            .await
+            .unwrap();
+        let res = author
+            .clone()
+            .destroy(AuthorDeletePolicy::Cascade, &other, &pool)
+            .await;
+        assert!(matches!(
+            res,
+            Err(OmniError::AuthError(AuthError::InsufficientClearance))
+        ));
+        assert!(Author::get_by_id(&author.id, &pool)
+            .await
+            .unwrap()
+            .is_some());
+    }
+
+    #[sqlx::test]
+    async fn a_real_author_named_unknown_does_not_block_the_placeholder(pool: PgPool) {
+        let (admin, author, _) = setup(&pool).await;
+        // created before the names were reserved
+        sqlx::query!(
+            "INSERT INTO authors (id, fullname, codename) VALUES ($1, 'Unknown', 'unknown')",
+            Uuid::now_v7()
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        author
+            .destroy(AuthorDeletePolicy::Reassign, &admin, &pool)
+            .await
+            .unwrap();
+        let unknown = Author::get_by_id(&Author::unknown_id(), &pool)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_ne!(unknown.codename, UNKNOWN_CODENAME);
+    }
+
+    #[sqlx::test]
+    async fn unknown_can_only_be_deleted_with_forbid(pool: PgPool) {
+        let (admin, author, _) = setup(&pool).await;
+        author
+            .destroy(AuthorDeletePolicy::Reassign, &admin, &pool)
+            .await
+            .unwrap();
+        let unknown = Author::get_by_id(&Author::unknown_id(), &pool)
+            .await
+            .unwrap()
+            .unwrap();
+        for policy in [AuthorDeletePolicy::Reassign, AuthorDeletePolicy::Cascade] {
+            let res = unknown.clone().destroy(policy, &admin, &pool).await;
+            assert!(matches!(
+                res,
+                Err(OmniError::AuthorValidityError(
+                    AuthorValidityError::UnknownPolicyInvalid
+                ))
+            ));
+        }
+    }
+}
@@ -0,0 +1,425 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{PgExecutor, PgPool, Postgres, Transaction};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    logs::{Log, LogAction},
+    omnierror::OmniError,
+};
+
+use super::{validity::AuthorValidityError, Author};
+
+#[derive(Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct AuthorMerge {
+    /// The author that survives the merge
+    pub target: Uuid,
+}
+
+/// What a merge does (or did); returned by both preview and the merge itself.
+#[derive(Serialize, ToSchema)]
+pub struct AuthorMergeReport {
+    pub source: Author,
+    pub target: Author,
+    pub affected_quotes: u32,
+    pub affected_lines: u32,
+    /// Names of the source that become aliases of the target
+    pub new_aliases: Vec<String>,
+}
+
+impl Author {
+    pub async fn preview_merge(
+        self,
+        target: Author,
+        pool: &PgPool,
+    ) -> Result<AuthorMergeReport, OmniError> {
+        merge_report(self, target, pool).await
+    }
+    /// Moves every line and claim of `self` to `target`, turns the names of `self` into aliases
+    /// of `target` and deletes `self`; all in one transaction, recorded in the audit log.
+    /// Refused when both authors are linked to users.
+    pub async fn merge_into(
+        self,
+        target: Author,
+        actor_id: &Uuid,
+        pool: &PgPool,
+    ) -> Result<AuthorMergeReport, OmniError> {
+        if self.id == target.id {
+            return Err(AuthorValidityError::MergeIntoSelf)?;
+        }
+
+        let mut tr = pool.begin().await?;
+        let merged = async {
+            // read both again under lock, so the report describes exactly what is changed
+            let (source, target) = lock_pair(&self.id, &target.id, &mut tr).await?;
+            let planned = merge_report(source, target, &mut *tr).await?;
+            let (source, target) = (&planned.source, &planned.target);
+
+            let lines = sqlx::query!(
+                "UPDATE lines SET author_id = $1 WHERE author_id = $2",
+                target.id,
+                source.id
+            )
+            .execute(&mut *tr)
+            .await?;
+            sqlx::query!("DELETE FROM author_aliases WHERE author_id = $1", source.id)
+                .execute(&mut *tr)
+                .await?;
+            let mut new_aliases = vec![];
+            for name in &planned.new_aliases {
+                // a name another author already goes by stays theirs
+                let added = sqlx::query_scalar!(
+                    r#"
+                    INSERT INTO author_aliases (id, author_id, alias)
+                    SELECT $1, $2, $3 WHERE NOT EXISTS (
+                        SELECT 1 FROM authors WHERE id <> $2 AND id <> $4
+                        AND (lower(fullname) = lower($3) OR lower(codename) = lower($3))
+                    )
+                    ON CONFLICT DO NOTHING RETURNING alias AS "alias!"
+                    "#,
+                    Uuid::now_v7(),
+                    target.id,
+                    name,
+                    source.id
+                )
+                .fetch_optional(&mut *tr)
+                .await?;
+                new_aliases.extend(added);
+            }
+            // claims move with the lines; a pending claim the same user also has on the target
+            // would be a duplicate
+            sqlx::query!(
+                r#"
+                DELETE FROM author_claims AS c WHERE c.author_id = $1 AND c.status = 'pending'
+                AND EXISTS (
+                    SELECT 1 FROM author_claims WHERE author_id = $2
+                    AND user_id = c.user_id AND status = 'pending'
+                )
+                "#,
+                source.id,
+                target.id
+            )
+            .execute(&mut *tr)
+            .await?;
+            sqlx::query!(
+                "UPDATE author_claims SET author_id = $1 WHERE author_id = $2",
+                target.id,
+                source.id
+            )
+            .execute(&mut *tr)
+            .await?;
+            sqlx::query!("DELETE FROM author_avatars WHERE author_id = $1", source.id)
+                .execute(&mut *tr)
+                .await?;
+            sqlx::query!("DELETE FROM authors WHERE id = $1", source.id)
+                .execute(&mut *tr)
+                .await?;
+            if let (None, Some(user_id)) = (target.user_id, source.user_id) {
+                // the link can only move once the source no longer holds it
+                sqlx::query!(
+                    "UPDATE authors SET user_id = $1 WHERE id = $2",
+                    user_id,
+                    target.id
+                )
+                .execute(&mut *tr)
+                .await?;
+            }
+            Log::record(
+                actor_id,
+                &target.id,
+                LogAction::AuthorsMerged,
+                json!({
+                    "source": source,
+                    "affected_quotes": planned.affected_quotes,
+                    "affected_lines": lines.rows_affected(),
+                    "new_aliases": new_aliases,
+                }),
+                &mut *tr,
+            )
+            .await?;
+            Ok::<AuthorMergeReport, OmniError>(AuthorMergeReport {
+                affected_lines: lines.rows_affected() as u32,
+                new_aliases,
+                ..planned
+            })
+        }
+        .await;
+
+        match merged {
+            Ok(mut report) => {
+                tr.commit().await?;
+                report
+                    .target
+                    .aliases
+                    .extend(report.new_aliases.iter().cloned());
+                report.target.aliases.sort();
+                report.target.user_id = report.target.user_id.or(report.source.user_id);
+                Ok(report)
+            }
+            Err(e) => {
+                tr.rollback().await?;
+                Err(e)
+            }
+        }
+    }
+}
+
+async fn merge_report<'e, E: PgExecutor<'e>>(
+    source: Author,
+    target: Author,
+    executor: E,
+) -> Result<AuthorMergeReport, OmniError> {
+    if source.id == target.id {
+        return Err(AuthorValidityError::MergeIntoSelf)?;
+    }
+    // only one of the two links could survive
+    if source.user_id.is_some() && target.user_id.is_some() {
+        return Err(AuthorValidityError::MergeBothLinked)?;
+    }
+    let rec = sqlx::query!(
+        r#"
+        SELECT
+            COUNT(DISTINCT quote_id) AS "affected_quotes!",
+            COUNT(id) AS "affected_lines!"
+        FROM lines WHERE author_id = $1
+        "#,
+        source.id
+    )
+    .fetch_one(executor)
+    .await?;
+    let new_aliases = merged_aliases(&source, &target);
+    Ok(AuthorMergeReport {
+        source,
+        target,
+        affected_quotes: rec.affected_quotes as u32,
+        affected_lines: rec.affected_lines as u32,
+        new_aliases,
+    })
+}
+
+/// Locks both authors, in id order, and reads them as they are now.
+async fn lock_pair(
+    source_id: &Uuid,
+    target_id: &Uuid,
+    tr: &mut Transaction<'_, Postgres>,
+) -> Result<(Author, Author), OmniError> {
+    let mut authors = sqlx::query_as!(
+        Author,
+        r#"
+        SELECT
+            id, fullname, codename, bio, avatar, user_id,
+            ARRAY(
+                SELECT alias FROM author_aliases
+                WHERE author_id = authors.id ORDER BY alias
+            ) AS "aliases!"
+        FROM authors WHERE id = ANY($1)
+        ORDER BY id FOR UPDATE
+        "#,
+        &[*source_id, *target_id]
+    )
+    .fetch_all(&mut **tr)
+    .await?;
+    let mut take = |id: &Uuid| {
+        let i = authors.iter().position(|a| a.id == *id);
+        i.map(|i| authors.swap_remove(i))
+    };
+    match (take(source_id), take(target_id)) {
+        (Some(source), Some(target)) => Ok((source, target)),
+        _ => Err(OmniError::NotFoundError("author")),
+    }
+}
+
+/// Every alias and name of `source` that `target` doesn't already go by.
+fn merged_aliases(source: &Author, target: &Author) -> Vec<String> {
+    let mut known: Vec<String> = [&target.fullname, &target.codename]
+        .into_iter()
+        .chain(target.aliases.iter())
+        .map(|n| n.to_lowercase())
+        .collect();
+    let mut aliases = vec![];
+    for name in [&source.fullname, &source.codename]
+        .into_iter()
+        .chain(source.aliases.iter())
+    {
+        if !known.contains(&name.to_lowercase()) {
+            known.push(name.to_lowercase());
+            aliases.push(name.clone());
+        }
+    }
+    aliases
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{quotes::Quote, testing};
+
+    async fn reload(author: &Author, pool: &PgPool) -> Option<Author> {
+        Author::get_by_id(&author.id, pool).await.unwrap()
+    }
+
+    #[test]
+    fn names_the_target_already_goes_by_are_not_aliased_again() {
+        let mut source = Author {
+            id: Uuid::now_v7(),
+            fullname: "Jan Kowalski".into(),
+            codename: "jk".into(),
+            bio: None,
+            avatar: None,
+            aliases: vec!["Janek".into(), "JK".into()],
+            user_id: None,
+        };
+        let mut target = source.clone();
+        target.fullname = "Jan Kowalski-Nowak".into();
+        target.codename = "jkn".into();
+        target.aliases = vec!["jan kowalski".into()];
+        assert_eq!(merged_aliases(&source, &target), vec!["jk", "Janek"]);
+        source.aliases.clear();
+        assert_eq!(merged_aliases(&source, &target), vec!["jk"]);
+    }
+
+    #[sqlx::test]
+    async fn merging_moves_lines_names_and_the_link(pool: PgPool) {
+        let admin = testing::user("admin", 0, &[], &pool).await;
+        let owner = testing::user("owner", 0, &[], &pool).await;
+        let source = testing::author("jk", &pool).await;
+        let target = testing::author("jan", &pool).await;
+        testing::link(&source, &owner, &pool).await;
+        let quote = testing::save(
+            testing::quote(&[(&source, "one"), (&target, "two"), (&source, "three")], 0),
+            &admin,
+            &pool,
+        )
+        .await;
+        let source = reload(&source, &pool).await.unwrap();
+
+        let preview = source
+            .clone()
+            .preview_merge(target.clone(), &pool)
+            .await
+            .unwrap();
+        assert_eq!((preview.affected_quotes, preview.affected_lines), (1, 2));
+        assert_eq!(preview.new_aliases, vec!["JK"]);
+
+        let report = source
+            .clone()
+            .merge_into(target.clone(), &admin.id, &pool)
+            .await
+            .unwrap();
+        assert_eq!(report.affected_lines, 2);
+
+        assert!(reload(&source, &pool).await.is_none());
+        let target = reload(&target, &pool).await.unwrap();
+        assert_eq!(target.user_id, Some(owner.id));
+        assert_eq!(target.aliases, vec!["JK"]);
+        assert_eq!(report.target.aliases, target.aliases);
+        let quote = Quote::get_by_id(&quote.id, &pool).await.unwrap().unwrap();
+        assert!(quote.lines.iter().all(|l| l.author_id == Some(target.id)));
+    }
+
+    #[sqlx::test]
+    async fn pending_claims_on_both_authors_are_not_duplicated(pool: PgPool) {
+        let admin = testing::user("admin", 0, &[], &pool).await;
+        let claimant = testing::user("claimant", 0, &[], &pool).await;
+        let source = testing::author("jk", &pool).await;
+        let target = testing::author("jan", &pool).await;
+        source.claim(&claimant, &pool).await.unwrap();
+        target.claim(&claimant, &pool).await.unwrap();
+
+        source
+            .merge_into(target.clone(), &admin.id, &pool)
+            .await
+            .unwrap();
+
+        let claims = sqlx::query_scalar!(
+            "SELECT author_id FROM author_claims WHERE user_id = $1",
+            claimant.id
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert_eq!(claims, vec![target.id]);
+    }
+
+    #[sqlx::test]
+    async fn impossible_merges_are_refused_and_change_nothing(pool: PgPool) {
+        let admin = testing::user("admin", 0, &[], &pool).await;
+        let source = testing::author("jk", &pool).await;
+        let target = testing::author("jan", &pool).await;
+
+        let res = source
+            .clone()
+            .merge_into(source.clone(), &admin.id, &pool)
+            .await;
+        assert!(matches!(
+            res,
+            Err(OmniError::AuthorValidityError(
+                AuthorValidityError::MergeIntoSelf
+            ))
+        ));
+
+        testing::link(&source, &testing::user("a", 0, &[], &pool).await, &pool).await;
+        testing::link(&target, &testing::user("b", 0, &[], &pool).await, &pool).await;
+        let source = reload(&source, &pool).await.unwrap();
+        let target = reload(&target, &pool).await.unwrap();
+        let res = source.clone().merge_into(target, &admin.id, &pool).await;
+        assert!(matches!(
+            res,
+            Err(OmniError::AuthorValidityError(
+                AuthorValidityError::MergeBothLinked
+            ))
+        ));
+        assert!(reload(&source, &pool).await.is_some());
+    }
+
+    #[sqlx::test]
+    async fn the_report_only_lists_aliases_that_were_added(pool: PgPool) {
+        let admin = testing::user("admin", 0, &[], &pool).await;
+        let source = testing::author("jk", &pool).await;
+        let target = testing::author("jan", &pool).await;
+        let other = testing::author("other", &pool).await;
+        testing::author("kuba", &pool).await;
+        for (author, alias) in [(&source, "Janek"), (&source, "Kuba"), (&other, "jk")] {
+            sqlx::query!(
+                "INSERT INTO author_aliases (id, author_id, alias) VALUES ($1, $2, $3)",
+                Uuid::now_v7(),
+                author.id,
+                alias
+            )
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+        let source = reload(&source, &pool).await.unwrap();
+
+        let report = source
+            .merge_into(target.clone(), &admin.id, &pool)
+            .await
+            .unwrap();
+        assert_eq!(report.new_aliases, vec!["Janek"]);
+        assert_eq!(report.target.aliases, vec!["Janek"]);
+        assert_eq!(reload(&target, &pool).await.unwrap().aliases, vec!["Janek"]);
+        assert_eq!(reload(&other, &pool).await.unwrap().aliases, vec!["jk"]);
+    }
+
+    #[sqlx::test]
+    async fn merges_check_the_authors_as_they_are_now(pool: PgPool) {
+        let admin = testing::user("admin", 0, &[], &pool).await;
+        let source = testing::author("jk", &pool).await;
+        let target = testing::author("jan", &pool).await;
+        testing::link(&source, &testing::user("a", 0, &[], &pool).await, &pool).await;
+        testing::link(&target, &testing::user("b", 0, &[], &pool).await, &pool).await;
+
+        // both copies are stale and unlinked
+        let res = source.clone().merge_into(target, &admin.id, &pool).await;
+        assert!(matches!(
+            res,
+            Err(OmniError::AuthorValidityError(
+                AuthorValidityError::MergeBothLinked
+            ))
+        ));
+        assert!(reload(&source, &pool).await.is_some());
+    }
+}
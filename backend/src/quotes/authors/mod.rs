@@ -1,11 +1,22 @@
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
+use sqlx::{PgPool, Postgres, Transaction};
 use tracing::error;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 use crate::omnierror::OmniError;
 
-#[derive(Debug, Serialize, Deserialize)]
+use validity::AuthorValidityError;
+
+pub mod avatar;
+pub mod claims;
+pub mod deletion;
+pub mod merge;
+pub mod stats;
+pub mod suggest;
+pub mod validity;
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 #[serde(deny_unknown_fields)]
 pub struct Author {
     #[serde(skip_deserializing)]
@@ -13,27 +24,54 @@ pub struct Author {
     pub id: Uuid,
     pub fullname: String,
     pub codename: String,
+    #[serde(default)]
+    pub bio: Option<String>,
+    /// An external image URL, or the path of an uploaded avatar
+    #[serde(default)]
+    pub avatar: Option<String>,
+    /// Other names the author is known under; they resolve to this author
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    /// The user account linked to this author, once a claim has been approved
+    #[serde(skip_deserializing)]
+    pub user_id: Option<Uuid>,
 }
 
-#[derive(Serialize)]
+/// Counts only include quotes the viewer has the clearance for.
+#[derive(Serialize, ToSchema)]
 pub struct ExtendedAuthor {
     pub author: Author,
     pub quote_count: u32,
     pub line_count: u32,
+    /// Only present when a single author is requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats: Option<stats::AuthorStats>,
 }
 
-#[derive(Deserialize)]
+/// Empty `bio` or `avatar` strings clear the field; `aliases` replaces the whole list.
+#[derive(Deserialize, ToSchema)]
 #[serde(deny_unknown_fields)]
 pub struct AuthorPatch {
     pub fullname: Option<String>,
     pub codename: Option<String>,
+    pub bio: Option<String>,
+    pub avatar: Option<String>,
+    pub aliases: Option<Vec<String>>,
 }
 
 impl Author {
     pub async fn get_by_id(id: &Uuid, pool: &PgPool) -> Result<Option<Author>, OmniError> {
         match sqlx::query_as!(
             Author,
-            "SELECT id, fullname, codename FROM authors WHERE id = $1",
+            r#"
+            SELECT
+                id, fullname, codename, bio, avatar, user_id,
+                ARRAY(
+                    SELECT alias FROM author_aliases
+                    WHERE author_id = authors.id ORDER BY alias
+                ) AS "aliases!"
+            FROM authors WHERE id = $1
+            "#,
             id
         )
         .fetch_optional(pool)
@@ -43,11 +81,50 @@ impl Author {
             Err(e) => Err(OmniError::from(e)),
         }
     }
+    /// Resolves a full name, codename or alias (case-insensitive) to the canonical author;
+    /// a full name wins over a codename, which wins over an alias.
+    pub async fn get_by_name(name: &str, pool: &PgPool) -> Result<Option<Author>, OmniError> {
+        match sqlx::query_as!(
+            Author,
+            r#"
+            SELECT
+                id, fullname, codename, bio, avatar, user_id,
+                ARRAY(
+                    SELECT alias FROM author_aliases
+                    WHERE author_id = authors.id ORDER BY alias
+                ) AS "aliases!"
+            FROM authors
+            WHERE lower(fullname) = lower($1) OR lower(codename) = lower($1)
+            OR id = (SELECT author_id FROM author_aliases WHERE lower(alias) = lower($1))
+            ORDER BY
+                CASE
+                    WHEN lower(fullname) = lower($1) THEN 0
+                    WHEN lower(codename) = lower($1) THEN 1
+                    ELSE 2
+                END,
+                fullname
+            LIMIT 1
+            "#,
+            name
+        )
+        .fetch_optional(pool)
+        .await
+        {
+            Ok(opt) => Ok(opt),
+            Err(e) => Err(OmniError::from(e)),
+        }
+    }
     pub async fn get_all(pool: &PgPool) -> Result<Vec<Author>, OmniError> {
         match sqlx::query_as!(
             Author,
             r#"
-            SELECT id, fullname, codename FROM authors
+            SELECT
+                id, fullname, codename, bio, avatar, user_id,
+                ARRAY(
+                    SELECT alias FROM author_aliases
+                    WHERE author_id = authors.id ORDER BY alias
+                ) AS "aliases!"
+            FROM authors
             ORDER BY fullname
             "#
         )
@@ -59,111 +136,253 @@ impl Author {
         }
     }
     pub async fn create(author: Author, pool: &PgPool) -> Result<Author, OmniError> {
+        let mut tr = pool.begin().await?;
         match sqlx::query!(
-            "INSERT INTO authors (id, fullname, codename) VALUES ($1, $2, $3)",
+            "INSERT INTO authors (id, fullname, codename, bio, avatar) VALUES ($1, $2, $3, $4, $5)",
             &author.id,
             &author.fullname,
-            &author.codename
+            &author.codename,
+            author.bio,
+            author.avatar
         )
-        .execute(pool)
+        .execute(&mut *tr)
         .await
         {
-            Ok(_) => Ok(author),
+            Ok(_) => (),
             Err(e) => {
                 error!("err: {e}");
-                Err(OmniError::from(e))
+                tr.rollback().await?;
+                return Err(OmniError::from(e));
             }
         }
+        if let Err(e) = author.ensure_names_free(&mut tr).await {
+            tr.rollback().await?;
+            return Err(e);
+        }
+        if let Err(e) = Author::replace_aliases(&author.id, &author.aliases, &mut tr).await {
+            tr.rollback().await?;
+            return Err(e);
+        }
+        tr.commit().await?;
+        Ok(author)
     }
     pub async fn patch(self, patch: AuthorPatch, pool: &PgPool) -> Result<Author, OmniError> {
         let author = Author {
             id: self.id,
             fullname: patch.fullname.unwrap_or(self.fullname),
             codename: patch.codename.unwrap_or(self.codename),
+            bio: match patch.bio {
+                Some(bio) => Some(bio).filter(|b| !b.is_empty()),
+                None => self.bio,
+            },
+            avatar: match &patch.avatar {
+                Some(avatar) => Some(avatar.clone()).filter(|a| !a.is_empty()),
+                None => self.avatar,
+            },
+            aliases: patch.aliases.unwrap_or(self.aliases),
+            user_id: self.user_id,
         };
+        let mut tr = pool.begin().await?;
+        if patch.avatar.is_some() {
+            // a new URL (or clearing) supersedes an uploaded image
+            if let Err(e) = sqlx::query!("DELETE FROM author_avatars WHERE author_id = $1", self.id)
+                .execute(&mut *tr)
+                .await
+            {
+                tr.rollback().await?;
+                return Err(OmniError::from(e));
+            }
+        }
         match sqlx::query!(
-            "UPDATE authors SET fullname = $1, codename = $2 WHERE id = $3",
+            "UPDATE authors SET fullname = $1, codename = $2, bio = $3, avatar = $4 WHERE id = $5",
             &author.fullname,
             &author.codename,
+            author.bio,
+            author.avatar,
             self.id
         )
-        .execute(pool)
+        .execute(&mut *tr)
         .await
         {
-            Ok(_) => Ok(author),
+            Ok(_) => (),
             Err(e) => {
                 error!("err: {e}");
-                Err(OmniError::from(e))
+                tr.rollback().await?;
+                return Err(OmniError::from(e));
             }
         }
-    }
-    pub async fn destroy(self, pool: &PgPool) -> Result<(), OmniError> {
-        match sqlx::query!("DELETE FROM authors WHERE id = $1", self.id)
-            .execute(pool)
-            .await
-        {
-            Ok(_) => Ok(()),
-            Err(e) => Err(OmniError::from(e)),
+        if let Err(e) = author.ensure_names_free(&mut tr).await {
+            tr.rollback().await?;
+            return Err(e);
+        }
+        if let Err(e) = Author::replace_aliases(&author.id, &author.aliases, &mut tr).await {
+            tr.rollback().await?;
+            return Err(e);
         }
+        tr.commit().await?;
+        Ok(author)
     }
-}
-
-impl ExtendedAuthor {
-    pub async fn get_by_id(id: &Uuid, pool: &PgPool) -> Result<Option<ExtendedAuthor>, OmniError> {
-        match sqlx::query!(
+    /// Aliases may not be another author's name or alias, nor the names another author's alias,
+    /// ignoring case; otherwise a name could resolve to either of them.
+    async fn ensure_names_free(&self, tr: &mut Transaction<'_, Postgres>) -> Result<(), OmniError> {
+        let aliases: Vec<String> = self
+            .aliases
+            .iter()
+            .map(|a| a.trim().to_lowercase())
+            .collect();
+        let mut names = aliases.clone();
+        names.push(self.fullname.to_lowercase());
+        names.push(self.codename.to_lowercase());
+        let taken = sqlx::query_scalar!(
             r#"
-            SELECT
-                authors.id, authors.fullname, authors.codename,
-                COUNT(DISTINCT lines.id) AS quote_count,
-                COUNT(lines.id) as line_count
-            FROM authors LEFT JOIN lines ON authors.id = lines.author_id
-            WHERE authors.id = $1 GROUP BY authors.id
+            SELECT EXISTS (
+                SELECT 1 FROM authors WHERE id <> $1
+                AND (lower(fullname) = ANY($2) OR lower(codename) = ANY($2))
+                UNION ALL
+                SELECT 1 FROM author_aliases WHERE author_id <> $1 AND lower(alias) = ANY($3)
+            ) AS "taken!"
             "#,
-            id
+            self.id,
+            &aliases,
+            &names
         )
-        .fetch_optional(pool)
-        .await
-        {
-            Ok(opt) => Ok(opt.map(|rec| ExtendedAuthor {
-                author: Author {
-                    id: rec.id,
-                    fullname: rec.fullname,
-                    codename: rec.codename,
-                },
-                quote_count: rec.quote_count.unwrap_or(0) as u32,
-                line_count: rec.line_count.unwrap_or(0) as u32,
-            })),
-            Err(e) => Err(OmniError::from(e)),
+        .fetch_one(&mut **tr)
+        .await?;
+        match taken {
+            true => Err(AuthorValidityError::NameTaken)?,
+            false => Ok(()),
         }
     }
-    pub async fn get_all(pool: &PgPool) -> Result<Vec<ExtendedAuthor>, OmniError> {
-        match sqlx::query!(
-            r#"
-            SELECT
-                authors.id, authors.fullname, authors.codename,
-                COUNT(DISTINCT lines.id) AS quote_count,
-                COUNT(lines.id) as line_count
-            FROM authors LEFT JOIN lines ON authors.id = lines.author_id
-            GROUP BY authors.id
-            ORDER BY quote_count DESC, line_count DESC, authors.fullname
-            "#
+    async fn replace_aliases(
+        id: &Uuid,
+        aliases: &[String],
+        tr: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), OmniError> {
+        sqlx::query!("DELETE FROM author_aliases WHERE author_id = $1", id)
+            .execute(&mut **tr)
+            .await?;
+        for alias in aliases {
+            sqlx::query!(
+                "INSERT INTO author_aliases (id, author_id, alias) VALUES ($1, $2, $3)",
+                Uuid::now_v7(),
+                id,
+                alias.trim()
+            )
+            .execute(&mut **tr)
+            .await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing;
+
+    use super::{avatar::AvatarImage, *};
+
+    fn patch() -> AuthorPatch {
+        AuthorPatch {
+            fullname: None,
+            codename: None,
+            bio: None,
+            avatar: None,
+            aliases: None,
+        }
+    }
+
+    #[sqlx::test]
+    async fn authors_are_found_by_any_of_their_names(pool: PgPool) {
+        let jake = testing::author("jake", &pool).await;
+        let aliases = AuthorPatch {
+            aliases: Some(vec!["Kuba".into(), " Jakey ".into()]),
+            ..patch()
+        };
+        let jake = jake.patch(aliases, &pool).await.unwrap();
+
+        for name in ["JAKE", "jake", "kuba", "jakey"] {
+            let found = Author::get_by_name(name, &pool).await.unwrap();
+            assert_eq!(found.map(|a| a.id), Some(jake.id), "{name}");
+        }
+        let stored = Author::get_by_id(&jake.id, &pool).await.unwrap().unwrap();
+        assert_eq!(stored.aliases, ["Jakey", "Kuba"]);
+        assert!(Author::get_by_name("jak", &pool).await.unwrap().is_none());
+    }
+
+    #[sqlx::test]
+    async fn names_cannot_be_shared_between_authors(pool: PgPool) {
+        let jake = testing::author("jake", &pool).await;
+        let kuba = testing::author("kuba", &pool).await;
+        for taken in ["JAKE", "Jake"] {
+            let aliases = AuthorPatch {
+                aliases: Some(vec![taken.into()]),
+                ..patch()
+            };
+            let res = kuba.clone().patch(aliases, &pool).await;
+            assert!(matches!(
+                res,
+                Err(OmniError::AuthorValidityError(
+                    AuthorValidityError::NameTaken
+                ))
+            ));
+        }
+        let jake = jake
+            .patch(
+                AuthorPatch {
+                    aliases: Some(vec!["Jakey".into()]),
+                    ..patch()
+                },
+                &pool,
+            )
+            .await
+            .unwrap();
+        let rename = AuthorPatch {
+            codename: Some("jakey".into()),
+            ..patch()
+        };
+        assert!(kuba.clone().patch(rename, &pool).await.is_err());
+
+        // a name that predates these checks resolves by precedence, not by chance
+        sqlx::query!(
+            "UPDATE authors SET codename = 'Jakey' WHERE id = $1",
+            kuba.id
         )
-        .fetch_all(pool)
+        .execute(&pool)
         .await
-        {
-            Ok(opt) => Ok(opt
-                .into_iter()
-                .map(|rec| ExtendedAuthor {
-                    author: Author {
-                        id: rec.id,
-                        fullname: rec.fullname,
-                        codename: rec.codename,
-                    },
-                    quote_count: rec.quote_count.unwrap_or(0) as u32,
-                    line_count: rec.line_count.unwrap_or(0) as u32,
-                })
-                .collect()),
-            Err(e) => Err(OmniError::from(e)),
-        }
+        .unwrap();
+        let found = Author::get_by_name("jakey", &pool).await.unwrap();
+        assert_eq!(found.map(|a| a.id), Some(kuba.id));
+        let found = Author::get_by_name("JAKE", &pool).await.unwrap();
+        assert_eq!(found.map(|a| a.id), Some(jake.id));
+    }
+
+    #[sqlx::test]
+    async fn empty_patch_values_clear_and_urls_replace_uploads(pool: PgPool) {
+        let jake = testing::author("jake", &pool).await;
+        let bio = AuthorPatch {
+            bio: Some("likes tea".into()),
+            ..patch()
+        };
+        let jake = jake.patch(bio, &pool).await.unwrap();
+        let image = AvatarImage {
+            content_type: "image/png".into(),
+            data: vec![0x89, b'P', b'N', b'G'],
+        };
+        let jake = jake.set_avatar(image, &pool).await.unwrap();
+        assert_eq!(jake.avatar, Some(Author::uploaded_avatar_path(&jake.id)));
+
+        let cleared = AuthorPatch {
+            bio: Some(String::new()),
+            avatar: Some("https://example.com/jake.png".into()),
+            ..patch()
+        };
+        let id = jake.patch(cleared, &pool).await.unwrap().id;
+        let stored = Author::get_by_id(&id, &pool).await.unwrap().unwrap();
+        assert_eq!(stored.bio, None);
+        assert_eq!(
+            stored.avatar.as_deref(),
+            Some("https://example.com/jake.png")
+        );
+        assert!(Author::get_avatar(&id, &pool).await.unwrap().is_none());
     }
 }
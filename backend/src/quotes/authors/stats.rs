@@ -0,0 +1,478 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::{
+    omnierror::OmniError,
+    pagination::{Page, PageQuery},
+    user::User,
+};
+
+use super::{Author, ExtendedAuthor};
+
+const CO_SPEAKERS_LIMIT: i64 = 5;
+
+#[derive(Serialize, ToSchema)]
+pub struct AuthorStats {
+    pub first_quoted: Option<NaiveDateTime>,
+    pub last_quoted: Option<NaiveDateTime>,
+    pub word_count: u32,
+    pub longest_line: Option<AuthorLine>,
+    /// Authors sharing the most quotes with this one, most frequent first
+    pub co_speakers: Vec<CoSpeaker>,
+    pub quotes_per_year: Vec<YearlyCount>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AuthorLine {
+    pub quote_id: Uuid,
+    pub content: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CoSpeaker {
+    pub id: Uuid,
+    pub fullname: String,
+    pub shared_quotes: u32,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct YearlyCount {
+    pub year: i32,
+    pub quote_count: u32,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LeaderboardOrder {
+    #[default]
+    Quotes,
+    Lines,
+    Words,
+}
+
+impl LeaderboardOrder {
+    fn as_str(&self) -> &'static str {
+        match self {
+            LeaderboardOrder::Quotes => "quotes",
+            LeaderboardOrder::Lines => "lines",
+            LeaderboardOrder::Words => "words",
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LeaderboardQuery {
+    #[serde(default)]
+    #[param(inline)]
+    pub by: LeaderboardOrder,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct LeaderboardEntry {
+    pub rank: u32,
+    pub author: Author,
+    pub quote_count: u32,
+    pub line_count: u32,
+    pub word_count: u32,
+}
+
+impl ExtendedAuthor {
+    pub async fn get_by_id(
+        id: &Uuid,
+        viewer: &User,
+        pool: &PgPool,
+    ) -> Result<Option<ExtendedAuthor>, OmniError> {
+        match sqlx::query!(
+            r#"
+            SELECT
+                authors.id, authors.fullname, authors.codename, authors.bio, authors.avatar,
+                authors.user_id,
+                ARRAY(
+                    SELECT alias FROM author_aliases
+                    WHERE author_id = authors.id ORDER BY alias
+                ) AS "aliases!",
+                COUNT(DISTINCT quotes.id) AS "quote_count!",
+                COUNT(lines.id) AS "line_count!"
+            FROM authors
+            LEFT JOIN (
+                lines INNER JOIN quotes ON quotes.id = lines.quote_id
+                AND lines.clearance <= $2 AND quote_visible_to(quotes.id, $2, $3)
+            ) ON authors.id = lines.author_id
+            WHERE authors.id = $1 GROUP BY authors.id
+            "#,
+            id,
+            viewer.clearance as i64,
+            viewer.id
+        )
+        .fetch_optional(pool)
+        .await
+        {
+            Ok(Some(rec)) => Ok(Some(ExtendedAuthor {
+                author: Author {
+                    id: rec.id,
+                    fullname: rec.fullname,
+                    codename: rec.codename,
+                    bio: rec.bio,
+                    avatar: rec.avatar,
+                    aliases: rec.aliases,
+                    user_id: rec.user_id,
+                },
+                quote_count: rec.quote_count as u32,
+                line_count: rec.line_count as u32,
+                stats: Some(AuthorStats::get(&rec.id, viewer, pool).await?),
+            })),
+            Ok(None) => Ok(None),
+            Err(e) => Err(OmniError::from(e)),
+        }
+    }
+    pub async fn get_all(viewer: &User, pool: &PgPool) -> Result<Vec<ExtendedAuthor>, OmniError> {
+        match sqlx::query!(
+            r#"
+            SELECT
+                authors.id, authors.fullname, authors.codename, authors.bio, authors.avatar,
+                authors.user_id,
+                ARRAY(
+                    SELECT alias FROM author_aliases
+                    WHERE author_id = authors.id ORDER BY alias
+                ) AS "aliases!",
+                COUNT(DISTINCT quotes.id) AS "quote_count!",
+                COUNT(lines.id) AS "line_count!"
+            FROM authors
+            LEFT JOIN (
+                lines INNER JOIN quotes ON quotes.id = lines.quote_id
+                AND lines.clearance <= $1 AND quote_visible_to(quotes.id, $1, $2)
+            ) ON authors.id = lines.author_id
+            GROUP BY authors.id
+            ORDER BY "quote_count!" DESC, "line_count!" DESC, authors.fullname
+            "#,
+            viewer.clearance as i64,
+            viewer.id
+        )
+        .fetch_all(pool)
+        .await
+        {
+            Ok(opt) => Ok(opt
+                .into_iter()
+                .map(|rec| ExtendedAuthor {
+                    author: Author {
+                        id: rec.id,
+                        fullname: rec.fullname,
+                        codename: rec.codename,
+                        bio: rec.bio,
+                        avatar: rec.avatar,
+                        aliases: rec.aliases,
+                        user_id: rec.user_id,
+                    },
+                    quote_count: rec.quote_count as u32,
+                    line_count: rec.line_count as u32,
+                    stats: None,
+                })
+                .collect()),
+            Err(e) => Err(OmniError::from(e)),
+        }
+    }
+}
+
+impl AuthorStats {
+    pub async fn get(id: &Uuid, viewer: &User, pool: &PgPool) -> Result<AuthorStats, OmniError> {
+        let clearance = viewer.clearance as i64;
+        let totals = sqlx::query!(
+            r#"
+            SELECT
+                MIN(quotes.timestamp) AS first_quoted,
+                MAX(quotes.timestamp) AS last_quoted,
+                COALESCE(SUM(array_length(
+                    regexp_split_to_array(NULLIF(trim(lines.content), ''), '\s+'), 1
+                )), 0) AS "word_count!"
+            FROM lines INNER JOIN quotes ON quotes.id = lines.quote_id
+            WHERE lines.author_id = $1 AND lines.clearance <= $2
+            AND quote_visible_to(quotes.id, $2, $3)
+            "#,
+            id,
+            clearance,
+            viewer.id
+        )
+        .fetch_one(pool)
+        .await?;
+
+        let longest_line = sqlx::query_as!(
+            AuthorLine,
+            r#"
+            SELECT lines.quote_id, lines.content
+            FROM lines INNER JOIN quotes ON quotes.id = lines.quote_id
+            WHERE lines.author_id = $1 AND lines.clearance <= $2
+            AND quote_visible_to(quotes.id, $2, $3)
+            ORDER BY length(lines.content) DESC, quotes.timestamp LIMIT 1
+            "#,
+            id,
+            clearance,
+            viewer.id
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        let co_speakers = sqlx::query!(
+            r#"
+            SELECT authors.id, authors.fullname, COUNT(DISTINCT quotes.id) AS "shared_quotes!"
+            FROM lines AS own
+            INNER JOIN quotes ON quotes.id = own.quote_id
+                AND quote_visible_to(quotes.id, $2, $4)
+            INNER JOIN lines AS other ON other.quote_id = quotes.id AND other.author_id <> $1
+                AND other.clearance <= $2
+            INNER JOIN authors ON authors.id = other.author_id
+            WHERE own.author_id = $1 AND own.clearance <= $2
+            GROUP BY authors.id
+            ORDER BY "shared_quotes!" DESC, authors.fullname LIMIT $3
+            "#,
+            id,
+            clearance,
+            CO_SPEAKERS_LIMIT,
+            viewer.id
+        )
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|rec| CoSpeaker {
+            id: rec.id,
+            fullname: rec.fullname,
+            shared_quotes: rec.shared_quotes as u32,
+        })
+        .collect();
+
+        let quotes_per_year = sqlx::query!(
+            r#"
+            SELECT
+                EXTRACT(YEAR FROM quotes.timestamp)::INTEGER AS "year!",
+                COUNT(DISTINCT quotes.id) AS "quote_count!"
+            FROM lines INNER JOIN quotes ON quotes.id = lines.quote_id
+            WHERE lines.author_id = $1 AND lines.clearance <= $2
+            AND quote_visible_to(quotes.id, $2, $3)
+            GROUP BY "year!" ORDER BY "year!"
+            "#,
+            id,
+            clearance,
+            viewer.id
+        )
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|rec| YearlyCount {
+            year: rec.year,
+            quote_count: rec.quote_count as u32,
+        })
+        .collect();
+
+        Ok(AuthorStats {
+            first_quoted: totals.first_quoted,
+            last_quoted: totals.last_quoted,
+            word_count: totals.word_count as u32,
+            longest_line,
+            co_speakers,
+            quotes_per_year,
+        })
+    }
+}
+
+impl LeaderboardEntry {
+    /// Authors without any quote visible to the viewer are left out.
+    pub async fn get_page(
+        order: LeaderboardOrder,
+        viewer: &User,
+        page: &PageQuery,
+        pool: &PgPool,
+    ) -> Result<Page<LeaderboardEntry>, OmniError> {
+        let rows = sqlx::query!(
+            r#"
+            WITH counts AS (
+                SELECT
+                    lines.author_id,
+                    COUNT(DISTINCT quotes.id) AS quote_count,
+                    COUNT(lines.id) AS line_count,
+                    COALESCE(SUM(array_length(
+                        regexp_split_to_array(NULLIF(trim(lines.content), ''), '\s+'), 1
+                    )), 0) AS word_count
+                FROM lines INNER JOIN quotes ON quotes.id = lines.quote_id
+                WHERE lines.clearance <= $1
+                AND quote_visible_to(quotes.id, $1, $5)
+                GROUP BY lines.author_id
+            ), ranked AS (
+                SELECT counts.*, RANK() OVER (ORDER BY CASE $2
+                    WHEN 'lines' THEN counts.line_count
+                    WHEN 'words' THEN counts.word_count
+                    ELSE counts.quote_count
+                END DESC) AS rank
+                FROM counts
+            )
+            SELECT
+                authors.id, authors.fullname, authors.codename, authors.bio, authors.avatar,
+                authors.user_id,
+                ARRAY(
+                    SELECT alias FROM author_aliases
+                    WHERE author_id = authors.id ORDER BY alias
+                ) AS "aliases!",
+                ranked.quote_count AS "quote_count!",
+                ranked.line_count AS "line_count!",
+                ranked.word_count AS "word_count!",
+                ranked.rank AS "rank!",
+                COUNT(*) OVER () AS "total!"
+            FROM ranked INNER JOIN authors ON authors.id = ranked.author_id
+            ORDER BY ranked.rank, authors.fullname
+            LIMIT $3 OFFSET $4
+            "#,
+            viewer.clearance as i64,
+            order.as_str(),
+            page.limit(),
+            page.offset(),
+            viewer.id
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let total = match rows.first() {
+            Some(rec) => rec.total,
+            None => {
+                sqlx::query_scalar!(
+                    r#"
+                SELECT COUNT(DISTINCT lines.author_id) AS "total!"
+                FROM lines INNER JOIN quotes ON quotes.id = lines.quote_id
+                WHERE lines.clearance <= $1
+                AND quote_visible_to(quotes.id, $1, $2)
+                "#,
+                    viewer.clearance as i64,
+                    viewer.id
+                )
+                .fetch_one(pool)
+                .await?
+            }
+        };
+        let entries = rows
+            .into_iter()
+            .map(|rec| LeaderboardEntry {
+                rank: rec.rank as u32,
+                author: Author {
+                    id: rec.id,
+                    fullname: rec.fullname,
+                    codename: rec.codename,
+                    bio: rec.bio,
+                    avatar: rec.avatar,
+                    aliases: rec.aliases,
+                    user_id: rec.user_id,
+                },
+                quote_count: rec.quote_count as u32,
+                line_count: rec.line_count as u32,
+                word_count: rec.word_count as u32,
+            })
+            .collect();
+        Ok(Page::new(entries, page, total))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::{quotes::moderation::QuoteStatus, testing};
+
+    fn year(y: i32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(y, 6, 1)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn stats_only_count_what_the_viewer_may_see(pool: PgPool) {
+        let admin = testing::user("admin", 5, &[], &pool).await;
+        let viewer = testing::user("viewer", 1, &[], &pool).await;
+        let jk = testing::author("jk", &pool).await;
+        let jan = testing::author("jan", &pool).await;
+
+        let mut quote = testing::quote(&[(&jk, "one two three"), (&jan, "four")], 0);
+        quote.timestamp = year(2020);
+        testing::save(quote, &admin, &pool).await;
+        let mut quote = testing::quote(&[(&jk, "five"), (&jk, "a secret line")], 1);
+        quote.timestamp = year(2022);
+        quote.lines[1].clearance = 2;
+        testing::save(quote, &admin, &pool).await;
+        let mut quote = testing::quote(&[(&jk, "far too secret")], 2);
+        quote.timestamp = year(2019);
+        testing::save(quote, &admin, &pool).await;
+        let mut quote = testing::quote(&[(&jk, "still a draft")], 0);
+        quote.status = QuoteStatus::Draft;
+        testing::save(quote, &admin, &pool).await;
+        let trashed = testing::save(testing::quote(&[(&jk, "gone")], 0), &admin, &pool).await;
+        trashed.trash(&admin.id, &pool).await.unwrap();
+
+        let author = ExtendedAuthor::get_by_id(&jk.id, &viewer, &pool)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!((author.quote_count, author.line_count), (2, 2));
+        let stats = author.stats.unwrap();
+        assert_eq!(stats.first_quoted, Some(year(2020)));
+        assert_eq!(stats.last_quoted, Some(year(2022)));
+        assert_eq!(stats.word_count, 4);
+        assert_eq!(stats.longest_line.unwrap().content, "one two three");
+        let co_speakers: Vec<_> = stats
+            .co_speakers
+            .iter()
+            .map(|c| (c.id, c.shared_quotes))
+            .collect();
+        assert_eq!(co_speakers, vec![(jan.id, 1)]);
+        let years: Vec<_> = stats
+            .quotes_per_year
+            .iter()
+            .map(|y| (y.year, y.quote_count))
+            .collect();
+        assert_eq!(years, vec![(2020, 1), (2022, 1)]);
+
+        let all = ExtendedAuthor::get_all(&viewer, &pool).await.unwrap();
+        let counts: Vec<_> = all.iter().map(|a| (a.author.id, a.quote_count)).collect();
+        assert_eq!(counts, vec![(jk.id, 2), (jan.id, 1)]);
+    }
+
+    #[sqlx::test]
+    async fn leaderboard_ranks_ties_together(pool: PgPool) {
+        let admin = testing::user("admin", 0, &[], &pool).await;
+        let jk = testing::author("jk", &pool).await;
+        let jan = testing::author("jan", &pool).await;
+        let ola = testing::author("ola", &pool).await;
+        testing::author("silent", &pool).await;
+        for lines in [
+            vec![(&jk, "one two"), (&jan, "three")],
+            vec![(&jk, "four"), (&ola, "five six seven")],
+        ] {
+            testing::save(testing::quote(&lines, 0), &admin, &pool).await;
+        }
+        let page = PageQuery {
+            page: 1,
+            per_page: 20,
+        };
+
+        let by_quotes = LeaderboardEntry::get_page(LeaderboardOrder::Quotes, &admin, &page, &pool)
+            .await
+            .unwrap();
+        let ranks: Vec<_> = by_quotes
+            .items
+            .iter()
+            .map(|e| (e.rank, e.author.id))
+            .collect();
+        assert_eq!(ranks, vec![(1, jk.id), (2, jan.id), (2, ola.id)]);
+        assert_eq!(by_quotes.total, 3);
+
+        let by_words = LeaderboardEntry::get_page(LeaderboardOrder::Words, &admin, &page, &pool)
+            .await
+            .unwrap();
+        let ranks: Vec<_> = by_words
+            .items
+            .iter()
+            .map(|e| (e.rank, e.author.id, e.word_count))
+            .collect();
+        assert_eq!(ranks, vec![(1, jk.id, 3), (1, ola.id, 3), (3, jan.id, 1)]);
+    }
+}
@@ -0,0 +1,195 @@
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::{omnierror::OmniError, user::User};
+
+use super::Author;
+
+const SUGGESTIONS_DEFAULT: u32 = 8;
+const SUGGESTIONS_BOUND_UPPER: u32 = 25;
+
+#[derive(Deserialize, IntoParams)]
+#[serde(deny_unknown_fields)]
+pub struct SuggestQuery {
+    /// Part of a full name, codename or alias
+    pub q: String,
+    pub limit: Option<u32>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AuthorSuggestion {
+    pub id: Uuid,
+    pub fullname: String,
+    pub codename: String,
+    /// The name, codename or alias that matched best
+    pub matched: String,
+    /// Quotes visible to the caller
+    pub quote_count: u32,
+}
+
+impl Author {
+    /// Ranks authors by trigram similarity (prefix matches count as close ones),
+    /// with a small bonus for having been quoted recently.
+    pub async fn suggest(
+        query: &SuggestQuery,
+        viewer: &User,
+        pool: &PgPool,
+    ) -> Result<Vec<AuthorSuggestion>, OmniError> {
+        let q = query.q.trim();
+        if q.is_empty() {
+            return Ok(vec![]);
+        }
+        let prefix = format!("{}%", escape_like(q));
+        let limit = query
+            .limit
+            .unwrap_or(SUGGESTIONS_DEFAULT)
+            .clamp(1, SUGGESTIONS_BOUND_UPPER);
+
+        match sqlx::query!(
+            r#"
+            WITH names AS (
+                SELECT id AS author_id, fullname AS name FROM authors
+                WHERE fullname % $1 OR fullname ILIKE $2
+                UNION ALL
+                SELECT id, codename FROM authors
+                WHERE codename % $1 OR codename ILIKE $2
+                UNION ALL
+                SELECT author_id, alias FROM author_aliases
+                WHERE alias % $1 OR alias ILIKE $2
+            ), scored AS (
+                SELECT
+                    author_id, name,
+                    GREATEST(similarity(name, $1), CASE WHEN name ILIKE $2 THEN 0.6 ELSE 0 END)
+                        AS score
+                FROM names
+            ), best AS (
+                SELECT DISTINCT ON (author_id) author_id, name, score FROM scored
+                ORDER BY author_id, score DESC
+            )
+            SELECT
+                authors.id, authors.fullname, authors.codename, best.name AS "matched!",
+                COUNT(DISTINCT quotes.id) AS "quote_count!"
+            FROM best
+            INNER JOIN authors ON authors.id = best.author_id
+            LEFT JOIN (
+                lines INNER JOIN quotes ON quotes.id = lines.quote_id
+                AND lines.clearance <= $3 AND quote_visible_to(quotes.id, $3, $5)
+            ) ON lines.author_id = authors.id
+            GROUP BY authors.id, best.name, best.score
+            ORDER BY
+                best.score + COALESCE(
+                    0.2 / (1 + EXTRACT(EPOCH FROM NOW()::timestamp - MAX(quotes.timestamp)) / 2592000),
+                    0
+                ) DESC,
+                authors.fullname
+            LIMIT $4
+            "#,
+            q,
+            prefix,
+            viewer.clearance as i64,
+            limit as i64,
+            viewer.id
+        )
+        .fetch_all(pool)
+        .await
+        {
+            Ok(rows) => Ok(rows
+                .into_iter()
+                .map(|r| AuthorSuggestion {
+                    id: r.id,
+                    fullname: r.fullname,
+                    codename: r.codename,
+                    matched: r.matched,
+                    quote_count: r.quote_count as u32,
+                })
+                .collect()),
+            Err(e) => Err(e)?,
+        }
+    }
+}
+
+fn escape_like(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn query(q: &str) -> SuggestQuery {
+        SuggestQuery {
+            q: q.to_string(),
+            limit: None,
+        }
+    }
+
+    #[test]
+    fn like_wildcards_are_escaped() {
+        assert_eq!(escape_like(r"50%_a\b"), r"50\%\_a\\b");
+    }
+
+    #[sqlx::test]
+    async fn names_codenames_and_aliases_are_matched_fuzzily(pool: PgPool) {
+        let viewer = testing::user("viewer", 0, &[], &pool).await;
+        let kowalski = testing::author("kowalski", &pool).await;
+        let nowak = testing::author("nowak", &pool).await;
+        sqlx::query!(
+            "INSERT INTO author_aliases (id, author_id, alias) VALUES ($1, $2, 'Kowal')",
+            Uuid::now_v7(),
+            nowak.id
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        testing::author("zielinski", &pool).await;
+
+        let found = Author::suggest(&query("kowalsky"), &viewer, &pool)
+            .await
+            .unwrap();
+        assert_eq!(found[0].id, kowalski.id);
+        assert!(found.iter().all(|s| s.fullname != "ZIELINSKI"));
+
+        let found = Author::suggest(&query(" kowal "), &viewer, &pool)
+            .await
+            .unwrap();
+        let ids: Vec<_> = found.iter().map(|s| s.id).collect();
+        assert!(ids.contains(&kowalski.id) && ids.contains(&nowak.id));
+        let alias = found.iter().find(|s| s.id == nowak.id).unwrap();
+        assert_eq!(alias.matched, "Kowal");
+
+        assert!(Author::suggest(&query("  "), &viewer, &pool)
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(Author::suggest(&query("%"), &viewer, &pool)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[sqlx::test]
+    async fn quote_counts_respect_clearance(pool: PgPool) {
+        let admin = testing::user("admin", 1, &[], &pool).await;
+        let viewer = testing::user("viewer", 0, &[], &pool).await;
+        let author = testing::author("kowalski", &pool).await;
+        testing::save(testing::quote(&[(&author, "hi")], 0), &admin, &pool).await;
+        testing::save(testing::quote(&[(&author, "psst")], 1), &admin, &pool).await;
+
+        let count = |user| {
+            let pool = &pool;
+            async move {
+                Author::suggest(&query("kowalski"), user, pool)
+                    .await
+                    .unwrap()[0]
+                    .quote_count
+            }
+        };
+        assert_eq!(count(&viewer).await, 1);
+        assert_eq!(count(&admin).await, 2);
+    }
+}
@@ -0,0 +1,265 @@
+use super::{
+    deletion::{UNKNOWN_CODENAME, UNKNOWN_NAME},
+    Author, AuthorPatch,
+};
+
+const ALIAS_LEN_BOUND_UPPER: usize = 64;
+pub const AVATAR_SIZE_BOUND_UPPER: usize = 2 * 1024 * 1024;
+pub const AVATAR_CONTENT_TYPES: [&str; 4] = ["image/png", "image/jpeg", "image/webp", "image/gif"];
+
+#[derive(Debug, thiserror::Error)]
+pub enum AuthorValidityError {
+    #[error("Aliases must not be empty and at most {ALIAS_LEN_BOUND_UPPER} characters long.")]
+    AliasLengthInvalid,
+    #[error("Aliases must not repeat the author's full name, codename or each other.")]
+    AliasDuplicated,
+    #[error("Another author already goes by this name.")]
+    NameTaken,
+    #[error("Avatar must be an http(s) URL.")]
+    AvatarUrlInvalid,
+    #[error("Avatar images must be at most {AVATAR_SIZE_BOUND_UPPER} bytes.")]
+    AvatarTooLarge,
+    #[error("Avatar images must be PNG, JPEG, WebP or GIF.")]
+    AvatarUnsupportedType,
+    #[error("An author cannot be merged into themselves.")]
+    MergeIntoSelf,
+    #[error("Both authors are linked to users; unlink one of them before merging.")]
+    MergeBothLinked,
+    #[error("The placeholder author can only be deleted with the forbid policy.")]
+    UnknownPolicyInvalid,
+    #[error("The names \"{UNKNOWN_NAME}\" and \"{UNKNOWN_CODENAME}\" are reserved for the placeholder author.")]
+    NameReserved,
+    #[error("This author is already linked to a user.")]
+    AlreadyLinked,
+    #[error("You are already linked to an author.")]
+    UserAlreadyLinked,
+    #[error("This claim has already been decided.")]
+    ClaimAlreadyDecided,
+}
+
+impl AuthorValidityError {
+    pub fn code(&self) -> &'static str {
+        use AuthorValidityError as AV;
+        match self {
+            AV::AliasLengthInvalid => "alias_length_invalid",
+            AV::AliasDuplicated => "alias_duplicated",
+            AV::NameTaken => "name_taken",
+            AV::AvatarUrlInvalid => "avatar_url_invalid",
+            AV::AvatarTooLarge => "avatar_too_large",
+            AV::AvatarUnsupportedType => "avatar_unsupported_type",
+            AV::MergeIntoSelf => "merge_into_self",
+            AV::MergeBothLinked => "merge_both_linked",
+            AV::UnknownPolicyInvalid => "unknown_policy_invalid",
+            AV::NameReserved => "name_reserved",
+            AV::AlreadyLinked => "author_already_linked",
+            AV::UserAlreadyLinked => "user_already_linked",
+            AV::ClaimAlreadyDecided => "claim_already_decided",
+        }
+    }
+    pub fn field(&self) -> &'static str {
+        use AuthorValidityError as AV;
+        match self {
+            AV::AliasLengthInvalid | AV::AliasDuplicated | AV::NameTaken => "aliases",
+            AV::AvatarUrlInvalid | AV::AvatarTooLarge | AV::AvatarUnsupportedType => "avatar",
+            AV::MergeIntoSelf | AV::MergeBothLinked => "target",
+            AV::UnknownPolicyInvalid => "policy",
+            AV::NameReserved => "fullname",
+            AV::AlreadyLinked | AV::UserAlreadyLinked => "user_id",
+            AV::ClaimAlreadyDecided => "status",
+        }
+    }
+}
+
+impl Author {
+    pub fn is_valid(&self) -> Result<(), AuthorValidityError> {
+        if let Some(avatar) = &self.avatar {
+            is_valid_avatar_url(avatar)?;
+        }
+        if !self.is_unknown() {
+            are_unreserved_names(
+                [&self.fullname, &self.codename]
+                    .into_iter()
+                    .chain(&self.aliases),
+            )?;
+        }
+        are_valid_aliases(&self.aliases, &self.fullname, &self.codename)
+    }
+}
+
+impl AuthorPatch {
+    /// Aliases are checked against the names the author will have after patching.
+    pub fn is_valid(&self, author: &Author) -> Result<(), AuthorValidityError> {
+        if let Some(avatar) = self.avatar.as_deref().filter(|a| !a.is_empty()) {
+            is_valid_avatar_url(avatar)?;
+        }
+        if !author.is_unknown() {
+            are_unreserved_names(
+                [&self.fullname, &self.codename]
+                    .into_iter()
+                    .flatten()
+                    .chain(self.aliases.iter().flatten()),
+            )?;
+        }
+        if let Some(aliases) = &self.aliases {
+            let fullname = self.fullname.as_ref().unwrap_or(&author.fullname);
+            let codename = self.codename.as_ref().unwrap_or(&author.codename);
+            are_valid_aliases(aliases, fullname, codename)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn is_valid_avatar_upload(content_type: &str, len: usize) -> Result<(), AuthorValidityError> {
+    if !AVATAR_CONTENT_TYPES.contains(&content_type) {
+        return Err(AuthorValidityError::AvatarUnsupportedType);
+    }
+    if len > AVATAR_SIZE_BOUND_UPPER {
+        return Err(AuthorValidityError::AvatarTooLarge);
+    }
+    Ok(())
+}
+
+fn is_valid_avatar_url(avatar: &str) -> Result<(), AuthorValidityError> {
+    match avatar.starts_with("https://") || avatar.starts_with("http://") {
+        true => Ok(()),
+        false => Err(AuthorValidityError::AvatarUrlInvalid),
+    }
+}
+
+/// The placeholder's names, in any case, would clash with it or make lookups by name ambiguous.
+fn are_unreserved_names<'a>(
+    mut names: impl Iterator<Item = &'a String>,
+) -> Result<(), AuthorValidityError> {
+    let reserved = |name: &&String| {
+        let name = name.trim().to_lowercase();
+        name == UNKNOWN_NAME.to_lowercase() || name == UNKNOWN_CODENAME
+    };
+    match names.any(|n| reserved(&n)) {
+        true => Err(AuthorValidityError::NameReserved),
+        false => Ok(()),
+    }
+}
+
+fn are_valid_aliases(
+    aliases: &[String],
+    fullname: &str,
+    codename: &str,
+) -> Result<(), AuthorValidityError> {
+    let mut seen = vec![fullname.to_lowercase(), codename.to_lowercase()];
+    for alias in aliases {
+        let alias = alias.trim();
+        if alias.is_empty() || alias.chars().count() > ALIAS_LEN_BOUND_UPPER {
+            return Err(AuthorValidityError::AliasLengthInvalid);
+        }
+        let alias = alias.to_lowercase();
+        if seen.contains(&alias) {
+            return Err(AuthorValidityError::AliasDuplicated);
+        }
+        seen.push(alias);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn aliases(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn aliases_must_be_distinct_from_the_names_and_each_other() {
+        let valid = |names: &[&str]| are_valid_aliases(&aliases(names), "Jake M", "jake");
+        assert!(valid(&["Jakey", "Kuba"]).is_ok());
+        assert!(matches!(
+            valid(&["JAKE"]),
+            Err(AuthorValidityError::AliasDuplicated)
+        ));
+        assert!(matches!(
+            valid(&["Kuba", " kuba "]),
+            Err(AuthorValidityError::AliasDuplicated)
+        ));
+        assert!(matches!(
+            valid(&["  "]),
+            Err(AuthorValidityError::AliasLengthInvalid)
+        ));
+        let long = "x".repeat(ALIAS_LEN_BOUND_UPPER + 1);
+        assert!(matches!(
+            valid(&[&long]),
+            Err(AuthorValidityError::AliasLengthInvalid)
+        ));
+    }
+
+    #[test]
+    fn patched_aliases_are_checked_against_the_new_names() {
+        let author = Author {
+            id: Default::default(),
+            fullname: "Jake M".into(),
+            codename: "jake".into(),
+            bio: None,
+            avatar: None,
+            aliases: vec![],
+            user_id: None,
+        };
+        let patch = AuthorPatch {
+            fullname: None,
+            codename: Some("kuba".into()),
+            bio: None,
+            avatar: None,
+            aliases: Some(aliases(&["jake"])),
+        };
+        assert!(patch.is_valid(&author).is_ok());
+        let patch = AuthorPatch {
+            aliases: Some(aliases(&["Kuba"])),
+            ..patch
+        };
+        assert!(patch.is_valid(&author).is_err());
+    }
+
+    #[test]
+    fn only_the_placeholder_goes_by_unknown() {
+        let mut author = Author {
+            id: Uuid::now_v7(),
+            fullname: "UNKNOWN".into(),
+            codename: "jake".into(),
+            bio: None,
+            avatar: None,
+            aliases: vec![],
+            user_id: None,
+        };
+        assert!(matches!(
+            author.is_valid(),
+            Err(AuthorValidityError::NameReserved)
+        ));
+        let patch = AuthorPatch {
+            fullname: None,
+            codename: None,
+            bio: None,
+            avatar: None,
+            aliases: Some(aliases(&[" Unknown "])),
+        };
+        author.fullname = "Jake M".into();
+        assert!(author.is_valid().is_ok());
+        assert!(patch.is_valid(&author).is_err());
+        author.id = Author::unknown_id();
+        assert!(patch.is_valid(&author).is_ok());
+    }
+
+    #[test]
+    fn avatars_must_be_web_urls_or_small_images() {
+        assert!(is_valid_avatar_url("https://example.com/a.png").is_ok());
+        assert!(is_valid_avatar_url("javascript:alert(1)").is_err());
+        assert!(is_valid_avatar_upload("image/webp", 1024).is_ok());
+        assert!(matches!(
+            is_valid_avatar_upload("image/svg+xml", 1024),
+            Err(AuthorValidityError::AvatarUnsupportedType)
+        ));
+        assert!(matches!(
+            is_valid_avatar_upload("image/png", AVATAR_SIZE_BOUND_UPPER + 1),
+            Err(AuthorValidityError::AvatarTooLarge)
+        ));
+    }
+}
@@ -0,0 +1,425 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Write,
+    sync::{Arc, LazyLock},
+};
+
+use axum::body::Bytes;
+use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
+use resvg::{
+    tiny_skia::{Pixmap, Transform},
+    usvg::{self, fontdb},
+};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use ttf_parser::Face;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{omnierror::OmniError, xml::escape};
+
+use super::Quote;
+
+const SANS: &[u8] = include_bytes!("../../assets/fonts/DejaVuSans.ttf");
+const SANS_BOLD: &[u8] = include_bytes!("../../assets/fonts/DejaVuSans-Bold.ttf");
+const SERIF: &[u8] = include_bytes!("../../assets/fonts/DejaVuSerif.ttf");
+const SERIF_BOLD: &[u8] = include_bytes!("../../assets/fonts/DejaVuSerif-Bold.ttf");
+
+const WIDTH: f32 = 1200.0;
+const PADDING: f32 = 72.0;
+const TEXT_WIDTH: f32 = WIDTH - 2.0 * PADDING;
+const LINE_SIZE: f32 = 38.0;
+const AUTHOR_SIZE: f32 = 24.0;
+const CONTEXT_SIZE: f32 = 24.0;
+const DATE_SIZE: f32 = 22.0;
+const LEADING: f32 = 1.35;
+/// PNGs are rendered at this multiple of the SVG size, so they stay sharp on slides
+const PNG_SCALE: f32 = 2.0;
+/// Wrapped rows of quote lines on one card; anything past them is cut off with an ellipsis
+const MAX_LINE_ROWS: usize = 24;
+const MAX_CONTEXT_ROWS: usize = 4;
+/// Cards are never taller than this, before scaling; the row limits keep them well below it
+const MAX_HEIGHT: f32 = 4096.0;
+const ELLIPSIS: char = '…';
+/// Rendered cards kept in memory; the oldest are dropped first
+const CACHE_ENTRIES: usize = 256;
+
+/// Both fonts of every family are bundled, so cards look the same wherever the server runs.
+static FONTS: LazyLock<Arc<fontdb::Database>> = LazyLock::new(|| {
+    let mut db = fontdb::Database::new();
+    for font in [SANS, SANS_BOLD, SERIF, SERIF_BOLD] {
+        db.load_font_data(font.to_vec());
+    }
+    Arc::new(db)
+});
+
+static FACES: LazyLock<[Face<'static>; 4]> = LazyLock::new(|| {
+    // bundled at compile time and known to parse
+    [SANS, SANS_BOLD, SERIF, SERIF_BOLD].map(|font| Face::parse(font, 0).unwrap())
+});
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CardTheme {
+    #[default]
+    Light,
+    Dark,
+    /// Serif type on warm paper
+    Paper,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CardFormat {
+    Svg,
+    Png,
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[serde(deny_unknown_fields)]
+pub struct CardQuery {
+    #[serde(default)]
+    #[param(inline)]
+    pub theme: CardTheme,
+}
+
+struct Palette {
+    background: &'static str,
+    text: &'static str,
+    muted: &'static str,
+    accent: &'static str,
+    family: &'static str,
+    serif: bool,
+}
+
+impl CardTheme {
+    fn palette(self) -> Palette {
+        match self {
+            CardTheme::Light => Palette {
+                background: "#ffffff",
+                text: "#1f2328",
+                muted: "#6e7781",
+                accent: "#8250df",
+                family: "DejaVu Sans",
+                serif: false,
+            },
+            CardTheme::Dark => Palette {
+                background: "#0d1117",
+                text: "#e6edf3",
+                muted: "#8b949e",
+                accent: "#d2a8ff",
+                family: "DejaVu Sans",
+                serif: false,
+            },
+            CardTheme::Paper => Palette {
+                background: "#f8f1e3",
+                text: "#3b3024",
+                muted: "#8a7a66",
+                accent: "#b5651d",
+                family: "DejaVu Serif",
+                serif: true,
+            },
+        }
+    }
+}
+
+impl CardFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            CardFormat::Svg => "image/svg+xml",
+            CardFormat::Png => "image/png",
+        }
+    }
+    /// Identifies the rendered card; it only changes when something shown on it does.
+    pub fn digest(self, source: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.content_type());
+        hasher.update(source);
+        BASE64_URL_SAFE_NO_PAD.encode(hasher.finalize())
+    }
+    /// Text is turned into paths, so SVGs don't depend on the viewer's fonts either.
+    /// CPU-bound; run it off the async executor.
+    pub fn render(self, source: &str) -> Result<Vec<u8>, OmniError> {
+        let options = usvg::Options {
+            fontdb: FONTS.clone(),
+            ..Default::default()
+        };
+        let tree = usvg::Tree::from_str(source, &options)
+            .map_err(|e| OmniError::CardRenderError(e.to_string()))?;
+        if tree.size().height() > MAX_HEIGHT {
+            return Err(OmniError::CardRenderError("card is too large".to_string()));
+        }
+        match self {
+            CardFormat::Svg => Ok(tree.to_string(&usvg::WriteOptions::default()).into_bytes()),
+            CardFormat::Png => {
+                let size = tree.size().to_int_size().scale_by(PNG_SCALE);
+                let mut pixmap = size
+                    .and_then(|s| Pixmap::new(s.width(), s.height()))
+                    .ok_or(OmniError::CardRenderError("card is too large".to_string()))?;
+                resvg::render(
+                    &tree,
+                    Transform::from_scale(PNG_SCALE, PNG_SCALE),
+                    &mut pixmap.as_mut(),
+                );
+                pixmap
+                    .encode_png()
+                    .map_err(|e| OmniError::CardRenderError(e.to_string()))
+            }
+        }
+    }
+}
+
+/// Rendered cards by digest.
+#[derive(Debug, Default)]
+pub struct CardCache {
+    entries: HashMap<String, Bytes>,
+    order: VecDeque<String>,
+}
+
+impl CardCache {
+    pub fn get(&self, digest: &str) -> Option<Bytes> {
+        self.entries.get(digest).cloned()
+    }
+    pub fn insert(&mut self, digest: String, card: Bytes) {
+        if self.entries.contains_key(&digest) {
+            return;
+        }
+        while self.order.len() >= CACHE_ENTRIES {
+            if let Some(oldest) = self.order.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.order.push_back(digest.clone());
+        self.entries.insert(digest, card);
+    }
+}
+
+fn text_width(text: &str, size: f32, face: &Face) -> f32 {
+    let units = face.units_per_em() as f32;
+    let advance: f32 = text
+        .chars()
+        .map(|c| {
+            face.glyph_index(c)
+                .and_then(|g| face.glyph_hor_advance(g))
+                .map_or(units / 2.0, |a| a as f32)
+        })
+        .sum();
+    advance * size / units
+}
+
+/// Greedy word wrap into at most `max_rows` rows; words too long for a line on their own
+/// are broken between characters. If the text doesn't fit, the last row ends in an ellipsis
+/// and `true` is returned alongside.
+fn wrap(text: &str, size: f32, face: &Face, max_rows: usize) -> (Vec<String>, bool) {
+    let mut rows = vec![];
+    for paragraph in text.lines() {
+        let mut row = String::new();
+        for word in paragraph.split_whitespace() {
+            let candidate = match row.is_empty() {
+                true => word.to_string(),
+                false => format!("{row} {word}"),
+            };
+            if text_width(&candidate, size, face) <= TEXT_WIDTH {
+                row = candidate;
+                continue;
+            }
+            if !row.is_empty() {
+                if rows.len() + 1 >= max_rows {
+                    return (ellipsize(rows, row, size, face), true);
+                }
+                rows.push(std::mem::take(&mut row));
+            }
+            for c in word.chars() {
+                row.push(c);
+                if text_width(&row, size, face) > TEXT_WIDTH {
+                    row.pop();
+                    if rows.len() + 1 >= max_rows {
+                        return (ellipsize(rows, row, size, face), true);
+                    }
+                    rows.push(std::mem::replace(&mut row, c.to_string()));
+                }
+            }
+        }
+        if !row.is_empty() {
+            if rows.len() >= max_rows {
+                let last = rows.pop().unwrap_or_default();
+                return (ellipsize(rows, last, size, face), true);
+            }
+            rows.push(row);
+        }
+    }
+    (rows, false)
+}
+
+/// Ends `rows` with `last` cut short enough to fit an ellipsis.
+fn ellipsize(mut rows: Vec<String>, mut last: String, size: f32, face: &Face) -> Vec<String> {
+    last.push(ELLIPSIS);
+    while last.chars().count() > 1 && text_width(&last, size, face) > TEXT_WIDTH {
+        last.pop();
+        last.pop();
+        last.push(ELLIPSIS);
+    }
+    rows.push(last);
+    rows
+}
+
+impl Quote {
+    /// SVG markup for a card of the quote as it is; redact it for the viewer first.
+    pub fn card_source(&self, theme: CardTheme) -> String {
+        let p = theme.palette();
+        let (regular, bold) = match p.serif {
+            true => (&FACES[2], &FACES[3]),
+            false => (&FACES[0], &FACES[1]),
+        };
+        let mut body = String::new();
+        let mut y = PADDING;
+        let mut rows_left = MAX_LINE_ROWS;
+
+        for line in &self.lines {
+            if rows_left == 0 {
+                // lines that don't fit at all are summed up by a single ellipsis
+                y += LINE_SIZE * LEADING;
+                let _ = write!(
+                    body,
+                    r#"<text x="{PADDING}" y="{y}" font-size="{LINE_SIZE}" fill="{}">{ELLIPSIS}</text>"#,
+                    p.muted
+                );
+                y += LINE_SIZE * 0.75;
+                break;
+            }
+            let author = line.author_id.and_then(|id| self.authors.get(&id));
+            if let Some(author) = author {
+                y += AUTHOR_SIZE;
+                let _ = write!(
+                    body,
+                    r#"<text x="{PADDING}" y="{y}" font-size="{AUTHOR_SIZE}" font-weight="bold" fill="{}">{}</text>"#,
+                    p.accent,
+                    escape(&author.fullname)
+                );
+                y += AUTHOR_SIZE * (LEADING - 1.0);
+            }
+            let colour = match line.redacted {
+                true => p.muted,
+                false => p.text,
+            };
+            let (rows, cut) = wrap(&line.content, LINE_SIZE, regular, rows_left);
+            rows_left -= rows.len();
+            for row in rows {
+                y += LINE_SIZE * LEADING;
+                let _ = write!(
+                    body,
+                    r#"<text x="{PADDING}" y="{y}" font-size="{LINE_SIZE}" fill="{colour}">{}</text>"#,
+                    escape(&row)
+                );
+            }
+            y += LINE_SIZE * 0.75;
+            if cut {
+                break;
+            }
+        }
+        if let Some(context) = &self.context {
+            for row in wrap(context, CONTEXT_SIZE, regular, MAX_CONTEXT_ROWS).0 {
+                y += CONTEXT_SIZE * LEADING;
+                let _ = write!(
+                    body,
+                    r#"<text x="{PADDING}" y="{y}" font-size="{CONTEXT_SIZE}" fill="{}">{}</text>"#,
+                    p.muted,
+                    escape(&row)
+                );
+            }
+            y += CONTEXT_SIZE * 0.75;
+        }
+        y += DATE_SIZE * LEADING;
+        let date = self.timestamp.format("%-d %B %Y").to_string();
+        let _ = write!(
+            body,
+            r#"<text x="{}" y="{y}" font-size="{DATE_SIZE}" font-weight="bold" fill="{}">{}</text>"#,
+            WIDTH - PADDING - text_width(&date, DATE_SIZE, bold),
+            p.muted,
+            date
+        );
+        let height = (y + PADDING).ceil();
+
+        format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{height}" viewBox="0 0 {WIDTH} {height}" font-family="{}"><rect width="{WIDTH}" height="{height}" fill="{}"/><rect width="10" height="{height}" fill="{}"/>{body}</svg>"#,
+            p.family, p.background, p.accent
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::quotes::{placeholder::return_placeholder_random_public_quote, Quote, QuoteLine};
+
+    use super::*;
+
+    fn quote_saying(lines: &[&str]) -> Quote {
+        let mut q = return_placeholder_random_public_quote();
+        let template = q.lines[0].clone();
+        q.lines = lines
+            .iter()
+            .map(|content| QuoteLine {
+                content: content.to_string(),
+                ..template.clone()
+            })
+            .collect();
+        q
+    }
+
+    /// Wrapped rows of quote lines, ellipses included.
+    fn line_rows(source: &str) -> usize {
+        source
+            .matches(&format!(r#"font-size="{LINE_SIZE}""#))
+            .count()
+    }
+
+    #[test]
+    fn short_text_is_not_cut() {
+        let (rows, cut) = wrap("Short and sweet.", LINE_SIZE, &FACES[0], 3);
+        assert_eq!(rows, ["Short and sweet."]);
+        assert!(!cut);
+    }
+
+    #[test]
+    fn long_lines_are_cut_off_with_an_ellipsis() {
+        let q = quote_saying(&["word ".repeat(64 * 1024).as_str()]);
+        let source = q.card_source(CardTheme::Light);
+        assert_eq!(line_rows(&source), MAX_LINE_ROWS);
+        assert!(source.contains(&format!("{ELLIPSIS}</text>")));
+
+        let png = CardFormat::Png.render(&source).unwrap();
+        let height = u32::from_be_bytes(png[20..24].try_into().unwrap());
+        assert!(height as f32 <= MAX_HEIGHT * PNG_SCALE);
+    }
+
+    #[test]
+    fn unbroken_words_are_cut_off_too() {
+        let q = quote_saying(&["a".repeat(320 * 1024).as_str()]);
+        let source = q.card_source(CardTheme::Paper);
+        assert_eq!(line_rows(&source), MAX_LINE_ROWS);
+        assert!(source.contains(&format!("{ELLIPSIS}</text>")));
+    }
+
+    #[test]
+    fn lines_past_the_limit_become_one_ellipsis() {
+        let q = quote_saying(&["Hi."; 1000]);
+        let source = q.card_source(CardTheme::Dark);
+        assert_eq!(line_rows(&source), MAX_LINE_ROWS + 1);
+        assert_eq!(source.matches(ELLIPSIS).count(), 1);
+    }
+
+    #[test]
+    fn text_is_escaped() {
+        let q = quote_saying(&[r#"<script>alert("&")</script>"#]);
+        let source = q.card_source(CardTheme::Light);
+        assert!(source.contains("&lt;script&gt;alert(&quot;&amp;&quot;)&lt;/script&gt;"));
+        assert!(CardFormat::Svg.render(&source).is_ok());
+    }
+
+    #[test]
+    fn oversized_cards_are_refused() {
+        let source = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{}"/>"#,
+            MAX_HEIGHT + 1.0
+        );
+        assert!(CardFormat::Png.render(&source).is_err());
+    }
+}
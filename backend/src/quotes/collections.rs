@@ -0,0 +1,732 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use strum::{AsRefStr, EnumString};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    omnierror::OmniError,
+    user::{auth::crypto::generate_token, User},
+};
+
+use super::{fold_rows, query_quote_rows, Quote};
+
+const NAME_LEN_BOUND_UPPER: usize = 64;
+const DESCRIPTION_LEN_BOUND_UPPER: usize = 1000;
+
+/// A user's curated, ordered list of quotes.
+#[derive(Serialize, ToSchema)]
+pub struct Collection {
+    pub id: Uuid,
+    pub owner_id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub visibility: CollectionVisibility,
+    /// Opens the collection at `/collections/link/{token}`; only shown to the owner
+    pub link_token: Option<String>,
+    /// Users who may see the collection while it is `shared`; only shown to the owner
+    pub shared_with: Vec<Uuid>,
+    pub created: DateTime<Utc>,
+    pub updated: DateTime<Utc>,
+}
+
+/// A collection with the quotes in it the viewer can read, in order.
+#[derive(Serialize, ToSchema)]
+pub struct CollectionDetail {
+    #[serde(flatten)]
+    pub collection: Collection,
+    pub items: Vec<Quote>,
+}
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, ToSchema, AsRefStr, EnumString,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum CollectionVisibility {
+    /// Only the owner
+    #[default]
+    Private,
+    /// The owner and the users it is shared with
+    Shared,
+    /// Anyone with the link
+    PublicLink,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct NewCollection {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub visibility: CollectionVisibility,
+}
+
+/// An empty `description` string clears it. Switching to `public_link` issues a new link,
+/// switching away from it revokes the link.
+#[derive(Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct CollectionPatch {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub visibility: Option<CollectionVisibility>,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct CollectionShares {
+    pub users: Vec<Uuid>,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct CollectionOrder {
+    /// Quotes to put first, in this order; the rest keep their order after them
+    pub quotes: Vec<Uuid>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CollectionValidityError {
+    #[error(
+        "Collection names must not be empty and at most {NAME_LEN_BOUND_UPPER} characters long."
+    )]
+    NameLengthInvalid,
+    #[error(
+        "Collection descriptions must be at most {DESCRIPTION_LEN_BOUND_UPPER} characters long."
+    )]
+    DescriptionTooLong,
+    #[error("User {0} does not exist.")]
+    UserUndefined(Uuid),
+    #[error("Quote {0} is not in the collection, or listed twice.")]
+    OrderInvalid(Uuid),
+}
+
+impl CollectionValidityError {
+    pub fn code(&self) -> &'static str {
+        use CollectionValidityError as CV;
+        match self {
+            CV::NameLengthInvalid => "collection_name_length_invalid",
+            CV::DescriptionTooLong => "collection_description_too_long",
+            CV::UserUndefined(_) => "user_undefined",
+            CV::OrderInvalid(_) => "collection_order_invalid",
+        }
+    }
+    pub fn field(&self) -> &'static str {
+        use CollectionValidityError as CV;
+        match self {
+            CV::NameLengthInvalid => "name",
+            CV::DescriptionTooLong => "description",
+            CV::UserUndefined(_) => "users",
+            CV::OrderInvalid(_) => "quotes",
+        }
+    }
+}
+
+struct CollectionRow {
+    id: Uuid,
+    owner_id: Uuid,
+    name: String,
+    description: Option<String>,
+    visibility: String,
+    link_token: Option<String>,
+    shared_with: Vec<Uuid>,
+    created: DateTime<Utc>,
+    updated: DateTime<Utc>,
+}
+
+impl From<CollectionRow> for Collection {
+    fn from(row: CollectionRow) -> Self {
+        Collection {
+            id: row.id,
+            owner_id: row.owner_id,
+            name: row.name,
+            description: row.description,
+            // guarded by a CHECK constraint
+            visibility: row.visibility.parse().unwrap_or_default(),
+            link_token: row.link_token,
+            shared_with: row.shared_with,
+            created: row.created,
+            updated: row.updated,
+        }
+    }
+}
+
+impl NewCollection {
+    pub fn is_valid(&self) -> Result<(), CollectionValidityError> {
+        is_valid_name(&self.name)?;
+        if let Some(description) = &self.description {
+            is_valid_description(description)?;
+        }
+        Ok(())
+    }
+}
+
+impl Collection {
+    /// By id, collections are only for their owner and, while shared, the users it is shared with.
+    pub fn is_visible_to(&self, viewer: &User) -> bool {
+        self.owner_id == viewer.id
+            || (self.visibility == CollectionVisibility::Shared
+                && self.shared_with.contains(&viewer.id))
+    }
+    /// Hides the link and the shares from anyone but the owner.
+    pub fn for_viewer(mut self, viewer: Option<&User>) -> Collection {
+        if viewer.is_none_or(|u| u.id != self.owner_id) {
+            self.link_token = None;
+            self.shared_with = vec![];
+        }
+        self
+    }
+    pub async fn get_by_id(id: &Uuid, pool: &PgPool) -> Result<Option<Collection>, OmniError> {
+        match sqlx::query_as!(
+            CollectionRow,
+            r#"
+            SELECT
+                id, owner_id, name, description, visibility, link_token, created, updated,
+                ARRAY(
+                    SELECT user_id FROM collection_shares
+                    WHERE collection_id = collections.id ORDER BY user_id
+                ) AS "shared_with!"
+            FROM collections WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(pool)
+        .await
+        {
+            Ok(row) => Ok(row.map(Collection::from)),
+            Err(e) => Err(e)?,
+        }
+    }
+    pub async fn get_by_link(token: &str, pool: &PgPool) -> Result<Option<Collection>, OmniError> {
+        match sqlx::query_as!(
+            CollectionRow,
+            r#"
+            SELECT
+                id, owner_id, name, description, visibility, link_token, created, updated,
+                ARRAY(
+                    SELECT user_id FROM collection_shares
+                    WHERE collection_id = collections.id ORDER BY user_id
+                ) AS "shared_with!"
+            FROM collections WHERE link_token = $1 AND visibility = 'public_link'
+            "#,
+            token
+        )
+        .fetch_optional(pool)
+        .await
+        {
+            Ok(row) => Ok(row.map(Collection::from)),
+            Err(e) => Err(e)?,
+        }
+    }
+    /// The user's own collections and those shared with them, most recently updated first.
+    pub async fn get_visible_to(
+        user_id: &Uuid,
+        pool: &PgPool,
+    ) -> Result<Vec<Collection>, OmniError> {
+        match sqlx::query_as!(
+            CollectionRow,
+            r#"
+            SELECT
+                id, owner_id, name, description, visibility, link_token, created, updated,
+                ARRAY(
+                    SELECT user_id FROM collection_shares
+                    WHERE collection_id = collections.id ORDER BY user_id
+                ) AS "shared_with!"
+            FROM collections
+            WHERE owner_id = $1 OR (
+                visibility = 'shared' AND id IN (
+                    SELECT collection_id FROM collection_shares WHERE user_id = $1
+                )
+            )
+            ORDER BY updated DESC, id
+            "#,
+            user_id
+        )
+        .fetch_all(pool)
+        .await
+        {
+            Ok(rows) => Ok(rows.into_iter().map(Collection::from).collect()),
+            Err(e) => Err(e)?,
+        }
+    }
+    /// Quotes in the collection the viewer can read, in order; the rest are left out.
+    pub async fn get_items(
+        &self,
+        viewer: Option<&User>,
+        pool: &PgPool,
+    ) -> Result<Vec<Quote>, OmniError> {
+        match query_quote_rows!(
+            r#"
+                INNER JOIN collection_items
+                    ON collection_items.quote_id = quotes.id
+                    AND collection_items.collection_id = $1
+                WHERE quote_visible_to(quotes.id, $2, $3)
+                ORDER BY collection_items.position ASC, quotes.id ASC, lines.position ASC
+            "#,
+            self.id,
+            viewer.map(|u| u.clearance).unwrap_or(0) as i64,
+            viewer.map(|u| u.id)
+        )
+        .fetch_all(pool)
+        .await
+        {
+            Ok(rows) => Ok(fold_rows(rows)),
+            Err(e) => Err(e)?,
+        }
+    }
+    pub async fn create(
+        collection: NewCollection,
+        owner_id: &Uuid,
+        pool: &PgPool,
+    ) -> Result<Collection, OmniError> {
+        let description = collection.description.filter(|d| !d.is_empty());
+        let link_token = match collection.visibility {
+            CollectionVisibility::PublicLink => Some(generate_token()),
+            _ => None,
+        };
+        match sqlx::query_as!(
+            CollectionRow,
+            r#"
+            INSERT INTO collections (id, owner_id, name, description, visibility, link_token)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING
+                id, owner_id, name, description, visibility, link_token, created, updated,
+                ARRAY[]::uuid[] AS "shared_with!"
+            "#,
+            Uuid::now_v7(),
+            owner_id,
+            collection.name.trim(),
+            description,
+            collection.visibility.as_ref(),
+            link_token
+        )
+        .fetch_one(pool)
+        .await
+        {
+            Ok(row) => Ok(row.into()),
+            Err(e) => Err(e)?,
+        }
+    }
+    pub async fn patch(
+        mut self,
+        patch: CollectionPatch,
+        pool: &PgPool,
+    ) -> Result<Collection, OmniError> {
+        if let Some(name) = patch.name {
+            is_valid_name(&name)?;
+            self.name = name.trim().to_string();
+        }
+        if let Some(description) = patch.description {
+            is_valid_description(&description)?;
+            self.description = Some(description).filter(|d| !d.is_empty());
+        }
+        if let Some(visibility) = patch.visibility {
+            self.link_token = match visibility {
+                CollectionVisibility::PublicLink => {
+                    self.link_token.or_else(|| Some(generate_token()))
+                }
+                _ => None,
+            };
+            self.visibility = visibility;
+        }
+        match sqlx::query_scalar!(
+            r#"
+            UPDATE collections
+            SET name = $1, description = $2, visibility = $3, link_token = $4, updated = NOW()
+            WHERE id = $5 RETURNING updated
+            "#,
+            self.name,
+            self.description,
+            self.visibility.as_ref(),
+            self.link_token,
+            self.id
+        )
+        .fetch_one(pool)
+        .await
+        {
+            Ok(updated) => {
+                self.updated = updated;
+                Ok(self)
+            }
+            Err(e) => Err(e)?,
+        }
+    }
+    pub async fn destroy(self, pool: &PgPool) -> Result<(), OmniError> {
+        let mut tr = pool.begin().await?;
+        let destroyed = async {
+            sqlx::query!(
+                "DELETE FROM collection_items WHERE collection_id = $1",
+                self.id
+            )
+            .execute(&mut *tr)
+            .await?;
+            sqlx::query!(
+                "DELETE FROM collection_shares WHERE collection_id = $1",
+                self.id
+            )
+            .execute(&mut *tr)
+            .await?;
+            sqlx::query!("DELETE FROM collections WHERE id = $1", self.id)
+                .execute(&mut *tr)
+                .await?;
+            Ok::<(), OmniError>(())
+        }
+        .await;
+        match destroyed {
+            Ok(()) => Ok(tr.commit().await?),
+            Err(e) => {
+                tr.rollback().await?;
+                Err(e)
+            }
+        }
+    }
+    /// Replaces who the collection is shared with; takes effect while it is `shared`.
+    pub async fn set_shares(
+        mut self,
+        mut users: Vec<Uuid>,
+        pool: &PgPool,
+    ) -> Result<Collection, OmniError> {
+        users.sort();
+        users.dedup();
+        users.retain(|u| *u != self.owner_id);
+        let missing = sqlx::query_scalar!(
+            r#"
+            SELECT wanted AS "id!" FROM UNNEST($1::uuid[]) AS wanted
+            WHERE wanted NOT IN (SELECT id FROM users)
+            LIMIT 1
+            "#,
+            &users
+        )
+        .fetch_optional(pool)
+        .await?;
+        if let Some(id) = missing {
+            return Err(CollectionValidityError::UserUndefined(id))?;
+        }
+
+        let mut tr = pool.begin().await?;
+        let set = async {
+            sqlx::query!(
+                "DELETE FROM collection_shares WHERE collection_id = $1",
+                self.id
+            )
+            .execute(&mut *tr)
+            .await?;
+            sqlx::query!(
+                r#"
+                INSERT INTO collection_shares (collection_id, user_id)
+                SELECT $1, user_id FROM UNNEST($2::uuid[]) AS user_id
+                "#,
+                self.id,
+                &users
+            )
+            .execute(&mut *tr)
+            .await?;
+            let updated = sqlx::query_scalar!(
+                "UPDATE collections SET updated = NOW() WHERE id = $1 RETURNING updated",
+                self.id
+            )
+            .fetch_one(&mut *tr)
+            .await?;
+            Ok::<DateTime<Utc>, OmniError>(updated)
+        }
+        .await;
+        match set {
+            Ok(updated) => {
+                tr.commit().await?;
+                self.shared_with = users;
+                self.updated = updated;
+                Ok(self)
+            }
+            Err(e) => {
+                tr.rollback().await?;
+                Err(e)
+            }
+        }
+    }
+    /// Appends the quote; adding it again leaves it where it is.
+    pub async fn add_item(&self, quote_id: &Uuid, pool: &PgPool) -> Result<(), OmniError> {
+        match sqlx::query!(
+            r#"
+            WITH touched AS (UPDATE collections SET updated = NOW() WHERE id = $1)
+            INSERT INTO collection_items (collection_id, quote_id, position)
+            SELECT $1, $2, COALESCE(MAX(position) + 1, 0)
+            FROM collection_items WHERE collection_id = $1
+            ON CONFLICT (collection_id, quote_id) DO NOTHING
+            "#,
+            self.id,
+            quote_id
+        )
+        .execute(pool)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e)?,
+        }
+    }
+    pub async fn remove_item(&self, quote_id: &Uuid, pool: &PgPool) -> Result<(), OmniError> {
+        match sqlx::query!(
+            r#"
+            WITH touched AS (UPDATE collections SET updated = NOW() WHERE id = $1)
+            DELETE FROM collection_items WHERE collection_id = $1 AND quote_id = $2
+            "#,
+            self.id,
+            quote_id
+        )
+        .execute(pool)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e)?,
+        }
+    }
+    /// Moves the listed quotes to the front in the given order; the rest keep theirs after them.
+    pub async fn reorder(&self, order: CollectionOrder, pool: &PgPool) -> Result<(), OmniError> {
+        let current = sqlx::query_scalar!(
+            r#"
+            SELECT quote_id FROM collection_items WHERE collection_id = $1
+            ORDER BY position, quote_id
+            "#,
+            self.id
+        )
+        .fetch_all(pool)
+        .await?;
+        let mut listed: Vec<Uuid> = vec![];
+        for id in order.quotes {
+            if !current.contains(&id) || listed.contains(&id) {
+                return Err(CollectionValidityError::OrderInvalid(id))?;
+            }
+            listed.push(id);
+        }
+        let rest = current.into_iter().filter(|id| !listed.contains(id));
+        let ordered: Vec<Uuid> = listed.iter().copied().chain(rest).collect();
+
+        let mut tr = pool.begin().await?;
+        let reordered = async {
+            sqlx::query!(
+                r#"
+                UPDATE collection_items SET position = ordered.position - 1
+                FROM UNNEST($2::uuid[]) WITH ORDINALITY AS ordered(quote_id, position)
+                WHERE collection_items.collection_id = $1
+                AND collection_items.quote_id = ordered.quote_id
+                "#,
+                self.id,
+                &ordered
+            )
+            .execute(&mut *tr)
+            .await?;
+            sqlx::query!(
+                "UPDATE collections SET updated = NOW() WHERE id = $1",
+                self.id
+            )
+            .execute(&mut *tr)
+            .await?;
+            Ok::<(), OmniError>(())
+        }
+        .await;
+        match reordered {
+            Ok(()) => Ok(tr.commit().await?),
+            Err(e) => {
+                tr.rollback().await?;
+                Err(e)
+            }
+        }
+    }
+}
+
+fn is_valid_name(name: &str) -> Result<(), CollectionValidityError> {
+    match name.trim().is_empty() || name.chars().count() > NAME_LEN_BOUND_UPPER {
+        true => Err(CollectionValidityError::NameLengthInvalid),
+        false => Ok(()),
+    }
+}
+
+fn is_valid_description(description: &str) -> Result<(), CollectionValidityError> {
+    match description.chars().count() > DESCRIPTION_LEN_BOUND_UPPER {
+        true => Err(CollectionValidityError::DescriptionTooLong),
+        false => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn named(name: &str, visibility: CollectionVisibility) -> NewCollection {
+        NewCollection {
+            name: name.to_string(),
+            description: None,
+            visibility,
+        }
+    }
+
+    fn to(visibility: CollectionVisibility) -> CollectionPatch {
+        CollectionPatch {
+            name: None,
+            description: None,
+            visibility: Some(visibility),
+        }
+    }
+
+    async fn item_ids(collection: &Collection, viewer: Option<&User>, pool: &PgPool) -> Vec<Uuid> {
+        let items = collection.get_items(viewer, pool).await.unwrap();
+        items.into_iter().map(|q| q.id).collect()
+    }
+
+    #[test]
+    fn names_and_descriptions_are_checked() {
+        assert!(named("Best of", CollectionVisibility::Private)
+            .is_valid()
+            .is_ok());
+        assert!(named("  ", CollectionVisibility::Private)
+            .is_valid()
+            .is_err());
+        let mut long = named("Best of", CollectionVisibility::Private);
+        long.description = Some("x".repeat(DESCRIPTION_LEN_BOUND_UPPER + 1));
+        assert!(matches!(
+            long.is_valid(),
+            Err(CollectionValidityError::DescriptionTooLong)
+        ));
+    }
+
+    #[sqlx::test]
+    async fn shared_collections_are_visible_while_shared(pool: PgPool) {
+        let owner = testing::user("owner", 0, &[], &pool).await;
+        let friend = testing::user("friend", 0, &[], &pool).await;
+        let stranger = testing::user("stranger", 0, &[], &pool).await;
+        let collection = Collection::create(
+            named("Best of", CollectionVisibility::Shared),
+            &owner.id,
+            &pool,
+        )
+        .await
+        .unwrap();
+
+        let id = collection.id;
+        let res = collection.set_shares(vec![Uuid::nil()], &pool).await;
+        assert!(matches!(
+            res,
+            Err(OmniError::CollectionValidityError(
+                CollectionValidityError::UserUndefined(_)
+            ))
+        ));
+        let collection = Collection::get_by_id(&id, &pool).await.unwrap().unwrap();
+        let collection = collection
+            .set_shares(vec![friend.id, owner.id, friend.id], &pool)
+            .await
+            .unwrap();
+        assert_eq!(collection.shared_with, vec![friend.id]);
+        assert!(collection.is_visible_to(&owner));
+        assert!(collection.is_visible_to(&friend));
+        assert!(!collection.is_visible_to(&stranger));
+        assert_eq!(
+            Collection::get_visible_to(&friend.id, &pool)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+        let seen = collection.for_viewer(Some(&friend));
+        assert!(seen.shared_with.is_empty());
+
+        let collection = Collection::get_by_id(&seen.id, &pool)
+            .await
+            .unwrap()
+            .unwrap();
+        let collection = collection
+            .patch(to(CollectionVisibility::Private), &pool)
+            .await
+            .unwrap();
+        assert!(!collection.is_visible_to(&friend));
+        assert!(Collection::get_visible_to(&friend.id, &pool)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[sqlx::test]
+    async fn links_are_issued_and_revoked_with_the_visibility(pool: PgPool) {
+        let owner = testing::user("owner", 0, &[], &pool).await;
+        let collection = Collection::create(
+            named("Best of", CollectionVisibility::Private),
+            &owner.id,
+            &pool,
+        )
+        .await
+        .unwrap();
+        assert_eq!(collection.link_token, None);
+
+        let collection = collection
+            .patch(to(CollectionVisibility::PublicLink), &pool)
+            .await
+            .unwrap();
+        let token = collection.link_token.clone().unwrap();
+        let found = Collection::get_by_link(&token, &pool)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.id, collection.id);
+        assert_eq!(found.for_viewer(None).link_token, None);
+
+        collection
+            .patch(to(CollectionVisibility::Shared), &pool)
+            .await
+            .unwrap();
+        assert!(Collection::get_by_link(&token, &pool)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[sqlx::test]
+    async fn items_are_ordered_and_filtered_for_the_viewer(pool: PgPool) {
+        let owner = testing::user("owner", 1, &[], &pool).await;
+        let author = testing::author("jk", &pool).await;
+        let collection = Collection::create(
+            named("Best of", CollectionVisibility::PublicLink),
+            &owner.id,
+            &pool,
+        )
+        .await
+        .unwrap();
+        let mut quotes = vec![];
+        for clearance in [0, 1, 0] {
+            let quote = testing::quote(&[(&author, "hi")], clearance);
+            quotes.push(testing::save(quote, &owner, &pool).await.id);
+        }
+        for id in quotes.iter().chain(&quotes[..1]) {
+            collection.add_item(id, &pool).await.unwrap();
+        }
+        assert_eq!(item_ids(&collection, Some(&owner), &pool).await, quotes);
+        assert_eq!(
+            item_ids(&collection, None, &pool).await,
+            vec![quotes[0], quotes[2]]
+        );
+
+        let order = |quotes: Vec<Uuid>| CollectionOrder { quotes };
+        collection
+            .reorder(order(vec![quotes[2], quotes[1]]), &pool)
+            .await
+            .unwrap();
+        assert_eq!(
+            item_ids(&collection, Some(&owner), &pool).await,
+            vec![quotes[2], quotes[1], quotes[0]]
+        );
+        for invalid in [vec![quotes[0], quotes[0]], vec![Uuid::nil()]] {
+            assert!(matches!(
+                collection.reorder(order(invalid), &pool).await,
+                Err(OmniError::CollectionValidityError(
+                    CollectionValidityError::OrderInvalid(_)
+                ))
+            ));
+        }
+
+        collection.remove_item(&quotes[1], &pool).await.unwrap();
+        assert_eq!(
+            item_ids(&collection, Some(&owner), &pool).await,
+            vec![quotes[2], quotes[0]]
+        );
+    }
+}
@@ -0,0 +1,584 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{PgPool, Postgres, Transaction};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    groups::Group,
+    logs::{Log, LogAction},
+    omnierror::OmniError,
+    pagination::{Page, PageQuery},
+    user::{
+        attributes::UserAttribute as UA,
+        auth::error::AuthError,
+        notifications::{Notification, NotificationKind},
+        User,
+    },
+};
+
+use super::Quote;
+
+const CONTENT_LEN_BOUND_UPPER: usize = 2000;
+const EDIT_WINDOW_MINUTES: i64 = 15;
+/// Mentions beyond this many in one comment are ignored
+const MENTIONS_LIMIT: usize = 10;
+
+/// A comment on a quote; only top-level comments can be replied to.
+#[derive(Serialize, ToSchema)]
+pub struct Comment {
+    pub id: Uuid,
+    pub quote_id: Uuid,
+    /// Set on replies
+    pub parent_id: Option<Uuid>,
+    pub user_id: Uuid,
+    pub user_handle: String,
+    pub content: String,
+    /// Users mentioned by `@handle` who can see the quote
+    pub mentions: Vec<Uuid>,
+    pub created: DateTime<Utc>,
+    pub edited: Option<DateTime<Utc>>,
+    /// Replies to a top-level comment, oldest first; always empty on replies
+    #[schema(no_recursion)]
+    pub replies: Vec<Comment>,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct NewComment {
+    pub content: String,
+    /// A top-level comment on the same quote to reply to
+    #[serde(default)]
+    pub parent_id: Option<Uuid>,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct CommentPatch {
+    pub content: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CommentValidityError {
+    #[error("Comments must not be empty and at most {CONTENT_LEN_BOUND_UPPER} characters long.")]
+    ContentLengthInvalid,
+    #[error("Comments can only be edited for {EDIT_WINDOW_MINUTES} minutes after posting.")]
+    EditWindowClosed,
+    #[error("Replies must be to a top-level comment on the same quote.")]
+    ParentInvalid,
+}
+
+impl CommentValidityError {
+    pub fn code(&self) -> &'static str {
+        use CommentValidityError as CV;
+        match self {
+            CV::ContentLengthInvalid => "comment_length_invalid",
+            CV::EditWindowClosed => "comment_edit_window_closed",
+            CV::ParentInvalid => "comment_parent_invalid",
+        }
+    }
+    pub fn field(&self) -> &'static str {
+        use CommentValidityError as CV;
+        match self {
+            CV::ContentLengthInvalid => "content",
+            CV::EditWindowClosed => "created",
+            CV::ParentInvalid => "parent_id",
+        }
+    }
+}
+
+struct CommentRow {
+    id: Uuid,
+    quote_id: Uuid,
+    parent_id: Option<Uuid>,
+    user_id: Uuid,
+    user_handle: String,
+    content: String,
+    mentions: Vec<Uuid>,
+    created: DateTime<Utc>,
+    edited: Option<DateTime<Utc>>,
+}
+
+impl From<CommentRow> for Comment {
+    fn from(row: CommentRow) -> Self {
+        Comment {
+            id: row.id,
+            quote_id: row.quote_id,
+            parent_id: row.parent_id,
+            user_id: row.user_id,
+            user_handle: row.user_handle,
+            content: row.content,
+            mentions: row.mentions,
+            created: row.created,
+            edited: row.edited,
+            replies: vec![],
+        }
+    }
+}
+
+fn is_valid_content(content: &str) -> Result<String, CommentValidityError> {
+    let content = content.trim();
+    if content.is_empty() || content.chars().count() > CONTENT_LEN_BOUND_UPPER {
+        return Err(CommentValidityError::ContentLengthInvalid);
+    }
+    Ok(content.to_string())
+}
+
+fn is_handle_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-'
+}
+
+/// Every distinct `@handle` in the text, in order of appearance.
+/// Trailing dots and dashes are taken as punctuation, not part of the handle.
+fn mentioned_handles(content: &str) -> Vec<&str> {
+    let mut handles: Vec<&str> = vec![];
+    let mut previous = None;
+    for (i, c) in content.char_indices() {
+        if c == '@' && !previous.is_some_and(is_handle_char) {
+            let rest = &content[i + 1..];
+            let end = rest.find(|c| !is_handle_char(c)).unwrap_or(rest.len());
+            let handle = rest[..end].trim_end_matches(['.', '_', '-']);
+            if !handle.is_empty() && !handles.contains(&handle) {
+                handles.push(handle);
+            }
+        }
+        previous = Some(c);
+    }
+    handles
+}
+
+impl Comment {
+    /// Resolves mentions to users who can read the quote; anyone else is silently left out.
+    async fn resolve_mentions(
+        content: &str,
+        quote: &Quote,
+        pool: &PgPool,
+    ) -> Result<Vec<Uuid>, OmniError> {
+        let mut mentions = vec![];
+        for handle in mentioned_handles(content).into_iter().take(MENTIONS_LIMIT) {
+            let user = match User::get_by_handle(handle, pool).await? {
+                Some(user) => user,
+                None => continue,
+            };
+            let groups = Group::ids_of(Some(&user), pool).await?;
+            if quote.is_readable_by(&user, &groups) {
+                mentions.push(user.id);
+            }
+        }
+        Ok(mentions)
+    }
+    async fn notify_mentioned(
+        &self,
+        users: &[Uuid],
+        tr: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), OmniError> {
+        for user_id in users.iter().filter(|u| **u != self.user_id) {
+            Notification::send(
+                user_id,
+                NotificationKind::Mentioned,
+                Some(self.quote_id),
+                json!({ "comment_id": self.id, "by": self.user_handle }),
+                &mut **tr,
+            )
+            .await?;
+        }
+        Ok(())
+    }
+    pub async fn get_by_id(id: &Uuid, pool: &PgPool) -> Result<Option<Comment>, OmniError> {
+        match sqlx::query_as!(
+            CommentRow,
+            r#"
+            SELECT
+                quote_comments.id, quote_id, parent_id, user_id, users.handle AS user_handle,
+                content, mentions, created, edited
+            FROM quote_comments INNER JOIN users ON users.id = quote_comments.user_id
+            WHERE quote_comments.id = $1
+            "#,
+            id
+        )
+        .fetch_optional(pool)
+        .await
+        {
+            Ok(row) => Ok(row.map(Comment::from)),
+            Err(e) => Err(e)?,
+        }
+    }
+    /// Top-level comments on the quote, oldest first, each with all of its replies.
+    pub async fn get_page(
+        quote_id: &Uuid,
+        page: &PageQuery,
+        pool: &PgPool,
+    ) -> Result<Page<Comment>, OmniError> {
+        let total = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) AS "total!" FROM quote_comments
+            WHERE quote_id = $1 AND parent_id IS NULL
+            "#,
+            quote_id
+        )
+        .fetch_one(pool)
+        .await?;
+        let rows = sqlx::query_as!(
+            CommentRow,
+            r#"
+            WITH threads AS (
+                SELECT id FROM quote_comments
+                WHERE quote_id = $1 AND parent_id IS NULL
+                ORDER BY created, id LIMIT $2 OFFSET $3
+            )
+            SELECT
+                quote_comments.id, quote_id, parent_id, user_id, users.handle AS user_handle,
+                content, mentions, created, edited
+            FROM quote_comments INNER JOIN users ON users.id = quote_comments.user_id
+            WHERE quote_comments.id IN (SELECT id FROM threads)
+            OR quote_comments.parent_id IN (SELECT id FROM threads)
+            ORDER BY created, quote_comments.id
+            "#,
+            quote_id,
+            page.limit(),
+            page.offset()
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let mut threads: Vec<Comment> = vec![];
+        for row in rows {
+            match row.parent_id {
+                None => threads.push(row.into()),
+                Some(parent) => {
+                    if let Some(thread) = threads.iter_mut().find(|t| t.id == parent) {
+                        thread.replies.push(row.into());
+                    }
+                }
+            }
+        }
+        Ok(Page::new(threads, page, total))
+    }
+    /// Mentioned users are notified once the comment is saved.
+    pub async fn create(
+        quote: &Quote,
+        comment: NewComment,
+        user: &User,
+        pool: &PgPool,
+    ) -> Result<Comment, OmniError> {
+        let content = is_valid_content(&comment.content)?;
+        if let Some(parent_id) = comment.parent_id {
+            match Comment::get_by_id(&parent_id, pool).await? {
+                Some(parent) if parent.quote_id == quote.id && parent.parent_id.is_none() => (),
+                _ => return Err(CommentValidityError::ParentInvalid)?,
+            }
+        }
+        let mentions = Comment::resolve_mentions(&content, quote, pool).await?;
+        let mut new = Comment {
+            id: Uuid::now_v7(),
+            quote_id: quote.id,
+            parent_id: comment.parent_id,
+            user_id: user.id,
+            user_handle: user.handle.clone(),
+            content,
+            mentions,
+            created: Utc::now(),
+            edited: None,
+            replies: vec![],
+        };
+
+        let mut tr = pool.begin().await?;
+        let created = async {
+            let created = sqlx::query_scalar!(
+                r#"
+                INSERT INTO quote_comments (id, quote_id, parent_id, user_id, content, mentions)
+                VALUES ($1, $2, $3, $4, $5, $6) RETURNING created
+                "#,
+                new.id,
+                new.quote_id,
+                new.parent_id,
+                new.user_id,
+                new.content,
+                &new.mentions
+            )
+            .fetch_one(&mut *tr)
+            .await?;
+            new.notify_mentioned(&new.mentions, &mut tr).await?;
+            Ok::<DateTime<Utc>, OmniError>(created)
+        }
+        .await;
+        match created {
+            Ok(created) => {
+                tr.commit().await?;
+                new.created = created;
+                Ok(new)
+            }
+            Err(e) => {
+                tr.rollback().await?;
+                Err(e)
+            }
+        }
+    }
+    /// Only newly mentioned users are notified of an edit.
+    pub async fn edit(
+        mut self,
+        patch: CommentPatch,
+        quote: &Quote,
+        pool: &PgPool,
+    ) -> Result<Comment, OmniError> {
+        if Utc::now() - self.created > Duration::minutes(EDIT_WINDOW_MINUTES) {
+            return Err(CommentValidityError::EditWindowClosed)?;
+        }
+        let content = is_valid_content(&patch.content)?;
+        let mentions = Comment::resolve_mentions(&content, quote, pool).await?;
+        let added: Vec<Uuid> = mentions
+            .iter()
+            .filter(|m| !self.mentions.contains(m))
+            .copied()
+            .collect();
+
+        let mut tr = pool.begin().await?;
+        let edited = async {
+            let edited = sqlx::query_scalar!(
+                r#"
+                UPDATE quote_comments SET content = $1, mentions = $2, edited = NOW()
+                WHERE id = $3 RETURNING edited AS "edited!"
+                "#,
+                content,
+                &mentions,
+                self.id
+            )
+            .fetch_one(&mut *tr)
+            .await?;
+            self.notify_mentioned(&added, &mut tr).await?;
+            Ok::<DateTime<Utc>, OmniError>(edited)
+        }
+        .await;
+        match edited {
+            Ok(edited) => {
+                tr.commit().await?;
+                self.content = content;
+                self.mentions = mentions;
+                self.edited = Some(edited);
+                Ok(self)
+            }
+            Err(e) => {
+                tr.rollback().await?;
+                Err(e)
+            }
+        }
+    }
+    /// Takes the replies with it, so a comment with replies can only be deleted by a moderator.
+    /// Deleting someone else's comment, or any replies, is recorded in the audit log.
+    pub async fn destroy(self, actor: &User, pool: &PgPool) -> Result<(), OmniError> {
+        let mut tr = pool.begin().await?;
+        let destroyed = async {
+            // keeps replies from being added meanwhile
+            sqlx::query!(
+                "SELECT id FROM quote_comments WHERE id = $1 FOR UPDATE",
+                self.id
+            )
+            .fetch_optional(&mut *tr)
+            .await?;
+            let replies = sqlx::query!(
+                r#"
+                SELECT id, user_id, content FROM quote_comments
+                WHERE parent_id = $1 ORDER BY created, id
+                "#,
+                self.id
+            )
+            .fetch_all(&mut *tr)
+            .await?;
+            if !replies.is_empty() && !actor.has_permission(UA::QuotesModerateCommentsPermission) {
+                return Err(AuthError::CommentHasReplies)?;
+            }
+            sqlx::query!("DELETE FROM quote_comments WHERE parent_id = $1", self.id)
+                .execute(&mut *tr)
+                .await?;
+            sqlx::query!("DELETE FROM quote_comments WHERE id = $1", self.id)
+                .execute(&mut *tr)
+                .await?;
+            if actor.id != self.user_id || !replies.is_empty() {
+                let replies: Vec<_> = replies
+                    .iter()
+                    .map(|r| json!({ "id": r.id, "user_id": r.user_id, "content": r.content }))
+                    .collect();
+                Log::record(
+                    &actor.id,
+                    &self.id,
+                    LogAction::CommentsDeleted,
+                    json!({
+                        "quote_id": self.quote_id,
+                        "user_id": self.user_id,
+                        "content": self.content,
+                        "replies": replies,
+                    }),
+                    &mut *tr,
+                )
+                .await?;
+            }
+            Ok::<(), OmniError>(())
+        }
+        .await;
+        match destroyed {
+            Ok(()) => Ok(tr.commit().await?),
+            Err(e) => {
+                tr.rollback().await?;
+                Err(e)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn new(content: &str, parent_id: Option<Uuid>) -> NewComment {
+        NewComment {
+            content: content.to_string(),
+            parent_id,
+        }
+    }
+
+    #[test]
+    fn mentions_are_found_once_without_trailing_punctuation() {
+        assert_eq!(
+            mentioned_handles("@ana, @bob.smith. and @ana again; mail@example.com @-"),
+            vec!["ana", "bob.smith"]
+        );
+        assert!(mentioned_handles("no one @").is_empty());
+    }
+
+    #[test]
+    fn content_is_trimmed_and_bounded() {
+        assert_eq!(is_valid_content("  hi \n").unwrap(), "hi");
+        assert!(is_valid_content(" \n ").is_err());
+        assert!(is_valid_content(&"x".repeat(CONTENT_LEN_BOUND_UPPER)).is_ok());
+        assert!(is_valid_content(&"x".repeat(CONTENT_LEN_BOUND_UPPER + 1)).is_err());
+    }
+
+    #[sqlx::test]
+    async fn only_readers_of_the_quote_are_mentioned(pool: PgPool) {
+        let poster = testing::user("poster", 1, &[], &pool).await;
+        let reader = testing::user("reader", 1, &[], &pool).await;
+        let outsider = testing::user("outsider", 0, &[], &pool).await;
+        let author = testing::author("jk", &pool).await;
+        let quote = testing::save(testing::quote(&[(&author, "psst")], 1), &poster, &pool).await;
+
+        let comment = Comment::create(
+            &quote,
+            new("@reader @outsider @nobody @poster", None),
+            &poster,
+            &pool,
+        )
+        .await
+        .unwrap();
+        assert_eq!(comment.mentions, vec![reader.id, poster.id]);
+        let notified =
+            sqlx::query_scalar!("SELECT user_id FROM notifications WHERE kind = 'mentioned'")
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+        assert_eq!(notified, vec![reader.id]);
+        assert!(!notified.contains(&outsider.id));
+    }
+
+    #[sqlx::test]
+    async fn replies_are_threaded_one_level_deep(pool: PgPool) {
+        let user = testing::user("user", 0, &[], &pool).await;
+        let author = testing::author("jk", &pool).await;
+        let quote = testing::save(testing::quote(&[(&author, "hi")], 0), &user, &pool).await;
+        let other = testing::save(testing::quote(&[(&author, "yo")], 0), &user, &pool).await;
+
+        let first = Comment::create(&quote, new("first", None), &user, &pool)
+            .await
+            .unwrap();
+        let reply = Comment::create(&quote, new("reply", Some(first.id)), &user, &pool)
+            .await
+            .unwrap();
+        Comment::create(&quote, new("second", None), &user, &pool)
+            .await
+            .unwrap();
+        for (on, parent) in [
+            (&quote, reply.id),
+            (&other, first.id),
+            (&quote, Uuid::now_v7()),
+        ] {
+            let res = Comment::create(on, new("nope", Some(parent)), &user, &pool).await;
+            assert!(matches!(
+                res,
+                Err(OmniError::CommentValidityError(
+                    CommentValidityError::ParentInvalid
+                ))
+            ));
+        }
+
+        let page = PageQuery {
+            page: 1,
+            per_page: 1,
+        };
+        let threads = Comment::get_page(&quote.id, &page, &pool).await.unwrap();
+        assert_eq!(threads.total, 2);
+        assert_eq!(threads.items.len(), 1);
+        assert_eq!(threads.items[0].content, "first");
+        let replies: Vec<_> = threads.items[0].replies.iter().map(|r| r.id).collect();
+        assert_eq!(replies, vec![reply.id]);
+
+        let res = Comment::get_by_id(&first.id, &pool)
+            .await
+            .unwrap()
+            .unwrap()
+            .destroy(&user, &pool)
+            .await;
+        assert!(matches!(
+            res,
+            Err(OmniError::AuthError(AuthError::CommentHasReplies))
+        ));
+        let moderator =
+            testing::user("mod", 0, &[UA::QuotesModerateCommentsPermission], &pool).await;
+        first.destroy(&moderator, &pool).await.unwrap();
+        assert!(Comment::get_by_id(&reply.id, &pool)
+            .await
+            .unwrap()
+            .is_none());
+        let logs = Log::get_recent(None, &pool).await.unwrap();
+        assert_eq!(logs[0].details["replies"][0]["id"], json!(reply.id));
+    }
+
+    #[sqlx::test]
+    async fn comments_can_only_be_edited_for_a_while(pool: PgPool) {
+        let user = testing::user("user", 0, &[], &pool).await;
+        let friend = testing::user("friend", 0, &[], &pool).await;
+        let author = testing::author("jk", &pool).await;
+        let quote = testing::save(testing::quote(&[(&author, "hi")], 0), &user, &pool).await;
+        let comment = Comment::create(&quote, new("hi", None), &user, &pool)
+            .await
+            .unwrap();
+
+        let patch = |content: &str| CommentPatch {
+            content: content.to_string(),
+        };
+        let comment = comment
+            .edit(patch("hi @friend"), &quote, &pool)
+            .await
+            .unwrap();
+        assert_eq!(comment.mentions, vec![friend.id]);
+        assert!(comment.edited.is_some());
+
+        sqlx::query!(
+            "UPDATE quote_comments SET created = NOW() - INTERVAL '16 minutes' WHERE id = $1",
+            comment.id
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let comment = Comment::get_by_id(&comment.id, &pool)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(matches!(
+            comment.edit(patch("too late"), &quote, &pool).await,
+            Err(OmniError::CommentValidityError(
+                CommentValidityError::EditWindowClosed
+            ))
+        ));
+    }
+}
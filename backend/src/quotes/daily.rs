@@ -0,0 +1,323 @@
+use chrono::{NaiveDate, Utc};
+use chrono_tz::Tz;
+use serde::Serialize;
+use sqlx::PgPool;
+use tracing::warn;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    omnierror::OmniError,
+    pagination::{Page, PageQuery},
+};
+
+use super::{fold_rows, query_quote_rows, Quote};
+
+const TIMEZONE_DEFAULT: Tz = Tz::UTC;
+const NO_REPEAT_DAYS_DEFAULT: i32 = 30;
+
+/// The public quote everyone is shown on a given calendar day.
+#[derive(Serialize, ToSchema)]
+pub struct QuoteOfTheDay {
+    pub day: NaiveDate,
+    pub quote: Quote,
+}
+
+/// Read from `QUOTE_OF_THE_DAY_TIMEZONE`, an IANA name such as `Europe/Berlin`.
+fn timezone() -> Tz {
+    match std::env::var("QUOTE_OF_THE_DAY_TIMEZONE") {
+        Ok(name) => match name.parse::<Tz>() {
+            Ok(tz) => tz,
+            Err(_) => {
+                warn!(
+                    "QUOTE_OF_THE_DAY_TIMEZONE is not a known timezone, defaulting to {}.",
+                    TIMEZONE_DEFAULT
+                );
+                TIMEZONE_DEFAULT
+            }
+        },
+        Err(_) => TIMEZONE_DEFAULT,
+    }
+}
+
+/// Read from `QUOTE_OF_THE_DAY_NO_REPEAT_DAYS`; a quote is not picked again within this many days
+/// unless every public quote has been.
+fn no_repeat_days() -> i32 {
+    match std::env::var("QUOTE_OF_THE_DAY_NO_REPEAT_DAYS") {
+        Ok(days) => match days.parse::<i32>() {
+            Ok(days) if days >= 0 => days,
+            _ => {
+                warn!(
+                    "QUOTE_OF_THE_DAY_NO_REPEAT_DAYS is not a valid number of days, defaulting to {}.",
+                    NO_REPEAT_DAYS_DEFAULT
+                );
+                NO_REPEAT_DAYS_DEFAULT
+            }
+        },
+        Err(_) => NO_REPEAT_DAYS_DEFAULT,
+    }
+}
+
+impl QuoteOfTheDay {
+    /// The current calendar day in the configured timezone.
+    pub fn today() -> NaiveDate {
+        Utc::now().with_timezone(&timezone()).date_naive()
+    }
+    /// Earlier days whose quote, among those with `tag` if given, is still public;
+    /// most recent first.
+    pub async fn get_history(
+        tag: Option<&str>,
+        page: &PageQuery,
+        pool: &PgPool,
+    ) -> Result<Page<QuoteOfTheDay>, OmniError> {
+        let today = QuoteOfTheDay::today();
+        let tag = tag.unwrap_or_default();
+        let total = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) AS "total!" FROM quote_of_the_day
+            INNER JOIN quotes ON quotes.id = quote_of_the_day.quote_id
+            WHERE quote_of_the_day.day < $1 AND quote_of_the_day.tag = $2
+            AND quote_visible_to(quotes.id, 0, NULL)
+            "#,
+            today,
+            tag
+        )
+        .fetch_one(pool)
+        .await?;
+        let days = sqlx::query!(
+            r#"
+            SELECT quote_of_the_day.day, quote_of_the_day.quote_id FROM quote_of_the_day
+            INNER JOIN quotes ON quotes.id = quote_of_the_day.quote_id
+            WHERE quote_of_the_day.day < $1 AND quote_of_the_day.tag = $2
+            AND quote_visible_to(quotes.id, 0, NULL)
+            ORDER BY quote_of_the_day.day DESC LIMIT $3 OFFSET $4
+            "#,
+            today,
+            tag,
+            page.limit(),
+            page.offset()
+        )
+        .fetch_all(pool)
+        .await?;
+        let ids: Vec<Uuid> = days.iter().map(|d| d.quote_id).collect();
+        let quotes = Quote::get_public_by_ids(&ids, pool).await?;
+
+        let items = days
+            .into_iter()
+            .filter_map(|d| {
+                let quote = quotes.iter().find(|q| q.id == d.quote_id)?.clone();
+                Some(QuoteOfTheDay { day: d.day, quote })
+            })
+            .collect();
+        Ok(Page::new(items, page, total))
+    }
+}
+
+impl Quote {
+    /// The quote for the day, picked on first request and kept for the rest of it,
+    /// so every screen shows the same one. Should the quote stop being public during the day,
+    /// another is picked in its place. With a tag, the pick is among the quotes with it,
+    /// kept apart from the untagged one. `None` when no public quote qualifies at all.
+    pub async fn get_of_the_day(
+        day: &NaiveDate,
+        tag: Option<&str>,
+        pool: &PgPool,
+    ) -> Result<Option<Quote>, OmniError> {
+        let tag = tag.unwrap_or_default();
+        let chosen = sqlx::query_scalar!(
+            "SELECT quote_id FROM quote_of_the_day WHERE day = $1 AND tag = $2",
+            day,
+            tag
+        )
+        .fetch_optional(pool)
+        .await?;
+        if let Some(id) = chosen {
+            if let Some(q) = Quote::get_public_by_ids(&[id], pool).await?.pop() {
+                return Ok(Some(q));
+            }
+            sqlx::query!(
+                "DELETE FROM quote_of_the_day WHERE day = $1 AND tag = $2 AND quote_id = $3",
+                day,
+                tag,
+                id
+            )
+            .execute(pool)
+            .await?;
+        }
+
+        // Quotes not shown within the no-repeat window come first, in an order fixed by the day;
+        // failing those, the one shown longest ago. Concurrent first requests agree on the pick,
+        // and whichever inserts it first wins.
+        sqlx::query!(
+            r#"
+            INSERT INTO quote_of_the_day (day, tag, quote_id)
+            SELECT $1, $3, quotes.id FROM quotes
+            LEFT JOIN (
+                SELECT quote_id, MAX(day) AS last_shown FROM quote_of_the_day
+                WHERE day < $1 AND tag = $3 GROUP BY quote_id
+            ) AS shown ON shown.quote_id = quotes.id
+            WHERE quote_visible_to(quotes.id, 0, NULL)
+            AND ($3 = '' OR EXISTS (
+                SELECT 1 FROM quote_tags WHERE quote_id = quotes.id AND tag = $3
+            ))
+            ORDER BY
+                COALESCE(shown.last_shown > $1::date - $2::integer, FALSE),
+                CASE WHEN shown.last_shown > $1::date - $2::integer THEN shown.last_shown END,
+                md5(quotes.id::text || $1::date::text)
+            LIMIT 1
+            ON CONFLICT (day, tag) DO NOTHING
+            "#,
+            day,
+            no_repeat_days(),
+            tag
+        )
+        .execute(pool)
+        .await?;
+        let chosen = sqlx::query_scalar!(
+            "SELECT quote_id FROM quote_of_the_day WHERE day = $1 AND tag = $2",
+            day,
+            tag
+        )
+        .fetch_optional(pool)
+        .await?;
+        match chosen {
+            Some(id) => Ok(Quote::get_public_by_ids(&[id], pool).await?.pop()),
+            None => Ok(None),
+        }
+    }
+    /// Quotes anyone may read, by id; any others are left out.
+    async fn get_public_by_ids(ids: &[Uuid], pool: &PgPool) -> Result<Vec<Quote>, OmniError> {
+        let rows = query_quote_rows!(
+            r#"
+                WHERE quotes.id = ANY($1)
+                AND quote_visible_to(quotes.id, 0, NULL)
+                ORDER BY quotes.id DESC, lines.position ASC
+            "#,
+            ids
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(fold_rows(rows))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Days;
+
+    use super::*;
+    use crate::testing;
+
+    #[sqlx::test]
+    async fn every_public_quote_is_shown_before_one_repeats(pool: PgPool) {
+        let admin = testing::user("admin", 1, &[], &pool).await;
+        let author = testing::author("jk", &pool).await;
+        let today = QuoteOfTheDay::today();
+        assert!(Quote::get_of_the_day(&today, None, &pool)
+            .await
+            .unwrap()
+            .is_none());
+
+        let mut public = vec![];
+        for content in ["one", "two", "three"] {
+            let quote = testing::quote(&[(&author, content)], 0);
+            public.push(testing::save(quote, &admin, &pool).await.id);
+        }
+        testing::save(testing::quote(&[(&author, "psst")], 1), &admin, &pool).await;
+
+        let start = today - Days::new(10);
+        let mut shown = vec![];
+        for offset in 0..4 {
+            let day = start + Days::new(offset);
+            let quote = Quote::get_of_the_day(&day, None, &pool)
+                .await
+                .unwrap()
+                .unwrap();
+            let again = Quote::get_of_the_day(&day, None, &pool)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(quote.id, again.id);
+            shown.push(quote.id);
+        }
+        let mut first_three = shown[..3].to_vec();
+        first_three.sort();
+        public.sort();
+        assert_eq!(first_three, public);
+        // the one shown longest ago comes back first
+        assert_eq!(shown[3], shown[0]);
+    }
+
+    #[sqlx::test]
+    async fn a_pick_that_stops_being_public_is_replaced(pool: PgPool) {
+        let admin = testing::user("admin", 0, &[], &pool).await;
+        let author = testing::author("jk", &pool).await;
+        for content in ["one", "two"] {
+            testing::save(testing::quote(&[(&author, content)], 0), &admin, &pool).await;
+        }
+        let today = QuoteOfTheDay::today();
+        let yesterday = today - Days::new(1);
+        let picked = Quote::get_of_the_day(&yesterday, None, &pool)
+            .await
+            .unwrap()
+            .unwrap();
+        let other = Quote::get_of_the_day(&today, None, &pool)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_ne!(picked.id, other.id);
+
+        let page = PageQuery {
+            page: 1,
+            per_page: 20,
+        };
+        let history = QuoteOfTheDay::get_history(None, &page, &pool)
+            .await
+            .unwrap();
+        let days: Vec<_> = history.items.iter().map(|d| (d.day, d.quote.id)).collect();
+        assert_eq!(days, vec![(yesterday, picked.id)]);
+
+        let trashed = picked.trash(&admin.id, &pool).await.unwrap();
+        assert_eq!(
+            QuoteOfTheDay::get_history(None, &page, &pool)
+                .await
+                .unwrap()
+                .total,
+            0
+        );
+        let replaced = Quote::get_of_the_day(&yesterday, None, &pool)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(replaced.id, other.id);
+        assert_ne!(replaced.id, trashed.id);
+    }
+
+    #[sqlx::test]
+    async fn a_tag_gets_its_own_pick(pool: PgPool) {
+        let admin = testing::user("admin", 0, &[], &pool).await;
+        let author = testing::author("jk", &pool).await;
+        let mut tagged = vec![];
+        for (content, tags) in [("one", vec!["dnd".to_string()]), ("two", vec![])] {
+            let mut quote = testing::quote(&[(&author, content)], 0);
+            quote.tags = tags;
+            tagged.push(testing::save(quote, &admin, &pool).await);
+        }
+        let today = QuoteOfTheDay::today();
+        for _ in 0..2 {
+            let pick = Quote::get_of_the_day(&today, Some("dnd"), &pool)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(pick.id, tagged[0].id);
+        }
+        Quote::get_of_the_day(&today, None, &pool)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(Quote::get_of_the_day(&today, Some("work"), &pool)
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
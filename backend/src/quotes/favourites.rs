@@ -0,0 +1,90 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{omnierror::OmniError, user::User};
+
+use super::{fold_rows, query_quote_rows, Quote};
+
+impl Quote {
+    pub async fn star(&self, user_id: &Uuid, pool: &PgPool) -> Result<(), OmniError> {
+        match sqlx::query!(
+            r#"
+            INSERT INTO quote_favourites (user_id, quote_id) VALUES ($1, $2)
+            ON CONFLICT (user_id, quote_id) DO NOTHING
+            "#,
+            user_id,
+            self.id
+        )
+        .execute(pool)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e)?,
+        }
+    }
+    pub async fn unstar(&self, user_id: &Uuid, pool: &PgPool) -> Result<(), OmniError> {
+        match sqlx::query!(
+            "DELETE FROM quote_favourites WHERE user_id = $1 AND quote_id = $2",
+            user_id,
+            self.id
+        )
+        .execute(pool)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e)?,
+        }
+    }
+    /// Starred quotes the user can still read, most recently starred first.
+    pub async fn get_favourites_of(viewer: &User, pool: &PgPool) -> Result<Vec<Quote>, OmniError> {
+        match query_quote_rows!(
+            r#"
+                INNER JOIN quote_favourites
+                    ON quote_favourites.quote_id = quotes.id AND quote_favourites.user_id = $2
+                WHERE quote_visible_to(quotes.id, $1, $2)
+                ORDER BY quote_favourites.created DESC, quotes.id DESC, lines.position ASC
+            "#,
+            viewer.clearance as i64,
+            viewer.id
+        )
+        .fetch_all(pool)
+        .await
+        {
+            Ok(rows) => Ok(fold_rows(rows)),
+            Err(e) => Err(e)?,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[sqlx::test]
+    async fn favourites_are_limited_to_readable_quotes(pool: PgPool) {
+        let admin = testing::user("admin", 1, &[], &pool).await;
+        let fan = testing::user("fan", 0, &[], &pool).await;
+        let author = testing::author("jk", &pool).await;
+        let first = testing::save(testing::quote(&[(&author, "one")], 0), &admin, &pool).await;
+        let second = testing::save(testing::quote(&[(&author, "two")], 0), &admin, &pool).await;
+        let secret = testing::save(testing::quote(&[(&author, "psst")], 1), &admin, &pool).await;
+        for quote in [&first, &second, &first, &secret] {
+            quote.star(&fan.id, &pool).await.unwrap();
+        }
+
+        let ids = |user| {
+            let pool = &pool;
+            async move {
+                let quotes = Quote::get_favourites_of(user, pool).await.unwrap();
+                quotes.into_iter().map(|q| q.id).collect::<Vec<_>>()
+            }
+        };
+        assert_eq!(ids(&fan).await, vec![second.id, first.id]);
+
+        second.unstar(&fan.id, &pool).await.unwrap();
+        second.unstar(&fan.id, &pool).await.unwrap();
+        assert_eq!(ids(&fan).await, vec![first.id]);
+        assert!(ids(&admin).await.is_empty());
+    }
+}
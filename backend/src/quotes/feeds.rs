@@ -0,0 +1,526 @@
+use atom_syndication as atom;
+use chrono::{DateTime, Utc};
+use rss::extension::dublincore::DublinCoreExtension;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tracing::warn;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::{
+    omnierror::OmniError,
+    user::{
+        auth::crypto::{generate_token, hash_token},
+        User,
+    },
+    xml::escape,
+};
+
+use super::{authors::Author, fold_rows, query_quote_rows, Quote};
+
+/// How many of the newest quotes a feed lists
+const FEED_SIZE: i64 = 50;
+/// Entry titles are the first line, cut off after this many characters
+const TITLE_LENGTH: usize = 80;
+const SITE_NAME: &str = "Quote Engine";
+const SITE_URL_DEFAULT: &str = "http://localhost:3000";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FeedFormat {
+    Atom,
+    Rss,
+    /// JSON Feed 1.1
+    Json,
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[serde(deny_unknown_fields)]
+pub struct FeedQuery {
+    /// Only quotes with a line by this author the reader can read
+    pub author: Option<Uuid>,
+    /// Only quotes with this tag, compared case-insensitively
+    pub tag: Option<String>,
+    /// A feed token; the feed then lists what its owner can read, not just public quotes
+    pub token: Option<String>,
+}
+
+/// Lets feed readers, which cannot log in, fetch a user's private feeds.
+#[derive(Serialize, ToSchema)]
+pub struct FeedToken {
+    pub created: DateTime<Utc>,
+}
+
+/// A freshly created feed token; the token cannot be looked up again later.
+#[derive(Serialize, ToSchema)]
+pub struct CreatedFeedToken {
+    #[serde(flatten)]
+    pub feed_token: FeedToken,
+    /// Add as `?token=` to any feed URL
+    pub token: String,
+}
+
+/// The newest quotes a reader can read, ready to be rendered in any format.
+pub struct Feed {
+    pub title: String,
+    /// Stable IRI identifying the feed, whatever the format
+    pub id: String,
+    pub quotes: Vec<Quote>,
+}
+
+#[derive(Serialize)]
+struct JsonFeed<'a> {
+    version: &'static str,
+    title: &'a str,
+    home_page_url: String,
+    authors: Vec<JsonFeedAuthor>,
+    items: Vec<JsonFeedItem>,
+}
+
+#[derive(Serialize)]
+struct JsonFeedItem {
+    id: String,
+    title: String,
+    content_text: String,
+    date_published: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    authors: Vec<JsonFeedAuthor>,
+}
+
+#[derive(Serialize)]
+struct JsonFeedAuthor {
+    name: String,
+}
+
+/// Read from `FEED_SITE_URL`, where the frontend is served; feeds link back to it.
+fn site_url() -> String {
+    match std::env::var("FEED_SITE_URL") {
+        Ok(url) if url.starts_with("http://") || url.starts_with("https://") => {
+            url.trim_end_matches('/').to_string()
+        }
+        Ok(_) => {
+            warn!("FEED_SITE_URL is not an http(s) URL, defaulting to {SITE_URL_DEFAULT}.");
+            SITE_URL_DEFAULT.to_string()
+        }
+        Err(_) => SITE_URL_DEFAULT.to_string(),
+    }
+}
+
+impl FeedFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            FeedFormat::Atom => "application/atom+xml; charset=utf-8",
+            FeedFormat::Rss => "application/rss+xml; charset=utf-8",
+            FeedFormat::Json => "application/feed+json; charset=utf-8",
+        }
+    }
+}
+
+impl FeedToken {
+    pub async fn get_for(user: &User, pool: &PgPool) -> Result<Option<FeedToken>, OmniError> {
+        match sqlx::query_as!(
+            FeedToken,
+            "SELECT created FROM feed_tokens WHERE user_id = $1",
+            user.id
+        )
+        .fetch_optional(pool)
+        .await
+        {
+            Ok(t) => Ok(t),
+            Err(e) => Err(e)?,
+        }
+    }
+    /// Whoever the token belongs to, unless it has since been replaced or revoked.
+    pub async fn get_user(token: &str, pool: &PgPool) -> Result<Option<User>, OmniError> {
+        let user_id = sqlx::query_scalar!(
+            "SELECT user_id FROM feed_tokens WHERE token = $1",
+            hash_token(token)
+        )
+        .fetch_optional(pool)
+        .await?;
+        match user_id {
+            Some(id) => User::get_by_id(&id, pool).await,
+            None => Ok(None),
+        }
+    }
+    /// Replaces any earlier token of the user. The unhashed token is only ever returned from here.
+    pub async fn create(user: &User, pool: &PgPool) -> Result<CreatedFeedToken, OmniError> {
+        let token = generate_token();
+        match sqlx::query_as!(
+            FeedToken,
+            r#"
+            INSERT INTO feed_tokens (user_id, token) VALUES ($1, $2)
+            ON CONFLICT (user_id) DO UPDATE SET token = EXCLUDED.token, created = NOW()
+            RETURNING created
+            "#,
+            user.id,
+            hash_token(&token)
+        )
+        .fetch_one(pool)
+        .await
+        {
+            Ok(feed_token) => Ok(CreatedFeedToken { feed_token, token }),
+            Err(e) => Err(e)?,
+        }
+    }
+    /// Whether the user had a token to revoke.
+    pub async fn revoke(user: &User, pool: &PgPool) -> Result<bool, OmniError> {
+        match sqlx::query!("DELETE FROM feed_tokens WHERE user_id = $1", user.id)
+            .execute(pool)
+            .await
+        {
+            Ok(res) => Ok(res.rows_affected() > 0),
+            Err(e) => Err(e)?,
+        }
+    }
+}
+
+impl Feed {
+    /// Without a reader, only public quotes are listed. Lines the reader cannot read are redacted.
+    pub async fn load(
+        author: Option<&Author>,
+        tag: Option<&str>,
+        reader: Option<&User>,
+        pool: &PgPool,
+    ) -> Result<Feed, OmniError> {
+        let quotes = Quote::get_newest(author.map(|a| a.id), tag, reader, pool)
+            .await?
+            .into_iter()
+            .map(|q| q.redact_for(reader))
+            .collect();
+
+        let mut title = SITE_NAME.to_string();
+        let mut id = format!("{}/feeds/quotes", site_url());
+        if let Some(author) = author {
+            title.push_str(&format!(": quotes by {}", author.fullname));
+            id.push_str(&format!("/authors/{}", author.id));
+        }
+        if let Some(tag) = tag {
+            title.push_str(&format!(" tagged {tag}"));
+            id.push_str(&format!("/tags/{tag}"));
+        }
+        if let Some(reader) = reader {
+            title.push_str(&format!(" (for {})", reader.handle));
+            id.push_str(&format!("/users/{}", reader.id));
+        }
+        Ok(Feed { title, id, quotes })
+    }
+    /// When the newest quote was added, or now for an empty feed.
+    fn updated(&self) -> DateTime<Utc> {
+        self.quotes
+            .first()
+            .map(Quote::added)
+            .unwrap_or_else(Utc::now)
+    }
+    pub fn render(&self, format: FeedFormat) -> Result<String, OmniError> {
+        let rendered = match format {
+            FeedFormat::Atom => self.atom().write_to(Vec::new()).map_err(|e| e.to_string()),
+            FeedFormat::Rss => self.rss().write_to(Vec::new()).map_err(|e| e.to_string()),
+            FeedFormat::Json => serde_json::to_vec(&self.json()).map_err(|e| e.to_string()),
+        };
+        match rendered {
+            Ok(bytes) => Ok(String::from_utf8(bytes)?),
+            Err(e) => Err(OmniError::FeedRenderError(e)),
+        }
+    }
+    fn atom(&self) -> atom::Feed {
+        atom::Feed {
+            title: atom::Text::plain(&self.title),
+            id: self.id.clone(),
+            updated: self.updated().fixed_offset(),
+            // entries whose every author is redacted fall back to this
+            authors: vec![atom::Person {
+                name: SITE_NAME.to_string(),
+                ..Default::default()
+            }],
+            links: vec![atom::Link {
+                href: site_url(),
+                rel: "alternate".to_string(),
+                ..Default::default()
+            }],
+            entries: self
+                .quotes
+                .iter()
+                .map(|q| atom::Entry {
+                    title: atom::Text::plain(q.feed_title()),
+                    id: q.urn(),
+                    updated: q.added().fixed_offset(),
+                    published: Some(q.added().fixed_offset()),
+                    authors: q
+                        .feed_authors()
+                        .into_iter()
+                        .map(|name| atom::Person {
+                            name,
+                            ..Default::default()
+                        })
+                        .collect(),
+                    content: Some(atom::Content {
+                        value: Some(q.feed_text()),
+                        content_type: Some("text".to_string()),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+    fn rss(&self) -> rss::Channel {
+        rss::Channel {
+            title: self.title.clone(),
+            link: site_url(),
+            description: format!("The newest quotes on {SITE_NAME}"),
+            last_build_date: Some(self.updated().to_rfc2822()),
+            items: self
+                .quotes
+                .iter()
+                .map(|q| rss::Item {
+                    title: Some(q.feed_title()),
+                    description: Some(q.feed_html()),
+                    guid: Some(rss::Guid {
+                        value: q.urn(),
+                        permalink: false,
+                    }),
+                    pub_date: Some(q.added().to_rfc2822()),
+                    // <author> must be an email address, so names go in dc:creator
+                    dublin_core_ext: Some(DublinCoreExtension {
+                        creators: q.feed_authors(),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+    fn json(&self) -> JsonFeed<'_> {
+        JsonFeed {
+            version: "https://jsonfeed.org/version/1.1",
+            title: &self.title,
+            home_page_url: site_url(),
+            authors: vec![JsonFeedAuthor {
+                name: SITE_NAME.to_string(),
+            }],
+            items: self
+                .quotes
+                .iter()
+                .map(|q| JsonFeedItem {
+                    id: q.urn(),
+                    title: q.feed_title(),
+                    content_text: q.feed_text(),
+                    date_published: q.added(),
+                    authors: q
+                        .feed_authors()
+                        .into_iter()
+                        .map(|name| JsonFeedAuthor { name })
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Quote {
+    /// Quote ids are UUIDv7, so they carry when the quote was added.
+    fn added(&self) -> DateTime<Utc> {
+        self.id
+            .get_timestamp()
+            .and_then(|ts| {
+                let (secs, nanos) = ts.to_unix();
+                DateTime::from_timestamp(secs as i64, nanos)
+            })
+            .unwrap_or_else(|| self.timestamp.and_utc())
+    }
+    fn urn(&self) -> String {
+        format!("urn:uuid:{}", self.id)
+    }
+    /// Names of the quoted authors, in the order they first speak.
+    fn feed_authors(&self) -> Vec<String> {
+        let mut names: Vec<String> = vec![];
+        for line in &self.lines {
+            let author = line.author_id.and_then(|id| self.authors.get(&id));
+            if let Some(author) = author {
+                if !names.contains(&author.fullname) {
+                    names.push(author.fullname.clone());
+                }
+            }
+        }
+        names
+    }
+    fn feed_line(&self, index: usize) -> Option<String> {
+        let line = self.lines.get(index)?;
+        match line.author_id.and_then(|id| self.authors.get(&id)) {
+            Some(author) => Some(format!("{}: {}", author.fullname, line.content)),
+            None => Some(line.content.clone()),
+        }
+    }
+    fn feed_title(&self) -> String {
+        let first = self.feed_line(0).unwrap_or_default();
+        match first.char_indices().nth(TITLE_LENGTH) {
+            Some((cut, _)) => format!("{}…", first[..cut].trim_end()),
+            None => first,
+        }
+    }
+    /// Every line on its own, then the context.
+    fn feed_text(&self) -> String {
+        let mut text: Vec<String> = (0..self.lines.len())
+            .filter_map(|i| self.feed_line(i))
+            .collect();
+        if let Some(context) = &self.context {
+            text.push(String::new());
+            text.push(context.clone());
+        }
+        text.join("\n")
+    }
+    /// `feed_text` as HTML, since RSS readers render descriptions as markup.
+    fn feed_html(&self) -> String {
+        let lines: Vec<String> = self.feed_text().lines().map(escape).collect();
+        lines.join("<br>\n")
+    }
+    /// The newest published quotes the reader can read, newest first.
+    async fn get_newest(
+        author: Option<Uuid>,
+        tag: Option<&str>,
+        reader: Option<&User>,
+        pool: &PgPool,
+    ) -> Result<Vec<Quote>, OmniError> {
+        let rows = query_quote_rows!(
+            r#"
+                WHERE quotes.id IN (
+                    SELECT quotes.id FROM quotes
+                    WHERE quote_visible_to(quotes.id, $1, $2)
+                    AND (
+                        $3::uuid IS NULL
+                        OR EXISTS (
+                            SELECT 1 FROM lines AS by_author
+                            INNER JOIN authors ON authors.id = by_author.author_id
+                            WHERE by_author.quote_id = quotes.id AND by_author.author_id = $3
+                            AND (by_author.clearance <= $1 OR authors.user_id = $2)
+                        )
+                    )
+                    AND ($5::text IS NULL OR EXISTS (
+                        SELECT 1 FROM quote_tags WHERE quote_id = quotes.id AND tag = $5
+                    ))
+                    ORDER BY quotes.id DESC LIMIT $4
+                )
+                ORDER BY quotes.id DESC, lines.position ASC
+            "#,
+            reader.map(|u| u.clearance).unwrap_or(0) as i64,
+            reader.map(|u| u.id),
+            author,
+            FEED_SIZE,
+            tag
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(fold_rows(rows))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{quotes::redaction::REDACTED_MARKER, testing};
+
+    #[sqlx::test]
+    async fn titles_and_text_name_the_speakers(pool: PgPool) {
+        let jk = testing::author("jk", &pool).await;
+        let jan = testing::author("jan", &pool).await;
+        let long = "x".repeat(TITLE_LENGTH + 5);
+        let mut quote = testing::quote(&[(&jk, "hi"), (&jan, "yo"), (&jk, &long)], 0);
+        quote.context = Some("at lunch".into());
+
+        assert_eq!(quote.feed_authors(), vec!["JK", "JAN"]);
+        assert_eq!(quote.feed_title(), "JK: hi");
+        assert_eq!(
+            quote.feed_text(),
+            format!("JK: hi\nJAN: yo\nJK: {long}\n\nat lunch")
+        );
+        quote.lines.swap(0, 2);
+        let title = quote.feed_title();
+        assert_eq!(title.chars().count(), TITLE_LENGTH + 1);
+        assert!(title.starts_with("JK: xxx") && title.ends_with('…'));
+    }
+
+    #[sqlx::test]
+    async fn feeds_list_what_the_reader_can_read(pool: PgPool) {
+        let admin = testing::user("admin", 1, &[], &pool).await;
+        let jk = testing::author("jk", &pool).await;
+        let jan = testing::author("jan", &pool).await;
+        let mut mixed = testing::quote(&[(&jk, "hi"), (&jan, "psst")], 0);
+        mixed.lines[1].clearance = 1;
+        testing::save(mixed, &admin, &pool).await;
+        testing::save(testing::quote(&[(&jan, "secret")], 1), &admin, &pool).await;
+
+        let public = Feed::load(None, None, None, &pool).await.unwrap();
+        assert_eq!(public.title, SITE_NAME);
+        assert_eq!(public.quotes.len(), 1);
+        assert_eq!(public.quotes[0].feed_authors(), vec!["JK"]);
+        assert!(public.quotes[0].feed_text().ends_with(REDACTED_MARKER));
+
+        let by_jan = Feed::load(Some(&jan), None, None, &pool).await.unwrap();
+        assert!(by_jan.quotes.is_empty());
+        assert!(by_jan.id.ends_with(&format!("/authors/{}", jan.id)));
+
+        let private = Feed::load(Some(&jan), None, Some(&admin), &pool)
+            .await
+            .unwrap();
+        assert_eq!(private.quotes.len(), 2);
+        assert_eq!(private.title, "Quote Engine: quotes by JAN (for admin)");
+    }
+
+    #[sqlx::test]
+    async fn tag_feeds_only_list_quotes_with_the_tag(pool: PgPool) {
+        let admin = testing::user("admin", 0, &[], &pool).await;
+        let jk = testing::author("jk", &pool).await;
+        let mut tagged = testing::quote(&[(&jk, "roll for it")], 0);
+        tagged.tags = vec!["dnd".into()];
+        let tagged = testing::save(tagged, &admin, &pool).await;
+        testing::save(testing::quote(&[(&jk, "hi")], 0), &admin, &pool).await;
+
+        let feed = Feed::load(Some(&jk), Some("dnd"), None, &pool)
+            .await
+            .unwrap();
+        let ids: Vec<Uuid> = feed.quotes.iter().map(|q| q.id).collect();
+        assert_eq!(ids, vec![tagged.id]);
+        assert_eq!(feed.title, "Quote Engine: quotes by JK tagged dnd");
+        assert!(feed.id.ends_with(&format!("/authors/{}/tags/dnd", jk.id)));
+    }
+
+    #[sqlx::test]
+    async fn every_format_escapes_the_content(pool: PgPool) {
+        let admin = testing::user("admin", 0, &[], &pool).await;
+        let author = testing::author("jk", &pool).await;
+        let quote = testing::quote(&[(&author, "<b>bold</b> & \"quoted\"")], 0);
+        testing::save(quote, &admin, &pool).await;
+        let feed = Feed::load(None, None, None, &pool).await.unwrap();
+
+        for format in [FeedFormat::Atom, FeedFormat::Rss] {
+            let xml = feed.render(format).unwrap();
+            assert!(!xml.contains("<b>"), "{format:?}");
+            assert!(xml.contains("&lt;b&gt;bold&lt;/b&gt; &amp;"), "{format:?}");
+        }
+        let json: serde_json::Value =
+            serde_json::from_str(&feed.render(FeedFormat::Json).unwrap()).unwrap();
+        assert_eq!(
+            json["items"][0]["content_text"],
+            "JK: <b>bold</b> & \"quoted\""
+        );
+        assert_eq!(json["items"][0]["authors"][0]["name"], "JK");
+    }
+
+    #[sqlx::test]
+    async fn a_new_feed_token_replaces_the_old_one(pool: PgPool) {
+        let user = testing::user("reader", 0, &[], &pool).await;
+        assert!(FeedToken::get_for(&user, &pool).await.unwrap().is_none());
+        let old = FeedToken::create(&user, &pool).await.unwrap().token;
+        let new = FeedToken::create(&user, &pool).await.unwrap().token;
+
+        assert!(FeedToken::get_user(&old, &pool).await.unwrap().is_none());
+        let found = FeedToken::get_user(&new, &pool).await.unwrap().unwrap();
+        assert_eq!(found.id, user.id);
+
+        assert!(FeedToken::revoke(&user, &pool).await.unwrap());
+        assert!(!FeedToken::revoke(&user, &pool).await.unwrap());
+        assert!(FeedToken::get_user(&new, &pool).await.unwrap().is_none());
+    }
+}
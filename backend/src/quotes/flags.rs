@@ -0,0 +1,379 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::PgPool;
+use strum::{AsRefStr, EnumString};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    clearance::ClearanceLevel,
+    logs::{Log, LogAction},
+    omnierror::OmniError,
+    user::{
+        auth::error::AuthError,
+        notifications::{Notification, NotificationKind},
+        User,
+    },
+};
+
+use super::{validity::QuoteValidityError, Quote};
+
+/// A request by a quoted user to have a quote reviewed or hidden.
+#[derive(Serialize, ToSchema)]
+pub struct QuoteFlag {
+    pub id: Uuid,
+    pub quote_id: Uuid,
+    pub user_id: Uuid,
+    pub kind: FlagKind,
+    pub reason: Option<String>,
+    pub status: FlagStatus,
+    pub created: DateTime<Utc>,
+    pub resolved: Option<DateTime<Utc>>,
+    pub resolved_by: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema, AsRefStr, EnumString)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum FlagKind {
+    Review,
+    Hide,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ToSchema, AsRefStr, EnumString)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum FlagStatus {
+    Open,
+    Accepted,
+    Dismissed,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct NewQuoteFlag {
+    pub kind: FlagKind,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct FlagResolution {
+    pub accept: bool,
+    /// Required when accepting a `hide` flag; the quote's clearance is raised to it
+    #[serde(default)]
+    pub clearance: Option<u8>,
+}
+
+struct FlagRow {
+    id: Uuid,
+    quote_id: Uuid,
+    user_id: Uuid,
+    kind: String,
+    reason: Option<String>,
+    status: String,
+    created: DateTime<Utc>,
+    resolved: Option<DateTime<Utc>>,
+    resolved_by: Option<Uuid>,
+}
+
+impl From<FlagRow> for QuoteFlag {
+    fn from(row: FlagRow) -> Self {
+        // both guarded by CHECK constraints
+        QuoteFlag {
+            id: row.id,
+            quote_id: row.quote_id,
+            user_id: row.user_id,
+            kind: row.kind.parse().unwrap_or(FlagKind::Review),
+            reason: row.reason,
+            status: row.status.parse().unwrap_or(FlagStatus::Open),
+            created: row.created,
+            resolved: row.resolved,
+            resolved_by: row.resolved_by,
+        }
+    }
+}
+
+impl Quote {
+    pub fn is_attributed_to(&self, user_id: &Uuid) -> bool {
+        self.authors.values().any(|a| a.user_id == Some(*user_id))
+    }
+}
+
+impl QuoteFlag {
+    /// Only users linked to one of the quote's authors may flag it.
+    pub async fn create(
+        quote: &Quote,
+        flag: NewQuoteFlag,
+        user: &User,
+        pool: &PgPool,
+    ) -> Result<QuoteFlag, OmniError> {
+        if !quote.is_attributed_to(&user.id) {
+            return Err(AuthError::NotLinkedUser)?;
+        }
+        let reason = flag.reason.filter(|r| !r.trim().is_empty());
+        match sqlx::query_as!(
+            FlagRow,
+            r#"
+            INSERT INTO quote_flags (id, quote_id, user_id, kind, reason) VALUES ($1, $2, $3, $4, $5)
+            RETURNING id, quote_id, user_id, kind, reason, status, created, resolved, resolved_by
+            "#,
+            Uuid::now_v7(),
+            quote.id,
+            user.id,
+            flag.kind.as_ref(),
+            reason
+        )
+        .fetch_one(pool)
+        .await
+        {
+            Ok(row) => Ok(row.into()),
+            Err(e) => Err(e)?,
+        }
+    }
+    pub async fn get_by_id(id: &Uuid, pool: &PgPool) -> Result<Option<QuoteFlag>, OmniError> {
+        match sqlx::query_as!(
+            FlagRow,
+            r#"
+            SELECT id, quote_id, user_id, kind, reason, status, created, resolved, resolved_by
+            FROM quote_flags WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(pool)
+        .await
+        {
+            Ok(row) => Ok(row.map(QuoteFlag::from)),
+            Err(e) => Err(e)?,
+        }
+    }
+    pub async fn get_open(pool: &PgPool) -> Result<Vec<QuoteFlag>, OmniError> {
+        match sqlx::query_as!(
+            FlagRow,
+            r#"
+            SELECT id, quote_id, user_id, kind, reason, status, created, resolved, resolved_by
+            FROM quote_flags WHERE status = 'open' ORDER BY created
+            "#
+        )
+        .fetch_all(pool)
+        .await
+        {
+            Ok(rows) => Ok(rows.into_iter().map(QuoteFlag::from).collect()),
+            Err(e) => Err(e)?,
+        }
+    }
+    /// Accepting a `hide` flag raises the quote's clearance, which the reviewer must hold;
+    /// it can never lower it.
+    /// The flagger is notified either way.
+    pub async fn resolve(
+        mut self,
+        resolution: FlagResolution,
+        actor: &User,
+        pool: &PgPool,
+    ) -> Result<QuoteFlag, OmniError> {
+        if self.status != FlagStatus::Open {
+            return Err(QuoteValidityError::FlagAlreadyResolved)?;
+        }
+        let hide_clearance = match (resolution.accept, self.kind) {
+            (true, FlagKind::Hide) => match resolution.clearance {
+                Some(c) if c > actor.clearance => return Err(AuthError::InsufficientClearance)?,
+                Some(c) => Some(c),
+                None => return Err(QuoteValidityError::HideWithoutClearance)?,
+            },
+            _ => None,
+        };
+        if let Some(clearance) = hide_clearance {
+            ClearanceLevel::ensure_defined(&[clearance], pool).await?;
+        }
+        let status = match resolution.accept {
+            true => FlagStatus::Accepted,
+            false => FlagStatus::Dismissed,
+        };
+
+        let mut tr = pool.begin().await?;
+        let resolved = async {
+            let resolved = sqlx::query_scalar!(
+                r#"
+                UPDATE quote_flags SET status = $1, resolved = NOW(), resolved_by = $2
+                WHERE id = $3 RETURNING resolved AS "resolved!"
+                "#,
+                status.as_ref(),
+                actor.id,
+                self.id
+            )
+            .fetch_one(&mut *tr)
+            .await?;
+            if let Some(clearance) = hide_clearance {
+                // checked here so a concurrent change can't slip under it
+                let raised = sqlx::query!(
+                    "UPDATE quotes SET clearance = $1 WHERE id = $2 AND clearance < $1",
+                    clearance as i64,
+                    self.quote_id
+                )
+                .execute(&mut *tr)
+                .await?;
+                if raised.rows_affected() == 0 {
+                    return Err(QuoteValidityError::HideNotRaising)?;
+                }
+                Log::record(
+                    &actor.id,
+                    &self.quote_id,
+                    LogAction::QuotesHidden,
+                    json!({ "flag_id": self.id, "clearance": clearance }),
+                    &mut *tr,
+                )
+                .await?;
+            }
+            Notification::send(
+                &self.user_id,
+                NotificationKind::FlagResolved,
+                Some(self.quote_id),
+                json!({ "flag_id": self.id, "status": status.as_ref() }),
+                &mut *tr,
+            )
+            .await?;
+            Ok::<DateTime<Utc>, OmniError>(resolved)
+        }
+        .await;
+
+        match resolved {
+            Ok(resolved) => {
+                tr.commit().await?;
+                self.status = status;
+                self.resolved = Some(resolved);
+                self.resolved_by = Some(actor.id);
+                Ok(self)
+            }
+            Err(e) => {
+                tr.rollback().await?;
+                Err(e)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    struct Setup {
+        reviewer: User,
+        quoted: User,
+        quote: Quote,
+    }
+
+    async fn setup(pool: &PgPool) -> Setup {
+        let reviewer = testing::user("reviewer", 1, &[], pool).await;
+        let quoted = testing::user("quoted", 0, &[], pool).await;
+        let author = testing::author("jk", pool).await;
+        testing::link(&author, &quoted, pool).await;
+        let quote = testing::save(testing::quote(&[(&author, "oops")], 0), &reviewer, pool).await;
+        let quote = Quote::get_by_id(&quote.id, pool).await.unwrap().unwrap();
+        Setup {
+            reviewer,
+            quoted,
+            quote,
+        }
+    }
+
+    async fn flag(setup: &Setup, pool: &PgPool) -> QuoteFlag {
+        let flag = NewQuoteFlag {
+            kind: FlagKind::Hide,
+            reason: Some("please".into()),
+        };
+        QuoteFlag::create(&setup.quote, flag, &setup.quoted, pool)
+            .await
+            .unwrap()
+    }
+
+    fn accept(clearance: Option<u8>) -> FlagResolution {
+        FlagResolution {
+            accept: true,
+            clearance,
+        }
+    }
+
+    async fn clearance_of(quote: &Quote, pool: &PgPool) -> u8 {
+        Quote::get_by_id(&quote.id, pool)
+            .await
+            .unwrap()
+            .unwrap()
+            .clearance
+    }
+
+    #[sqlx::test]
+    async fn only_quoted_users_may_flag(pool: PgPool) {
+        let setup = setup(&pool).await;
+        let flag = NewQuoteFlag {
+            kind: FlagKind::Review,
+            reason: Some(" ".into()),
+        };
+        let res = QuoteFlag::create(&setup.quote, flag, &setup.reviewer, &pool).await;
+        assert!(matches!(
+            res,
+            Err(OmniError::AuthError(AuthError::NotLinkedUser))
+        ));
+        let flag = NewQuoteFlag {
+            kind: FlagKind::Review,
+            reason: Some(" ".into()),
+        };
+        let flag = QuoteFlag::create(&setup.quote, flag, &setup.quoted, &pool)
+            .await
+            .unwrap();
+        assert_eq!((flag.status, flag.reason), (FlagStatus::Open, None));
+    }
+
+    #[sqlx::test]
+    async fn accepting_a_hide_flag_raises_the_clearance(pool: PgPool) {
+        let setup = setup(&pool).await;
+        let flag = flag(&setup, &pool).await;
+        let flag = flag
+            .resolve(accept(Some(1)), &setup.reviewer, &pool)
+            .await
+            .unwrap();
+        assert_eq!(flag.status, FlagStatus::Accepted);
+        assert_eq!(clearance_of(&setup.quote, &pool).await, 1);
+
+        let res = flag.resolve(accept(Some(1)), &setup.reviewer, &pool).await;
+        assert!(matches!(
+            res,
+            Err(OmniError::QuoteValidityError(
+                QuoteValidityError::FlagAlreadyResolved
+            ))
+        ));
+    }
+
+    #[sqlx::test]
+    async fn hiding_must_raise_to_a_clearance_the_reviewer_holds(pool: PgPool) {
+        let setup = setup(&pool).await;
+        let id = flag(&setup, &pool).await.id;
+        let (setup, pool) = (&setup, &pool);
+        let resolve = |clearance| async move {
+            let flag = QuoteFlag::get_by_id(&id, pool).await.unwrap().unwrap();
+            flag.resolve(accept(clearance), &setup.reviewer, pool).await
+        };
+        assert!(matches!(
+            resolve(None).await,
+            Err(OmniError::QuoteValidityError(
+                QuoteValidityError::HideWithoutClearance
+            ))
+        ));
+        assert!(matches!(
+            resolve(Some(2)).await,
+            Err(OmniError::AuthError(AuthError::InsufficientClearance))
+        ));
+        assert!(matches!(
+            resolve(Some(0)).await,
+            Err(OmniError::QuoteValidityError(
+                QuoteValidityError::HideNotRaising
+            ))
+        ));
+        assert_eq!(clearance_of(&setup.quote, pool).await, 0);
+        // the refused resolution was rolled back
+        let flag = QuoteFlag::get_by_id(&id, pool).await.unwrap().unwrap();
+        assert_eq!(flag.status, FlagStatus::Open);
+    }
+}
@@ -1,16 +1,41 @@
 use authors::Author;
-use chrono::NaiveDateTime;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use moderation::QuoteStatus;
+use reactions::QuoteReactions;
 use serde::{Deserialize, Serialize};
+use source::{QuoteSource, SourceFilter};
 use sqlx::PgPool;
 use std::collections::HashMap;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
-use crate::omnierror::OmniError;
+use crate::{
+    clearance::ClearanceLevel,
+    omnierror::OmniError,
+    user::{notifications::Notification, User},
+};
 
 pub mod authors;
+pub mod card;
+pub mod collections;
+pub mod comments;
+pub mod daily;
+pub mod favourites;
+pub mod feeds;
+pub mod flags;
+pub mod moderation;
 pub mod placeholder;
+pub mod random;
+pub mod reactions;
+pub mod redaction;
+pub mod share_links;
+pub mod source;
+pub mod tags;
+pub mod trash;
+pub mod validity;
+pub mod visibility;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
 #[serde(deny_unknown_fields)]
 pub struct Quote {
     #[serde(skip_deserializing)]
@@ -21,237 +46,288 @@ pub struct Quote {
     pub authors: HashMap<Uuid, Author>,
 
     pub context: Option<String>,
+    #[serde(default)]
+    pub source: Option<QuoteSource>,
     pub timestamp: NaiveDateTime,
     pub clearance: u8,
+    /// Name of the clearance level, if defined
+    #[serde(skip_deserializing)]
+    pub clearance_name: Option<String>,
+    /// When set, only members of one of these groups can see the quote
+    #[serde(default)]
+    pub groups: Vec<Uuid>,
+    /// Lowercase labels such as `work` or `dnd`, for narrowing listings and feeds
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// New quotes may start as `draft` or `pending`; only reviewers may publish directly
+    #[serde(default)]
+    pub status: QuoteStatus,
+    #[serde(skip_deserializing)]
+    pub submitted_by: Option<Uuid>,
+    /// Why a reviewer rejected the quote or asked for changes
+    #[serde(skip_deserializing)]
+    pub review_note: Option<String>,
+    /// Set while the quote is in the trash
+    #[serde(skip_deserializing)]
+    pub deleted_at: Option<DateTime<Utc>>,
+    #[serde(skip_deserializing)]
+    pub deleted_by: Option<Uuid>,
+    #[serde(skip_deserializing)]
+    pub reactions: QuoteReactions,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
 #[serde(deny_unknown_fields)]
 pub struct QuoteLine {
     #[serde(skip_deserializing)]
     #[serde(default = "Uuid::now_v7")]
     pub id: Uuid,
     pub content: String,
-    pub author_id: Uuid,
+    /// Required when creating; `null` when the line is redacted
+    pub author_id: Option<Uuid>,
+    /// Viewers below this clearance see the line redacted
+    #[serde(default)]
+    pub clearance: u8,
+    #[serde(skip_deserializing)]
+    pub redacted: bool,
 }
 
-impl Quote {
-    pub async fn get_all(pool: &PgPool) -> Result<Vec<Quote>, OmniError> {
-        // TODO: fetching everything at once is bad at scale. do pagination.
-        // TODO: is the quote metadata repeated in every row, or is it cleverly
-        // allocated in such a way that it doesn't pose a problem?
-        match sqlx::query!(
+/// One line of a quote joined with its quote and author;
+/// quote queries select these and fold them with `fold_rows`.
+struct QuoteRow {
+    quote_id: Uuid,
+    timestamp: NaiveDateTime,
+    context: Option<String>,
+    clearance: i64,
+    clearance_name: Option<String>,
+    groups: Vec<Uuid>,
+    tags: Vec<String>,
+    source_medium: Option<String>,
+    source_reference: Option<String>,
+    source_location: Option<String>,
+    status: String,
+    submitted_by: Option<Uuid>,
+    review_note: Option<String>,
+    deleted_at: Option<DateTime<Utc>>,
+    deleted_by: Option<Uuid>,
+    line_id: Uuid,
+    line_content: String,
+    line_clearance: i64,
+    author_id: Uuid,
+    author_fullname: String,
+    author_codename: String,
+    author_bio: Option<String>,
+    author_avatar: Option<String>,
+    author_aliases: Vec<String>,
+    author_user_id: Option<Uuid>,
+}
+
+/// `sqlx::query_as!` for `QuoteRow`s: every line of every quote, with its author.
+/// The literal is appended to the query and adds the joins, conditions and ordering
+/// (by quote first, for `fold_rows`); the remaining arguments bind its parameters.
+macro_rules! query_quote_rows {
+    ($rest:literal $(, $arg:expr)* $(,)?) => {
+        sqlx::query_as!(
+            $crate::quotes::QuoteRow,
             r#"
                 SELECT
                     quotes.id AS quote_id, quotes.timestamp AS timestamp,
                     quotes.context AS context, quotes.clearance AS clearance,
+                    (
+                        SELECT name FROM clearance_levels WHERE level = quotes.clearance
+                    ) AS clearance_name,
+                    ARRAY(
+                        SELECT group_id FROM quote_groups
+                        WHERE quote_id = quotes.id ORDER BY group_id
+                    ) AS "groups!",
+                    ARRAY(
+                        SELECT tag FROM quote_tags WHERE quote_id = quotes.id ORDER BY tag
+                    ) AS "tags!",
+                    quotes.source_medium, quotes.source_reference, quotes.source_location,
+                    quotes.status, quotes.submitted_by, quotes.review_note,
+                    quotes.deleted_at, quotes.deleted_by,
                     lines.id AS line_id, lines.content AS line_content,
+                    lines.clearance AS line_clearance,
                     authors.id AS author_id, authors.fullname AS author_fullname,
-                    authors.codename AS author_codename
+                    authors.codename AS author_codename,
+                    authors.bio AS author_bio, authors.avatar AS author_avatar,
+                    authors.user_id AS author_user_id,
+                    ARRAY(
+                        SELECT alias FROM author_aliases
+                        WHERE author_id = authors.id ORDER BY alias
+                    ) AS "author_aliases!"
                 FROM quotes
                 LEFT JOIN lines ON quotes.id = lines.quote_id
                 LEFT JOIN authors ON lines.author_id = authors.id
-                ORDER BY quotes.id DESC, lines.position ASC
-            "#
+            "# + $rest
+            $(, $arg)*
+        )
+    };
+}
+pub(crate) use query_quote_rows;
+
+/// Rows must be ordered by quote first, so that lines of one quote are adjacent.
+fn fold_rows(rows: Vec<QuoteRow>) -> Vec<Quote> {
+    let mut quotes: Vec<Quote> = vec![];
+    for row in rows {
+        if quotes.last().is_none_or(|q| q.id != row.quote_id) {
+            quotes.push(Quote {
+                id: row.quote_id,
+                clearance: row.clearance as u8,
+                clearance_name: row.clearance_name,
+                groups: row.groups,
+                tags: row.tags,
+                // guarded by a CHECK constraint
+                status: row.status.parse().unwrap_or_default(),
+                submitted_by: row.submitted_by,
+                review_note: row.review_note,
+                deleted_at: row.deleted_at,
+                deleted_by: row.deleted_by,
+                timestamp: row.timestamp,
+                context: row.context,
+                source: QuoteSource::from_columns(
+                    row.source_medium,
+                    row.source_reference,
+                    row.source_location,
+                ),
+                reactions: QuoteReactions::default(),
+                authors: HashMap::new(),
+                lines: Vec::new(),
+            });
+        }
+        let q = quotes.last_mut().unwrap();
+        q.lines.push(QuoteLine {
+            id: row.line_id,
+            content: row.line_content,
+            author_id: Some(row.author_id),
+            clearance: row.line_clearance as u8,
+            redacted: false,
+        });
+        q.authors.entry(row.author_id).or_insert_with(|| Author {
+            id: row.author_id,
+            fullname: row.author_fullname,
+            codename: row.author_codename,
+            bio: row.author_bio,
+            avatar: row.author_avatar,
+            aliases: row.author_aliases,
+            user_id: row.author_user_id,
+        });
+    }
+    quotes
+}
+
+impl Quote {
+    /// Quotes the viewer may see by clearance and groups; lines still need redacting.
+    pub async fn get_all(
+        filter: &SourceFilter,
+        viewer: &User,
+        pool: &PgPool,
+    ) -> Result<Vec<Quote>, OmniError> {
+        // TODO: fetching everything at once is bad at scale. do pagination.
+        match query_quote_rows!(
+            r#"
+                WHERE ($1::text IS NULL OR quotes.source_medium = $1)
+                AND ($2::text IS NULL OR quotes.source_location ILIKE '%' || $2 || '%')
+                AND quote_visible_to(quotes.id, $3, $4)
+                ORDER BY
+                    CASE WHEN $5 = 'score' THEN (
+                        SELECT COALESCE(SUM(vote), 0) FROM quote_reactions
+                        WHERE quote_id = quotes.id
+                    ) END DESC,
+                    CASE WHEN $5 = 'reactions' THEN (
+                        SELECT COUNT(reaction) FROM quote_reactions
+                        WHERE quote_id = quotes.id
+                    ) END DESC,
+                    CASE WHEN $5 = 'oldest' THEN quotes.id END ASC,
+                    quotes.id DESC, lines.position ASC
+            "#,
+            filter.medium.map(|m| m.as_ref().to_string()),
+            filter.location,
+            viewer.clearance as i64,
+            viewer.id,
+            filter.sort.as_ref()
         )
         .fetch_all(pool)
         .await
         {
-            Ok(recs) => {
-                let mut qvec: Vec<Quote> = vec![];
-                let mut q: Quote;
-                if let Some(rec) = recs.first() {
-                    q = Quote {
-                        id: rec.quote_id,
-                        clearance: rec.clearance as u8,
-                        timestamp: rec.timestamp,
-                        context: rec.context.clone(),
-                        authors: HashMap::new(),
-                        lines: Vec::new(),
-                    };
-                } else {
-                    return Ok(vec![]);
-                }
-                for rec in recs {
-                    match q.id == rec.quote_id {
-                        true => {
-                            q.lines.push(QuoteLine {
-                                id: rec.line_id,
-                                content: rec.line_content,
-                                author_id: rec.author_id,
-                            });
-                            if !q.authors.contains_key(&rec.author_id) {
-                                q.authors.insert(
-                                    rec.author_id,
-                                    Author {
-                                        id: rec.author_id,
-                                        fullname: rec.author_fullname,
-                                        codename: rec.author_codename,
-                                    },
-                                );
-                            }
-                        }
-                        false => {
-                            // q is done, push it and start a new one
-                            qvec.push(q);
-                            q = Quote {
-                                id: rec.quote_id,
-                                clearance: rec.clearance as u8,
-                                timestamp: rec.timestamp,
-                                context: rec.context.clone(),
-                                authors: HashMap::new(),
-                                lines: Vec::new(),
-                            };
-                            q.lines.push(QuoteLine {
-                                id: rec.line_id,
-                                content: rec.line_content,
-                                author_id: rec.author_id,
-                            });
-                            if !q.authors.contains_key(&rec.author_id) {
-                                q.authors.insert(
-                                    rec.author_id,
-                                    Author {
-                                        id: rec.author_id,
-                                        fullname: rec.author_fullname,
-                                        codename: rec.author_codename,
-                                    },
-                                );
-                            }
-                        }
-                    }
-                }
-
-                Ok(qvec)
-            }
+            Ok(rows) => Ok(fold_rows(rows)),
             Err(e) => Err(e)?,
         }
     }
-    pub async fn get_random_public(pool: &PgPool) -> Result<Option<Quote>, OmniError> {
-        match sqlx::query!(
+    /// Trashed quotes are not found; see `Quote::get_trashed_by_id`.
+    pub async fn get_by_id(id: &Uuid, pool: &PgPool) -> Result<Option<Quote>, OmniError> {
+        Quote::fetch_by_id(id, false, pool).await
+    }
+    async fn fetch_by_id(
+        id: &Uuid,
+        trashed: bool,
+        pool: &PgPool,
+    ) -> Result<Option<Quote>, OmniError> {
+        match query_quote_rows!(
             r#"
-                WITH randomquote AS (
-                    SELECT id FROM quotes WHERE clearance = 0
-                    ORDER BY random() LIMIT 1
-                )
-                SELECT
-                    quotes.id AS quote_id, quotes.timestamp AS timestamp,
-                    quotes.context AS context, quotes.clearance AS clearance,
-                    lines.id AS line_id, lines.content AS line_content,
-                    authors.id AS author_id, authors.fullname AS author_fullname,
-                    authors.codename AS author_codename
-                FROM quotes
-                LEFT JOIN lines ON quotes.id = lines.quote_id
-                LEFT JOIN authors ON lines.author_id = authors.id
-                WHERE quotes.id = (SELECT id FROM randomquote)
+                WHERE quotes.id = $1 AND (quotes.deleted_at IS NOT NULL) = $2
                 ORDER BY quotes.id DESC, lines.position ASC
-            "#
+            "#,
+            id,
+            trashed
         )
         .fetch_all(pool)
         .await
         {
-            Ok(recs) => {
-                let mut q: Quote;
-                if let Some(rec) = recs.first() {
-                    q = Quote {
-                        id: rec.quote_id,
-                        clearance: rec.clearance as u8,
-                        timestamp: rec.timestamp,
-                        context: rec.context.clone(),
-                        authors: HashMap::new(),
-                        lines: Vec::new(),
-                    };
-                } else {
-                    return Ok(None);
-                }
-                for rec in recs {
-                    q.lines.push(QuoteLine {
-                        id: rec.line_id,
-                        content: rec.line_content,
-                        author_id: rec.author_id,
-                    });
-                    if !q.authors.contains_key(&rec.author_id) {
-                        q.authors.insert(
-                            rec.author_id,
-                            Author {
-                                id: rec.author_id,
-                                fullname: rec.author_fullname,
-                                codename: rec.author_codename,
-                            },
-                        );
-                    }
-                }
-
-                Ok(Some(q))
-            }
+            Ok(rows) => Ok(fold_rows(rows).pop()),
             Err(e) => Err(e)?,
         }
     }
-    pub async fn get_by_id(id: &Uuid, pool: &PgPool) -> Result<Option<Quote>, OmniError> {
-        match sqlx::query!(
+    /// Every published quote with a line by the author linked to the user, regardless of clearance.
+    pub async fn get_attributed_to(user_id: &Uuid, pool: &PgPool) -> Result<Vec<Quote>, OmniError> {
+        match query_quote_rows!(
             r#"
-                SELECT
-                    quotes.id AS quote_id, quotes.timestamp AS timestamp,
-                    quotes.context AS context, quotes.clearance AS clearance,
-                    lines.id AS line_id, lines.content AS line_content,
-                    authors.id AS author_id, authors.fullname AS author_fullname,
-                    authors.codename AS author_codename
-                FROM quotes
-                LEFT JOIN lines ON quotes.id = lines.quote_id
-                LEFT JOIN authors ON lines.author_id = authors.id
-                WHERE quotes.id = $1
+                WHERE quotes.id IN (
+                    SELECT lines.quote_id FROM lines
+                    INNER JOIN authors ON authors.id = lines.author_id
+                    WHERE authors.user_id = $1
+                )
+                AND quotes.status = 'published' AND quotes.deleted_at IS NULL
                 ORDER BY quotes.id DESC, lines.position ASC
             "#,
-            id
+            user_id
         )
         .fetch_all(pool)
         .await
         {
-            Ok(recs) => {
-                let mut q: Quote;
-                if let Some(rec) = recs.first() {
-                    q = Quote {
-                        id: rec.quote_id,
-                        clearance: rec.clearance as u8,
-                        timestamp: rec.timestamp,
-                        context: rec.context.clone(),
-                        authors: HashMap::new(),
-                        lines: Vec::new(),
-                    };
-                } else {
-                    return Ok(None);
-                }
-                for rec in recs {
-                    q.lines.push(QuoteLine {
-                        id: rec.line_id,
-                        content: rec.line_content,
-                        author_id: rec.author_id,
-                    });
-                    if !q.authors.contains_key(&rec.author_id) {
-                        q.authors.insert(
-                            rec.author_id,
-                            Author {
-                                id: rec.author_id,
-                                fullname: rec.author_fullname,
-                                codename: rec.author_codename,
-                            },
-                        );
-                    }
-                }
-
-                Ok(Some(q))
-            }
+            Ok(rows) => Ok(fold_rows(rows)),
             Err(e) => Err(e)?,
         }
     }
-    pub async fn create(quote: Quote, pool: &PgPool) -> Result<Quote, OmniError> {
+    pub async fn create(
+        mut quote: Quote,
+        submitter: &User,
+        pool: &PgPool,
+    ) -> Result<Quote, OmniError> {
+        quote.submitted_by = Some(submitter.id);
+        quote.clearance_name = ClearanceLevel::name_of(quote.clearance, pool).await?;
+        quote.groups.sort();
+        quote.groups.dedup();
+        quote.tags = tags::normalize_tags(&quote.tags);
         let mut tr = pool.begin().await?;
 
+        let source = quote.source.clone().unwrap_or_default();
         match sqlx::query!(
-            "INSERT INTO quotes(id, context, clearance, timestamp) VALUES ($1, $2, $3, $4)",
+            r#"
+            INSERT INTO quotes(
+                id, context, clearance, timestamp,
+                source_medium, source_reference, source_location, status, submitted_by
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            "#,
             quote.id,
             quote.context,
             quote.clearance as i16,
-            quote.timestamp
+            quote.timestamp,
+            source.medium.map(|m| m.as_ref().to_string()),
+            source.reference,
+            source.location,
+            quote.status.as_ref(),
+            quote.submitted_by
         )
         .execute(&mut *tr)
         .await
@@ -265,12 +341,16 @@ impl Quote {
 
         for (index, line) in quote.lines.iter().enumerate() {
             match sqlx::query!(
-                "INSERT INTO lines(id, quote_id, author_id, content, position) VALUES ($1, $2, $3, $4, $5)",
+                r#"
+                INSERT INTO lines(id, quote_id, author_id, content, position, clearance)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                "#,
                 line.id,
                 quote.id,
                 line.author_id,
                 line.content,
-                index as i32
+                index as i32,
+                line.clearance as i64
             )
             .execute(&mut *tr)
             .await
@@ -283,15 +363,16 @@ impl Quote {
             }
         }
 
-        tr.commit().await?;
-        Ok(quote)
-    }
-    pub async fn delete(self, pool: &PgPool) -> Result<(), OmniError> {
-        let mut tr = pool.begin().await?;
-
-        match sqlx::query!("DELETE FROM lines WHERE quote_id = $1", self.id)
-            .execute(&mut *tr)
-            .await
+        match sqlx::query!(
+            r#"
+            INSERT INTO quote_groups (quote_id, group_id)
+            SELECT $1, group_id FROM UNNEST($2::uuid[]) AS group_id
+            "#,
+            quote.id,
+            &quote.groups
+        )
+        .execute(&mut *tr)
+        .await
         {
             Ok(_) => (),
             Err(e) => {
@@ -300,18 +381,19 @@ impl Quote {
             }
         }
 
-        match sqlx::query!("DELETE FROM quotes WHERE id = $1", self.id)
-            .execute(&mut *tr)
-            .await
-        {
-            Ok(_) => (),
-            Err(e) => {
+        if let Err(e) = tags::insert_tags(&quote.id, &quote.tags, &mut tr).await {
+            tr.rollback().await?;
+            return Err(e);
+        }
+
+        if quote.status == QuoteStatus::Published {
+            if let Err(e) = Notification::send_quoted(&quote.id, &mut tr).await {
                 tr.rollback().await?;
-                return Err(e)?;
+                return Err(e);
             }
         }
 
         tr.commit().await?;
-        Ok(())
+        Ok(quote)
     }
 }
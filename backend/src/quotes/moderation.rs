@@ -0,0 +1,426 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::PgPool;
+use strum::{AsRefStr, EnumString};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    clearance::ClearanceLevel,
+    logs::{Log, LogAction},
+    omnierror::OmniError,
+    user::{
+        attributes::UserAttribute,
+        notifications::{Notification, NotificationKind},
+        User,
+    },
+};
+
+use super::{fold_rows, query_quote_rows, tags, validity::QuoteValidityError, Quote};
+
+/// Where a quote is in its lifecycle; only published quotes are listed.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, ToSchema, AsRefStr, EnumString,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum QuoteStatus {
+    /// Only visible to the submitter, who can still edit it
+    Draft,
+    /// Waiting in the moderation queue
+    #[default]
+    Pending,
+    Published,
+    Rejected,
+    /// Taken out of listings by a reviewer
+    Archived,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ReviewReason {
+    pub reason: String,
+}
+
+impl QuoteStatus {
+    pub fn can_become(self, to: QuoteStatus) -> bool {
+        use QuoteStatus as S;
+        matches!(
+            (self, to),
+            (S::Draft, S::Pending)
+                | (S::Pending, S::Published | S::Rejected | S::Draft)
+                | (S::Published, S::Archived)
+                | (S::Archived, S::Published)
+        )
+    }
+}
+
+impl Quote {
+    /// Unpublished quotes are only for their submitter and reviewers.
+    pub fn status_admits(&self, viewer: Option<&User>) -> bool {
+        match (self.status, viewer) {
+            (QuoteStatus::Published, _) => true,
+            (_, Some(u)) => {
+                self.submitted_by == Some(u.id)
+                    || u.has_permission(UserAttribute::QuotesReviewPermission)
+            }
+            (_, None) => false,
+        }
+    }
+    /// Pending quotes the reviewer may see, oldest first.
+    pub async fn get_pending(viewer: &User, pool: &PgPool) -> Result<Vec<Quote>, OmniError> {
+        match query_quote_rows!(
+            r#"
+                WHERE quotes.status = 'pending' AND quotes.deleted_at IS NULL
+                AND quotes.clearance <= $1 AND quote_shared_with(quotes.id, $2)
+                ORDER BY quotes.id ASC, lines.position ASC
+            "#,
+            viewer.clearance as i64,
+            viewer.id
+        )
+        .fetch_all(pool)
+        .await
+        {
+            Ok(rows) => Ok(fold_rows(rows)),
+            Err(e) => Err(e)?,
+        }
+    }
+    /// Everything the user has submitted, in any status, newest first.
+    pub async fn get_submitted_by(user_id: &Uuid, pool: &PgPool) -> Result<Vec<Quote>, OmniError> {
+        match query_quote_rows!(
+            r#"
+                WHERE quotes.submitted_by = $1 AND quotes.deleted_at IS NULL
+                ORDER BY quotes.id DESC, lines.position ASC
+            "#,
+            user_id
+        )
+        .fetch_all(pool)
+        .await
+        {
+            Ok(rows) => Ok(fold_rows(rows)),
+            Err(e) => Err(e)?,
+        }
+    }
+    /// Moves the quote along its lifecycle. Rejecting and asking for changes need a reason,
+    /// which is kept as the review note until the quote is published.
+    /// The submitter is notified of review outcomes, and linked users once it is published.
+    pub async fn transition(
+        mut self,
+        to: QuoteStatus,
+        reason: Option<String>,
+        actor: &User,
+        pool: &PgPool,
+    ) -> Result<Quote, OmniError> {
+        use QuoteStatus as S;
+        let from = self.status;
+        if !from.can_become(to) {
+            return Err(QuoteValidityError::StatusTransitionInvalid(from, to))?;
+        }
+        let reason = reason
+            .map(|r| r.trim().to_string())
+            .filter(|r| !r.is_empty());
+        let needs_reason = matches!((from, to), (S::Pending, S::Rejected | S::Draft));
+        if needs_reason && reason.is_none() {
+            return Err(QuoteValidityError::ReviewReasonRequired)?;
+        }
+        let note = match to {
+            S::Published => None,
+            _ => reason.clone().or(self.review_note.take()),
+        };
+        let outcome = match (from, to) {
+            (S::Pending, S::Published) => Some(NotificationKind::SubmissionApproved),
+            (S::Pending, S::Rejected) => Some(NotificationKind::SubmissionRejected),
+            (S::Pending, S::Draft) => Some(NotificationKind::ChangesRequested),
+            _ => None,
+        };
+
+        let mut tr = pool.begin().await?;
+        let moved = async {
+            sqlx::query!(
+                "UPDATE quotes SET status = $1, review_note = $2 WHERE id = $3",
+                to.as_ref(),
+                note,
+                self.id
+            )
+            .execute(&mut *tr)
+            .await?;
+            Log::record(
+                &actor.id,
+                &self.id,
+                LogAction::QuotesStatusChanged,
+                json!({ "from": from.as_ref(), "to": to.as_ref(), "reason": reason }),
+                &mut *tr,
+            )
+            .await?;
+            if let (Some(kind), Some(submitter)) = (outcome, self.submitted_by) {
+                if submitter != actor.id {
+                    Notification::send(
+                        &submitter,
+                        kind,
+                        Some(self.id),
+                        json!({ "reason": reason }),
+                        &mut *tr,
+                    )
+                    .await?;
+                }
+            }
+            if (from, to) == (S::Pending, S::Published) {
+                Notification::send_quoted(&self.id, &mut tr).await?;
+            }
+            Ok::<(), OmniError>(())
+        }
+        .await;
+
+        match moved {
+            Ok(()) => {
+                tr.commit().await?;
+                self.status = to;
+                self.review_note = note;
+                Ok(self)
+            }
+            Err(e) => {
+                tr.rollback().await?;
+                Err(e)
+            }
+        }
+    }
+    /// Replaces the content of a draft; its id, status and submitter stay.
+    pub async fn replace_draft(self, mut new: Quote, pool: &PgPool) -> Result<Quote, OmniError> {
+        if self.status != QuoteStatus::Draft {
+            return Err(QuoteValidityError::NotADraft)?;
+        }
+        new.id = self.id;
+        new.status = self.status;
+        new.submitted_by = self.submitted_by;
+        new.review_note = self.review_note;
+        new.clearance_name = ClearanceLevel::name_of(new.clearance, pool).await?;
+        new.groups.sort();
+        new.groups.dedup();
+        new.tags = tags::normalize_tags(&new.tags);
+        let source = new.source.clone().unwrap_or_default();
+
+        let mut tr = pool.begin().await?;
+        let replaced = async {
+            sqlx::query!(
+                r#"
+                UPDATE quotes SET
+                    context = $1, clearance = $2, timestamp = $3,
+                    source_medium = $4, source_reference = $5, source_location = $6
+                WHERE id = $7
+                "#,
+                new.context,
+                new.clearance as i64,
+                new.timestamp,
+                source.medium.map(|m| m.as_ref().to_string()),
+                source.reference,
+                source.location,
+                new.id
+            )
+            .execute(&mut *tr)
+            .await?;
+            sqlx::query!("DELETE FROM lines WHERE quote_id = $1", new.id)
+                .execute(&mut *tr)
+                .await?;
+            for (index, line) in new.lines.iter().enumerate() {
+                sqlx::query!(
+                    r#"
+                    INSERT INTO lines(id, quote_id, author_id, content, position, clearance)
+                    VALUES ($1, $2, $3, $4, $5, $6)
+                    "#,
+                    line.id,
+                    new.id,
+                    line.author_id,
+                    line.content,
+                    index as i32,
+                    line.clearance as i64
+                )
+                .execute(&mut *tr)
+                .await?;
+            }
+            sqlx::query!("DELETE FROM quote_groups WHERE quote_id = $1", new.id)
+                .execute(&mut *tr)
+                .await?;
+            sqlx::query!(
+                r#"
+                INSERT INTO quote_groups (quote_id, group_id)
+                SELECT $1, group_id FROM UNNEST($2::uuid[]) AS group_id
+                "#,
+                new.id,
+                &new.groups
+            )
+            .execute(&mut *tr)
+            .await?;
+            sqlx::query!("DELETE FROM quote_tags WHERE quote_id = $1", new.id)
+                .execute(&mut *tr)
+                .await?;
+            tags::insert_tags(&new.id, &new.tags, &mut tr).await?;
+            Ok::<(), OmniError>(())
+        }
+        .await;
+
+        match replaced {
+            Ok(()) => {
+                tr.commit().await?;
+                Ok(new)
+            }
+            Err(e) => {
+                tr.rollback().await?;
+                Err(e)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    async fn notified(user: &User, pool: &PgPool) -> Vec<String> {
+        sqlx::query_scalar!(
+            "SELECT kind FROM notifications WHERE user_id = $1 ORDER BY created",
+            user.id
+        )
+        .fetch_all(pool)
+        .await
+        .unwrap()
+    }
+
+    async fn reload(quote: &Quote, pool: &PgPool) -> Quote {
+        Quote::get_by_id(&quote.id, pool).await.unwrap().unwrap()
+    }
+
+    #[test]
+    fn only_the_lifecycle_transitions_are_allowed() {
+        use QuoteStatus as S;
+        let all = [S::Draft, S::Pending, S::Published, S::Rejected, S::Archived];
+        let allowed: Vec<_> = all
+            .iter()
+            .flat_map(|from| all.iter().map(move |to| (*from, *to)))
+            .filter(|(from, to)| from.can_become(*to))
+            .collect();
+        assert_eq!(
+            allowed,
+            vec![
+                (S::Draft, S::Pending),
+                (S::Pending, S::Draft),
+                (S::Pending, S::Published),
+                (S::Pending, S::Rejected),
+                (S::Published, S::Archived),
+                (S::Archived, S::Published),
+            ]
+        );
+    }
+
+    #[sqlx::test]
+    async fn unpublished_quotes_are_for_submitters_and_reviewers(pool: PgPool) {
+        let submitter = testing::user("submitter", 0, &[], &pool).await;
+        let reviewer = testing::user(
+            "reviewer",
+            0,
+            &[UserAttribute::QuotesReviewPermission],
+            &pool,
+        )
+        .await;
+        let other = testing::user("other", 0, &[], &pool).await;
+        let author = testing::author("jk", &pool).await;
+        let mut quote = testing::quote(&[(&author, "hi")], 0);
+        quote.status = QuoteStatus::Pending;
+        let quote = testing::save(quote, &submitter, &pool).await;
+
+        assert!(quote.status_admits(Some(&submitter)));
+        assert!(quote.status_admits(Some(&reviewer)));
+        assert!(!quote.status_admits(Some(&other)));
+        assert!(!quote.status_admits(None));
+        let pending = Quote::get_pending(&reviewer, &pool).await.unwrap();
+        assert_eq!(pending.len(), 1);
+        let submitted = Quote::get_submitted_by(&submitter.id, &pool).await.unwrap();
+        assert_eq!(submitted.len(), 1);
+    }
+
+    #[sqlx::test]
+    async fn reviews_need_reasons_and_notify_the_submitter(pool: PgPool) {
+        let submitter = testing::user("submitter", 0, &[], &pool).await;
+        let reviewer = testing::user("reviewer", 0, &[], &pool).await;
+        let quoted = testing::user("quoted", 0, &[], &pool).await;
+        let author = testing::author("jk", &pool).await;
+        testing::link(&author, &quoted, &pool).await;
+        let mut quote = testing::quote(&[(&author, "hi")], 0);
+        quote.status = QuoteStatus::Draft;
+        let quote = testing::save(quote, &submitter, &pool).await;
+
+        let res = reload(&quote, &pool)
+            .await
+            .transition(QuoteStatus::Published, None, &reviewer, &pool)
+            .await;
+        assert!(matches!(
+            res,
+            Err(OmniError::QuoteValidityError(
+                QuoteValidityError::StatusTransitionInvalid(
+                    QuoteStatus::Draft,
+                    QuoteStatus::Published
+                )
+            ))
+        ));
+        let quote = quote
+            .transition(QuoteStatus::Pending, None, &submitter, &pool)
+            .await
+            .unwrap();
+        let res = reload(&quote, &pool)
+            .await
+            .transition(QuoteStatus::Draft, Some("  ".into()), &reviewer, &pool)
+            .await;
+        assert!(matches!(
+            res,
+            Err(OmniError::QuoteValidityError(
+                QuoteValidityError::ReviewReasonRequired
+            ))
+        ));
+        let quote = quote
+            .transition(QuoteStatus::Draft, Some("typo".into()), &reviewer, &pool)
+            .await
+            .unwrap();
+        assert_eq!(quote.review_note.as_deref(), Some("typo"));
+        assert!(notified(&quoted, &pool).await.is_empty());
+
+        let quote = quote
+            .transition(QuoteStatus::Pending, None, &submitter, &pool)
+            .await
+            .unwrap();
+        // the note stays until the quote is published
+        assert_eq!(quote.review_note.as_deref(), Some("typo"));
+        let quote = quote
+            .transition(QuoteStatus::Published, None, &reviewer, &pool)
+            .await
+            .unwrap();
+        assert_eq!(quote.review_note, None);
+        assert_eq!(
+            notified(&submitter, &pool).await,
+            vec!["changes_requested", "submission_approved"]
+        );
+        assert_eq!(notified(&quoted, &pool).await.len(), 1);
+    }
+
+    #[sqlx::test]
+    async fn only_drafts_can_be_replaced(pool: PgPool) {
+        let submitter = testing::user("submitter", 0, &[], &pool).await;
+        let author = testing::author("jk", &pool).await;
+        let mut draft = testing::quote(&[(&author, "hi")], 0);
+        draft.status = QuoteStatus::Draft;
+        let draft = testing::save(draft, &submitter, &pool).await;
+
+        let new = || testing::quote(&[(&author, "hello"), (&author, "again")], 0);
+        let replaced = draft.replace_draft(new(), &pool).await.unwrap();
+        assert_eq!(replaced.status, QuoteStatus::Draft);
+        let stored = Quote::get_submitted_by(&submitter.id, &pool).await.unwrap();
+        assert_eq!(stored[0].id, replaced.id);
+        assert_eq!(stored[0].lines.len(), 2);
+
+        let published =
+            testing::save(testing::quote(&[(&author, "x")], 0), &submitter, &pool).await;
+        assert!(matches!(
+            published.replace_draft(new(), &pool).await,
+            Err(OmniError::QuoteValidityError(QuoteValidityError::NotADraft))
+        ));
+    }
+}
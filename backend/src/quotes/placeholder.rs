@@ -3,7 +3,7 @@ use std::collections::HashMap;
 use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
 use uuid::Uuid;
 
-use super::{authors::Author, Quote, QuoteLine};
+use super::{authors::Author, moderation::QuoteStatus, Quote, QuoteLine};
 
 pub fn return_placeholder_random_public_quote() -> Quote {
     let mut authors = HashMap::new();
@@ -13,12 +13,26 @@ pub fn return_placeholder_random_public_quote() -> Quote {
             id: Uuid::nil(),
             fullname: String::from("Pixieline"),
             codename: String::from("The QuoteEngine Pixie"),
+            bio: None,
+            avatar: None,
+            aliases: vec![],
+            user_id: None,
         },
     );
     Quote {
         id: Uuid::nil(),
         clearance: 0,
+        clearance_name: None,
+        groups: vec![],
+        tags: vec![],
+        status: QuoteStatus::Published,
+        submitted_by: None,
+        review_note: None,
+        deleted_at: None,
+        deleted_by: None,
+        reactions: Default::default(),
         context: Some(String::from("About the lack of a public quote.")),
+        source: None,
         timestamp: NaiveDateTime::new(
             NaiveDate::from_ymd_opt(2025, 1, 27).unwrap(),
             NaiveTime::from_hms_opt(0, 24, 0).unwrap(),
@@ -28,12 +42,16 @@ pub fn return_placeholder_random_public_quote() -> Quote {
             QuoteLine {
                 id: Uuid::nil(),
                 content: String::from("Hey, you have no public quotes in your database yet!"),
-                author_id: Uuid::nil(),
+                author_id: Some(Uuid::nil()),
+                clearance: 0,
+                redacted: false,
             },
             QuoteLine {
                 id: Uuid::nil(),
                 content: String::from("You should add some."),
-                author_id: Uuid::nil(),
+                author_id: Some(Uuid::nil()),
+                clearance: 0,
+                redacted: false,
             },
         ],
     }
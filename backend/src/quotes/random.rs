@@ -0,0 +1,235 @@
+use chrono::NaiveDate;
+use rand::{distributions::WeightedIndex, prelude::Distribution};
+use serde::Deserialize;
+use sqlx::PgPool;
+use strum::AsRefStr;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::{omnierror::OmniError, user::User};
+
+use super::{tags, Quote};
+
+/// How many quotes past a random point on `random_key` are weighed against each other
+const SAMPLE_SIZE: i64 = 64;
+
+/// Narrows a random pick to quotes the caller may read; dates are inclusive
+/// and compared with when the quote was said.
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[serde(deny_unknown_fields)]
+pub struct RandomFilter {
+    /// Only quotes with a line by this author the caller can read
+    pub author: Option<Uuid>,
+    /// Only quotes with this tag, compared case-insensitively
+    pub tag: Option<String>,
+    pub from: Option<NaiveDate>,
+    pub until: Option<NaiveDate>,
+    #[serde(default)]
+    #[param(inline)]
+    pub weight: RandomWeight,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, ToSchema, AsRefStr)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum RandomWeight {
+    /// Every quote is as likely
+    #[default]
+    Uniform,
+    /// Favour quotes with a higher score and more emoji reactions
+    Reactions,
+    /// Favour recent quotes; one said 30 days ago is half as likely as one said today
+    Recency,
+}
+
+impl Quote {
+    /// A random quote matching the filter, or `None` only when nothing matches at all.
+    ///
+    /// Rather than sorting every quote, this seeks the `random_key` index from a random point,
+    /// wrapping around to the start if needed, and draws among the next few matches by weight.
+    pub async fn get_random(
+        filter: &RandomFilter,
+        viewer: Option<&User>,
+        pool: &PgPool,
+    ) -> Result<Option<Quote>, OmniError> {
+        let start = rand::random::<f64>();
+        let mut sample =
+            Quote::sample_random(filter, viewer, start, 1.0, SAMPLE_SIZE, pool).await?;
+        let missing = SAMPLE_SIZE - sample.len() as i64;
+        if missing > 0 {
+            sample.extend(Quote::sample_random(filter, viewer, 0.0, start, missing, pool).await?);
+        }
+        if sample.is_empty() {
+            return Ok(None);
+        }
+
+        let chosen = match WeightedIndex::new(sample.iter().map(|(_, weight)| *weight)) {
+            Ok(weights) => sample[weights.sample(&mut rand::thread_rng())].0,
+            Err(_) => sample[0].0,
+        };
+        Quote::get_by_id(&chosen, pool).await
+    }
+    /// Matching quote ids with their weights, in `random_key` order within `[lower, upper)`.
+    async fn sample_random(
+        filter: &RandomFilter,
+        viewer: Option<&User>,
+        lower: f64,
+        upper: f64,
+        limit: i64,
+        pool: &PgPool,
+    ) -> Result<Vec<(Uuid, f64)>, OmniError> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                quotes.id,
+                (CASE $8
+                    WHEN 'reactions' THEN 1 + COALESCE((
+                        SELECT GREATEST(COALESCE(SUM(vote), 0), 0) + COUNT(reaction)
+                        FROM quote_reactions WHERE quote_id = quotes.id
+                    ), 0)
+                    WHEN 'recency' THEN 1 / (
+                        1 + GREATEST(EXTRACT(EPOCH FROM LOCALTIMESTAMP - quotes.timestamp), 0)
+                            / 86400 / 30
+                    )
+                    ELSE 1
+                END)::double precision AS "weight!"
+            FROM quotes
+            WHERE quote_visible_to(quotes.id, $3, $4)
+            AND quotes.random_key >= $1 AND quotes.random_key < $2
+            AND (
+                $5::uuid IS NULL
+                OR EXISTS (
+                    SELECT 1 FROM lines AS by_author
+                    INNER JOIN authors ON authors.id = by_author.author_id
+                    WHERE by_author.quote_id = quotes.id AND by_author.author_id = $5
+                    AND (by_author.clearance <= $3 OR authors.user_id = $4)
+                )
+            )
+            AND ($6::date IS NULL OR quotes.timestamp >= $6)
+            AND ($7::date IS NULL OR quotes.timestamp < $7 + 1)
+            AND ($10::text IS NULL OR EXISTS (
+                SELECT 1 FROM quote_tags WHERE quote_id = quotes.id AND tag = $10
+            ))
+            ORDER BY quotes.random_key LIMIT $9
+            "#,
+            lower,
+            upper,
+            viewer.map(|u| u.clearance).unwrap_or(0) as i64,
+            viewer.map(|u| u.id),
+            filter.author,
+            filter.from,
+            filter.until,
+            filter.weight.as_ref(),
+            limit,
+            tags::normalize_filter(filter.tag.as_deref())
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(rows.into_iter().map(|r| (r.id, r.weight)).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Local};
+
+    use super::*;
+    use crate::{quotes::reactions::Vote, testing};
+
+    fn said_on(quote: &mut Quote, day: &str) {
+        quote.timestamp = day
+            .parse::<NaiveDate>()
+            .unwrap()
+            .and_hms_opt(23, 0, 0)
+            .unwrap();
+    }
+
+    async fn pick(filter: &RandomFilter, viewer: Option<&User>, pool: &PgPool) -> Option<Uuid> {
+        let quote = Quote::get_random(filter, viewer, pool).await.unwrap();
+        quote.map(|q| q.id)
+    }
+
+    #[sqlx::test]
+    async fn only_matching_readable_quotes_are_picked(pool: PgPool) {
+        let admin = testing::user("admin", 1, &[], &pool).await;
+        let jk = testing::author("jk", &pool).await;
+        let jan = testing::author("jan", &pool).await;
+        assert_eq!(pick(&RandomFilter::default(), None, &pool).await, None);
+
+        let mut old = testing::quote(&[(&jk, "old")], 0);
+        said_on(&mut old, "2020-01-31");
+        old.tags = vec!["dnd".into()];
+        let old = testing::save(old, &admin, &pool).await;
+        let mut new = testing::quote(&[(&jan, "new"), (&jk, "psst")], 0);
+        said_on(&mut new, "2024-06-01");
+        new.lines[1].clearance = 1;
+        let new = testing::save(new, &admin, &pool).await;
+        let mut secret = testing::quote(&[(&jan, "secret")], 1);
+        said_on(&mut secret, "2022-01-01");
+        testing::save(secret, &admin, &pool).await;
+
+        let by_jk = RandomFilter {
+            author: Some(jk.id),
+            ..Default::default()
+        };
+        let until = RandomFilter {
+            until: "2020-01-31".parse().ok(),
+            ..Default::default()
+        };
+        let from = RandomFilter {
+            from: "2021-01-01".parse().ok(),
+            ..Default::default()
+        };
+        let tagged = RandomFilter {
+            tag: Some("DnD".into()),
+            ..Default::default()
+        };
+        for _ in 0..5 {
+            assert_eq!(pick(&by_jk, None, &pool).await, Some(old.id));
+            assert_eq!(pick(&tagged, None, &pool).await, Some(old.id));
+            assert_eq!(pick(&until, None, &pool).await, Some(old.id));
+            assert_eq!(pick(&from, None, &pool).await, Some(new.id));
+            assert_ne!(pick(&RandomFilter::default(), None, &pool).await, None);
+        }
+        let picked = pick(&from, Some(&admin), &pool).await;
+        assert!(picked.is_some_and(|id| id != old.id));
+    }
+
+    #[sqlx::test]
+    async fn weights_favour_reactions_and_recency(pool: PgPool) {
+        let admin = testing::user("admin", 0, &[], &pool).await;
+        let author = testing::author("jk", &pool).await;
+        let now = Local::now().naive_local();
+        let mut fresh = testing::quote(&[(&author, "fresh")], 0);
+        fresh.timestamp = now;
+        let fresh = testing::save(fresh, &admin, &pool).await;
+        let mut month_old = testing::quote(&[(&author, "month old")], 0);
+        month_old.timestamp = now - Duration::days(30);
+        let month_old = testing::save(month_old, &admin, &pool).await;
+        month_old
+            .set_vote(&admin.id, Some(Vote::Up), &pool)
+            .await
+            .unwrap();
+
+        let weights = |weight| {
+            let pool = &pool;
+            async move {
+                let filter = RandomFilter {
+                    weight,
+                    ..Default::default()
+                };
+                let mut sample = Quote::sample_random(&filter, None, 0.0, 1.0, 10, pool)
+                    .await
+                    .unwrap();
+                sample.sort_by_key(|(id, _)| *id);
+                sample.into_iter().map(|(_, w)| w).collect::<Vec<_>>()
+            }
+        };
+        assert!(fresh.id < month_old.id);
+        assert_eq!(weights(RandomWeight::Uniform).await, vec![1.0, 1.0]);
+        assert_eq!(weights(RandomWeight::Reactions).await, vec![1.0, 2.0]);
+        let recency = weights(RandomWeight::Recency).await;
+        assert!((recency[0] - 1.0).abs() < 0.01);
+        assert!((recency[1] - 0.5).abs() < 0.01);
+    }
+}
@@ -0,0 +1,451 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use strum::{AsRefStr, EnumString};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    omnierror::OmniError,
+    pagination::{Page, PageQuery},
+    user::User,
+};
+
+use super::{fold_rows, query_quote_rows, Quote};
+
+/// The fixed set of emoji users can react with; stored by name.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    ToSchema,
+    AsRefStr,
+    EnumString,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum Reaction {
+    /// 😂
+    Laugh,
+    /// ❤️
+    Heart,
+    /// 🔥
+    Fire,
+    /// 💀
+    Skull,
+    /// 👀
+    Eyes,
+    /// 👏
+    Clap,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Vote {
+    Up,
+    Down,
+}
+
+impl Vote {
+    fn value(self) -> i16 {
+        match self {
+            Vote::Up => 1,
+            Vote::Down => -1,
+        }
+    }
+    fn from_value(value: i16) -> Option<Vote> {
+        match value {
+            1 => Some(Vote::Up),
+            -1 => Some(Vote::Down),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct QuoteReactions {
+    pub upvotes: i64,
+    pub downvotes: i64,
+    /// Upvotes minus downvotes
+    pub score: i64,
+    /// How many users reacted with each emoji; emoji nobody used are left out
+    pub reactions: BTreeMap<Reaction, i64>,
+    /// The caller's own reaction and vote, if they left either
+    pub mine: Option<OwnReaction>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct OwnReaction {
+    pub reaction: Option<Reaction>,
+    pub vote: Option<Vote>,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct NewReaction {
+    pub reaction: Reaction,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct NewVote {
+    pub vote: Vote,
+}
+
+/// Order of quote listings; ties fall back to newest first.
+#[derive(Debug, Clone, Copy, Default, Deserialize, ToSchema, AsRefStr)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum QuoteSort {
+    #[default]
+    Newest,
+    Oldest,
+    /// Highest score first
+    Score,
+    /// Most emoji reactions first
+    Reactions,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TopPeriod {
+    /// Votes cast in the last 7 days
+    Week,
+    /// Votes cast in the last 30 days
+    Month,
+    All,
+}
+
+impl TopPeriod {
+    fn days(self) -> Option<i64> {
+        match self {
+            TopPeriod::Week => Some(7),
+            TopPeriod::Month => Some(30),
+            TopPeriod::All => None,
+        }
+    }
+}
+
+impl Quote {
+    /// Fills in `reactions` for every quote, with `mine` set for the viewer.
+    pub async fn load_reactions(
+        quotes: &mut [Quote],
+        viewer: Option<&User>,
+        pool: &PgPool,
+    ) -> Result<(), OmniError> {
+        let ids: Vec<Uuid> = quotes.iter().map(|q| q.id).collect();
+        let counts = sqlx::query!(
+            r#"
+            SELECT quote_id, reaction, vote, COUNT(*) AS "count!"
+            FROM quote_reactions WHERE quote_id = ANY($1)
+            GROUP BY quote_id, reaction, vote
+            "#,
+            &ids
+        )
+        .fetch_all(pool)
+        .await?;
+        let own = match viewer {
+            Some(u) => {
+                sqlx::query!(
+                    r#"
+                    SELECT quote_id, reaction, vote FROM quote_reactions
+                    WHERE quote_id = ANY($1) AND user_id = $2
+                    "#,
+                    &ids,
+                    u.id
+                )
+                .fetch_all(pool)
+                .await?
+            }
+            None => vec![],
+        };
+
+        let mut all: HashMap<Uuid, QuoteReactions> = HashMap::new();
+        for row in counts {
+            let r = all.entry(row.quote_id).or_default();
+            if let Some(reaction) = row.reaction.and_then(|r| r.parse().ok()) {
+                *r.reactions.entry(reaction).or_default() += row.count;
+            }
+            match row.vote.and_then(Vote::from_value) {
+                Some(Vote::Up) => r.upvotes += row.count,
+                Some(Vote::Down) => r.downvotes += row.count,
+                None => (),
+            }
+        }
+        for row in own {
+            let mine = OwnReaction {
+                reaction: row.reaction.and_then(|r| r.parse().ok()),
+                vote: row.vote.and_then(Vote::from_value),
+            };
+            if mine.reaction.is_some() || mine.vote.is_some() {
+                all.entry(row.quote_id).or_default().mine = Some(mine);
+            }
+        }
+        for q in quotes.iter_mut() {
+            let mut r = all.remove(&q.id).unwrap_or_default();
+            r.score = r.upvotes - r.downvotes;
+            q.reactions = r;
+        }
+        Ok(())
+    }
+    /// Replaces the user's emoji reaction, or takes it back with `None`.
+    pub async fn set_reaction(
+        &self,
+        user_id: &Uuid,
+        reaction: Option<Reaction>,
+        pool: &PgPool,
+    ) -> Result<(), OmniError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO quote_reactions (quote_id, user_id, reaction) VALUES ($1, $2, $3)
+            ON CONFLICT (quote_id, user_id) DO UPDATE SET reaction = EXCLUDED.reaction
+            "#,
+            self.id,
+            user_id,
+            reaction.map(|r| r.as_ref().to_string())
+        )
+        .execute(pool)
+        .await?;
+        Quote::forget_empty_reaction(&self.id, user_id, pool).await
+    }
+    /// Replaces the user's vote, or takes it back with `None`.
+    pub async fn set_vote(
+        &self,
+        user_id: &Uuid,
+        vote: Option<Vote>,
+        pool: &PgPool,
+    ) -> Result<(), OmniError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO quote_reactions (quote_id, user_id, vote, voted_at)
+            VALUES ($1, $2, $3, NOW())
+            ON CONFLICT (quote_id, user_id) DO UPDATE
+            SET vote = EXCLUDED.vote, voted_at = EXCLUDED.voted_at
+            "#,
+            self.id,
+            user_id,
+            vote.map(Vote::value)
+        )
+        .execute(pool)
+        .await?;
+        Quote::forget_empty_reaction(&self.id, user_id, pool).await
+    }
+    async fn forget_empty_reaction(
+        quote_id: &Uuid,
+        user_id: &Uuid,
+        pool: &PgPool,
+    ) -> Result<(), OmniError> {
+        sqlx::query!(
+            r#"
+            DELETE FROM quote_reactions
+            WHERE quote_id = $1 AND user_id = $2 AND reaction IS NULL AND vote IS NULL
+            "#,
+            quote_id,
+            user_id
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+    /// Published quotes the viewer may see, by the score of votes cast in the period.
+    /// Only quotes with a positive score make it.
+    pub async fn get_top(
+        period: TopPeriod,
+        viewer: Option<&User>,
+        page: &PageQuery,
+        pool: &PgPool,
+    ) -> Result<Page<Quote>, OmniError> {
+        let since = period.days().map(|days| Utc::now() - Duration::days(days));
+        let clearance = viewer.map(|u| u.clearance).unwrap_or(0) as i64;
+        let viewer_id = viewer.map(|u| u.id);
+        let total = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) AS "total!" FROM (
+                SELECT quote_reactions.quote_id FROM quote_reactions
+                INNER JOIN quotes ON quotes.id = quote_reactions.quote_id
+                WHERE quote_reactions.vote IS NOT NULL
+                AND ($1::timestamptz IS NULL OR quote_reactions.voted_at >= $1)
+                AND quote_visible_to(quotes.id, $2, $3)
+                GROUP BY quote_reactions.quote_id
+                HAVING SUM(quote_reactions.vote) > 0
+            ) AS ranked
+            "#,
+            since,
+            clearance,
+            viewer_id
+        )
+        .fetch_one(pool)
+        .await?;
+        let rows = query_quote_rows!(
+            r#"
+                INNER JOIN (
+                    SELECT
+                        quote_reactions.quote_id AS id,
+                        SUM(quote_reactions.vote) AS score,
+                        COUNT(*) FILTER (WHERE quote_reactions.vote = 1) AS upvotes
+                    FROM quote_reactions
+                    INNER JOIN quotes ON quotes.id = quote_reactions.quote_id
+                    WHERE quote_reactions.vote IS NOT NULL
+                    AND ($1::timestamptz IS NULL OR quote_reactions.voted_at >= $1)
+                    AND quote_visible_to(quotes.id, $2, $3)
+                    GROUP BY quote_reactions.quote_id
+                    HAVING SUM(quote_reactions.vote) > 0
+                    ORDER BY score DESC, upvotes DESC, id DESC
+                    LIMIT $4 OFFSET $5
+                ) AS ranked ON ranked.id = quotes.id
+                ORDER BY ranked.score DESC, ranked.upvotes DESC, quotes.id DESC, lines.position ASC
+            "#,
+            since,
+            clearance,
+            viewer_id,
+            page.limit(),
+            page.offset()
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(Page::new(fold_rows(rows), page, total))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{quotes::source::SourceFilter, testing};
+
+    fn first_page() -> PageQuery {
+        PageQuery {
+            page: 1,
+            per_page: 20,
+        }
+    }
+
+    async fn top(period: TopPeriod, viewer: Option<&User>, pool: &PgPool) -> Vec<Uuid> {
+        let page = Quote::get_top(period, viewer, &first_page(), pool)
+            .await
+            .unwrap();
+        page.items.iter().map(|q| q.id).collect()
+    }
+
+    #[sqlx::test]
+    async fn reactions_and_votes_are_counted_per_user(pool: PgPool) {
+        let alice = testing::user("alice", 0, &[], &pool).await;
+        let bob = testing::user("bob", 0, &[], &pool).await;
+        let author = testing::author("jk", &pool).await;
+        let quote = testing::save(testing::quote(&[(&author, "hi")], 0), &alice, &pool).await;
+
+        quote
+            .set_reaction(&alice.id, Some(Reaction::Fire), &pool)
+            .await
+            .unwrap();
+        quote
+            .set_reaction(&alice.id, Some(Reaction::Laugh), &pool)
+            .await
+            .unwrap();
+        quote
+            .set_vote(&alice.id, Some(Vote::Up), &pool)
+            .await
+            .unwrap();
+        quote
+            .set_reaction(&bob.id, Some(Reaction::Laugh), &pool)
+            .await
+            .unwrap();
+        quote
+            .set_vote(&bob.id, Some(Vote::Down), &pool)
+            .await
+            .unwrap();
+        quote.set_vote(&bob.id, None, &pool).await.unwrap();
+
+        let mut quotes = vec![Quote::get_by_id(&quote.id, &pool).await.unwrap().unwrap()];
+        Quote::load_reactions(&mut quotes, Some(&bob), &pool)
+            .await
+            .unwrap();
+        let r = &quotes[0].reactions;
+        assert_eq!((r.upvotes, r.downvotes, r.score), (1, 0, 1));
+        assert_eq!(r.reactions, BTreeMap::from([(Reaction::Laugh, 2)]));
+        let mine = r.mine.as_ref().unwrap();
+        assert_eq!((mine.reaction, mine.vote), (Some(Reaction::Laugh), None));
+
+        quote.set_reaction(&bob.id, None, &pool).await.unwrap();
+        let rows = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS "count!" FROM quote_reactions WHERE user_id = $1"#,
+            bob.id
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(rows, 0);
+        Quote::load_reactions(&mut quotes, None, &pool)
+            .await
+            .unwrap();
+        assert!(quotes[0].reactions.mine.is_none());
+    }
+
+    #[sqlx::test]
+    async fn top_quotes_only_count_visible_recent_positive_votes(pool: PgPool) {
+        let admin = testing::user("admin", 1, &[], &pool).await;
+        let voter = testing::user("voter", 0, &[], &pool).await;
+        let author = testing::author("jk", &pool).await;
+        let save = |content, clearance| {
+            let (admin, author, pool) = (&admin, &author, &pool);
+            async move {
+                testing::save(testing::quote(&[(author, content)], clearance), admin, pool).await
+            }
+        };
+        let liked = save("liked", 0).await;
+        let loved = save("loved", 0).await;
+        let disliked = save("disliked", 0).await;
+        let secret = save("secret", 1).await;
+        let old = save("old", 0).await;
+        for quote in [&liked, &loved, &secret, &old] {
+            quote
+                .set_vote(&voter.id, Some(Vote::Up), &pool)
+                .await
+                .unwrap();
+        }
+        loved
+            .set_vote(&admin.id, Some(Vote::Up), &pool)
+            .await
+            .unwrap();
+        disliked
+            .set_vote(&voter.id, Some(Vote::Down), &pool)
+            .await
+            .unwrap();
+        sqlx::query!(
+            "UPDATE quote_reactions SET voted_at = NOW() - INTERVAL '60 days' WHERE quote_id = $1",
+            old.id
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        assert_eq!(
+            top(TopPeriod::Week, Some(&voter), &pool).await,
+            vec![loved.id, liked.id]
+        );
+        assert_eq!(
+            top(TopPeriod::All, Some(&voter), &pool).await,
+            vec![loved.id, old.id, liked.id]
+        );
+        assert_eq!(
+            top(TopPeriod::Month, Some(&admin), &pool).await,
+            vec![loved.id, secret.id, liked.id]
+        );
+        assert_eq!(top(TopPeriod::Week, None, &pool).await.len(), 2);
+
+        let filter = SourceFilter {
+            sort: QuoteSort::Score,
+            ..Default::default()
+        };
+        let sorted = Quote::get_all(&filter, &voter, &pool).await.unwrap();
+        let ids: Vec<_> = sorted.iter().map(|q| q.id).collect();
+        assert_eq!(ids, vec![loved.id, old.id, liked.id, disliked.id]);
+    }
+}
@@ -0,0 +1,107 @@
+use uuid::Uuid;
+
+use crate::user::User;
+
+use super::Quote;
+
+pub const REDACTED_MARKER: &str = "[redacted]";
+
+impl Quote {
+    /// The highest line clearance; creating the quote requires at least this much.
+    pub fn max_clearance(&self) -> u8 {
+        self.lines
+            .iter()
+            .map(|l| l.clearance)
+            .fold(self.clearance, u8::max)
+    }
+    /// Replaces lines above the viewer's clearance with a marker and hides their authors.
+    /// Lines by the viewer's own linked author are never redacted for them.
+    pub fn redact_for(self, viewer: Option<&User>) -> Quote {
+        let clearance = viewer.map(|u| u.clearance).unwrap_or(0);
+        self.redact_above(clearance, viewer.map(|u| u.id))
+    }
+    /// Share links show the quote as someone with just its own clearance would see it.
+    pub fn redact_for_link(self) -> Quote {
+        let clearance = self.clearance;
+        self.redact_above(clearance, None)
+    }
+    fn redact_above(mut self, clearance: u8, viewer_id: Option<Uuid>) -> Quote {
+        for line in self.lines.iter_mut() {
+            if line.clearance <= clearance {
+                continue;
+            }
+            let own = line
+                .author_id
+                .and_then(|id| self.authors.get(&id))
+                .is_some_and(|a| a.user_id.is_some() && a.user_id == viewer_id);
+            if !own {
+                line.content = REDACTED_MARKER.to_string();
+                line.author_id = None;
+                line.redacted = true;
+            }
+        }
+        let lines = &self.lines;
+        self.authors
+            .retain(|id, _| lines.iter().any(|l| l.author_id == Some(*id)));
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::testing;
+
+    fn contents(quote: &Quote) -> Vec<&str> {
+        quote.lines.iter().map(|l| l.content.as_str()).collect()
+    }
+
+    #[sqlx::test]
+    async fn lines_above_the_clearance_are_redacted(pool: PgPool) {
+        let viewer = testing::user("viewer", 1, &[], &pool).await;
+        let jk = testing::author("jk", &pool).await;
+        let jan = testing::author("jan", &pool).await;
+        let mut quote = testing::quote(&[(&jk, "hi"), (&jan, "psst"), (&jk, "shh")], 0);
+        quote.lines[1].clearance = 2;
+        quote.lines[2].clearance = 1;
+        assert_eq!(quote.max_clearance(), 2);
+
+        let seen = quote.clone().redact_for(Some(&viewer));
+        assert_eq!(contents(&seen), vec!["hi", REDACTED_MARKER, "shh"]);
+        assert_eq!(seen.lines[1].author_id, None);
+        assert!(seen.lines[1].redacted && !seen.lines[2].redacted);
+        assert!(!seen.authors.contains_key(&jan.id));
+
+        let seen = quote.clone().redact_for(None);
+        assert_eq!(
+            contents(&seen),
+            vec!["hi", REDACTED_MARKER, REDACTED_MARKER]
+        );
+
+        let seen = quote.redact_for_link();
+        assert_eq!(
+            contents(&seen),
+            vec!["hi", REDACTED_MARKER, REDACTED_MARKER]
+        );
+    }
+
+    #[sqlx::test]
+    async fn own_lines_are_never_redacted(pool: PgPool) {
+        let viewer = testing::user("viewer", 0, &[], &pool).await;
+        let mut jk = testing::author("jk", &pool).await;
+        jk.user_id = Some(viewer.id);
+        let jan = testing::author("jan", &pool).await;
+        let mut quote = testing::quote(&[(&jk, "mine"), (&jan, "theirs")], 0);
+        quote.lines[0].clearance = 3;
+        quote.lines[1].clearance = 3;
+
+        let seen = quote.clone().redact_for(Some(&viewer));
+        assert_eq!(contents(&seen), vec!["mine", REDACTED_MARKER]);
+        assert_eq!(seen.lines[0].author_id, Some(jk.id));
+        // nobody owns the lines of an unlinked author
+        let seen = quote.redact_for_link();
+        assert_eq!(contents(&seen), vec![REDACTED_MARKER, REDACTED_MARKER]);
+    }
+}
@@ -0,0 +1,198 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    omnierror::OmniError,
+    user::{
+        auth::crypto::{generate_token, hash_token},
+        User,
+    },
+};
+
+use super::Quote;
+
+/// Opens a single quote to anyone holding the token, without logging in.
+#[derive(Serialize, ToSchema)]
+pub struct ShareLink {
+    pub id: Uuid,
+    pub quote_id: Uuid,
+    pub created_by: Uuid,
+    pub created: DateTime<Utc>,
+    /// The link stops working after this; `null` links never expire
+    pub expiry: Option<DateTime<Utc>>,
+    pub revoked: Option<DateTime<Utc>>,
+    /// How often the link has been opened
+    pub views: i64,
+    pub last_viewed: Option<DateTime<Utc>>,
+}
+
+/// A freshly created link; the token cannot be looked up again later.
+#[derive(Serialize, ToSchema)]
+pub struct CreatedShareLink {
+    #[serde(flatten)]
+    pub link: ShareLink,
+    /// Opens the quote at `/quotes/link/{token}`
+    pub token: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct NewShareLink {
+    #[serde(default)]
+    pub expiry: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ShareLinkValidityError {
+    #[error("Share links must expire in the future.")]
+    ExpiryInPast,
+}
+
+impl ShareLinkValidityError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ShareLinkValidityError::ExpiryInPast => "share_link_expiry_in_past",
+        }
+    }
+    pub fn field(&self) -> &'static str {
+        match self {
+            ShareLinkValidityError::ExpiryInPast => "expiry",
+        }
+    }
+}
+
+impl ShareLink {
+    /// Whoever created the link, and the user who submitted the quote, may see its views
+    /// and revoke it.
+    pub fn is_managed_by(&self, user: &User, quote: &Quote) -> bool {
+        self.created_by == user.id || quote.submitted_by == Some(user.id)
+    }
+    pub async fn get_by_id(id: &Uuid, pool: &PgPool) -> Result<Option<ShareLink>, OmniError> {
+        match sqlx::query_as!(
+            ShareLink,
+            r#"
+            SELECT
+                id, quote_id, created_by, created, expiry, revoked,
+                (SELECT COUNT(*) FROM quote_share_link_views WHERE link_id = quote_share_links.id) AS "views!",
+                (SELECT MAX(viewed) FROM quote_share_link_views WHERE link_id = quote_share_links.id) AS last_viewed
+            FROM quote_share_links WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(pool)
+        .await
+        {
+            Ok(link) => Ok(link),
+            Err(e) => Err(e)?,
+        }
+    }
+    /// Links on the quote the user manages, newest first.
+    pub async fn get_for_quote(
+        quote: &Quote,
+        user: &User,
+        pool: &PgPool,
+    ) -> Result<Vec<ShareLink>, OmniError> {
+        match sqlx::query_as!(
+            ShareLink,
+            r#"
+            SELECT
+                id, quote_id, created_by, created, expiry, revoked,
+                (SELECT COUNT(*) FROM quote_share_link_views WHERE link_id = quote_share_links.id) AS "views!",
+                (SELECT MAX(viewed) FROM quote_share_link_views WHERE link_id = quote_share_links.id) AS last_viewed
+            FROM quote_share_links
+            WHERE quote_id = $1 AND (created_by = $2 OR $3)
+            ORDER BY created DESC, id
+            "#,
+            quote.id,
+            user.id,
+            quote.submitted_by == Some(user.id)
+        )
+        .fetch_all(pool)
+        .await
+        {
+            Ok(links) => Ok(links),
+            Err(e) => Err(e)?,
+        }
+    }
+    /// The quote id behind a token, if the link is neither revoked nor expired
+    /// and the quote is still published. Counts as a view of the link.
+    pub async fn open(token: &str, pool: &PgPool) -> Result<Option<Uuid>, OmniError> {
+        match sqlx::query_scalar!(
+            r#"
+            WITH opened AS (
+                INSERT INTO quote_share_link_views (link_id)
+                SELECT quote_share_links.id FROM quote_share_links
+                INNER JOIN quotes ON quotes.id = quote_share_links.quote_id
+                WHERE quote_share_links.token = $1 AND quote_share_links.revoked IS NULL
+                AND (quote_share_links.expiry IS NULL OR quote_share_links.expiry > NOW())
+                AND quotes.status = 'published' AND quotes.deleted_at IS NULL
+                RETURNING link_id
+            )
+            SELECT quote_share_links.quote_id FROM quote_share_links
+            INNER JOIN opened ON opened.link_id = quote_share_links.id
+            "#,
+            hash_token(token)
+        )
+        .fetch_optional(pool)
+        .await
+        {
+            Ok(quote_id) => Ok(quote_id),
+            Err(e) => Err(e)?,
+        }
+    }
+    /// The unhashed token is only ever returned from here.
+    pub async fn create(
+        quote: &Quote,
+        new: NewShareLink,
+        user: &User,
+        pool: &PgPool,
+    ) -> Result<CreatedShareLink, OmniError> {
+        if new.expiry.is_some_and(|e| e <= Utc::now()) {
+            return Err(ShareLinkValidityError::ExpiryInPast)?;
+        }
+        let token = generate_token();
+        match sqlx::query_as!(
+            ShareLink,
+            r#"
+            INSERT INTO quote_share_links (id, quote_id, created_by, token, expiry)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING
+                id, quote_id, created_by, created, expiry, revoked,
+                0::bigint AS "views!", NULL::timestamptz AS last_viewed
+            "#,
+            Uuid::now_v7(),
+            quote.id,
+            user.id,
+            hash_token(&token),
+            new.expiry
+        )
+        .fetch_one(pool)
+        .await
+        {
+            Ok(link) => Ok(CreatedShareLink { link, token }),
+            Err(e) => Err(e)?,
+        }
+    }
+    /// The link stays listed with its views; revoking twice keeps the first time.
+    pub async fn revoke(mut self, pool: &PgPool) -> Result<ShareLink, OmniError> {
+        match sqlx::query_scalar!(
+            r#"
+            UPDATE quote_share_links SET revoked = COALESCE(revoked, NOW())
+            WHERE id = $1 RETURNING revoked
+            "#,
+            self.id
+        )
+        .fetch_one(pool)
+        .await
+        {
+            Ok(revoked) => {
+                self.revoked = revoked;
+                Ok(self)
+            }
+            Err(e) => Err(e)?,
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use serde::{Deserialize, Serialize};
+use strum::{AsRefStr, EnumString};
+use utoipa::{IntoParams, ToSchema};
+
+use super::reactions::QuoteSort;
+
+/// Where and how something was said; stored in the `quotes.source_*` columns.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct QuoteSource {
+    pub medium: Option<SourceMedium>,
+    /// URL or any other reference (message link, meeting name, VOD timestamp)
+    pub reference: Option<String>,
+    pub location: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema, EnumString, AsRefStr)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum SourceMedium {
+    InPerson,
+    Chat,
+    Meeting,
+    Stream,
+    Email,
+}
+
+/// Listing filters and order; `location` matches case-insensitively on a substring.
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[serde(deny_unknown_fields)]
+pub struct SourceFilter {
+    pub medium: Option<SourceMedium>,
+    pub location: Option<String>,
+    #[serde(default)]
+    #[param(inline)]
+    pub sort: QuoteSort,
+}
+
+impl QuoteSource {
+    pub fn from_columns(
+        medium: Option<String>,
+        reference: Option<String>,
+        location: Option<String>,
+    ) -> Option<QuoteSource> {
+        if medium.is_none() && reference.is_none() && location.is_none() {
+            return None;
+        }
+        Some(QuoteSource {
+            medium: medium.and_then(|m| m.parse().ok()),
+            reference,
+            location,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use crate::{quotes::Quote, testing};
+
+    use super::*;
+
+    #[test]
+    fn empty_columns_are_no_source() {
+        assert!(QuoteSource::from_columns(None, None, None).is_none());
+        let source = QuoteSource::from_columns(Some("in_person".into()), None, None).unwrap();
+        assert_eq!(source.medium, Some(SourceMedium::InPerson));
+    }
+
+    #[sqlx::test]
+    async fn quotes_are_filtered_by_source(pool: PgPool) {
+        let viewer = testing::user("viewer", 0, &[], &pool).await;
+        let jake = testing::author("jake", &pool).await;
+        let mut chat = testing::quote(&[(&jake, "in the chat")], 0);
+        chat.source = Some(QuoteSource {
+            medium: Some(SourceMedium::Chat),
+            reference: Some("https://chat.example/m/1".into()),
+            location: Some("Discord".into()),
+        });
+        let chat = testing::save(chat, &viewer, &pool).await;
+        let mut meeting = testing::quote(&[(&jake, "in the meeting")], 0);
+        meeting.source = Some(QuoteSource {
+            medium: Some(SourceMedium::Meeting),
+            reference: None,
+            location: Some("Weekly sync".into()),
+        });
+        let meeting = testing::save(meeting, &viewer, &pool).await;
+        let unsourced = testing::quote(&[(&jake, "somewhere")], 0);
+        let unsourced = testing::save(unsourced, &viewer, &pool).await;
+
+        let ids = |filter: SourceFilter| {
+            let (viewer, pool) = (&viewer, &pool);
+            async move {
+                let quotes = Quote::get_all(&filter, viewer, pool).await.unwrap();
+                quotes.into_iter().map(|q| q.id).collect::<Vec<_>>()
+            }
+        };
+        let by_medium = SourceFilter {
+            medium: Some(SourceMedium::Chat),
+            ..Default::default()
+        };
+        assert_eq!(ids(by_medium).await, [chat.id]);
+        let by_location = SourceFilter {
+            location: Some("SYNC".into()),
+            ..Default::default()
+        };
+        assert_eq!(ids(by_location).await, [meeting.id]);
+        assert_eq!(ids(SourceFilter::default()).await.len(), 3);
+
+        let stored = Quote::get_by_id(&chat.id, &pool).await.unwrap().unwrap();
+        let source = stored.source.unwrap();
+        assert_eq!(source.medium, Some(SourceMedium::Chat));
+        assert_eq!(
+            source.reference.as_deref(),
+            Some("https://chat.example/m/1")
+        );
+        let stored = Quote::get_by_id(&unsourced.id, &pool)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(stored.source.is_none());
+    }
+}
@@ -0,0 +1,175 @@
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::{PgPool, Postgres, Transaction};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::{
+    logs::{Log, LogAction},
+    omnierror::OmniError,
+};
+
+use super::{validity::QuoteValidityError, Quote};
+
+pub const TAG_LEN_BOUND_UPPER: usize = 32;
+pub const TAGS_LIMIT: usize = 10;
+
+#[derive(Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct QuoteTags {
+    /// Replaces every tag of the quote; an empty list removes them all
+    pub tags: Vec<String>,
+}
+
+/// Narrows a listing to quotes with the tag, compared case-insensitively.
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TagFilter {
+    pub tag: Option<String>,
+}
+
+impl TagFilter {
+    /// The tag as it is stored, if one was given.
+    pub fn normalized(&self) -> Option<String> {
+        normalize_filter(self.tag.as_deref())
+    }
+}
+
+/// A tag to filter by as it is stored; an empty one filters nothing.
+pub fn normalize_filter(tag: Option<&str>) -> Option<String> {
+    tag.map(normalize_tag).filter(|t| !t.is_empty())
+}
+
+fn normalize_tag(tag: &str) -> String {
+    tag.trim().to_lowercase()
+}
+
+/// Trimmed, lowercase, sorted and without repeats.
+pub fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut tags: Vec<String> = tags.iter().map(|t| normalize_tag(t)).collect();
+    tags.sort();
+    tags.dedup();
+    tags
+}
+
+/// Letters, digits, dashes and underscores; at most `TAGS_LIMIT` of them on a quote.
+pub fn are_valid_tags(tags: &[String]) -> Result<(), QuoteValidityError> {
+    let tags = normalize_tags(tags);
+    let is_valid_tag = |tag: &String| {
+        !tag.is_empty()
+            && tag.chars().count() <= TAG_LEN_BOUND_UPPER
+            && tag
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    };
+    match tags.len() <= TAGS_LIMIT && tags.iter().all(is_valid_tag) {
+        true => Ok(()),
+        false => Err(QuoteValidityError::TagsInvalid),
+    }
+}
+
+/// Expects tags already normalized.
+pub(super) async fn insert_tags(
+    quote_id: &Uuid,
+    tags: &[String],
+    tr: &mut Transaction<'_, Postgres>,
+) -> Result<(), OmniError> {
+    match sqlx::query!(
+        r#"
+        INSERT INTO quote_tags (quote_id, tag)
+        SELECT $1, tag FROM UNNEST($2::text[]) AS tag
+        "#,
+        quote_id,
+        tags
+    )
+    .execute(&mut **tr)
+    .await
+    {
+        Ok(_) => Ok(()),
+        Err(e) => Err(e)?,
+    }
+}
+
+impl Quote {
+    /// Replaces the quote's tags; recorded in the audit log.
+    pub async fn set_tags(
+        mut self,
+        tags: Vec<String>,
+        actor_id: &Uuid,
+        pool: &PgPool,
+    ) -> Result<Quote, OmniError> {
+        are_valid_tags(&tags)?;
+        let tags = normalize_tags(&tags);
+
+        let mut tr = pool.begin().await?;
+        let set = async {
+            sqlx::query!("DELETE FROM quote_tags WHERE quote_id = $1", self.id)
+                .execute(&mut *tr)
+                .await?;
+            insert_tags(&self.id, &tags, &mut tr).await?;
+            Log::record(
+                actor_id,
+                &self.id,
+                LogAction::QuotesTagsChanged,
+                json!({ "from": self.tags, "to": tags }),
+                &mut *tr,
+            )
+            .await
+        }
+        .await;
+        match set {
+            Ok(()) => {
+                tr.commit().await?;
+                self.tags = tags;
+                Ok(self)
+            }
+            Err(e) => {
+                tr.rollback().await?;
+                Err(e)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn tags(tags: &[&str]) -> Vec<String> {
+        tags.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn tags_are_short_words_compared_without_case() {
+        assert_eq!(
+            normalize_tags(&tags(&[" Work", "dnd", "work "])),
+            vec!["dnd", "work"]
+        );
+        assert!(are_valid_tags(&tags(&["d&d"])).is_err());
+        assert!(are_valid_tags(&tags(&["  "])).is_err());
+        assert!(are_valid_tags(&tags(&[&"x".repeat(TAG_LEN_BOUND_UPPER + 1)])).is_err());
+        let many: Vec<String> = (0..=TAGS_LIMIT).map(|i| format!("t{i}")).collect();
+        assert!(are_valid_tags(&many).is_err());
+        assert!(are_valid_tags(&many[1..]).is_ok());
+    }
+
+    #[sqlx::test]
+    async fn tags_are_saved_and_replaced(pool: PgPool) {
+        let admin = testing::user("admin", 0, &[], &pool).await;
+        let author = testing::author("jk", &pool).await;
+        let mut quote = testing::quote(&[(&author, "hi")], 0);
+        quote.tags = tags(&["Work", "work", "dnd"]);
+        let quote = testing::save(quote, &admin, &pool).await;
+        assert_eq!(quote.tags, vec!["dnd", "work"]);
+        let stored = Quote::get_by_id(&quote.id, &pool).await.unwrap().unwrap();
+        assert_eq!(stored.tags, quote.tags);
+
+        stored
+            .set_tags(tags(&["office"]), &admin.id, &pool)
+            .await
+            .unwrap();
+        let stored = Quote::get_by_id(&quote.id, &pool).await.unwrap().unwrap();
+        assert_eq!(stored.tags, vec!["office"]);
+    }
+}
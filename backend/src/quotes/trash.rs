@@ -0,0 +1,375 @@
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    logs::{Log, LogAction},
+    omnierror::OmniError,
+    pagination::{Page, PageQuery},
+    user::User,
+};
+
+use super::{fold_rows, query_quote_rows, Quote};
+
+impl Quote {
+    pub async fn get_trashed_by_id(id: &Uuid, pool: &PgPool) -> Result<Option<Quote>, OmniError> {
+        Quote::fetch_by_id(id, true, pool).await
+    }
+    /// Trashed quotes the viewer may see by clearance and groups, most recently deleted first.
+    pub async fn get_trash_page(
+        viewer: &User,
+        page: &PageQuery,
+        pool: &PgPool,
+    ) -> Result<Page<Quote>, OmniError> {
+        let total = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) AS "total!" FROM quotes
+            WHERE deleted_at IS NOT NULL AND clearance <= $1 AND quote_shared_with(id, $2)
+            "#,
+            viewer.clearance as i64,
+            viewer.id
+        )
+        .fetch_one(pool)
+        .await?;
+        let rows = query_quote_rows!(
+            r#"
+                WHERE quotes.id IN (
+                    SELECT id FROM quotes
+                    WHERE deleted_at IS NOT NULL AND clearance <= $1 AND quote_shared_with(id, $2)
+                    ORDER BY deleted_at DESC, id LIMIT $3 OFFSET $4
+                )
+                ORDER BY quotes.deleted_at DESC, quotes.id, lines.position ASC
+            "#,
+            viewer.clearance as i64,
+            viewer.id,
+            page.limit(),
+            page.offset()
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(Page::new(fold_rows(rows), page, total))
+    }
+    /// Moves the quote to the trash, from where it can be restored until purged.
+    pub async fn trash(mut self, actor_id: &Uuid, pool: &PgPool) -> Result<Quote, OmniError> {
+        let mut tr = pool.begin().await?;
+        let trashed = async {
+            let deleted_at = sqlx::query_scalar!(
+                r#"
+                UPDATE quotes SET deleted_at = NOW(), deleted_by = $1
+                WHERE id = $2 RETURNING deleted_at AS "deleted_at!"
+                "#,
+                actor_id,
+                self.id
+            )
+            .fetch_one(&mut *tr)
+            .await?;
+            Log::record(
+                actor_id,
+                &self.id,
+                LogAction::QuotesTrashed,
+                json!({}),
+                &mut *tr,
+            )
+            .await?;
+            Ok::<DateTime<Utc>, OmniError>(deleted_at)
+        }
+        .await;
+        match trashed {
+            Ok(deleted_at) => {
+                tr.commit().await?;
+                self.deleted_at = Some(deleted_at);
+                self.deleted_by = Some(*actor_id);
+                Ok(self)
+            }
+            Err(e) => {
+                tr.rollback().await?;
+                Err(e)
+            }
+        }
+    }
+    pub async fn restore(mut self, actor_id: &Uuid, pool: &PgPool) -> Result<Quote, OmniError> {
+        let mut tr = pool.begin().await?;
+        let restored = async {
+            sqlx::query!(
+                "UPDATE quotes SET deleted_at = NULL, deleted_by = NULL WHERE id = $1",
+                self.id
+            )
+            .execute(&mut *tr)
+            .await?;
+            Log::record(
+                actor_id,
+                &self.id,
+                LogAction::QuotesRestored,
+                json!({ "deleted_at": self.deleted_at, "deleted_by": self.deleted_by }),
+                &mut *tr,
+            )
+            .await
+        }
+        .await;
+        match restored {
+            Ok(()) => {
+                tr.commit().await?;
+                self.deleted_at = None;
+                self.deleted_by = None;
+                Ok(self)
+            }
+            Err(e) => {
+                tr.rollback().await?;
+                Err(e)
+            }
+        }
+    }
+    /// Permanently removes the quote with its lines; there is no way back.
+    pub async fn purge(self, actor_id: &Uuid, pool: &PgPool) -> Result<(), OmniError> {
+        let mut tr = pool.begin().await?;
+        let purged = async {
+            purge_in(&[self.id], &mut tr).await?;
+            Log::record(
+                actor_id,
+                &self.id,
+                LogAction::QuotesPurged,
+                json!({ "deleted_at": self.deleted_at, "deleted_by": self.deleted_by }),
+                &mut *tr,
+            )
+            .await
+        }
+        .await;
+        match purged {
+            Ok(()) => Ok(tr.commit().await?),
+            Err(e) => {
+                tr.rollback().await?;
+                Err(e)
+            }
+        }
+    }
+    /// Purges every quote that has been in the trash since before `cutoff`.
+    /// Returns how many were purged.
+    pub async fn purge_trashed_before(
+        cutoff: DateTime<Utc>,
+        pool: &PgPool,
+    ) -> Result<usize, OmniError> {
+        let mut tr = pool.begin().await?;
+        let purged = async {
+            let ids = sqlx::query_scalar!(
+                "SELECT id FROM quotes WHERE deleted_at < $1 FOR UPDATE",
+                cutoff
+            )
+            .fetch_all(&mut *tr)
+            .await?;
+            purge_in(&ids, &mut tr).await?;
+            Ok::<usize, OmniError>(ids.len())
+        }
+        .await;
+        match purged {
+            Ok(count) => {
+                tr.commit().await?;
+                Ok(count)
+            }
+            Err(e) => {
+                tr.rollback().await?;
+                Err(e)
+            }
+        }
+    }
+}
+
+async fn purge_in(ids: &[Uuid], tr: &mut Transaction<'_, Postgres>) -> Result<(), OmniError> {
+    sqlx::query!("DELETE FROM quote_groups WHERE quote_id = ANY($1)", ids)
+        .execute(&mut **tr)
+        .await?;
+    sqlx::query!("DELETE FROM quote_tags WHERE quote_id = ANY($1)", ids)
+        .execute(&mut **tr)
+        .await?;
+    sqlx::query!(
+        "DELETE FROM quote_comments WHERE quote_id = ANY($1) AND parent_id IS NOT NULL",
+        ids
+    )
+    .execute(&mut **tr)
+    .await?;
+    sqlx::query!("DELETE FROM quote_comments WHERE quote_id = ANY($1)", ids)
+        .execute(&mut **tr)
+        .await?;
+    sqlx::query!("DELETE FROM quote_favourites WHERE quote_id = ANY($1)", ids)
+        .execute(&mut **tr)
+        .await?;
+    sqlx::query!("DELETE FROM collection_items WHERE quote_id = ANY($1)", ids)
+        .execute(&mut **tr)
+        .await?;
+    sqlx::query!(
+        r#"
+        DELETE FROM quote_share_link_views WHERE link_id IN (
+            SELECT id FROM quote_share_links WHERE quote_id = ANY($1)
+        )
+        "#,
+        ids
+    )
+    .execute(&mut **tr)
+    .await?;
+    sqlx::query!(
+        "DELETE FROM quote_share_links WHERE quote_id = ANY($1)",
+        ids
+    )
+    .execute(&mut **tr)
+    .await?;
+    sqlx::query!("DELETE FROM quote_of_the_day WHERE quote_id = ANY($1)", ids)
+        .execute(&mut **tr)
+        .await?;
+    sqlx::query!("DELETE FROM quote_reactions WHERE quote_id = ANY($1)", ids)
+        .execute(&mut **tr)
+        .await?;
+    sqlx::query!("DELETE FROM quote_flags WHERE quote_id = ANY($1)", ids)
+        .execute(&mut **tr)
+        .await?;
+    sqlx::query!("DELETE FROM lines WHERE quote_id = ANY($1)", ids)
+        .execute(&mut **tr)
+        .await?;
+    sqlx::query!("DELETE FROM quotes WHERE id = ANY($1)", ids)
+        .execute(&mut **tr)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+    use crate::{
+        groups::{Group, NewGroup},
+        quotes::source::SourceFilter,
+        testing,
+    };
+
+    fn first_page() -> PageQuery {
+        PageQuery {
+            page: 1,
+            per_page: 20,
+        }
+    }
+
+    async fn rows_about(id: &Uuid, pool: &PgPool) -> i64 {
+        sqlx::query_scalar!(
+            r#"
+            SELECT (SELECT COUNT(*) FROM lines WHERE quote_id = $1)
+                + (SELECT COUNT(*) FROM quote_groups WHERE quote_id = $1)
+                + (SELECT COUNT(*) FROM quote_favourites WHERE quote_id = $1)
+                AS "count!"
+            "#,
+            id
+        )
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn trashed_quotes_are_hidden_until_restored(pool: PgPool) {
+        let admin = testing::user("admin", 0, &[], &pool).await;
+        let author = testing::author("jk", &pool).await;
+        let quote = testing::save(testing::quote(&[(&author, "hi")], 0), &admin, &pool).await;
+
+        let quote = quote.trash(&admin.id, &pool).await.unwrap();
+        assert_eq!(quote.deleted_by, Some(admin.id));
+        assert!(Quote::get_by_id(&quote.id, &pool).await.unwrap().is_none());
+        let listed = Quote::get_all(&SourceFilter::default(), &admin, &pool)
+            .await
+            .unwrap();
+        assert!(listed.is_empty());
+        let trash = Quote::get_trash_page(&admin, &first_page(), &pool)
+            .await
+            .unwrap();
+        assert_eq!((trash.total, trash.items[0].id), (1, quote.id));
+
+        let quote = Quote::get_trashed_by_id(&quote.id, &pool)
+            .await
+            .unwrap()
+            .unwrap();
+        let id = quote.restore(&admin.id, &pool).await.unwrap().id;
+        let quote = Quote::get_by_id(&id, &pool).await.unwrap().unwrap();
+        assert_eq!((quote.deleted_at, quote.deleted_by), (None, None));
+    }
+
+    #[sqlx::test]
+    async fn the_trash_respects_clearance(pool: PgPool) {
+        let admin = testing::user("admin", 1, &[], &pool).await;
+        let viewer = testing::user("viewer", 0, &[], &pool).await;
+        let author = testing::author("jk", &pool).await;
+        let secret = testing::save(testing::quote(&[(&author, "psst")], 1), &admin, &pool).await;
+        secret.trash(&admin.id, &pool).await.unwrap();
+
+        let trash = Quote::get_trash_page(&viewer, &first_page(), &pool)
+            .await
+            .unwrap();
+        assert_eq!(trash.total, 0);
+        assert!(trash.items.is_empty());
+    }
+
+    #[sqlx::test]
+    async fn purging_removes_everything_about_the_quote(pool: PgPool) {
+        let admin = testing::user("admin", 0, &[], &pool).await;
+        let author = testing::author("jk", &pool).await;
+        let group = Group::create(
+            NewGroup {
+                name: "Team".into(),
+                description: None,
+            },
+            &pool,
+        )
+        .await
+        .unwrap();
+        let quote = testing::save(testing::quote(&[(&author, "hi")], 0), &admin, &pool).await;
+        let quote = quote
+            .set_groups(vec![group.id], &admin.id, &pool)
+            .await
+            .unwrap();
+        quote.star(&admin.id, &pool).await.unwrap();
+        assert_eq!(rows_about(&quote.id, &pool).await, 3);
+
+        let id = quote.id;
+        quote
+            .trash(&admin.id, &pool)
+            .await
+            .unwrap()
+            .purge(&admin.id, &pool)
+            .await
+            .unwrap();
+        assert_eq!(rows_about(&id, &pool).await, 0);
+        assert!(Quote::get_trashed_by_id(&id, &pool)
+            .await
+            .unwrap()
+            .is_none());
+        // the group is free to go once no quote is restricted to it
+        group.destroy(&pool).await.unwrap();
+    }
+
+    #[sqlx::test]
+    async fn only_quotes_trashed_before_the_cutoff_are_purged(pool: PgPool) {
+        let admin = testing::user("admin", 0, &[], &pool).await;
+        let author = testing::author("jk", &pool).await;
+        let old = testing::save(testing::quote(&[(&author, "old")], 0), &admin, &pool).await;
+        let recent = testing::save(testing::quote(&[(&author, "new")], 0), &admin, &pool).await;
+        let kept = testing::save(testing::quote(&[(&author, "kept")], 0), &admin, &pool).await;
+        let old = old.trash(&admin.id, &pool).await.unwrap();
+        sqlx::query!(
+            "UPDATE quotes SET deleted_at = NOW() - INTERVAL '31 days' WHERE id = $1",
+            old.id
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        recent.trash(&admin.id, &pool).await.unwrap();
+
+        let cutoff = Utc::now() - Duration::days(30);
+        assert_eq!(Quote::purge_trashed_before(cutoff, &pool).await.unwrap(), 1);
+        assert!(Quote::get_trashed_by_id(&old.id, &pool)
+            .await
+            .unwrap()
+            .is_none());
+        let trash = Quote::get_trash_page(&admin, &first_page(), &pool)
+            .await
+            .unwrap();
+        assert_eq!(trash.items.len(), 1);
+        assert!(Quote::get_by_id(&kept.id, &pool).await.unwrap().is_some());
+    }
+}
@@ -0,0 +1,77 @@
+use super::{
+    moderation::QuoteStatus,
+    tags::{are_valid_tags, TAGS_LIMIT, TAG_LEN_BOUND_UPPER},
+    Quote,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum QuoteValidityError {
+    #[error("The quote must have quote lines.")]
+    NoLines,
+    #[error("Every quote line must have an author.")]
+    LineWithoutAuthor,
+    #[error("This flag has already been resolved.")]
+    FlagAlreadyResolved,
+    #[error("Hiding a quote requires the clearance to raise it to.")]
+    HideWithoutClearance,
+    #[error("Hiding a quote must raise its clearance above the current one.")]
+    HideNotRaising,
+    #[error("A {} quote cannot become {}.", .0.as_ref(), .1.as_ref())]
+    StatusTransitionInvalid(QuoteStatus, QuoteStatus),
+    #[error("Rejecting a quote or asking for changes requires a reason.")]
+    ReviewReasonRequired,
+    #[error("Only drafts can be edited.")]
+    NotADraft,
+    #[error("This can only be done with published quotes.")]
+    NotPublished,
+    #[error(
+        "Tags must be at most {TAG_LEN_BOUND_UPPER} letters, digits, dashes or underscores, \
+        and at most {TAGS_LIMIT} per quote."
+    )]
+    TagsInvalid,
+}
+
+impl QuoteValidityError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            QuoteValidityError::NoLines => "quote_no_lines",
+            QuoteValidityError::LineWithoutAuthor => "line_without_author",
+            QuoteValidityError::FlagAlreadyResolved => "flag_already_resolved",
+            QuoteValidityError::HideWithoutClearance => "hide_without_clearance",
+            QuoteValidityError::HideNotRaising => "hide_not_raising",
+            QuoteValidityError::StatusTransitionInvalid(..) => "status_transition_invalid",
+            QuoteValidityError::ReviewReasonRequired => "review_reason_required",
+            QuoteValidityError::NotADraft => "not_a_draft",
+            QuoteValidityError::NotPublished => "quote_not_published",
+            QuoteValidityError::TagsInvalid => "tags_invalid",
+        }
+    }
+    pub fn field(&self) -> &'static str {
+        match self {
+            QuoteValidityError::NoLines | QuoteValidityError::LineWithoutAuthor => "lines",
+            QuoteValidityError::FlagAlreadyResolved
+            | QuoteValidityError::StatusTransitionInvalid(..)
+            | QuoteValidityError::NotADraft
+            | QuoteValidityError::NotPublished => "status",
+            QuoteValidityError::HideWithoutClearance | QuoteValidityError::HideNotRaising => {
+                "clearance"
+            }
+            QuoteValidityError::ReviewReasonRequired => "reason",
+            QuoteValidityError::TagsInvalid => "tags",
+        }
+    }
+}
+
+impl Quote {
+    pub fn is_valid(&self) -> Result<(), QuoteValidityError> {
+        if self.lines.is_empty() {
+            return Err(QuoteValidityError::NoLines);
+        }
+        if self.lines.iter().any(|l| l.author_id.is_none()) {
+            return Err(QuoteValidityError::LineWithoutAuthor);
+        }
+        are_valid_tags(&self.tags)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,161 @@
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::PgPool;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    groups::Group,
+    logs::{Log, LogAction},
+    omnierror::OmniError,
+    user::User,
+};
+
+use super::Quote;
+
+#[derive(Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct QuoteGroups {
+    /// An empty list leaves the quote to clearance alone
+    pub groups: Vec<Uuid>,
+}
+
+impl Quote {
+    /// Quotes without groups are shared with everyone; otherwise the viewer has to be
+    /// in one of them, or be linked to one of the quote's authors.
+    /// Mirrors the `quote_shared_with` SQL function used by listings.
+    pub fn is_shared_with(&self, viewer: Option<&User>, viewer_groups: &[Uuid]) -> bool {
+        self.groups.is_empty()
+            || self.groups.iter().any(|g| viewer_groups.contains(g))
+            || viewer.is_some_and(|u| self.is_attributed_to(&u.id))
+    }
+    /// Whether the user could read the quote by status, groups and clearance.
+    pub fn is_readable_by(&self, user: &User, user_groups: &[Uuid]) -> bool {
+        self.status_admits(Some(user))
+            && self.is_shared_with(Some(user), user_groups)
+            && (self.clearance <= user.clearance || self.is_attributed_to(&user.id))
+    }
+    /// Replaces the quote's groups; recorded in the audit log.
+    pub async fn set_groups(
+        mut self,
+        mut groups: Vec<Uuid>,
+        actor_id: &Uuid,
+        pool: &PgPool,
+    ) -> Result<Quote, OmniError> {
+        groups.sort();
+        groups.dedup();
+        Group::ensure_exist(&groups, pool).await?;
+
+        let mut tr = pool.begin().await?;
+        let set = async {
+            sqlx::query!("DELETE FROM quote_groups WHERE quote_id = $1", self.id)
+                .execute(&mut *tr)
+                .await?;
+            sqlx::query!(
+                r#"
+                INSERT INTO quote_groups (quote_id, group_id)
+                SELECT $1, group_id FROM UNNEST($2::uuid[]) AS group_id
+                "#,
+                self.id,
+                &groups
+            )
+            .execute(&mut *tr)
+            .await?;
+            Log::record(
+                actor_id,
+                &self.id,
+                LogAction::QuotesGroupsChanged,
+                json!({ "from": self.groups, "to": groups }),
+                &mut *tr,
+            )
+            .await
+        }
+        .await;
+        match set {
+            Ok(()) => {
+                tr.commit().await?;
+                self.groups = groups;
+                Ok(self)
+            }
+            Err(e) => {
+                tr.rollback().await?;
+                Err(e)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        groups::{GroupValidityError, NewGroup},
+        quotes::source::SourceFilter,
+        testing,
+    };
+
+    async fn listed(viewer: &User, pool: &PgPool) -> Vec<Uuid> {
+        let quotes = Quote::get_all(&SourceFilter::default(), viewer, pool)
+            .await
+            .unwrap();
+        quotes.into_iter().map(|q| q.id).collect()
+    }
+
+    #[sqlx::test]
+    async fn group_quotes_are_shown_to_members_and_quoted_users(pool: PgPool) {
+        let admin = testing::user("admin", 1, &[], &pool).await;
+        let member = testing::user("member", 0, &[], &pool).await;
+        let quoted = testing::user("quoted", 0, &[], &pool).await;
+        let outsider = testing::user("outsider", 1, &[], &pool).await;
+        let author = testing::author("jk", &pool).await;
+        testing::link(&author, &quoted, &pool).await;
+        let group = Group::create(
+            NewGroup {
+                name: "Team".into(),
+                description: None,
+            },
+            &pool,
+        )
+        .await
+        .unwrap();
+        group.add_member(&member.id, &pool).await.unwrap();
+
+        let open = testing::save(testing::quote(&[(&author, "hi")], 0), &admin, &pool).await;
+        let quote = testing::save(testing::quote(&[(&author, "psst")], 1), &admin, &pool).await;
+        let quote = quote
+            .set_groups(vec![group.id, group.id], &admin.id, &pool)
+            .await
+            .unwrap();
+        assert_eq!(quote.groups, vec![group.id]);
+
+        // membership doesn't lift the clearance, but being quoted does
+        assert_eq!(listed(&member, &pool).await, vec![open.id]);
+        assert_eq!(listed(&outsider, &pool).await, vec![open.id]);
+        assert_eq!(listed(&quoted, &pool).await, vec![quote.id, open.id]);
+
+        let quote = Quote::get_by_id(&quote.id, &pool).await.unwrap().unwrap();
+        assert!(quote.is_shared_with(Some(&member), &[group.id]));
+        assert!(quote.is_shared_with(Some(&quoted), &[]));
+        assert!(!quote.is_shared_with(Some(&outsider), &[]));
+        assert!(!quote.is_shared_with(None, &[]));
+        assert!(!quote.is_readable_by(&member, &[group.id]));
+        assert!(quote.is_readable_by(&quoted, &[]));
+        assert!(open.is_shared_with(None, &[]));
+
+        let res = group.destroy(&pool).await;
+        assert!(matches!(res, Err(OmniError::GroupInUse(ids)) if ids == vec![quote.id]));
+    }
+
+    #[sqlx::test]
+    async fn unknown_groups_are_refused(pool: PgPool) {
+        let admin = testing::user("admin", 0, &[], &pool).await;
+        let author = testing::author("jk", &pool).await;
+        let quote = testing::save(testing::quote(&[(&author, "hi")], 0), &admin, &pool).await;
+        let missing = Uuid::now_v7();
+        let res = quote.set_groups(vec![missing], &admin.id, &pool).await;
+        assert!(matches!(
+            res,
+            Err(OmniError::GroupValidityError(GroupValidityError::Undefined(id))) if id == missing
+        ));
+    }
+}
@@ -8,6 +8,7 @@ use axum::{
 use serde::Deserialize;
 use sqlx::PgPool;
 use tower_cookies::Cookies;
+use utoipa::{OpenApi, ToSchema};
 
 use crate::{
     omnierror::OmniError,
@@ -15,7 +16,10 @@ use crate::{
     user::{
         auth::{
             cookie::{clear_session_token_cookie, set_session_token_cookie},
-            error::AuthError::{ClearSessionBearerOnly, NonAsciiHeaderCharacters},
+            error::AuthError::{
+                ClearSessionBearerOnly, ClearSessionTooManyTokens, NoCredentials,
+                NonAsciiHeaderCharacters,
+            },
             session::Session,
             SESSION_COOKIE_NAME,
         },
@@ -23,13 +27,17 @@ use crate::{
     },
 };
 
+#[derive(OpenApi)]
+#[openapi(paths(login, clear), components(schemas(LoginData)))]
+pub struct AuthApi;
+
 pub fn routes() -> Router<SharedState> {
     Router::new()
         .route("/auth/login", post(login))
         .route("/auth/clear", post(clear))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 #[serde(deny_unknown_fields)]
 struct LoginData {
     #[serde(alias = "handle")]
@@ -37,6 +45,14 @@ struct LoginData {
     #[serde(alias = "password")]
     passw: String,
 }
+#[utoipa::path(
+    post, path = "/auth/login", tag = "auth", security(()),
+    request_body = LoginData,
+    responses(
+        (status = 201, description = "Session created; the token is also set as a cookie", body = String),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+    )
+)]
 async fn login(
     cookies: Cookies,
     State(state): State<SharedState>,
@@ -49,10 +65,17 @@ async fn login(
     Ok((StatusCode::CREATED, token).into_response())
 }
 
-const TOO_MANY_TOKENS: &str = "Please provide one token at a time.";
-const NO_TOKENS: &str = "Please provide a token.";
 const SUCCESS: &str = "Logged out - session destroyed.";
 
+#[utoipa::path(
+    post, path = "/auth/clear", tag = "auth",
+    security(("cookie" = []), ("bearer" = [])),
+    responses(
+        (status = 200, description = "Session destroyed", body = String),
+        (status = 400, response = crate::router::openapi::ProblemResponse),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+    )
+)]
 async fn clear(
     headers: HeaderMap,
     cookies: Cookies,
@@ -80,8 +103,8 @@ async fn clear(
     };
 
     match (header, cookie) {
-        (Some(_), Some(_)) => Ok((StatusCode::BAD_REQUEST, TOO_MANY_TOKENS).into_response()),
-        (None, None) => Ok((StatusCode::BAD_REQUEST, NO_TOKENS).into_response()),
+        (Some(_), Some(_)) => Err(ClearSessionTooManyTokens)?,
+        (None, None) => Err(NoCredentials)?,
         (None, Some(c)) => auth_clear_and_respond(&c, cookies, &state.dbpool).await,
         (Some(h), None) => {
             let (scheme, data) = match h.split_once(' ') {
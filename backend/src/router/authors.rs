@@ -1,20 +1,86 @@
 use axum::{
-    extract::{Path, State},
-    http::{HeaderMap, StatusCode},
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{header::CONTENT_TYPE, HeaderMap, StatusCode},
     response::{IntoResponse, Response},
-    routing::get,
+    routing::{delete, get, post},
     Json, Router,
 };
-use tower_cookies::Cookies;
+use serde::Deserialize;
+use utoipa::{IntoParams, OpenApi};
 use uuid::Uuid;
 
 use crate::{
     omnierror::OmniError,
-    quotes::authors::{Author, AuthorPatch, ExtendedAuthor},
+    pagination::{Page, PageQuery},
+    quotes::authors::{
+        avatar::AvatarImage,
+        claims::{AuthorClaim, ClaimStatus},
+        deletion::{AuthorDeletePolicy, AuthorDeletion},
+        merge::{AuthorMerge, AuthorMergeReport},
+        stats::{
+            AuthorLine, AuthorStats, CoSpeaker, LeaderboardEntry, LeaderboardOrder,
+            LeaderboardQuery, YearlyCount,
+        },
+        suggest::{AuthorSuggestion, SuggestQuery},
+        validity::is_valid_avatar_upload,
+        Author, AuthorPatch, ExtendedAuthor,
+    },
     state::SharedState,
-    user::{attributes::UserAttribute as UA, User},
+    user::{
+        attributes::UserAttribute as UA,
+        auth::guard::{
+            AuthorsCreatePermission, AuthorsDeletePermission, AuthorsInspectPermission,
+            AuthorsManageLinksPermission, AuthorsModifyPermission, Require,
+        },
+        User,
+    },
 };
 
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        get_all,
+        get_all_extended,
+        leaderboard_handler,
+        post_handler,
+        by_id_handler,
+        by_id_extended_handler,
+        patch_handler,
+        delete_handler,
+        lookup_handler,
+        suggest_handler,
+        get_avatar_handler,
+        put_avatar_handler,
+        delete_avatar_handler,
+        merge_handler,
+        claim_handler,
+        get_claims,
+        approve_claim,
+        reject_claim,
+        unlink_handler
+    ),
+    components(schemas(
+        Author,
+        AuthorPatch,
+        ExtendedAuthor,
+        AuthorMerge,
+        AuthorMergeReport,
+        AuthorDeletion,
+        AuthorDeletePolicy,
+        AuthorStats,
+        AuthorLine,
+        CoSpeaker,
+        YearlyCount,
+        LeaderboardEntry,
+        LeaderboardOrder,
+        AuthorClaim,
+        ClaimStatus,
+        AuthorSuggestion
+    ))
+)]
+pub struct AuthorsApi;
+
 pub fn routes() -> Router<SharedState> {
     Router::new()
         .route("/authors", get(get_all).post(post_handler))
@@ -26,121 +92,534 @@ pub fn routes() -> Router<SharedState> {
         )
         .route("/authors/{id}/extended", get(by_id_extended_handler))
         .route("/authors/extended", get(get_all_extended))
+        .route("/authors/leaderboard", get(leaderboard_handler))
+        .route("/authors/lookup", get(lookup_handler))
+        .route("/authors/suggest", get(suggest_handler))
+        .route("/authors/{id}/merge", post(merge_handler))
+        .route("/authors/{id}/claim", post(claim_handler))
+        .route("/authors/{id}/link", delete(unlink_handler))
+        .route("/authors/claims", get(get_claims))
+        .route("/authors/claims/{id}/approve", post(approve_claim))
+        .route("/authors/claims/{id}/reject", post(reject_claim))
+        .route(
+            "/authors/{id}/avatar",
+            get(get_avatar_handler)
+                .put(put_avatar_handler)
+                .delete(delete_avatar_handler),
+        )
 }
 
+#[utoipa::path(
+    get, path = "/authors", tag = "authors",
+    responses(
+        (status = 200, body = Vec<Author>),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+    )
+)]
 async fn get_all(
-    headers: HeaderMap,
-    cookies: Cookies,
+    _: Require<AuthorsInspectPermission>,
     State(state): State<SharedState>,
 ) -> Result<Response, OmniError> {
-    let u = User::authenticate(&headers, cookies, &state.dbpool).await?;
-    if !u.has_permission(UA::AuthorsInspectPermission) {
-        return Ok(StatusCode::FORBIDDEN.into_response());
-    }
-
     let authors = Author::get_all(&state.dbpool).await?;
     Ok(Json(authors).into_response())
 }
 
+#[utoipa::path(
+    get, path = "/authors/extended", tag = "authors",
+    responses(
+        (status = 200, body = Vec<ExtendedAuthor>),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+    )
+)]
 async fn get_all_extended(
-    headers: HeaderMap,
-    cookies: Cookies,
+    u: Require<AuthorsInspectPermission>,
     State(state): State<SharedState>,
 ) -> Result<Response, OmniError> {
-    let u = User::authenticate(&headers, cookies, &state.dbpool).await?;
-    if !u.has_permission(UA::AuthorsInspectPermission) {
-        return Ok(StatusCode::FORBIDDEN.into_response());
-    }
+    Ok(Json(ExtendedAuthor::get_all(&u, &state.dbpool).await?).into_response())
+}
 
-    Ok(Json(ExtendedAuthor::get_all(&state.dbpool).await?).into_response())
+#[utoipa::path(
+    get, path = "/authors/leaderboard", tag = "authors",
+    description = "Authors ranked by quotes, lines or words; \
+        only quotes within the caller's clearance are counted.",
+    params(LeaderboardQuery, PageQuery),
+    responses(
+        (status = 200, body = Page<LeaderboardEntry>),
+        (status = 400, response = crate::router::openapi::ProblemResponse),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn leaderboard_handler(
+    u: Require<AuthorsInspectPermission>,
+    Query(query): Query<LeaderboardQuery>,
+    Query(page): Query<PageQuery>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    let page = LeaderboardEntry::get_page(query.by, &u, &page, &state.dbpool).await?;
+    Ok(Json(page).into_response())
 }
 
+#[utoipa::path(
+    post, path = "/authors", tag = "authors",
+    request_body = Author,
+    responses(
+        (status = 201, body = Author),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 409, response = crate::router::openapi::ProblemResponse),
+    )
+)]
 async fn post_handler(
-    headers: HeaderMap,
-    cookies: Cookies,
+    _: Require<AuthorsCreatePermission>,
     State(state): State<SharedState>,
     Json(author): Json<Author>,
 ) -> Result<Response, OmniError> {
-    let u = User::authenticate(&headers, cookies, &state.dbpool).await?;
-    if !u.has_permission(UA::AuthorsCreatePermission) {
-        return Ok(StatusCode::FORBIDDEN.into_response());
-    }
-
+    author.is_valid()?;
     let author = Author::create(author, &state.dbpool).await?;
     Ok((StatusCode::CREATED, Json(author)).into_response())
 }
 
+#[utoipa::path(
+    get, path = "/authors/{id}", tag = "authors",
+    params(("id" = Uuid, Path, description = "Author id")),
+    responses(
+        (status = 200, body = Author),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
 async fn by_id_handler(
-    headers: HeaderMap,
-    cookies: Cookies,
+    _: Require<AuthorsInspectPermission>,
     Path(id): Path<Uuid>,
     State(state): State<SharedState>,
 ) -> Result<Response, OmniError> {
-    let u = User::authenticate(&headers, cookies, &state.dbpool).await?;
-    if !u.has_permission(UA::AuthorsInspectPermission) {
-        return Ok(StatusCode::FORBIDDEN.into_response());
-    }
-
     match Author::get_by_id(&id, &state.dbpool).await? {
         Some(author) => Ok(Json(author).into_response()),
-        None => Ok(StatusCode::NOT_FOUND.into_response()),
+        None => Err(OmniError::NotFoundError("author")),
     }
 }
 
+#[utoipa::path(
+    get, path = "/authors/{id}/extended", tag = "authors",
+    params(("id" = Uuid, Path, description = "Author id")),
+    responses(
+        (status = 200, body = ExtendedAuthor),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
 async fn by_id_extended_handler(
-    headers: HeaderMap,
-    cookies: Cookies,
+    u: Require<AuthorsInspectPermission>,
     Path(id): Path<Uuid>,
     State(state): State<SharedState>,
 ) -> Result<Response, OmniError> {
-    let u = User::authenticate(&headers, cookies, &state.dbpool).await?;
-    if !u.has_permission(UA::AuthorsInspectPermission) {
-        return Ok(StatusCode::FORBIDDEN.into_response());
-    }
-
-    match ExtendedAuthor::get_by_id(&id, &state.dbpool).await? {
+    match ExtendedAuthor::get_by_id(&id, &u, &state.dbpool).await? {
         Some(author) => Ok(Json(author).into_response()),
-        None => Ok(StatusCode::NOT_FOUND.into_response()),
+        None => Err(OmniError::NotFoundError("author")),
     }
 }
 
+#[utoipa::path(
+    patch, path = "/authors/{id}", tag = "authors",
+    params(("id" = Uuid, Path, description = "Author id")),
+    request_body = AuthorPatch,
+    responses(
+        (status = 200, body = Author),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+        (status = 409, response = crate::router::openapi::ProblemResponse),
+    )
+)]
 async fn patch_handler(
-    headers: HeaderMap,
-    cookies: Cookies,
+    _: Require<AuthorsModifyPermission>,
     Path(id): Path<Uuid>,
     State(state): State<SharedState>,
     Json(patch): Json<AuthorPatch>,
 ) -> Result<Response, OmniError> {
-    let u = User::authenticate(&headers, cookies, &state.dbpool).await?;
-    if !u.has_permission(UA::AuthorsModifyPermission) {
-        return Ok(StatusCode::FORBIDDEN.into_response());
-    }
-
     match Author::get_by_id(&id, &state.dbpool).await? {
         Some(author) => {
+            patch.is_valid(&author)?;
             let author = author.patch(patch, &state.dbpool).await?;
             Ok(Json(author).into_response())
         }
-        None => Ok(StatusCode::NOT_FOUND.into_response()),
+        None => Err(OmniError::NotFoundError("author")),
     }
 }
 
+#[derive(Deserialize, IntoParams)]
+#[serde(deny_unknown_fields)]
+struct DeleteQuery {
+    /// What to do with quotes the author has lines in; defaults to `forbid`
+    #[serde(default)]
+    #[param(inline)]
+    policy: AuthorDeletePolicy,
+}
+
+#[utoipa::path(
+    delete, path = "/authors/{id}", tag = "authors",
+    description = "With the `forbid` policy, an author with lines is refused with a 409 \
+        listing the blocking quotes in `references`. `cascade` moves those quotes to the trash \
+        with the author's lines credited to \"Unknown\", and requires that the caller could delete \
+        each of them: shared with them and below their clearance.",
+    params(("id" = Uuid, Path, description = "Author id"), DeleteQuery),
+    responses(
+        (status = 200, body = AuthorDeletion),
+        (status = 400, response = crate::router::openapi::ProblemResponse),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+        (status = 409, response = crate::router::openapi::ProblemResponse),
+    )
+)]
 async fn delete_handler(
+    u: Require<AuthorsDeletePermission>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<DeleteQuery>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    if query.policy == AuthorDeletePolicy::Cascade {
+        u.require_permission(UA::QuotesDeletePermission)?;
+    }
+    match Author::get_by_id(&id, &state.dbpool).await? {
+        Some(author) => {
+            let deletion = author.destroy(query.policy, &u, &state.dbpool).await?;
+            Ok(Json(deletion).into_response())
+        }
+        None => Err(OmniError::NotFoundError("author")),
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[serde(deny_unknown_fields)]
+struct LookupQuery {
+    /// Full name, codename or alias, case-insensitive
+    name: String,
+}
+
+#[utoipa::path(
+    get, path = "/authors/lookup", tag = "authors",
+    description = "Resolves a full name, codename or alias to the canonical author.",
+    params(LookupQuery),
+    responses(
+        (status = 200, body = Author),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn lookup_handler(
+    _: Require<AuthorsInspectPermission>,
+    Query(query): Query<LookupQuery>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    match Author::get_by_name(&query.name, &state.dbpool).await? {
+        Some(author) => Ok(Json(author).into_response()),
+        None => Err(OmniError::NotFoundError("author")),
+    }
+}
+
+#[utoipa::path(
+    get, path = "/authors/suggest", tag = "authors",
+    description = "Fuzzy author autocomplete over full names, codenames and aliases, \
+        best match first; recently quoted authors rank slightly higher.",
+    params(SuggestQuery),
+    responses(
+        (status = 200, body = Vec<AuthorSuggestion>),
+        (status = 400, response = crate::router::openapi::ProblemResponse),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn suggest_handler(
+    u: Require<AuthorsInspectPermission>,
+    Query(query): Query<SuggestQuery>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    Ok(Json(Author::suggest(&query, &u, &state.dbpool).await?).into_response())
+}
+
+#[utoipa::path(
+    get, path = "/authors/{id}/avatar", tag = "authors", security(()),
+    params(("id" = Uuid, Path, description = "Author id")),
+    responses(
+        (status = 200, description = "The uploaded avatar image", content_type = "image/*"),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn get_avatar_handler(
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    match Author::get_avatar(&id, &state.dbpool).await? {
+        Some(image) => Ok(([(CONTENT_TYPE, image.content_type)], image.data).into_response()),
+        None => Err(OmniError::NotFoundError("avatar")),
+    }
+}
+
+#[utoipa::path(
+    put, path = "/authors/{id}/avatar", tag = "authors",
+    params(("id" = Uuid, Path, description = "Author id")),
+    request_body(content = Vec<u8>, content_type = "image/*", description = "PNG, JPEG, WebP or GIF"),
+    responses(
+        (status = 200, body = Author),
+        (status = 400, response = crate::router::openapi::ProblemResponse),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn put_avatar_handler(
+    _: Require<AuthorsModifyPermission>,
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
     headers: HeaderMap,
-    cookies: Cookies,
+    body: Bytes,
+) -> Result<Response, OmniError> {
+    let content_type = headers
+        .get(CONTENT_TYPE)
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    is_valid_avatar_upload(&content_type, body.len())?;
+
+    match Author::get_by_id(&id, &state.dbpool).await? {
+        Some(author) => {
+            let image = AvatarImage {
+                content_type,
+                data: body.to_vec(),
+            };
+            Ok(Json(author.set_avatar(image, &state.dbpool).await?).into_response())
+        }
+        None => Err(OmniError::NotFoundError("author")),
+    }
+}
+
+#[utoipa::path(
+    delete, path = "/authors/{id}/avatar", tag = "authors",
+    params(("id" = Uuid, Path, description = "Author id")),
+    responses(
+        (status = 200, body = Author),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn delete_avatar_handler(
+    _: Require<AuthorsModifyPermission>,
     Path(id): Path<Uuid>,
     State(state): State<SharedState>,
 ) -> Result<Response, OmniError> {
-    let u = User::authenticate(&headers, cookies, &state.dbpool).await?;
-    if !u.has_permission(UA::AuthorsDeletePermission) {
-        return Ok(StatusCode::FORBIDDEN.into_response());
+    match Author::get_by_id(&id, &state.dbpool).await? {
+        Some(author) => Ok(Json(author.clear_avatar(&state.dbpool).await?).into_response()),
+        None => Err(OmniError::NotFoundError("author")),
     }
+}
 
+#[derive(Deserialize, IntoParams)]
+#[serde(deny_unknown_fields)]
+struct MergeQuery {
+    /// Only report what the merge would do
+    #[serde(default)]
+    preview: bool,
+}
+
+#[utoipa::path(
+    post, path = "/authors/{id}/merge", tag = "authors",
+    description = "Merges the author into `target`: their lines move over, their names become \
+        aliases of the target, and they are deleted. Requires modify and delete permissions, \
+        and the manage-links permission if the author is linked to a user, since the link \
+        moves to the target. Authors that are both linked to users cannot be merged.",
+    params(("id" = Uuid, Path, description = "Author to merge away"), MergeQuery),
+    request_body = AuthorMerge,
+    responses(
+        (status = 200, body = AuthorMergeReport),
+        (status = 400, response = crate::router::openapi::ProblemResponse),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+        (status = 409, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn merge_handler(
+    u: Require<AuthorsDeletePermission>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<MergeQuery>,
+    State(state): State<SharedState>,
+    Json(merge): Json<AuthorMerge>,
+) -> Result<Response, OmniError> {
+    u.require_permission(UA::AuthorsModifyPermission)?;
+
+    let source = Author::get_by_id(&id, &state.dbpool).await?;
+    let target = Author::get_by_id(&merge.target, &state.dbpool).await?;
+    let (source, target) = match (source, target) {
+        (Some(s), Some(t)) => (s, t),
+        _ => return Err(OmniError::NotFoundError("author")),
+    };
+    if source.user_id.is_some() {
+        u.require_permission(UA::AuthorsManageLinksPermission)?;
+    }
+
+    let report = match query.preview {
+        true => source.preview_merge(target, &state.dbpool).await?,
+        false => source.merge_into(target, &u.id, &state.dbpool).await?,
+    };
+    Ok(Json(report).into_response())
+}
+
+#[utoipa::path(
+    post, path = "/authors/{id}/claim", tag = "authors",
+    description = "Asks to link the author to the calling user; an admin has to approve it.",
+    params(("id" = Uuid, Path, description = "Author id")),
+    responses(
+        (status = 201, body = AuthorClaim),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+        (status = 409, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn claim_handler(
+    u: User,
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
     match Author::get_by_id(&id, &state.dbpool).await? {
         Some(author) => {
-            author.destroy(&state.dbpool).await?;
-            Ok(StatusCode::NO_CONTENT.into_response())
+            let claim = author.claim(&u, &state.dbpool).await?;
+            Ok((StatusCode::CREATED, Json(claim)).into_response())
         }
-        None => Ok(StatusCode::FORBIDDEN.into_response()),
+        None => Err(OmniError::NotFoundError("author")),
+    }
+}
+
+#[utoipa::path(
+    delete, path = "/authors/{id}/link", tag = "authors",
+    description = "Unlinks the author from its user; allowed for that user and for link managers.",
+    params(("id" = Uuid, Path, description = "Author id")),
+    responses(
+        (status = 200, body = Author),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn unlink_handler(
+    u: User,
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    let author = match Author::get_by_id(&id, &state.dbpool).await? {
+        Some(author) => author,
+        None => return Err(OmniError::NotFoundError("author")),
+    };
+    if author.user_id != Some(u.id) {
+        u.require_permission(UA::AuthorsManageLinksPermission)?;
+    }
+    Ok(Json(author.unlink(&u.id, &state.dbpool).await?).into_response())
+}
+
+#[utoipa::path(
+    get, path = "/authors/claims", tag = "authors",
+    responses(
+        (status = 200, description = "Pending claims, oldest first", body = Vec<AuthorClaim>),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn get_claims(
+    _: Require<AuthorsManageLinksPermission>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    Ok(Json(AuthorClaim::get_pending(&state.dbpool).await?).into_response())
+}
+
+#[utoipa::path(
+    post, path = "/authors/claims/{id}/approve", tag = "authors",
+    params(("id" = Uuid, Path, description = "Claim id")),
+    responses(
+        (status = 200, body = AuthorClaim),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+        (status = 409, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn approve_claim(
+    u: Require<AuthorsManageLinksPermission>,
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    match AuthorClaim::get_by_id(&id, &state.dbpool).await? {
+        Some(claim) => Ok(Json(claim.decide(true, &u.id, &state.dbpool).await?).into_response()),
+        None => Err(OmniError::NotFoundError("claim")),
+    }
+}
+
+#[utoipa::path(
+    post, path = "/authors/claims/{id}/reject", tag = "authors",
+    params(("id" = Uuid, Path, description = "Claim id")),
+    responses(
+        (status = 200, body = AuthorClaim),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+        (status = 409, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn reject_claim(
+    u: Require<AuthorsManageLinksPermission>,
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    match AuthorClaim::get_by_id(&id, &state.dbpool).await? {
+        Some(claim) => Ok(Json(claim.decide(false, &u.id, &state.dbpool).await?).into_response()),
+        None => Err(OmniError::NotFoundError("claim")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use sqlx::PgPool;
+
+    use crate::testing::{self, send};
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn merging_a_linked_author_needs_the_manage_links_permission(pool: PgPool) {
+        let app = testing::app(&pool);
+        let editor = testing::user(
+            "editor",
+            0,
+            &[UA::AuthorsModifyPermission, UA::AuthorsDeletePermission],
+            &pool,
+        )
+        .await;
+        let owner = testing::user("owner", 0, &[], &pool).await;
+        let source = testing::author("jk", &pool).await;
+        let target = testing::author("jan", &pool).await;
+        testing::link(&source, &owner, &pool).await;
+        let uri = format!("/authors/{}/merge", source.id);
+        let body = json!({ "target": target.id });
+
+        let auth = testing::bearer(&editor, &pool).await;
+        let (status, res) = send(&app, "POST", &uri, Some(&auth), Some(body.clone())).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(res["code"], "missing_permission");
+        assert!(Author::get_by_id(&source.id, &pool)
+            .await
+            .unwrap()
+            .is_some());
+
+        let unlinked = testing::author("ola", &pool).await;
+        let uri = format!("/authors/{}/merge", unlinked.id);
+        let (status, _) = send(&app, "POST", &uri, Some(&auth), Some(body)).await;
+        assert_eq!(status, StatusCode::OK);
     }
 }
@@ -0,0 +1,145 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use utoipa::OpenApi;
+
+use crate::{
+    clearance::{ClearanceLevel, ClearanceLevelPatch},
+    omnierror::OmniError,
+    state::SharedState,
+    user::{
+        auth::guard::{Require, UsersManageClearancesPermission},
+        User,
+    },
+};
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(get_all, by_level_handler, post_new, patch_handler, delete_handler),
+    components(schemas(ClearanceLevel, ClearanceLevelPatch))
+)]
+pub struct ClearanceApi;
+
+pub fn routes() -> Router<SharedState> {
+    Router::new()
+        .route("/clearance-levels", get(get_all).post(post_new))
+        .route(
+            "/clearance-levels/{level}",
+            get(by_level_handler)
+                .patch(patch_handler)
+                .delete(delete_handler),
+        )
+}
+
+#[utoipa::path(
+    get, path = "/clearance-levels", tag = "clearance",
+    responses(
+        (status = 200, body = Vec<ClearanceLevel>),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn get_all(_: User, State(state): State<SharedState>) -> Result<Response, OmniError> {
+    let levels = ClearanceLevel::get_all(&state.dbpool).await?;
+    Ok(Json(levels).into_response())
+}
+
+#[utoipa::path(
+    get, path = "/clearance-levels/{level}", tag = "clearance",
+    params(("level" = u8, Path, description = "Clearance level")),
+    responses(
+        (status = 200, body = ClearanceLevel),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn by_level_handler(
+    _: User,
+    Path(level): Path<u8>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    match ClearanceLevel::get_by_level(level, &state.dbpool).await? {
+        Some(level) => Ok(Json(level).into_response()),
+        None => Err(OmniError::NotFoundError("clearance level")),
+    }
+}
+
+#[utoipa::path(
+    post, path = "/clearance-levels", tag = "clearance",
+    request_body = ClearanceLevel,
+    responses(
+        (status = 201, body = ClearanceLevel),
+        (status = 400, response = crate::router::openapi::ProblemResponse),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 409, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn post_new(
+    _: Require<UsersManageClearancesPermission>,
+    State(state): State<SharedState>,
+    Json(level): Json<ClearanceLevel>,
+) -> Result<Response, OmniError> {
+    level.is_valid()?;
+    let level = ClearanceLevel::create(level, &state.dbpool).await?;
+    Ok((StatusCode::CREATED, Json(level)).into_response())
+}
+
+#[utoipa::path(
+    patch, path = "/clearance-levels/{level}", tag = "clearance",
+    params(("level" = u8, Path, description = "Clearance level")),
+    request_body = ClearanceLevelPatch,
+    responses(
+        (status = 200, body = ClearanceLevel),
+        (status = 400, response = crate::router::openapi::ProblemResponse),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+        (status = 409, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn patch_handler(
+    _: Require<UsersManageClearancesPermission>,
+    Path(level): Path<u8>,
+    State(state): State<SharedState>,
+    Json(patch): Json<ClearanceLevelPatch>,
+) -> Result<Response, OmniError> {
+    match ClearanceLevel::get_by_level(level, &state.dbpool).await? {
+        Some(level) => {
+            let level = level.patch(patch, &state.dbpool).await?;
+            Ok(Json(level).into_response())
+        }
+        None => Err(OmniError::NotFoundError("clearance level")),
+    }
+}
+
+#[utoipa::path(
+    delete, path = "/clearance-levels/{level}", tag = "clearance",
+    description = "Levels still used by a quote, line or user cannot be deleted, \
+        and neither can level 0.",
+    params(("level" = u8, Path, description = "Clearance level")),
+    responses(
+        (status = 204, description = "Clearance level deleted"),
+        (status = 400, response = crate::router::openapi::ProblemResponse),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+        (status = 409, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn delete_handler(
+    _: Require<UsersManageClearancesPermission>,
+    Path(level): Path<u8>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    match ClearanceLevel::get_by_level(level, &state.dbpool).await? {
+        Some(level) => {
+            level.destroy(&state.dbpool).await?;
+            Ok(StatusCode::NO_CONTENT.into_response())
+        }
+        None => Err(OmniError::NotFoundError("clearance level")),
+    }
+}
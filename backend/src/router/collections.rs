@@ -0,0 +1,390 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, put},
+    Json, Router,
+};
+use utoipa::OpenApi;
+use uuid::Uuid;
+
+use crate::{
+    omnierror::OmniError,
+    quotes::{
+        collections::{
+            Collection, CollectionDetail, CollectionOrder, CollectionPatch, CollectionShares,
+            CollectionVisibility, NewCollection,
+        },
+        moderation::QuoteStatus,
+        validity::QuoteValidityError,
+        Quote,
+    },
+    state::SharedState,
+    user::{auth::error::AuthError, User},
+};
+
+use super::quotes::visible_quote;
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        put_favourite,
+        delete_favourite,
+        get_favourites,
+        get_all,
+        post_new,
+        by_id_handler,
+        by_link_handler,
+        patch_handler,
+        delete_handler,
+        put_shares,
+        put_order,
+        put_item,
+        delete_item
+    ),
+    components(schemas(
+        Collection,
+        CollectionDetail,
+        CollectionVisibility,
+        NewCollection,
+        CollectionPatch,
+        CollectionShares,
+        CollectionOrder
+    ))
+)]
+pub struct CollectionsApi;
+
+pub fn routes() -> Router<SharedState> {
+    Router::new()
+        .route(
+            "/quotes/{id}/favourite",
+            put(put_favourite).delete(delete_favourite),
+        )
+        .route("/users/me/favourites", get(get_favourites))
+        .route("/collections", get(get_all).post(post_new))
+        .route(
+            "/collections/{id}",
+            get(by_id_handler)
+                .patch(patch_handler)
+                .delete(delete_handler),
+        )
+        .route("/collections/link/{token}", get(by_link_handler))
+        .route("/collections/{id}/shares", put(put_shares))
+        .route("/collections/{id}/order", put(put_order))
+        .route(
+            "/collections/{id}/items/{quote_id}",
+            put(put_item).delete(delete_item),
+        )
+}
+
+/// A published quote the user can read, to star or collect.
+async fn collectable_quote(id: &Uuid, u: &User, state: &SharedState) -> Result<Quote, OmniError> {
+    let q = visible_quote(id, Some(u), state).await?;
+    if q.status != QuoteStatus::Published {
+        return Err(QuoteValidityError::NotPublished)?;
+    }
+    Ok(q)
+}
+
+/// Collections the user can't see are not found; those they can see but don't own are forbidden.
+async fn owned_collection(
+    id: &Uuid,
+    u: &User,
+    state: &SharedState,
+) -> Result<Collection, OmniError> {
+    match Collection::get_by_id(id, &state.dbpool).await? {
+        Some(c) if c.owner_id == u.id => Ok(c),
+        Some(c) if c.is_visible_to(u) => Err(AuthError::NotCollectionOwner)?,
+        _ => Err(OmniError::NotFoundError("collection")),
+    }
+}
+
+async fn detail_of(
+    collection: Collection,
+    u: Option<&User>,
+    state: &SharedState,
+) -> Result<CollectionDetail, OmniError> {
+    let mut items = collection.get_items(u, &state.dbpool).await?;
+    Quote::load_reactions(&mut items, u, &state.dbpool).await?;
+    Ok(CollectionDetail {
+        collection: collection.for_viewer(u),
+        items: items.into_iter().map(|q| q.redact_for(u)).collect(),
+    })
+}
+
+#[utoipa::path(
+    put, path = "/quotes/{id}/favourite", tag = "collections",
+    params(("id" = Uuid, Path, description = "Quote id")),
+    responses(
+        (status = 204, description = "Quote is starred"),
+        (status = 400, response = crate::router::openapi::ProblemResponse),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn put_favourite(
+    u: User,
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    let q = collectable_quote(&id, &u, &state).await?;
+    q.star(&u.id, &state.dbpool).await?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+#[utoipa::path(
+    delete, path = "/quotes/{id}/favourite", tag = "collections",
+    params(("id" = Uuid, Path, description = "Quote id")),
+    responses(
+        (status = 204, description = "Quote is not starred"),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn delete_favourite(
+    u: User,
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    let q = visible_quote(&id, Some(&u), &state).await?;
+    q.unstar(&u.id, &state.dbpool).await?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+#[utoipa::path(
+    get, path = "/users/me/favourites", tag = "collections",
+    description = "Starred quotes the user can still read, most recently starred first.",
+    responses(
+        (status = 200, body = Vec<Quote>),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn get_favourites(u: User, State(state): State<SharedState>) -> Result<Response, OmniError> {
+    let mut quotes = Quote::get_favourites_of(&u, &state.dbpool).await?;
+    Quote::load_reactions(&mut quotes, Some(&u), &state.dbpool).await?;
+    let quotes: Vec<Quote> = quotes.into_iter().map(|q| q.redact_for(Some(&u))).collect();
+    Ok(Json(quotes).into_response())
+}
+
+#[utoipa::path(
+    get, path = "/collections", tag = "collections",
+    description = "The caller's own collections and those shared with them.",
+    responses(
+        (status = 200, body = Vec<Collection>),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn get_all(u: User, State(state): State<SharedState>) -> Result<Response, OmniError> {
+    let collections: Vec<Collection> = Collection::get_visible_to(&u.id, &state.dbpool)
+        .await?
+        .into_iter()
+        .map(|c| c.for_viewer(Some(&u)))
+        .collect();
+    Ok(Json(collections).into_response())
+}
+
+#[utoipa::path(
+    post, path = "/collections", tag = "collections",
+    request_body = NewCollection,
+    responses(
+        (status = 201, body = Collection),
+        (status = 400, response = crate::router::openapi::ProblemResponse),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 409, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn post_new(
+    u: User,
+    State(state): State<SharedState>,
+    Json(collection): Json<NewCollection>,
+) -> Result<Response, OmniError> {
+    collection.is_valid()?;
+    let collection = Collection::create(collection, &u.id, &state.dbpool).await?;
+    Ok((StatusCode::CREATED, Json(collection)).into_response())
+}
+
+#[utoipa::path(
+    get, path = "/collections/{id}", tag = "collections",
+    description = "Items the caller can't read are left out.",
+    params(("id" = Uuid, Path, description = "Collection id")),
+    responses(
+        (status = 200, body = CollectionDetail),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn by_id_handler(
+    u: User,
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    match Collection::get_by_id(&id, &state.dbpool).await? {
+        Some(c) if c.is_visible_to(&u) => {
+            Ok(Json(detail_of(c, Some(&u), &state).await?).into_response())
+        }
+        _ => Err(OmniError::NotFoundError("collection")),
+    }
+}
+
+#[utoipa::path(
+    get, path = "/collections/link/{token}", tag = "collections",
+    description = "Opens a `public_link` collection; anonymous viewers only see public quotes.",
+    security((), ("cookie" = []), ("bearer" = []), ("basic" = [])),
+    params(("token" = String, Path, description = "Link token")),
+    responses(
+        (status = 200, body = CollectionDetail),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn by_link_handler(
+    u: Option<User>,
+    Path(token): Path<String>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    match Collection::get_by_link(&token, &state.dbpool).await? {
+        Some(c) => Ok(Json(detail_of(c, u.as_ref(), &state).await?).into_response()),
+        None => Err(OmniError::NotFoundError("collection")),
+    }
+}
+
+#[utoipa::path(
+    patch, path = "/collections/{id}", tag = "collections",
+    params(("id" = Uuid, Path, description = "Collection id")),
+    request_body = CollectionPatch,
+    responses(
+        (status = 200, body = Collection),
+        (status = 400, response = crate::router::openapi::ProblemResponse),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+        (status = 409, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn patch_handler(
+    u: User,
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+    Json(patch): Json<CollectionPatch>,
+) -> Result<Response, OmniError> {
+    let c = owned_collection(&id, &u, &state).await?;
+    Ok(Json(c.patch(patch, &state.dbpool).await?).into_response())
+}
+
+#[utoipa::path(
+    delete, path = "/collections/{id}", tag = "collections",
+    params(("id" = Uuid, Path, description = "Collection id")),
+    responses(
+        (status = 204, description = "Collection deleted"),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn delete_handler(
+    u: User,
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    let c = owned_collection(&id, &u, &state).await?;
+    c.destroy(&state.dbpool).await?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+#[utoipa::path(
+    put, path = "/collections/{id}/shares", tag = "collections",
+    description = "Replaces the users the collection is shared with; \
+        they only see it while its visibility is `shared`.",
+    params(("id" = Uuid, Path, description = "Collection id")),
+    request_body = CollectionShares,
+    responses(
+        (status = 200, body = Collection),
+        (status = 400, response = crate::router::openapi::ProblemResponse),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn put_shares(
+    u: User,
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+    Json(body): Json<CollectionShares>,
+) -> Result<Response, OmniError> {
+    let c = owned_collection(&id, &u, &state).await?;
+    Ok(Json(c.set_shares(body.users, &state.dbpool).await?).into_response())
+}
+
+#[utoipa::path(
+    put, path = "/collections/{id}/order", tag = "collections",
+    params(("id" = Uuid, Path, description = "Collection id")),
+    request_body = CollectionOrder,
+    responses(
+        (status = 200, body = CollectionDetail),
+        (status = 400, response = crate::router::openapi::ProblemResponse),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn put_order(
+    u: User,
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+    Json(order): Json<CollectionOrder>,
+) -> Result<Response, OmniError> {
+    let c = owned_collection(&id, &u, &state).await?;
+    c.reorder(order, &state.dbpool).await?;
+    let c = owned_collection(&id, &u, &state).await?;
+    Ok(Json(detail_of(c, Some(&u), &state).await?).into_response())
+}
+
+#[utoipa::path(
+    put, path = "/collections/{id}/items/{quote_id}", tag = "collections",
+    description = "Appends the quote to the collection.",
+    params(
+        ("id" = Uuid, Path, description = "Collection id"),
+        ("quote_id" = Uuid, Path, description = "Quote id"),
+    ),
+    responses(
+        (status = 204, description = "Quote is in the collection"),
+        (status = 400, response = crate::router::openapi::ProblemResponse),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn put_item(
+    u: User,
+    Path((id, quote_id)): Path<(Uuid, Uuid)>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    let c = owned_collection(&id, &u, &state).await?;
+    let q = collectable_quote(&quote_id, &u, &state).await?;
+    c.add_item(&q.id, &state.dbpool).await?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+#[utoipa::path(
+    delete, path = "/collections/{id}/items/{quote_id}", tag = "collections",
+    params(
+        ("id" = Uuid, Path, description = "Collection id"),
+        ("quote_id" = Uuid, Path, description = "Quote id"),
+    ),
+    responses(
+        (status = 204, description = "Quote is not in the collection"),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn delete_item(
+    u: User,
+    Path((id, quote_id)): Path<(Uuid, Uuid)>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    let c = owned_collection(&id, &u, &state).await?;
+    c.remove_item(&quote_id, &state.dbpool).await?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
@@ -0,0 +1,154 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use utoipa::OpenApi;
+use uuid::Uuid;
+
+use crate::{
+    omnierror::OmniError,
+    pagination::{Page, PageQuery},
+    quotes::{
+        comments::{Comment, CommentPatch, NewComment},
+        Quote,
+    },
+    state::SharedState,
+    user::{attributes::UserAttribute as UA, auth::error::AuthError, User},
+};
+
+use super::quotes::visible_quote;
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(get_comments, post_comment, patch_comment, delete_comment),
+    components(schemas(Comment, NewComment, CommentPatch))
+)]
+pub struct CommentsApi;
+
+pub fn routes() -> Router<SharedState> {
+    Router::new()
+        .route(
+            "/quotes/{id}/comments",
+            get(get_comments).post(post_comment),
+        )
+        .route(
+            "/quotes/comments/{id}",
+            axum::routing::patch(patch_comment).delete(delete_comment),
+        )
+}
+
+/// A comment on a quote the user can still read, along with the quote.
+async fn visible_comment(
+    id: &Uuid,
+    u: &User,
+    state: &SharedState,
+) -> Result<(Comment, Quote), OmniError> {
+    let comment = match Comment::get_by_id(id, &state.dbpool).await? {
+        Some(comment) => comment,
+        None => return Err(OmniError::NotFoundError("comment")),
+    };
+    let q = visible_quote(&comment.quote_id, Some(u), state).await?;
+    Ok((comment, q))
+}
+
+#[utoipa::path(
+    get, path = "/quotes/{id}/comments", tag = "comments",
+    description = "Top-level comments, oldest first, each with its replies. \
+        Comments are visible to whoever can read the quote.",
+    security((), ("cookie" = []), ("bearer" = []), ("basic" = [])),
+    params(("id" = Uuid, Path, description = "Quote id"), PageQuery),
+    responses(
+        (status = 200, body = Page<Comment>),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn get_comments(
+    u: Option<User>,
+    Path(id): Path<Uuid>,
+    Query(page): Query<PageQuery>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    let q = visible_quote(&id, u.as_ref(), &state).await?;
+    Ok(Json(Comment::get_page(&q.id, &page, &state.dbpool).await?).into_response())
+}
+
+#[utoipa::path(
+    post, path = "/quotes/{id}/comments", tag = "comments",
+    description = "Users mentioned by `@handle` are notified, if they can read the quote.",
+    params(("id" = Uuid, Path, description = "Quote id")),
+    request_body = NewComment,
+    responses(
+        (status = 201, body = Comment),
+        (status = 400, response = crate::router::openapi::ProblemResponse),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn post_comment(
+    u: User,
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+    Json(comment): Json<NewComment>,
+) -> Result<Response, OmniError> {
+    let q = visible_quote(&id, Some(&u), &state).await?;
+    let comment = Comment::create(&q, comment, &u, &state.dbpool).await?;
+    Ok((StatusCode::CREATED, Json(comment)).into_response())
+}
+
+#[utoipa::path(
+    patch, path = "/quotes/comments/{id}", tag = "comments",
+    description = "Only the comment's author may edit it, shortly after posting.",
+    params(("id" = Uuid, Path, description = "Comment id")),
+    request_body = CommentPatch,
+    responses(
+        (status = 200, body = Comment),
+        (status = 400, response = crate::router::openapi::ProblemResponse),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn patch_comment(
+    u: User,
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+    Json(patch): Json<CommentPatch>,
+) -> Result<Response, OmniError> {
+    let (comment, q) = visible_comment(&id, &u, &state).await?;
+    if comment.user_id != u.id {
+        return Err(AuthError::NotCommenter)?;
+    }
+    Ok(Json(comment.edit(patch, &q, &state.dbpool).await?).into_response())
+}
+
+#[utoipa::path(
+    delete, path = "/quotes/comments/{id}", tag = "comments",
+    description = "Deletes the comment with its replies; \
+        allowed to its author and to comment moderators, \
+        but only moderators may delete a comment that has replies.",
+    params(("id" = Uuid, Path, description = "Comment id")),
+    responses(
+        (status = 204, description = "Comment deleted"),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn delete_comment(
+    u: User,
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    let (comment, _) = visible_comment(&id, &u, &state).await?;
+    if comment.user_id != u.id && !u.has_permission(UA::QuotesModerateCommentsPermission) {
+        return Err(AuthError::NotCommenter)?;
+    }
+    comment.destroy(&u, &state.dbpool).await?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
@@ -0,0 +1,144 @@
+use axum::{
+    extract::Request,
+    http::{
+        header::{AUTHORIZATION, ORIGIN, REFERER},
+        HeaderMap, Method,
+    },
+    middleware::Next,
+    response::Response,
+};
+use tower_cookies::Cookies;
+
+use crate::{
+    omnierror::OmniError,
+    user::auth::{error::AuthError, SESSION_COOKIE_NAME},
+};
+
+use super::ALLOWED_ORIGINS;
+
+/// Rejects state-changing requests authenticated by the session cookie
+/// unless they come from one of the allowed origins.
+/// Bearer and Basic callers send their credentials explicitly, so they are exempt.
+pub async fn verify_origin(
+    cookies: Cookies,
+    req: Request,
+    next: Next,
+) -> Result<Response, OmniError> {
+    if is_safe_method(req.method()) || req.headers().contains_key(AUTHORIZATION) {
+        return Ok(next.run(req).await);
+    }
+    match cookies.get(SESSION_COOKIE_NAME) {
+        Some(c) if !c.value().is_empty() => (),
+        _ => return Ok(next.run(req).await),
+    }
+
+    match request_origin(req.headers()) {
+        Some(origin) => match ALLOWED_ORIGINS.contains(&origin) {
+            true => Ok(next.run(req).await),
+            false => Err(AuthError::CsrfOriginMismatch)?,
+        },
+        None => Err(AuthError::CsrfMissingOrigin)?,
+    }
+}
+
+fn is_safe_method(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE
+    )
+}
+
+/// Origin header if present, otherwise the scheme://host[:port] part of the Referer
+fn request_origin(headers: &HeaderMap) -> Option<&str> {
+    if let Some(origin) = headers.get(ORIGIN) {
+        return origin.to_str().ok();
+    }
+    let referer = headers.get(REFERER)?.to_str().ok()?;
+    let (_, rest) = referer.split_once("://")?;
+    match rest.find('/') {
+        Some(i) => Some(&referer[..referer.len() - rest.len() + i]),
+        None => Some(referer),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+        middleware,
+        routing::post,
+        Router,
+    };
+    use tower::ServiceExt;
+    use tower_cookies::CookieManagerLayer;
+
+    use super::*;
+
+    async fn send(method: Method, headers: &[(&str, &str)]) -> StatusCode {
+        let app = Router::new()
+            .route("/", post(|| async {}).get(|| async {}))
+            .layer(middleware::from_fn(verify_origin))
+            .layer(CookieManagerLayer::new());
+        let mut req = Request::builder().method(method).uri("/");
+        for (name, value) in headers {
+            req = req.header(*name, *value);
+        }
+        let res = app.oneshot(req.body(Body::empty()).unwrap()).await;
+        res.unwrap().status()
+    }
+
+    const SESSION: (&str, &str) = ("cookie", "qesesh=token");
+
+    #[tokio::test]
+    async fn cookie_mutations_need_an_allowed_origin() {
+        let allowed = ("origin", ALLOWED_ORIGINS[0]);
+        let foreign = ("origin", "https://evil.example");
+        assert_eq!(send(Method::POST, &[SESSION]).await, StatusCode::FORBIDDEN);
+        assert_eq!(
+            send(Method::POST, &[SESSION, foreign]).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            send(Method::POST, &[SESSION, allowed]).await,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn referer_stands_in_for_a_missing_origin() {
+        let referer = ("referer", "http://localhost:3000/quotes/1?x=y");
+        let lookalike = ("referer", "http://localhost:3000.evil.example/");
+        assert_eq!(
+            send(Method::POST, &[SESSION, referer]).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            send(Method::POST, &[SESSION, lookalike]).await,
+            StatusCode::FORBIDDEN
+        );
+    }
+
+    #[tokio::test]
+    async fn other_requests_are_exempt() {
+        let bearer = ("authorization", "Bearer token");
+        assert_eq!(send(Method::GET, &[SESSION]).await, StatusCode::OK);
+        assert_eq!(send(Method::POST, &[SESSION, bearer]).await, StatusCode::OK);
+        assert_eq!(send(Method::POST, &[]).await, StatusCode::OK);
+        assert_eq!(
+            send(Method::POST, &[("cookie", "qesesh=")]).await,
+            StatusCode::OK
+        );
+    }
+
+    #[test]
+    fn origin_is_taken_from_the_referer() {
+        let mut headers = HeaderMap::new();
+        headers.insert(REFERER, "https://example.com:8080/a/b".parse().unwrap());
+        assert_eq!(request_origin(&headers), Some("https://example.com:8080"));
+        headers.insert(REFERER, "https://example.com".parse().unwrap());
+        assert_eq!(request_origin(&headers), Some("https://example.com"));
+        headers.insert(ORIGIN, "null".parse().unwrap());
+        assert_eq!(request_origin(&headers), Some("null"));
+    }
+}
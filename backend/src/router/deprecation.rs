@@ -0,0 +1,123 @@
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use chrono::{NaiveDate, NaiveTime};
+
+use crate::state::SharedState;
+
+pub const API_VERSION_PREFIX: &str = "/v1";
+
+const DEPRECATED_SINCE: (i32, u32, u32) = (2026, 10, 19);
+const SUNSET: (i32, u32, u32) = (2027, 4, 1);
+
+/// Marks unversioned compatibility aliases as deprecated (RFC 9745, RFC 8594)
+/// and counts their use per route, so we know when they can go away.
+///
+/// Only meant as a route layer: requests that match no route are neither marked nor counted,
+/// so the counts stay bounded by the number of routes.
+pub async fn mark_deprecated(
+    State(state): State<SharedState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let route = match req.extensions().get::<MatchedPath>() {
+        Some(p) if !is_versioned(req.uri().path()) => format!("{} {}", req.method(), p.as_str()),
+        _ => return next.run(req).await,
+    };
+    let successor = format!("<{API_VERSION_PREFIX}{}>", req.uri().path());
+
+    let mut lock = state.deprecated_hits.write().await;
+    *lock.entry(route).or_insert(0) += 1;
+    drop(lock);
+
+    let mut res = next.run(req).await;
+    let headers = res.headers_mut();
+    let since = date(DEPRECATED_SINCE).and_time(NaiveTime::MIN).and_utc();
+    let sunset = date(SUNSET).and_time(NaiveTime::MIN).and_utc();
+    if let Ok(v) = HeaderValue::from_str(&format!("@{}", since.timestamp())) {
+        headers.insert(HeaderName::from_static("deprecation"), v);
+    }
+    if let Ok(v) = HeaderValue::from_str(&sunset.format("%a, %d %b %Y %H:%M:%S GMT").to_string()) {
+        headers.insert(HeaderName::from_static("sunset"), v);
+    }
+    if let Ok(v) = HeaderValue::from_str(&format!("{successor}; rel=\"successor-version\"")) {
+        headers.append(HeaderName::from_static("link"), v);
+    }
+    res
+}
+
+fn is_versioned(path: &str) -> bool {
+    path.strip_prefix(API_VERSION_PREFIX)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+fn date((y, m, d): (i32, u32, u32)) -> NaiveDate {
+    NaiveDate::from_ymd_opt(y, m, d).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::StatusCode};
+    use sqlx::PgPool;
+    use tower::ServiceExt;
+
+    use crate::{router, state};
+
+    use super::*;
+
+    async fn get(app: &axum::Router, uri: &str) -> Response {
+        let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        app.clone().oneshot(req).await.unwrap()
+    }
+
+    #[sqlx::test]
+    async fn unversioned_aliases_are_marked_and_counted(pool: PgPool) {
+        let state = state::with_pool(pool);
+        let app = router::init(state.clone());
+
+        let res = get(&app, "/quotes/00000000-0000-0000-0000-000000000001").await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        assert!(res.headers()["deprecation"]
+            .to_str()
+            .unwrap()
+            .starts_with('@'));
+        assert!(res.headers().contains_key("sunset"));
+        assert_eq!(
+            res.headers()["link"],
+            "</v1/quotes/00000000-0000-0000-0000-000000000001>; rel=\"successor-version\""
+        );
+        get(&app, "/quotes/00000000-0000-0000-0000-000000000002").await;
+
+        let hits = state.deprecated_hits.read().await;
+        assert_eq!(hits.get("GET /quotes/{id}"), Some(&2));
+        assert_eq!(hits.len(), 1);
+    }
+
+    #[sqlx::test]
+    async fn versioned_and_unknown_paths_are_left_alone(pool: PgPool) {
+        let state = state::with_pool(pool);
+        let app = router::init(state.clone());
+
+        for uri in [
+            "/v1/quotes/00000000-0000-0000-0000-000000000001",
+            "/no/such/route",
+            "/v1/no/such/route",
+        ] {
+            let res = get(&app, uri).await;
+            assert_eq!(res.status(), StatusCode::NOT_FOUND);
+            assert!(!res.headers().contains_key("deprecation"), "{uri}");
+        }
+        assert!(state.deprecated_hits.read().await.is_empty());
+    }
+
+    #[test]
+    fn only_the_whole_prefix_is_a_version() {
+        assert!(is_versioned("/v1"));
+        assert!(is_versioned("/v1/quotes"));
+        assert!(!is_versioned("/v10/quotes"));
+        assert!(!is_versioned("/quotes/v1"));
+    }
+}
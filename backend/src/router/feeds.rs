@@ -0,0 +1,193 @@
+use axum::{
+    extract::{Query, State},
+    http::{
+        header::{CACHE_CONTROL, CONTENT_TYPE},
+        StatusCode,
+    },
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use utoipa::OpenApi;
+
+use crate::{
+    omnierror::OmniError,
+    quotes::{
+        authors::Author,
+        feeds::{CreatedFeedToken, Feed, FeedFormat, FeedQuery, FeedToken},
+        tags,
+    },
+    state::SharedState,
+    user::{auth::error::AuthError, User},
+};
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        atom_handler,
+        rss_handler,
+        json_handler,
+        get_feed_token,
+        post_feed_token,
+        revoke_feed_token
+    ),
+    components(schemas(FeedToken, CreatedFeedToken))
+)]
+pub struct FeedsApi;
+
+pub fn routes() -> Router<SharedState> {
+    Router::new()
+        .route("/feeds/quotes.atom", get(atom_handler))
+        .route("/feeds/quotes.rss", get(rss_handler))
+        .route("/feeds/quotes.json", get(json_handler))
+        .route(
+            "/users/me/feed-token",
+            get(get_feed_token)
+                .post(post_feed_token)
+                .delete(revoke_feed_token),
+        )
+}
+
+#[utoipa::path(
+    get, path = "/feeds/quotes.atom", tag = "feeds", security(()),
+    description = "The 50 newest published quotes anyone can read, newest first. \
+        With a feed token, those its owner can read instead, with lines above their clearance redacted.",
+    params(FeedQuery),
+    responses(
+        (status = 200, description = "Atom 1.0 feed", content_type = "application/atom+xml"),
+        (status = 400, response = crate::router::openapi::ProblemResponse),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn atom_handler(
+    Query(query): Query<FeedQuery>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    feed(query, FeedFormat::Atom, &state).await
+}
+
+#[utoipa::path(
+    get, path = "/feeds/quotes.rss", tag = "feeds", security(()),
+    description = "The same feed as `quotes.atom`, as RSS 2.0; author names are given as `dc:creator`.",
+    params(FeedQuery),
+    responses(
+        (status = 200, description = "RSS 2.0 feed", content_type = "application/rss+xml"),
+        (status = 400, response = crate::router::openapi::ProblemResponse),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn rss_handler(
+    Query(query): Query<FeedQuery>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    feed(query, FeedFormat::Rss, &state).await
+}
+
+#[utoipa::path(
+    get, path = "/feeds/quotes.json", tag = "feeds", security(()),
+    description = "The same feed as `quotes.atom`, as JSON Feed 1.1.",
+    params(FeedQuery),
+    responses(
+        (status = 200, description = "JSON Feed 1.1", content_type = "application/feed+json"),
+        (status = 400, response = crate::router::openapi::ProblemResponse),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn json_handler(
+    Query(query): Query<FeedQuery>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    feed(query, FeedFormat::Json, &state).await
+}
+
+/// Feed readers can't log in, so private feeds go by token alone.
+async fn feed(
+    query: FeedQuery,
+    format: FeedFormat,
+    state: &SharedState,
+) -> Result<Response, OmniError> {
+    let reader = match &query.token {
+        Some(token) => match FeedToken::get_user(token, &state.dbpool).await? {
+            Some(u) => Some(u),
+            None => return Err(AuthError::InvalidCredentials)?,
+        },
+        None => None,
+    };
+    let author = match query.author {
+        Some(id) => match Author::get_by_id(&id, &state.dbpool).await? {
+            Some(a) => Some(a),
+            None => return Err(OmniError::NotFoundError("author")),
+        },
+        None => None,
+    };
+    let tag = tags::normalize_filter(query.tag.as_deref());
+    let body = Feed::load(
+        author.as_ref(),
+        tag.as_deref(),
+        reader.as_ref(),
+        &state.dbpool,
+    )
+    .await?
+    .render(format)?;
+    let cache = match reader {
+        Some(_) => "private, max-age=300",
+        None => "public, max-age=300",
+    };
+    let headers = [
+        (CONTENT_TYPE, format.content_type()),
+        (CACHE_CONTROL, cache),
+    ];
+    Ok((headers, body).into_response())
+}
+
+#[utoipa::path(
+    get, path = "/users/me/feed-token", tag = "feeds",
+    description = "When the caller's current feed token was created; the token itself is not kept.",
+    responses(
+        (status = 200, body = FeedToken),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn get_feed_token(u: User, State(state): State<SharedState>) -> Result<Response, OmniError> {
+    match FeedToken::get_for(&u, &state.dbpool).await? {
+        Some(t) => Ok(Json(t).into_response()),
+        None => Err(OmniError::NotFoundError("feed token")),
+    }
+}
+
+#[utoipa::path(
+    post, path = "/users/me/feed-token", tag = "feeds",
+    description = "Creates a feed token for private feeds, replacing any earlier one. \
+        The token is only ever returned here; only its hash is stored.",
+    responses(
+        (status = 201, body = CreatedFeedToken),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn post_feed_token(u: User, State(state): State<SharedState>) -> Result<Response, OmniError> {
+    let created = FeedToken::create(&u, &state.dbpool).await?;
+    Ok((StatusCode::CREATED, Json(created)).into_response())
+}
+
+#[utoipa::path(
+    delete, path = "/users/me/feed-token", tag = "feeds",
+    description = "Stops the caller's private feeds from working until a new token is created.",
+    responses(
+        (status = 204, description = "Feed token revoked"),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn revoke_feed_token(
+    u: User,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    match FeedToken::revoke(&u, &state.dbpool).await? {
+        true => Ok(StatusCode::NO_CONTENT.into_response()),
+        false => Err(OmniError::NotFoundError("feed token")),
+    }
+}
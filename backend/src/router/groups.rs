@@ -0,0 +1,251 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, put},
+    Json, Router,
+};
+use utoipa::OpenApi;
+use uuid::Uuid;
+
+use crate::{
+    groups::{Group, GroupPatch, NewGroup},
+    omnierror::OmniError,
+    state::SharedState,
+    user::{
+        attributes::UserAttribute as UA,
+        auth::{
+            error::AuthError,
+            guard::{Require, UsersManageGroupsPermission},
+        },
+        User,
+    },
+};
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        get_all,
+        post_new,
+        by_id_handler,
+        patch_handler,
+        delete_handler,
+        get_members,
+        put_member,
+        delete_member
+    ),
+    components(schemas(Group, NewGroup, GroupPatch))
+)]
+pub struct GroupsApi;
+
+pub fn routes() -> Router<SharedState> {
+    Router::new()
+        .route("/groups", get(get_all).post(post_new))
+        .route(
+            "/groups/{id}",
+            get(by_id_handler)
+                .patch(patch_handler)
+                .delete(delete_handler),
+        )
+        .route("/groups/{id}/members", get(get_members))
+        .route(
+            "/groups/{id}/members/{user_id}",
+            put(put_member).delete(delete_member),
+        )
+}
+
+/// Members see their own groups; group managers see every group.
+async fn visible_group(id: &Uuid, u: &User, state: &SharedState) -> Result<Group, OmniError> {
+    let group = match Group::get_by_id(id, &state.dbpool).await? {
+        Some(group) => group,
+        None => return Err(OmniError::NotFoundError("group")),
+    };
+    if !u.has_permission(UA::UsersManageGroupsPermission)
+        && !group.has_member(&u.id, &state.dbpool).await?
+    {
+        return Err(AuthError::NotGroupMember)?;
+    }
+    Ok(group)
+}
+
+#[utoipa::path(
+    get, path = "/groups", tag = "groups",
+    description = "Every group for group managers; otherwise the groups the caller is in.",
+    responses(
+        (status = 200, body = Vec<Group>),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn get_all(u: User, State(state): State<SharedState>) -> Result<Response, OmniError> {
+    let groups = match u.has_permission(UA::UsersManageGroupsPermission) {
+        true => Group::get_all(&state.dbpool).await?,
+        false => Group::get_joined_by(&u.id, &state.dbpool).await?,
+    };
+    Ok(Json(groups).into_response())
+}
+
+#[utoipa::path(
+    post, path = "/groups", tag = "groups",
+    request_body = NewGroup,
+    responses(
+        (status = 201, body = Group),
+        (status = 400, response = crate::router::openapi::ProblemResponse),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 409, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn post_new(
+    _: Require<UsersManageGroupsPermission>,
+    State(state): State<SharedState>,
+    Json(group): Json<NewGroup>,
+) -> Result<Response, OmniError> {
+    group.is_valid()?;
+    let group = Group::create(group, &state.dbpool).await?;
+    Ok((StatusCode::CREATED, Json(group)).into_response())
+}
+
+#[utoipa::path(
+    get, path = "/groups/{id}", tag = "groups",
+    params(("id" = Uuid, Path, description = "Group id")),
+    responses(
+        (status = 200, body = Group),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn by_id_handler(
+    u: User,
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    Ok(Json(visible_group(&id, &u, &state).await?).into_response())
+}
+
+#[utoipa::path(
+    patch, path = "/groups/{id}", tag = "groups",
+    params(("id" = Uuid, Path, description = "Group id")),
+    request_body = GroupPatch,
+    responses(
+        (status = 200, body = Group),
+        (status = 400, response = crate::router::openapi::ProblemResponse),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+        (status = 409, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn patch_handler(
+    _: Require<UsersManageGroupsPermission>,
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+    Json(patch): Json<GroupPatch>,
+) -> Result<Response, OmniError> {
+    match Group::get_by_id(&id, &state.dbpool).await? {
+        Some(group) => Ok(Json(group.patch(patch, &state.dbpool).await?).into_response()),
+        None => Err(OmniError::NotFoundError("group")),
+    }
+}
+
+#[utoipa::path(
+    delete, path = "/groups/{id}", tag = "groups",
+    description = "Refused with a 409 listing the quotes in `references` \
+        while any quote is restricted to the group.",
+    params(("id" = Uuid, Path, description = "Group id")),
+    responses(
+        (status = 204, description = "Group deleted"),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+        (status = 409, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn delete_handler(
+    _: Require<UsersManageGroupsPermission>,
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    match Group::get_by_id(&id, &state.dbpool).await? {
+        Some(group) => {
+            group.destroy(&state.dbpool).await?;
+            Ok(StatusCode::NO_CONTENT.into_response())
+        }
+        None => Err(OmniError::NotFoundError("group")),
+    }
+}
+
+#[utoipa::path(
+    get, path = "/groups/{id}/members", tag = "groups",
+    params(("id" = Uuid, Path, description = "Group id")),
+    responses(
+        (status = 200, body = Vec<User>),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn get_members(
+    u: User,
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    let group = visible_group(&id, &u, &state).await?;
+    Ok(Json(group.get_members(&state.dbpool).await?).into_response())
+}
+
+#[utoipa::path(
+    put, path = "/groups/{id}/members/{user_id}", tag = "groups",
+    params(
+        ("id" = Uuid, Path, description = "Group id"),
+        ("user_id" = Uuid, Path, description = "User id"),
+    ),
+    responses(
+        (status = 204, description = "User is a member"),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn put_member(
+    _: Require<UsersManageGroupsPermission>,
+    Path((id, user_id)): Path<(Uuid, Uuid)>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    let group = match Group::get_by_id(&id, &state.dbpool).await? {
+        Some(group) => group,
+        None => return Err(OmniError::NotFoundError("group")),
+    };
+    if User::get_by_id(&user_id, &state.dbpool).await?.is_none() {
+        return Err(OmniError::NotFoundError("user"));
+    }
+    group.add_member(&user_id, &state.dbpool).await?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+#[utoipa::path(
+    delete, path = "/groups/{id}/members/{user_id}", tag = "groups",
+    params(
+        ("id" = Uuid, Path, description = "Group id"),
+        ("user_id" = Uuid, Path, description = "User id"),
+    ),
+    responses(
+        (status = 204, description = "User is not a member"),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn delete_member(
+    _: Require<UsersManageGroupsPermission>,
+    Path((id, user_id)): Path<(Uuid, Uuid)>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    match Group::get_by_id(&id, &state.dbpool).await? {
+        Some(group) => {
+            group.remove_member(&user_id, &state.dbpool).await?;
+            Ok(StatusCode::NO_CONTENT.into_response())
+        }
+        None => Err(OmniError::NotFoundError("group")),
+    }
+}
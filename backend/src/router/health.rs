@@ -3,15 +3,22 @@ use axum::{
         ws::{Message, Utf8Bytes, WebSocket},
         State, WebSocketUpgrade,
     },
-    http::HeaderMap,
     response::{IntoResponse, Response},
     routing::{any, get},
     Json, Router,
 };
-use tower_cookies::Cookies;
 use tracing::error;
+use utoipa::OpenApi;
 
-use crate::{omnierror::OmniError, state::SharedState, user::User};
+use crate::{
+    omnierror::OmniError,
+    state::{SharedState, SystemInfo},
+    user::User,
+};
+
+#[derive(OpenApi)]
+#[openapi(paths(health, health_ws_upgrade), components(schemas(SystemInfo)))]
+pub struct HealthApi;
 
 pub fn routes() -> Router<SharedState> {
     Router::new()
@@ -19,25 +26,39 @@ pub fn routes() -> Router<SharedState> {
         .route("/health/ws", any(health_ws_upgrade))
 }
 
-async fn health(
-    headers: HeaderMap,
-    cookies: Cookies,
-    State(state): State<SharedState>,
-) -> Result<Response, OmniError> {
-    // infosec: only show system health to actual users
-    User::authenticate(&headers, cookies, &state.dbpool).await?;
+#[utoipa::path(
+    get, path = "/", tag = "health", security(()),
+    responses((status = 200, description = "The server is up"))
+)]
+pub async fn root() {}
+
+// infosec: only show system health to actual users
+#[utoipa::path(
+    get, path = "/health", tag = "health",
+    responses(
+        (status = 200, body = SystemInfo),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn health(_: User, State(state): State<SharedState>) -> Result<Response, OmniError> {
     let sysinfo = state.sysinfo.read().await;
     Ok(Json(&*sysinfo).into_response())
 }
 
+// infosec: only show system health to actual users
+#[utoipa::path(
+    get, path = "/health/ws", tag = "health",
+    description = "WebSocket upgrade; streams `SystemInfo` JSON messages.",
+    responses(
+        (status = 101, description = "Switching protocols"),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+    )
+)]
 async fn health_ws_upgrade(
-    headers: HeaderMap,
-    cookies: Cookies,
+    _: User,
     ws: WebSocketUpgrade,
     State(state): State<SharedState>,
 ) -> Result<Response, OmniError> {
-    // infosec: only show system health to actual users
-    User::authenticate(&headers, cookies, &state.dbpool).await?;
     Ok(ws.on_upgrade(|ws| async { health_ws_stream(state, ws).await }))
 }
 
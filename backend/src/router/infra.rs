@@ -1,46 +1,74 @@
 use axum::{
     extract::State,
-    http::{HeaderMap, StatusCode},
     response::{IntoResponse, Response},
     routing::get,
     Json, Router,
 };
-use tower_cookies::Cookies;
+use utoipa::OpenApi;
 
 use crate::{
     omnierror::OmniError,
     state::SharedState,
-    user::{auth::session::Session, User},
+    user::{
+        auth::{
+            guard::{Infradmin, Require},
+            session::Session,
+        },
+        User,
+    },
 };
 
+#[derive(OpenApi)]
+#[openapi(paths(all_users, all_sessions, deprecated_usage))]
+pub struct InfraApi;
+
 pub fn routes() -> Router<SharedState> {
     Router::new()
         .route("/infra/all-users", get(all_users))
         .route("/infra/all-sessions", get(all_sessions))
+        .route("/infra/deprecated-usage", get(deprecated_usage))
 }
 
+#[utoipa::path(
+    get, path = "/infra/all-users", tag = "infra",
+    responses(
+        (status = 200, body = Vec<User>),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+    )
+)]
 async fn all_users(
-    headers: HeaderMap,
-    cookies: Cookies,
+    _: Require<Infradmin>,
     State(state): State<SharedState>,
 ) -> Result<Response, OmniError> {
-    let u = User::authenticate(&headers, cookies, &state.dbpool).await?;
-    if !u.is_infradmin() {
-        return Ok(StatusCode::FORBIDDEN.into_response());
-    }
-
     Ok(Json(User::get_all(&state.dbpool).await?).into_response())
 }
 
+#[utoipa::path(
+    get, path = "/infra/all-sessions", tag = "infra",
+    responses(
+        (status = 200, body = Vec<Session>),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+    )
+)]
 async fn all_sessions(
-    headers: HeaderMap,
-    cookies: Cookies,
+    _: Require<Infradmin>,
     State(state): State<SharedState>,
 ) -> Result<Response, OmniError> {
-    let u = User::authenticate(&headers, cookies, &state.dbpool).await?;
-    if !u.is_infradmin() {
-        return Ok(StatusCode::FORBIDDEN.into_response());
-    }
-
     Ok(Json(Session::get_all(&state.dbpool).await?).into_response())
 }
+
+#[utoipa::path(
+    get, path = "/infra/deprecated-usage", tag = "infra",
+    description = "Hits per route on the unversioned compatibility aliases since startup.",
+    responses(
+        (status = 200, body = std::collections::HashMap<String, u64>),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn deprecated_usage(_: Require<Infradmin>, State(state): State<SharedState>) -> Response {
+    let hits = state.deprecated_hits.read().await;
+    Json(&*hits).into_response()
+}
@@ -0,0 +1,48 @@
+use axum::{
+    extract::{Query, State},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde::Deserialize;
+use utoipa::{IntoParams, OpenApi};
+use uuid::Uuid;
+
+use crate::{
+    logs::Log,
+    omnierror::OmniError,
+    state::SharedState,
+    user::auth::guard::{LogsInspectPermission, Require},
+};
+
+#[derive(OpenApi)]
+#[openapi(paths(get_recent), components(schemas(Log)))]
+pub struct LogsApi;
+
+pub fn routes() -> Router<SharedState> {
+    Router::new().route("/logs", get(get_recent))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[serde(deny_unknown_fields)]
+struct LogsQuery {
+    subject_id: Option<Uuid>,
+}
+
+#[utoipa::path(
+    get, path = "/logs", tag = "logs",
+    description = "The most recent audit log entries, optionally about one subject.",
+    params(LogsQuery),
+    responses(
+        (status = 200, body = Vec<Log>),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn get_recent(
+    _: Require<LogsInspectPermission>,
+    Query(query): Query<LogsQuery>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    Ok(Json(Log::get_recent(query.subject_id, &state.dbpool).await?).into_response())
+}
@@ -1,33 +1,70 @@
 use crate::state::SharedState;
-use axum::{http::Method, routing::get, Router};
+use axum::{http::Method, middleware, routing::get, Router};
 use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
 use tower_cookies::CookieManagerLayer;
 use tower_http::cors::CorsLayer;
 
 mod auth;
 mod authors;
+mod clearance;
+mod collections;
+mod comments;
+mod csrf;
+mod deprecation;
+mod feeds;
+mod groups;
 mod health;
 mod infra;
+mod logs;
+mod notifications;
+pub mod openapi;
 mod quotes;
+mod share_links;
 mod users;
 
-pub fn init(state: SharedState) -> Router {
-    let allowed_origins = ["http://localhost:3000"];
+pub use deprecation::API_VERSION_PREFIX;
 
-    Router::new()
-        .route("/", get(|| async { () }))
+pub const ALLOWED_ORIGINS: [&str; 1] = ["http://localhost:3000"];
+
+pub fn init(state: SharedState) -> Router {
+    let api = Router::new()
         .merge(health::routes())
         .merge(infra::routes())
         .merge(auth::routes())
+        .merge(logs::routes())
+        .merge(notifications::routes())
         .merge(users::routes())
+        .merge(clearance::routes())
+        .merge(groups::routes())
         .merge(authors::routes())
         .merge(quotes::routes())
+        .merge(comments::routes())
+        .merge(share_links::routes())
+        .merge(collections::routes())
+        .merge(feeds::routes());
+    let legacy = api.clone().route_layer(middleware::from_fn_with_state(
+        state.clone(),
+        deprecation::mark_deprecated,
+    ));
+
+    Router::new()
+        .route("/", get(health::root))
+        .merge(openapi::routes())
+        .nest(deprecation::API_VERSION_PREFIX, api)
+        .merge(legacy)
         .with_state(state)
+        .layer(middleware::from_fn(csrf::verify_origin))
         .layer(CookieManagerLayer::new())
         .layer(
             CorsLayer::new()
-                .allow_origin(allowed_origins.map(|s| s.parse().unwrap()))
-                .allow_methods([Method::GET, Method::POST, Method::PATCH, Method::DELETE])
+                .allow_origin(ALLOWED_ORIGINS.map(|s| s.parse().unwrap()))
+                .allow_methods([
+                    Method::GET,
+                    Method::POST,
+                    Method::PUT,
+                    Method::PATCH,
+                    Method::DELETE,
+                ])
                 .allow_headers([AUTHORIZATION, CONTENT_TYPE])
                 .allow_credentials(true),
         )
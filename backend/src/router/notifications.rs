@@ -0,0 +1,89 @@
+use axum::{
+    extract::{Path, Query, State},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, OpenApi, ToSchema};
+use uuid::Uuid;
+
+use crate::{
+    omnierror::OmniError,
+    pagination::{Page, PageQuery},
+    state::SharedState,
+    user::{notifications::Notification, User},
+};
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(get_notifications, mark_read, mark_all_read),
+    components(schemas(Notification, MarkedRead))
+)]
+pub struct NotificationsApi;
+
+pub fn routes() -> Router<SharedState> {
+    Router::new()
+        .route("/notifications", get(get_notifications))
+        .route("/notifications/{id}/read", post(mark_read))
+        .route("/notifications/read-all", post(mark_all_read))
+}
+
+#[derive(Deserialize, IntoParams)]
+struct NotificationsQuery {
+    #[serde(default)]
+    unread: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+struct MarkedRead {
+    marked: u64,
+}
+
+#[utoipa::path(
+    get, path = "/notifications", tag = "notifications",
+    description = "The caller's notifications, newest first.",
+    params(NotificationsQuery, PageQuery),
+    responses(
+        (status = 200, body = Page<Notification>),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn get_notifications(
+    u: User,
+    Query(query): Query<NotificationsQuery>,
+    Query(page): Query<PageQuery>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    let page = Notification::get_page(&u.id, query.unread, &page, &state.dbpool).await?;
+    Ok(Json(page).into_response())
+}
+
+#[utoipa::path(
+    post, path = "/notifications/{id}/read", tag = "notifications",
+    params(("id" = Uuid, Path, description = "Notification id")),
+    responses(
+        (status = 200, body = MarkedRead),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn mark_read(
+    u: User,
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    let marked = Notification::mark_read(&u.id, Some(id), &state.dbpool).await?;
+    Ok(Json(MarkedRead { marked }).into_response())
+}
+
+#[utoipa::path(
+    post, path = "/notifications/read-all", tag = "notifications",
+    responses(
+        (status = 200, body = MarkedRead),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn mark_all_read(u: User, State(state): State<SharedState>) -> Result<Response, OmniError> {
+    let marked = Notification::mark_read(&u.id, None, &state.dbpool).await?;
+    Ok(Json(MarkedRead { marked }).into_response())
+}
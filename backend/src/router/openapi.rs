@@ -0,0 +1,200 @@
+use axum::{routing::get, Json, Router};
+use utoipa::{
+    openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme},
+    Modify, OpenApi, ToResponse,
+};
+use utoipa_scalar::{Scalar, Servable};
+
+use crate::{
+    omnierror::{FieldIssue, Problem},
+    state::SharedState,
+};
+
+use super::{
+    auth, authors, clearance, collections, comments, deprecation::API_VERSION_PREFIX, feeds,
+    groups, health, infra, logs, notifications, quotes, share_links, users,
+};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Quote Engine", description = "Quote Engine backend API"),
+    paths(health::root),
+    modifiers(&SecuritySchemes),
+    security(("cookie" = []), ("bearer" = []), ("basic" = [])),
+    components(schemas(Problem, FieldIssue), responses(ProblemResponse)),
+    tags(
+        (name = "health", description = "Liveness and system health"),
+        (name = "infra", description = "Infrastructure administrator tools"),
+        (name = "auth", description = "Logging in and out"),
+        (name = "logs", description = "Audit log"),
+        (name = "notifications", description = "Per-user notifications"),
+        (name = "users", description = "User accounts"),
+        (name = "clearance", description = "Named clearance levels"),
+        (name = "groups", description = "Circles of users that quotes can be restricted to"),
+        (name = "authors", description = "People who get quoted"),
+        (name = "quotes", description = "Quotes and their lines"),
+        (name = "comments", description = "Discussion threads on quotes"),
+        (name = "collections", description = "Favourite quotes and curated collections"),
+        (name = "share links", description = "Links that open a single quote without logging in"),
+        (name = "feeds", description = "Atom, RSS and JSON feeds of the newest quotes"),
+    )
+)]
+pub struct ApiDoc;
+
+/// Any error; see `code` for the machine-readable reason.
+/// Only exists to be documented, hence never constructed.
+#[allow(dead_code)]
+#[derive(ToResponse)]
+#[response(
+    description = "RFC 7807 problem details",
+    content_type = "application/problem+json"
+)]
+pub struct ProblemResponse(Problem);
+
+/// The full document; every router module contributes its own `OpenApi` struct,
+/// documented under the versioned prefix only.
+pub fn spec() -> utoipa::openapi::OpenApi {
+    let mut api = health::HealthApi::openapi();
+    api.merge(infra::InfraApi::openapi());
+    api.merge(auth::AuthApi::openapi());
+    api.merge(logs::LogsApi::openapi());
+    api.merge(notifications::NotificationsApi::openapi());
+    api.merge(users::UsersApi::openapi());
+    api.merge(clearance::ClearanceApi::openapi());
+    api.merge(groups::GroupsApi::openapi());
+    api.merge(authors::AuthorsApi::openapi());
+    api.merge(quotes::QuotesApi::openapi());
+    api.merge(comments::CommentsApi::openapi());
+    api.merge(collections::CollectionsApi::openapi());
+    api.merge(share_links::ShareLinksApi::openapi());
+    api.merge(feeds::FeedsApi::openapi());
+    ApiDoc::openapi().nest(API_VERSION_PREFIX, api)
+}
+
+struct SecuritySchemes;
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "cookie",
+            SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::new(
+                crate::user::auth::SESSION_COOKIE_NAME,
+            ))),
+        );
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+        components.add_security_scheme(
+            "basic",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Basic).build()),
+        );
+    }
+}
+
+pub fn routes() -> Router<SharedState> {
+    Router::new()
+        .route("/openapi.json", get(|| async { Json(spec()) }))
+        .merge(Scalar::with_url("/docs", spec()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeSet, fs};
+
+    use super::API_VERSION_PREFIX;
+
+    /// Routes that serve the documentation itself.
+    const UNDOCUMENTED: [&str; 1] = ["/openapi.json"];
+    const METHODS: [&str; 6] = ["get", "post", "put", "patch", "delete", "any"];
+
+    /// Scans `src/router/*.rs`, outside of tests, for `.route("path", get(..).post(..))` calls.
+    fn registered_routes() -> BTreeSet<(String, String)> {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/src/router");
+        let mut routes = BTreeSet::new();
+        for entry in fs::read_dir(dir).unwrap() {
+            let src = fs::read_to_string(entry.unwrap().path()).unwrap();
+            // test routers are not part of the API
+            let src = src.split("#[cfg(test)]").next().unwrap();
+            for (start, _) in src.match_indices(".route(") {
+                let line_start = src[..start].rfind('\n').map_or(0, |i| i + 1);
+                if src[line_start..start].trim_start().starts_with("//") {
+                    continue;
+                }
+                let call = balanced_call(&src[start + ".route".len()..]);
+                let path = call.split('"').nth(1).unwrap().to_string();
+                if UNDOCUMENTED.contains(&path.as_str()) {
+                    continue;
+                }
+                for method in METHODS {
+                    for (i, _) in call.match_indices(&format!("{method}(")) {
+                        let before = call[..i].chars().last().unwrap_or(' ');
+                        if before.is_alphanumeric() || before == '_' {
+                            continue;
+                        }
+                        // websocket upgrades are plain GETs
+                        let method = if method == "any" { "get" } else { method };
+                        routes.insert((path.clone(), method.to_string()));
+                    }
+                }
+            }
+        }
+        routes
+    }
+
+    fn balanced_call(src: &str) -> &str {
+        let mut depth = 0;
+        for (i, c) in src.char_indices() {
+            match c {
+                '(' => depth += 1,
+                ')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return &src[..=i];
+                    }
+                }
+                _ => (),
+            }
+        }
+        panic!("unbalanced .route( call");
+    }
+
+    fn documented_routes() -> BTreeSet<(String, String)> {
+        let spec = serde_json::to_value(super::spec()).unwrap();
+        let mut routes = BTreeSet::new();
+        for (path, item) in spec["paths"].as_object().unwrap() {
+            // routes are registered without the version prefix and nested in `router::init`
+            let path = path.strip_prefix(API_VERSION_PREFIX).unwrap_or(path);
+            for method in item.as_object().unwrap().keys() {
+                routes.insert((path.to_string(), method.clone()));
+            }
+        }
+        routes
+    }
+
+    #[test]
+    fn every_route_is_documented() {
+        let documented = documented_routes();
+        let missing: Vec<_> = registered_routes()
+            .into_iter()
+            .filter(|r| !documented.contains(r))
+            .collect();
+        assert!(
+            missing.is_empty(),
+            "routes without #[utoipa::path]: {missing:?}"
+        );
+    }
+
+    #[test]
+    fn every_documented_route_exists() {
+        let registered = registered_routes();
+        let stale: Vec<_> = documented_routes()
+            .into_iter()
+            .filter(|r| !registered.contains(r))
+            .collect();
+        assert!(
+            stale.is_empty(),
+            "documented routes that are not registered: {stale:?}"
+        );
+    }
+}
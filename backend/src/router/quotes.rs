@@ -1,118 +1,999 @@
 use axum::{
-    extract::{Path, State},
-    http::{HeaderMap, StatusCode},
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{
+        header::{CACHE_CONTROL, CONTENT_TYPE, ETAG, IF_NONE_MATCH},
+        HeaderMap, StatusCode,
+    },
     response::{IntoResponse, Response},
-    routing::{get, post},
+    routing::{self, get, post, put},
     Json, Router,
 };
-use tower_cookies::Cookies;
+use std::slice;
+use utoipa::OpenApi;
 use uuid::Uuid;
 
 use crate::{
+    clearance::ClearanceLevel,
+    groups::Group,
     omnierror::OmniError,
-    quotes::{placeholder::return_placeholder_random_public_quote, Quote},
+    pagination::{Page, PageQuery},
+    quotes::{
+        card::{CardFormat, CardQuery, CardTheme},
+        daily::QuoteOfTheDay,
+        flags::{FlagKind, FlagResolution, FlagStatus, NewQuoteFlag, QuoteFlag},
+        moderation::{QuoteStatus, ReviewReason},
+        placeholder::return_placeholder_random_public_quote,
+        random::{RandomFilter, RandomWeight},
+        reactions::{
+            NewReaction, NewVote, OwnReaction, QuoteReactions, QuoteSort, Reaction, TopPeriod, Vote,
+        },
+        source::{QuoteSource, SourceFilter, SourceMedium},
+        tags::{QuoteTags, TagFilter},
+        validity::QuoteValidityError,
+        visibility::QuoteGroups,
+        Quote, QuoteLine,
+    },
     state::SharedState,
-    user::{attributes::UserAttribute as UA, User},
+    user::{
+        attributes::UserAttribute as UA,
+        auth::{
+            error::AuthError,
+            guard::{
+                QuotesCreatePermission, QuotesDeletePermission, QuotesPurgePermission,
+                QuotesReviewPermission, Require, TheEverythingPermission,
+                UsersManageGroupsPermission,
+            },
+        },
+        User,
+    },
 };
 
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        get_by_id,
+        get_card_svg,
+        get_card_png,
+        get_random,
+        get_of_the_day,
+        get_daily_history,
+        get_all,
+        post_new,
+        put_draft,
+        submit,
+        get_review_queue,
+        approve,
+        reject,
+        request_changes,
+        archive,
+        delete,
+        get_trash,
+        restore,
+        purge,
+        get_top,
+        put_reaction,
+        delete_reaction,
+        put_vote,
+        delete_vote,
+        put_groups,
+        put_tags,
+        post_flag,
+        get_flags,
+        resolve_flag
+    ),
+    components(schemas(
+        Quote,
+        QuoteLine,
+        QuoteSource,
+        SourceMedium,
+        QuoteFlag,
+        FlagKind,
+        FlagStatus,
+        NewQuoteFlag,
+        FlagResolution,
+        QuoteGroups,
+        QuoteTags,
+        QuoteStatus,
+        ReviewReason,
+        QuoteReactions,
+        OwnReaction,
+        Reaction,
+        Vote,
+        NewReaction,
+        NewVote,
+        QuoteSort,
+        TopPeriod,
+        QuoteOfTheDay,
+        RandomWeight,
+        CardTheme
+    ))
+)]
+pub struct QuotesApi;
+
 pub fn routes() -> Router<SharedState> {
     Router::new()
         .route("/quotes", post(post_new))
         .route("/quotes/all", get(get_all))
-        .route("/quotes/{id}", get(get_by_id).delete(delete))
+        .route("/quotes/{id}", get(get_by_id).put(put_draft).delete(delete))
+        .route("/quotes/{id}/card.svg", get(get_card_svg))
+        .route("/quotes/{id}/card.png", get(get_card_png))
+        .route("/quotes/{id}/submit", post(submit))
+        .route("/quotes/review", get(get_review_queue))
+        .route("/quotes/{id}/approve", post(approve))
+        .route("/quotes/{id}/reject", post(reject))
+        .route("/quotes/{id}/request-changes", post(request_changes))
+        .route("/quotes/{id}/archive", post(archive))
+        .route("/quotes/trash", get(get_trash))
+        .route("/quotes/trash/{id}", routing::delete(purge))
+        .route("/quotes/trash/{id}/restore", post(restore))
         .route("/quotes/randompublic", get(get_random))
+        .route("/quotes/daily", get(get_of_the_day))
+        .route("/quotes/daily/history", get(get_daily_history))
+        .route("/quotes/top/{period}", get(get_top))
+        .route(
+            "/quotes/{id}/reaction",
+            put(put_reaction).delete(delete_reaction),
+        )
+        .route("/quotes/{id}/vote", put(put_vote).delete(delete_vote))
+        .route("/quotes/{id}/groups", put(put_groups))
+        .route("/quotes/{id}/tags", put(put_tags))
+        .route("/quotes/{id}/flags", post(post_flag))
+        .route("/quotes/flags", get(get_flags))
+        .route("/quotes/flags/{id}/resolve", post(resolve_flag))
 }
 
+#[utoipa::path(
+    get, path = "/quotes/{id}", tag = "quotes",
+    description = "Quotes with clearance 0 are public; others require a user with enough clearance, \
+        or one linked to an author of the quote. Lines above the viewer's clearance are redacted. \
+        Quotes restricted to groups are not found for anyone outside them, \
+        and unpublished quotes for anyone but their submitter and reviewers.",
+    security((), ("cookie" = []), ("bearer" = []), ("basic" = [])),
+    params(("id" = Uuid, Path, description = "Quote id")),
+    responses(
+        (status = 200, body = Quote),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
 async fn get_by_id(
+    u: Option<User>,
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    let mut q = visible_quote(&id, u.as_ref(), &state).await?;
+    Quote::load_reactions(slice::from_mut(&mut q), u.as_ref(), &state.dbpool).await?;
+    Ok(Json(q.redact_for(u.as_ref())).into_response())
+}
+
+#[utoipa::path(
+    get, path = "/quotes/{id}/card.svg", tag = "quotes",
+    description = "An image of the quote for pasting elsewhere, readable by the same viewers as the quote itself. \
+        Text is drawn as paths, so it looks the same without the fonts installed.",
+    security((), ("cookie" = []), ("bearer" = []), ("basic" = [])),
+    params(("id" = Uuid, Path, description = "Quote id"), CardQuery),
+    responses(
+        (status = 200, description = "The card; its ETag changes only with what is shown on it", content_type = "image/svg+xml"),
+        (status = 304, description = "The card still matches the ETag in If-None-Match"),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn get_card_svg(
+    u: Option<User>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<CardQuery>,
     headers: HeaderMap,
-    cookies: Cookies,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    card(u.as_ref(), &id, query, CardFormat::Svg, &headers, &state).await
+}
+
+#[utoipa::path(
+    get, path = "/quotes/{id}/card.png", tag = "quotes",
+    description = "The same card as `card.svg`, rendered at twice the size.",
+    security((), ("cookie" = []), ("bearer" = []), ("basic" = [])),
+    params(("id" = Uuid, Path, description = "Quote id"), CardQuery),
+    responses(
+        (status = 200, description = "The card; its ETag changes only with what is shown on it", content_type = "image/png"),
+        (status = 304, description = "The card still matches the ETag in If-None-Match"),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn get_card_png(
+    u: Option<User>,
     Path(id): Path<Uuid>,
+    Query(query): Query<CardQuery>,
+    headers: HeaderMap,
     State(state): State<SharedState>,
 ) -> Result<Response, OmniError> {
-    match Quote::get_by_id(&id, &state.dbpool).await? {
-        Some(q) => {
-            if q.clearance != 0 {
-                let u = User::authenticate(&headers, cookies, &state.dbpool).await?;
-                if u.clearance < q.clearance {
-                    return Ok(StatusCode::FORBIDDEN.into_response());
-                }
-            }
-            Ok(Json(q).into_response())
+    card(u.as_ref(), &id, query, CardFormat::Png, &headers, &state).await
+}
+
+/// Rendered cards are cached by a digest of their source,
+/// so editing the quote or renaming an author renders it afresh.
+async fn card(
+    u: Option<&User>,
+    id: &Uuid,
+    query: CardQuery,
+    format: CardFormat,
+    headers: &HeaderMap,
+    state: &SharedState,
+) -> Result<Response, OmniError> {
+    let q = visible_quote(id, u, state).await?;
+    let source = q.redact_for(u).card_source(query.theme);
+    let digest = format.digest(&source);
+    let etag = format!("\"{digest}\"");
+    if headers
+        .get(IF_NONE_MATCH)
+        .is_some_and(|v| v.as_bytes() == etag.as_bytes())
+    {
+        return Ok((StatusCode::NOT_MODIFIED, [(ETAG, etag)]).into_response());
+    }
+
+    let cached = state.card_cache.read().await.get(&digest);
+    let card = match cached {
+        Some(card) => card,
+        None => {
+            let rendered = tokio::task::spawn_blocking(move || format.render(&source)).await;
+            let card = match rendered {
+                Ok(card) => Bytes::from(card?),
+                Err(e) => return Err(OmniError::CardRenderError(e.to_string())),
+            };
+            state.card_cache.write().await.insert(digest, card.clone());
+            card
+        }
+    };
+    let headers = [
+        (CONTENT_TYPE, format.content_type().to_string()),
+        (ETAG, etag),
+        (CACHE_CONTROL, "private, no-cache".to_string()),
+    ];
+    Ok((headers, card).into_response())
+}
+
+/// A quote the viewer may read, by status, groups and clearance; lines still need redacting.
+pub(super) async fn visible_quote(
+    id: &Uuid,
+    u: Option<&User>,
+    state: &SharedState,
+) -> Result<Quote, OmniError> {
+    let q = match Quote::get_by_id(id, &state.dbpool).await? {
+        Some(q) => q,
+        None => return Err(OmniError::NotFoundError("quote")),
+    };
+    if !q.status_admits(u) {
+        return Err(OmniError::NotFoundError("quote"));
+    }
+    let groups = Group::ids_of(u, &state.dbpool).await?;
+    if !q.is_shared_with(u, &groups) {
+        return Err(OmniError::NotFoundError("quote"));
+    }
+    if q.clearance != 0 {
+        let u = u.ok_or(AuthError::NoCredentials)?;
+        if u.clearance < q.clearance && !q.is_attributed_to(&u.id) {
+            return Err(AuthError::InsufficientClearance)?;
         }
-        None => Ok(StatusCode::NOT_FOUND.into_response()),
     }
+    Ok(q)
 }
 
+#[utoipa::path(
+    get, path = "/quotes/randompublic", tag = "quotes",
+    description = "Anonymous callers draw from public quotes; signed-in users from every quote they may read, \
+        with lines above their clearance redacted.",
+    security((), ("cookie" = []), ("bearer" = []), ("basic" = [])),
+    params(RandomFilter),
+    responses(
+        (status = 200, description = "A random quote matching the filter, or a placeholder if none does", body = Quote),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+    )
+)]
 async fn get_random(
-    headers: HeaderMap,
-    cookies: Cookies,
+    u: Option<User>,
+    Query(filter): Query<RandomFilter>,
     State(state): State<SharedState>,
 ) -> Result<Response, OmniError> {
-    match Quote::get_random_public(&state.dbpool).await? {
-        Some(q) => Ok(Json(q).into_response()),
+    match Quote::get_random(&filter, u.as_ref(), &state.dbpool).await? {
+        Some(mut q) => {
+            Quote::load_reactions(slice::from_mut(&mut q), u.as_ref(), &state.dbpool).await?;
+            Ok(Json(q.redact_for(u.as_ref())).into_response())
+        }
         None => Ok(Json(return_placeholder_random_public_quote()).into_response()),
     }
 }
 
+#[utoipa::path(
+    get, path = "/quotes/daily", tag = "quotes", security(()),
+    description = "The same public quote all day, where the day follows `QUOTE_OF_THE_DAY_TIMEZONE`. \
+        A quote is not repeated within `QUOTE_OF_THE_DAY_NO_REPEAT_DAYS` days while others are left. \
+        With a tag, the quote is picked among those with it, separately from the untagged pick.",
+    params(TagFilter),
+    responses(
+        (status = 200, description = "Today's quote, or a placeholder if there are no public quotes", body = QuoteOfTheDay),
+    )
+)]
+async fn get_of_the_day(
+    Query(filter): Query<TagFilter>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    let day = QuoteOfTheDay::today();
+    let tag = filter.normalized();
+    let quote = match Quote::get_of_the_day(&day, tag.as_deref(), &state.dbpool).await? {
+        Some(mut q) => {
+            Quote::load_reactions(slice::from_mut(&mut q), None, &state.dbpool).await?;
+            q.redact_for(None)
+        }
+        None => return_placeholder_random_public_quote(),
+    };
+    Ok(Json(QuoteOfTheDay { day, quote }).into_response())
+}
+
+#[utoipa::path(
+    get, path = "/quotes/daily/history", tag = "quotes", security(()),
+    params(PageQuery, TagFilter),
+    responses(
+        (status = 200, description = "Earlier quotes of the day that are still public, most recent first", body = Page<QuoteOfTheDay>),
+    )
+)]
+async fn get_daily_history(
+    Query(page): Query<PageQuery>,
+    Query(filter): Query<TagFilter>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    let tag = filter.normalized();
+    let mut history = QuoteOfTheDay::get_history(tag.as_deref(), &page, &state.dbpool).await?;
+    let (days, mut quotes): (Vec<_>, Vec<_>) =
+        history.items.into_iter().map(|d| (d.day, d.quote)).unzip();
+    Quote::load_reactions(&mut quotes, None, &state.dbpool).await?;
+    history.items = days
+        .into_iter()
+        .zip(quotes)
+        .map(|(day, q)| QuoteOfTheDay {
+            day,
+            quote: q.redact_for(None),
+        })
+        .collect();
+    Ok(Json(history).into_response())
+}
+
 // NOTE: this is resource intensive in production
 // it MUST have pagination or streaming
+#[utoipa::path(
+    get, path = "/quotes/all", tag = "quotes",
+    params(SourceFilter),
+    responses(
+        (status = 200, body = Vec<Quote>),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+    )
+)]
 async fn get_all(
-    headers: HeaderMap,
-    cookies: Cookies,
+    u: Require<TheEverythingPermission>,
+    Query(filter): Query<SourceFilter>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    let mut quotes = Quote::get_all(&filter, &u, &state.dbpool).await?;
+    Quote::load_reactions(&mut quotes, Some(&u), &state.dbpool).await?;
+    let quotes: Vec<Quote> = quotes.into_iter().map(|q| q.redact_for(Some(&u))).collect();
+    Ok(Json(quotes).into_response())
+}
+
+#[utoipa::path(
+    post, path = "/quotes", tag = "quotes",
+    request_body = Quote,
+    responses(
+        (status = 201, body = Quote),
+        (status = 400, response = crate::router::openapi::ProblemResponse),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 409, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn post_new(
+    u: Require<QuotesCreatePermission>,
     State(state): State<SharedState>,
+    Json(quote): Json<Quote>,
 ) -> Result<Response, OmniError> {
-    let u = User::authenticate(&headers, cookies, &state.dbpool).await?;
-    if !u.has_permission(UA::TheEverythingPermission) {
-        return Ok(StatusCode::FORBIDDEN.into_response());
+    match quote.status {
+        QuoteStatus::Draft | QuoteStatus::Pending => (),
+        QuoteStatus::Published => u.require_permission(UA::QuotesReviewPermission)?,
+        other => {
+            return Err(QuoteValidityError::StatusTransitionInvalid(
+                QuoteStatus::Draft,
+                other,
+            ))?
+        }
     }
+    check_content(&quote, &u, &state).await?;
 
-    Ok(Json(Quote::get_all(&state.dbpool).await?).into_response())
+    let quote = Quote::create(quote, &u, &state.dbpool).await?;
+    Ok((StatusCode::CREATED, Json(quote)).into_response())
 }
 
-const BAD_CLEARANCE: &str = "The quote must have appropriate clearance in regard to its submitter.";
-const NO_LINES: &str = "The quote must have quote lines.";
+/// Checks shared by creating quotes and editing drafts.
+async fn check_content(quote: &Quote, u: &User, state: &SharedState) -> Result<(), OmniError> {
+    quote.is_valid()?;
+    if quote.max_clearance() > u.clearance {
+        return Err(AuthError::InsufficientClearance)?;
+    }
+    let mut levels: Vec<u8> = quote.lines.iter().map(|l| l.clearance).collect();
+    levels.push(quote.clearance);
+    ClearanceLevel::ensure_defined(&levels, &state.dbpool).await?;
+    Group::ensure_exist(&quote.groups, &state.dbpool).await?;
+    if !u.has_permission(UA::UsersManageGroupsPermission) {
+        let joined = Group::ids_of(Some(u), &state.dbpool).await?;
+        if quote.groups.iter().any(|g| !joined.contains(g)) {
+            return Err(AuthError::NotGroupMember)?;
+        }
+    }
+    Ok(())
+}
 
-async fn post_new(
-    headers: HeaderMap,
-    cookies: Cookies,
+#[utoipa::path(
+    put, path = "/quotes/{id}", tag = "quotes",
+    description = "Replaces the content of a draft; only its submitter may. \
+        The status in the body is ignored, submit the draft separately.",
+    params(("id" = Uuid, Path, description = "Quote id")),
+    request_body = Quote,
+    responses(
+        (status = 200, body = Quote),
+        (status = 400, response = crate::router::openapi::ProblemResponse),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn put_draft(
+    u: Require<QuotesCreatePermission>,
+    Path(id): Path<Uuid>,
     State(state): State<SharedState>,
     Json(quote): Json<Quote>,
 ) -> Result<Response, OmniError> {
-    let u = User::authenticate(&headers, cookies, &state.dbpool).await?;
-    if !u.has_permission(UA::QuotesCreatePermission) {
-        return Ok(StatusCode::FORBIDDEN.into_response());
+    let draft = submitted_quote(&id, &u, &state).await?;
+    check_content(&quote, &u, &state).await?;
+    let quote = draft.replace_draft(quote, &state.dbpool).await?;
+    Ok(Json(quote).into_response())
+}
+
+/// A quote the caller submitted; others are told it doesn't exist when they can't see it.
+async fn submitted_quote(id: &Uuid, u: &User, state: &SharedState) -> Result<Quote, OmniError> {
+    match Quote::get_by_id(id, &state.dbpool).await? {
+        Some(q) if q.submitted_by == Some(u.id) => Ok(q),
+        Some(q) if q.status_admits(Some(u)) => Err(AuthError::NotSubmitter)?,
+        _ => Err(OmniError::NotFoundError("quote")),
     }
+}
 
-    if quote.lines.is_empty() {
-        return Ok((StatusCode::BAD_REQUEST, NO_LINES).into_response());
+/// A quote the reviewer has the clearance and groups for.
+async fn reviewable_quote(id: &Uuid, u: &User, state: &SharedState) -> Result<Quote, OmniError> {
+    let q = match Quote::get_by_id(id, &state.dbpool).await? {
+        Some(q) => q,
+        None => return Err(OmniError::NotFoundError("quote")),
+    };
+    let groups = Group::ids_of(Some(u), &state.dbpool).await?;
+    if !q.is_shared_with(Some(u), &groups) {
+        return Err(OmniError::NotFoundError("quote"));
     }
-    if quote.clearance > u.clearance {
-        return Ok((StatusCode::FORBIDDEN, BAD_CLEARANCE).into_response());
+    if q.clearance > u.clearance && !q.is_attributed_to(&u.id) {
+        return Err(AuthError::InsufficientClearance)?;
     }
+    Ok(q)
+}
 
-    let quote = Quote::create(quote, &state.dbpool).await?;
-    Ok((StatusCode::CREATED, Json(quote)).into_response())
+#[utoipa::path(
+    post, path = "/quotes/{id}/submit", tag = "quotes",
+    description = "Sends the caller's draft to the moderation queue.",
+    params(("id" = Uuid, Path, description = "Quote id")),
+    responses(
+        (status = 200, body = Quote),
+        (status = 400, response = crate::router::openapi::ProblemResponse),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn submit(
+    u: User,
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    let q = submitted_quote(&id, &u, &state).await?;
+    let q = q
+        .transition(QuoteStatus::Pending, None, &u, &state.dbpool)
+        .await?;
+    Ok(Json(q.redact_for(Some(&u))).into_response())
+}
+
+#[utoipa::path(
+    get, path = "/quotes/review", tag = "quotes",
+    responses(
+        (status = 200, description = "Pending quotes within the caller's clearance and groups, \
+            oldest first", body = Vec<Quote>),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn get_review_queue(
+    u: Require<QuotesReviewPermission>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    let quotes: Vec<Quote> = Quote::get_pending(&u, &state.dbpool)
+        .await?
+        .into_iter()
+        .map(|q| q.redact_for(Some(&u)))
+        .collect();
+    Ok(Json(quotes).into_response())
+}
+
+#[utoipa::path(
+    post, path = "/quotes/{id}/approve", tag = "quotes",
+    description = "Publishes a pending quote, or brings back an archived one.",
+    params(("id" = Uuid, Path, description = "Quote id")),
+    responses(
+        (status = 200, body = Quote),
+        (status = 400, response = crate::router::openapi::ProblemResponse),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn approve(
+    u: Require<QuotesReviewPermission>,
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    let q = reviewable_quote(&id, &u, &state).await?;
+    let q = q
+        .transition(QuoteStatus::Published, None, &u, &state.dbpool)
+        .await?;
+    Ok(Json(q.redact_for(Some(&u))).into_response())
+}
+
+#[utoipa::path(
+    post, path = "/quotes/{id}/reject", tag = "quotes",
+    params(("id" = Uuid, Path, description = "Quote id")),
+    request_body = ReviewReason,
+    responses(
+        (status = 200, body = Quote),
+        (status = 400, response = crate::router::openapi::ProblemResponse),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn reject(
+    u: Require<QuotesReviewPermission>,
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+    Json(body): Json<ReviewReason>,
+) -> Result<Response, OmniError> {
+    let q = reviewable_quote(&id, &u, &state).await?;
+    let q = q
+        .transition(QuoteStatus::Rejected, Some(body.reason), &u, &state.dbpool)
+        .await?;
+    Ok(Json(q.redact_for(Some(&u))).into_response())
 }
 
+#[utoipa::path(
+    post, path = "/quotes/{id}/request-changes", tag = "quotes",
+    description = "Sends a pending quote back to its submitter as a draft.",
+    params(("id" = Uuid, Path, description = "Quote id")),
+    request_body = ReviewReason,
+    responses(
+        (status = 200, body = Quote),
+        (status = 400, response = crate::router::openapi::ProblemResponse),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn request_changes(
+    u: Require<QuotesReviewPermission>,
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+    Json(body): Json<ReviewReason>,
+) -> Result<Response, OmniError> {
+    let q = reviewable_quote(&id, &u, &state).await?;
+    let q = q
+        .transition(QuoteStatus::Draft, Some(body.reason), &u, &state.dbpool)
+        .await?;
+    Ok(Json(q.redact_for(Some(&u))).into_response())
+}
+
+#[utoipa::path(
+    post, path = "/quotes/{id}/archive", tag = "quotes",
+    description = "Takes a published quote out of listings.",
+    params(("id" = Uuid, Path, description = "Quote id")),
+    responses(
+        (status = 200, body = Quote),
+        (status = 400, response = crate::router::openapi::ProblemResponse),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn archive(
+    u: Require<QuotesReviewPermission>,
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    let q = reviewable_quote(&id, &u, &state).await?;
+    let q = q
+        .transition(QuoteStatus::Archived, None, &u, &state.dbpool)
+        .await?;
+    Ok(Json(q.redact_for(Some(&u))).into_response())
+}
+
+#[utoipa::path(
+    delete, path = "/quotes/{id}", tag = "quotes",
+    description = "Moves the quote to the trash, from where it can be restored until it is purged.",
+    params(("id" = Uuid, Path, description = "Quote id")),
+    responses(
+        (status = 204, description = "Quote moved to the trash"),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
 async fn delete(
-    headers: HeaderMap,
-    cookies: Cookies,
+    u: Require<QuotesDeletePermission>,
     Path(id): Path<Uuid>,
     State(state): State<SharedState>,
 ) -> Result<Response, OmniError> {
-    let u = User::authenticate(&headers, cookies, &state.dbpool).await?;
-    if !u.has_permission(UA::QuotesDeletePermission) {
-        return Ok(StatusCode::FORBIDDEN.into_response());
+    let q = match Quote::get_by_id(&id, &state.dbpool).await? {
+        Some(q) => q,
+        None => return Err(OmniError::NotFoundError("quote")),
+    };
+    let groups = Group::ids_of(Some(&u), &state.dbpool).await?;
+    if !q.is_shared_with(Some(&u), &groups) {
+        return Err(OmniError::NotFoundError("quote"));
+    }
+    if q.clearance >= u.clearance {
+        return Err(AuthError::InsufficientClearance)?;
     }
 
-    let q = match Quote::get_by_id(&id, &state.dbpool).await? {
+    q.trash(&u.id, &state.dbpool).await?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+/// A trashed quote the user may act on, by groups and clearance.
+async fn trashed_quote(id: &Uuid, u: &User, state: &SharedState) -> Result<Quote, OmniError> {
+    let q = match Quote::get_trashed_by_id(id, &state.dbpool).await? {
         Some(q) => q,
-        None => return Ok(StatusCode::NOT_FOUND.into_response()),
+        None => return Err(OmniError::NotFoundError("quote")),
     };
+    let groups = Group::ids_of(Some(u), &state.dbpool).await?;
+    if !q.is_shared_with(Some(u), &groups) {
+        return Err(OmniError::NotFoundError("quote"));
+    }
     if q.clearance >= u.clearance {
-        return Ok((StatusCode::FORBIDDEN, BAD_CLEARANCE).into_response());
+        return Err(AuthError::InsufficientClearance)?;
     }
+    Ok(q)
+}
+
+#[utoipa::path(
+    get, path = "/quotes/trash", tag = "quotes",
+    description = "Trashed quotes, most recently deleted first.",
+    params(PageQuery),
+    responses(
+        (status = 200, body = Page<Quote>),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn get_trash(
+    u: Require<QuotesDeletePermission>,
+    Query(page): Query<PageQuery>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    let mut page = Quote::get_trash_page(&u, &page, &state.dbpool).await?;
+    page.items = page
+        .items
+        .into_iter()
+        .map(|q| q.redact_for(Some(&u)))
+        .collect();
+    Ok(Json(page).into_response())
+}
+
+#[utoipa::path(
+    post, path = "/quotes/trash/{id}/restore", tag = "quotes",
+    params(("id" = Uuid, Path, description = "Quote id")),
+    responses(
+        (status = 200, body = Quote),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn restore(
+    u: Require<QuotesDeletePermission>,
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    let q = trashed_quote(&id, &u, &state).await?;
+    let q = q.restore(&u.id, &state.dbpool).await?;
+    Ok(Json(q.redact_for(Some(&u))).into_response())
+}
 
-    q.delete(&state.dbpool).await?;
+#[utoipa::path(
+    delete, path = "/quotes/trash/{id}", tag = "quotes",
+    description = "Permanently deletes a trashed quote.",
+    params(("id" = Uuid, Path, description = "Quote id")),
+    responses(
+        (status = 204, description = "Quote purged"),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn purge(
+    u: Require<QuotesPurgePermission>,
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    let q = trashed_quote(&id, &u, &state).await?;
+    q.purge(&u.id, &state.dbpool).await?;
     Ok(StatusCode::NO_CONTENT.into_response())
 }
+
+#[utoipa::path(
+    get, path = "/quotes/top/{period}", tag = "quotes",
+    description = "Published quotes ranked by the score of votes cast in the period; \
+        only quotes with a positive score are listed.",
+    security((), ("cookie" = []), ("bearer" = []), ("basic" = [])),
+    params(("period" = TopPeriod, Path), PageQuery),
+    responses(
+        (status = 200, body = Page<Quote>),
+        (status = 400, response = crate::router::openapi::ProblemResponse),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn get_top(
+    u: Option<User>,
+    Path(period): Path<TopPeriod>,
+    Query(page): Query<PageQuery>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    let mut page = Quote::get_top(period, u.as_ref(), &page, &state.dbpool).await?;
+    Quote::load_reactions(&mut page.items, u.as_ref(), &state.dbpool).await?;
+    page.items = page
+        .items
+        .into_iter()
+        .map(|q| q.redact_for(u.as_ref()))
+        .collect();
+    Ok(Json(page).into_response())
+}
+
+/// A published quote the user may read, to react to or vote on.
+async fn reactable_quote(id: &Uuid, u: &User, state: &SharedState) -> Result<Quote, OmniError> {
+    let q = visible_quote(id, Some(u), state).await?;
+    if q.status != QuoteStatus::Published {
+        return Err(QuoteValidityError::NotPublished)?;
+    }
+    Ok(q)
+}
+
+/// Responds with the quote's reactions after the caller's change.
+async fn reactions_of(mut q: Quote, u: &User, state: &SharedState) -> Result<Response, OmniError> {
+    Quote::load_reactions(slice::from_mut(&mut q), Some(u), &state.dbpool).await?;
+    Ok(Json(q.reactions).into_response())
+}
+
+#[utoipa::path(
+    put, path = "/quotes/{id}/reaction", tag = "quotes",
+    description = "Sets the caller's emoji reaction, replacing any previous one.",
+    params(("id" = Uuid, Path, description = "Quote id")),
+    request_body = NewReaction,
+    responses(
+        (status = 200, body = QuoteReactions),
+        (status = 400, response = crate::router::openapi::ProblemResponse),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn put_reaction(
+    u: User,
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+    Json(body): Json<NewReaction>,
+) -> Result<Response, OmniError> {
+    let q = reactable_quote(&id, &u, &state).await?;
+    q.set_reaction(&u.id, Some(body.reaction), &state.dbpool)
+        .await?;
+    reactions_of(q, &u, &state).await
+}
+
+#[utoipa::path(
+    delete, path = "/quotes/{id}/reaction", tag = "quotes",
+    params(("id" = Uuid, Path, description = "Quote id")),
+    responses(
+        (status = 200, body = QuoteReactions),
+        (status = 400, response = crate::router::openapi::ProblemResponse),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn delete_reaction(
+    u: User,
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    let q = reactable_quote(&id, &u, &state).await?;
+    q.set_reaction(&u.id, None, &state.dbpool).await?;
+    reactions_of(q, &u, &state).await
+}
+
+#[utoipa::path(
+    put, path = "/quotes/{id}/vote", tag = "quotes",
+    description = "Sets the caller's vote, replacing any previous one.",
+    params(("id" = Uuid, Path, description = "Quote id")),
+    request_body = NewVote,
+    responses(
+        (status = 200, body = QuoteReactions),
+        (status = 400, response = crate::router::openapi::ProblemResponse),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn put_vote(
+    u: User,
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+    Json(body): Json<NewVote>,
+) -> Result<Response, OmniError> {
+    let q = reactable_quote(&id, &u, &state).await?;
+    q.set_vote(&u.id, Some(body.vote), &state.dbpool).await?;
+    reactions_of(q, &u, &state).await
+}
+
+#[utoipa::path(
+    delete, path = "/quotes/{id}/vote", tag = "quotes",
+    params(("id" = Uuid, Path, description = "Quote id")),
+    responses(
+        (status = 200, body = QuoteReactions),
+        (status = 400, response = crate::router::openapi::ProblemResponse),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn delete_vote(
+    u: User,
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    let q = reactable_quote(&id, &u, &state).await?;
+    q.set_vote(&u.id, None, &state.dbpool).await?;
+    reactions_of(q, &u, &state).await
+}
+
+#[utoipa::path(
+    put, path = "/quotes/{id}/groups", tag = "quotes",
+    description = "Replaces the groups a quote is restricted to; an empty list lifts the restriction.",
+    params(("id" = Uuid, Path, description = "Quote id")),
+    request_body = QuoteGroups,
+    responses(
+        (status = 200, body = Quote),
+        (status = 400, response = crate::router::openapi::ProblemResponse),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn put_groups(
+    u: Require<UsersManageGroupsPermission>,
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+    Json(body): Json<QuoteGroups>,
+) -> Result<Response, OmniError> {
+    let q = match Quote::get_by_id(&id, &state.dbpool).await? {
+        Some(q) => q,
+        None => return Err(OmniError::NotFoundError("quote")),
+    };
+    if q.clearance > u.clearance && !q.is_attributed_to(&u.id) {
+        return Err(AuthError::InsufficientClearance)?;
+    }
+    let q = q.set_groups(body.groups, &u.id, &state.dbpool).await?;
+    Ok(Json(q.redact_for(Some(&u))).into_response())
+}
+
+#[utoipa::path(
+    put, path = "/quotes/{id}/tags", tag = "quotes",
+    description = "Replaces the tags of a quote the reviewer can read; an empty list removes them.",
+    params(("id" = Uuid, Path, description = "Quote id")),
+    request_body = QuoteTags,
+    responses(
+        (status = 200, body = Quote),
+        (status = 400, response = crate::router::openapi::ProblemResponse),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn put_tags(
+    u: Require<QuotesReviewPermission>,
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+    Json(body): Json<QuoteTags>,
+) -> Result<Response, OmniError> {
+    let q = visible_quote(&id, Some(&u), &state).await?;
+    let q = q.set_tags(body.tags, &u.id, &state.dbpool).await?;
+    Ok(Json(q.redact_for(Some(&u))).into_response())
+}
+
+#[utoipa::path(
+    post, path = "/quotes/{id}/flags", tag = "quotes",
+    description = "Lets a user linked to one of the quote's authors ask for review or hiding.",
+    params(("id" = Uuid, Path, description = "Quote id")),
+    request_body = NewQuoteFlag,
+    responses(
+        (status = 201, body = QuoteFlag),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+        (status = 409, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn post_flag(
+    u: User,
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+    Json(flag): Json<NewQuoteFlag>,
+) -> Result<Response, OmniError> {
+    match Quote::get_by_id(&id, &state.dbpool).await? {
+        Some(q) => {
+            let flag = QuoteFlag::create(&q, flag, &u, &state.dbpool).await?;
+            Ok((StatusCode::CREATED, Json(flag)).into_response())
+        }
+        None => Err(OmniError::NotFoundError("quote")),
+    }
+}
+
+#[utoipa::path(
+    get, path = "/quotes/flags", tag = "quotes",
+    responses(
+        (status = 200, description = "Open flags, oldest first", body = Vec<QuoteFlag>),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn get_flags(
+    _: Require<QuotesReviewPermission>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    Ok(Json(QuoteFlag::get_open(&state.dbpool).await?).into_response())
+}
+
+#[utoipa::path(
+    post, path = "/quotes/flags/{id}/resolve", tag = "quotes",
+    params(("id" = Uuid, Path, description = "Flag id")),
+    request_body = FlagResolution,
+    responses(
+        (status = 200, body = QuoteFlag),
+        (status = 400, response = crate::router::openapi::ProblemResponse),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn resolve_flag(
+    u: Require<QuotesReviewPermission>,
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+    Json(resolution): Json<FlagResolution>,
+) -> Result<Response, OmniError> {
+    match QuoteFlag::get_by_id(&id, &state.dbpool).await? {
+        Some(flag) => Ok(Json(flag.resolve(resolution, &u, &state.dbpool).await?).into_response()),
+        None => Err(OmniError::NotFoundError("flag")),
+    }
+}
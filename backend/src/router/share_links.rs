@@ -0,0 +1,228 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{delete, get},
+    Json, Router,
+};
+use std::slice;
+use utoipa::OpenApi;
+use uuid::Uuid;
+
+use crate::{
+    omnierror::OmniError,
+    quotes::{
+        moderation::QuoteStatus,
+        share_links::{CreatedShareLink, NewShareLink, ShareLink},
+        validity::QuoteValidityError,
+        Quote,
+    },
+    state::SharedState,
+    user::{auth::error::AuthError, User},
+};
+
+use super::quotes::visible_quote;
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(get_all, post_new, revoke, by_link_handler),
+    components(schemas(ShareLink, CreatedShareLink, NewShareLink))
+)]
+pub struct ShareLinksApi;
+
+pub fn routes() -> Router<SharedState> {
+    Router::new()
+        .route("/quotes/{id}/share-links", get(get_all).post(post_new))
+        .route("/quotes/share-links/{id}", delete(revoke))
+        .route("/quotes/link/{token}", get(by_link_handler))
+}
+
+#[utoipa::path(
+    get, path = "/quotes/{id}/share-links", tag = "share links",
+    description = "Links the caller created on the quote, or all of them for the user who submitted it, \
+        with how often each was opened.",
+    params(("id" = Uuid, Path, description = "Quote id")),
+    responses(
+        (status = 200, body = Vec<ShareLink>),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn get_all(
+    u: User,
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    let q = visible_quote(&id, Some(&u), &state).await?;
+    let links = ShareLink::get_for_quote(&q, &u, &state.dbpool).await?;
+    Ok(Json(links).into_response())
+}
+
+#[utoipa::path(
+    post, path = "/quotes/{id}/share-links", tag = "share links",
+    description = "Anyone cleared for a published quote may share it. \
+        The token is only ever returned here; only its hash is stored.",
+    params(("id" = Uuid, Path, description = "Quote id")),
+    request_body = NewShareLink,
+    responses(
+        (status = 201, body = CreatedShareLink),
+        (status = 400, response = crate::router::openapi::ProblemResponse),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn post_new(
+    u: User,
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+    Json(new): Json<NewShareLink>,
+) -> Result<Response, OmniError> {
+    let q = visible_quote(&id, Some(&u), &state).await?;
+    if q.status != QuoteStatus::Published {
+        return Err(QuoteValidityError::NotPublished)?;
+    }
+    // linked authors can read their own quotes at any clearance, but only those cleared
+    // for the quote may open it to everyone
+    if u.clearance < q.clearance {
+        return Err(AuthError::InsufficientClearance)?;
+    }
+    let created = ShareLink::create(&q, new, &u, &state.dbpool).await?;
+    Ok((StatusCode::CREATED, Json(created)).into_response())
+}
+
+#[utoipa::path(
+    delete, path = "/quotes/share-links/{id}", tag = "share links",
+    description = "Stops the link from working; it stays listed with its views.",
+    params(("id" = Uuid, Path, description = "Share link id")),
+    responses(
+        (status = 200, body = ShareLink),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn revoke(
+    u: User,
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    let link = match ShareLink::get_by_id(&id, &state.dbpool).await? {
+        Some(link) => link,
+        None => return Err(OmniError::NotFoundError("share link")),
+    };
+    let q = visible_quote(&link.quote_id, Some(&u), &state).await?;
+    if !link.is_managed_by(&u, &q) {
+        return Err(AuthError::NotShareLinkManager)?;
+    }
+    Ok(Json(link.revoke(&state.dbpool).await?).into_response())
+}
+
+#[utoipa::path(
+    get, path = "/quotes/link/{token}", tag = "share links", security(()),
+    description = "Opens a shared quote without logging in, with lines above the quote's own clearance redacted. \
+        Revoked and expired links, and quotes no longer published, are not found.",
+    params(("token" = String, Path, description = "Link token")),
+    responses(
+        (status = 200, body = Quote),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn by_link_handler(
+    Path(token): Path<String>,
+    State(state): State<SharedState>,
+) -> Result<Response, OmniError> {
+    let quote = match ShareLink::open(&token, &state.dbpool).await? {
+        Some(id) => Quote::get_by_id(&id, &state.dbpool).await?,
+        None => None,
+    };
+    match quote {
+        Some(mut q) => {
+            Quote::load_reactions(slice::from_mut(&mut q), None, &state.dbpool).await?;
+            Ok(Json(q.redact_for_link()).into_response())
+        }
+        None => Err(OmniError::NotFoundError("quote")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use sqlx::PgPool;
+
+    use crate::testing::{self, send};
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn only_users_cleared_for_the_quote_may_share_it(pool: PgPool) {
+        let app = testing::app(&pool);
+        let admin = testing::user("admin", 1, &[], &pool).await;
+        let quoted = testing::user("quoted", 0, &[], &pool).await;
+        let author = testing::author("jk", &pool).await;
+        testing::link(&author, &quoted, &pool).await;
+        let quote = testing::save(testing::quote(&[(&author, "psst")], 1), &admin, &pool).await;
+        let uri = format!("/quotes/{}/share-links", quote.id);
+
+        let quoted_auth = testing::bearer(&quoted, &pool).await;
+        let (status, _) = send(
+            &app,
+            "GET",
+            &format!("/quotes/{}", quote.id),
+            Some(&quoted_auth),
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, body) = send(&app, "POST", &uri, Some(&quoted_auth), Some(json!({}))).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["code"], "insufficient_clearance");
+
+        let admin_auth = testing::bearer(&admin, &pool).await;
+        let expired = json!({ "expiry": "2000-01-01T00:00:00Z" });
+        let (status, body) = send(&app, "POST", &uri, Some(&admin_auth), Some(expired)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "share_link_expiry_in_past");
+        let (status, _) = send(&app, "POST", &uri, Some(&admin_auth), Some(json!({}))).await;
+        assert_eq!(status, StatusCode::CREATED);
+    }
+
+    #[sqlx::test]
+    async fn links_open_the_quote_until_revoked(pool: PgPool) {
+        let app = testing::app(&pool);
+        let admin = testing::user("admin", 1, &[], &pool).await;
+        let other = testing::user("other", 1, &[], &pool).await;
+        let author = testing::author("jk", &pool).await;
+        let mut quote = testing::quote(&[(&author, "hi"), (&author, "psst")], 0);
+        quote.lines[1].clearance = 1;
+        let quote = testing::save(quote, &admin, &pool).await;
+        let admin_auth = testing::bearer(&admin, &pool).await;
+        let other_auth = testing::bearer(&other, &pool).await;
+
+        let uri = format!("/quotes/{}/share-links", quote.id);
+        let (_, created) = send(&app, "POST", &uri, Some(&other_auth), Some(json!({}))).await;
+        let link = format!("/v1/quotes/link/{}", created["token"].as_str().unwrap());
+        for _ in 0..2 {
+            let (status, body) = send(&app, "GET", &link, None, None).await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(body["lines"][0]["content"], "hi");
+            assert_eq!(body["lines"][1]["redacted"], true);
+        }
+
+        // the submitter manages every link on the quote
+        let (_, links) = send(&app, "GET", &uri, Some(&admin_auth), None).await;
+        assert_eq!(links[0]["views"], 2);
+        let revoke = format!("/quotes/share-links/{}", created["id"].as_str().unwrap());
+        let stranger = testing::user("stranger", 1, &[], &pool).await;
+        let stranger_auth = testing::bearer(&stranger, &pool).await;
+        let (status, _) = send(&app, "DELETE", &revoke, Some(&stranger_auth), None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, body) = send(&app, "DELETE", &revoke, Some(&admin_auth), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body["revoked"].is_string());
+
+        let (status, _) = send(&app, "GET", &link, None, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
@@ -1,21 +1,50 @@
 use axum::{
     extract::{Path, State},
-    http::{HeaderMap, StatusCode},
+    http::StatusCode,
     response::{IntoResponse, Response},
     routing::{get, patch, post},
     Json, Router,
 };
 use serde::Deserialize;
 use strum::VariantArray;
-use tower_cookies::Cookies;
+use utoipa::{OpenApi, ToSchema};
 use uuid::Uuid;
 
 use crate::{
     omnierror::OmniError,
+    quotes::{authors::Author, Quote},
     state::SharedState,
-    user::{attributes::UserAttribute as UA, patch::UserPatch, User},
+    user::{
+        attributes::UserAttribute as UA,
+        auth::{
+            error::AuthError,
+            guard::{
+                Require, UsersDeletePermission, UsersInspectPermission, UsersManualCreatePermission,
+            },
+        },
+        patch::UserPatch,
+        User,
+    },
 };
 
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        create_user_manually,
+        get_user_by_id,
+        get_me,
+        get_my_author,
+        get_my_quotes,
+        get_my_submissions,
+        patch_user,
+        delete_user,
+        change_password,
+        all_user_attributes
+    ),
+    components(schemas(ManualUserCreation, ChangePassword, UserPatch, UA))
+)]
+pub struct UsersApi;
+
 pub fn routes() -> Router<SharedState> {
     Router::new()
         .route("/users", post(create_user_manually))
@@ -24,26 +53,34 @@ pub fn routes() -> Router<SharedState> {
             get(get_user_by_id).patch(patch_user).delete(delete_user),
         )
         .route("/users/me", get(get_me))
+        .route("/users/me/author", get(get_my_author))
+        .route("/users/me/quotes", get(get_my_quotes))
+        .route("/users/me/submissions", get(get_my_submissions))
         .route("/users/{id}/change-password", patch(change_password))
         .route("/users/user-attributes", get(all_user_attributes))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct ManualUserCreation {
     handle: String,
     password: String,
 }
+#[utoipa::path(
+    post, path = "/users", tag = "users",
+    request_body = ManualUserCreation,
+    responses(
+        (status = 201, body = User),
+        (status = 400, response = crate::router::openapi::ProblemResponse),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 409, response = crate::router::openapi::ProblemResponse),
+    )
+)]
 async fn create_user_manually(
-    headers: HeaderMap,
-    cookies: Cookies,
+    _: Require<UsersManualCreatePermission>,
     State(state): State<SharedState>,
     Json(user): Json<ManualUserCreation>,
 ) -> Result<Response, OmniError> {
-    let u = User::authenticate(&headers, cookies, &state.dbpool).await?;
-    if !u.has_permission(UA::UsersManualCreatePermission) {
-        return Ok(StatusCode::FORBIDDEN.into_response());
-    }
-
     if let Err(e) = User::is_valid_handle(&user.handle) {
         return Err(e)?;
     }
@@ -60,64 +97,129 @@ async fn create_user_manually(
     Ok((StatusCode::CREATED, Json(nu)).into_response())
 }
 
+#[utoipa::path(
+    get, path = "/users/{id}", tag = "users",
+    params(("id" = Uuid, Path, description = "User id")),
+    responses(
+        (status = 200, body = User),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
 async fn get_user_by_id(
-    headers: HeaderMap,
-    cookies: Cookies,
+    _: Require<UsersInspectPermission>,
     Path(id): Path<Uuid>,
     State(state): State<SharedState>,
 ) -> Result<Response, OmniError> {
-    let u = User::authenticate(&headers, cookies, &state.dbpool).await?;
-    if !u.has_permission(UA::UsersInspectPermission) {
-        return Ok(StatusCode::FORBIDDEN.into_response());
-    }
-
     match User::get_by_id(&id, &state.dbpool).await? {
         Some(user) => Ok(Json(user).into_response()),
-        None => Ok((StatusCode::BAD_REQUEST, "No such user found.").into_response()),
+        None => Err(OmniError::NotFoundError("user")),
+    }
+}
+
+#[utoipa::path(
+    get, path = "/users/me", tag = "users",
+    responses(
+        (status = 200, description = "The authenticated user", body = User),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn get_me(u: User) -> Result<Response, OmniError> {
+    Ok(Json(u).into_response())
+}
+
+#[utoipa::path(
+    get, path = "/users/me/author", tag = "users",
+    responses(
+        (status = 200, description = "The author linked to the authenticated user", body = Author),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn get_my_author(u: User, State(state): State<SharedState>) -> Result<Response, OmniError> {
+    match Author::get_linked_to(&u.id, &state.dbpool).await? {
+        Some(author) => Ok(Json(author).into_response()),
+        None => Err(OmniError::NotFoundError("linked author")),
     }
 }
 
-async fn get_me(
-    headers: HeaderMap,
-    cookies: Cookies,
+#[utoipa::path(
+    get, path = "/users/me/quotes", tag = "users",
+    description = "Every quote attributed to the user's linked author, whatever its clearance.",
+    responses(
+        (status = 200, body = Vec<Quote>),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn get_my_quotes(u: User, State(state): State<SharedState>) -> Result<Response, OmniError> {
+    let mut quotes = Quote::get_attributed_to(&u.id, &state.dbpool).await?;
+    Quote::load_reactions(&mut quotes, Some(&u), &state.dbpool).await?;
+    let quotes: Vec<Quote> = quotes.into_iter().map(|q| q.redact_for(Some(&u))).collect();
+    Ok(Json(quotes).into_response())
+}
+
+#[utoipa::path(
+    get, path = "/users/me/submissions", tag = "users",
+    description = "Every quote the user submitted, in any status, with reviewers' notes.",
+    responses(
+        (status = 200, body = Vec<Quote>),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+    )
+)]
+async fn get_my_submissions(
+    u: User,
     State(state): State<SharedState>,
 ) -> Result<Response, OmniError> {
-    let u = User::authenticate(&headers, cookies, &state.dbpool).await?;
-    Ok(Json(u).into_response())
+    let quotes: Vec<Quote> = Quote::get_submitted_by(&u.id, &state.dbpool)
+        .await?
+        .into_iter()
+        .map(|q| q.redact_for(Some(&u)))
+        .collect();
+    Ok(Json(quotes).into_response())
 }
 
+#[utoipa::path(
+    patch, path = "/users/{id}", tag = "users",
+    params(("id" = Uuid, Path, description = "User id")),
+    request_body = UserPatch,
+    responses(
+        (status = 200, body = User),
+        (status = 400, response = crate::router::openapi::ProblemResponse),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+        (status = 409, response = crate::router::openapi::ProblemResponse),
+    )
+)]
 async fn patch_user(
-    headers: HeaderMap,
-    cookies: Cookies,
+    actor: User,
     Path(id): Path<Uuid>,
     State(state): State<SharedState>,
     Json(patch): Json<UserPatch>,
 ) -> Result<Response, OmniError> {
-    let actor = User::authenticate(&headers, cookies, &state.dbpool).await?;
     let target = match User::get_by_id(&id, &state.dbpool).await? {
         Some(u) => u,
-        None => return Ok((StatusCode::BAD_REQUEST, "No such user found.").into_response()),
+        None => return Err(OmniError::NotFoundError("user")),
     };
 
     if actor.id == target.id {
-        if patch.handle.is_some() && !actor.has_permission(UA::UsersChangeOwnHandlePermission) {
-            return Ok(StatusCode::FORBIDDEN.into_response());
+        if patch.handle.is_some() {
+            actor.require_permission(UA::UsersChangeOwnHandlePermission)?;
         }
     } else {
         if actor.clearance <= target.clearance {
-            return Ok(StatusCode::FORBIDDEN.into_response());
+            return Err(AuthError::InsufficientClearance)?;
         }
-        if patch.handle.is_some() && !actor.has_permission(UA::UsersManageHandlesPermission) {
-            return Ok(StatusCode::FORBIDDEN.into_response());
+        if patch.handle.is_some() {
+            actor.require_permission(UA::UsersManageHandlesPermission)?;
         }
     }
 
     if let Some(clearance) = patch.clearance {
-        if !actor.has_permission(UA::UsersManageClearancesPermission) {
-            return Ok(StatusCode::FORBIDDEN.into_response());
-        }
+        actor.require_permission(UA::UsersManageClearancesPermission)?;
         if clearance > actor.clearance && !actor.has_permission(UA::TheEverythingPermission) {
-            return Ok(StatusCode::FORBIDDEN.into_response());
+            return Err(AuthError::InsufficientClearance)?;
         }
     }
 
@@ -130,62 +232,69 @@ async fn patch_user(
     Ok(Json(target.patch(patch, &state.dbpool).await?).into_response())
 }
 
-const DELCLEAR: &str = "Cannot delete a user with higher clearance.";
-const DELADMIN: &str = "Cannot delete the infrastructure administrator.";
-
+#[utoipa::path(
+    delete, path = "/users/{id}", tag = "users",
+    params(("id" = Uuid, Path, description = "User id")),
+    responses(
+        (status = 204, description = "User deleted"),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
 async fn delete_user(
-    headers: HeaderMap,
-    cookies: Cookies,
+    u: Require<UsersDeletePermission>,
     Path(id): Path<Uuid>,
     State(state): State<SharedState>,
 ) -> Result<Response, OmniError> {
-    let u = User::authenticate(&headers, cookies, &state.dbpool).await?;
-    if !u.has_permission(UA::UsersDeletePermission) {
-        return Ok(StatusCode::FORBIDDEN.into_response());
-    }
-
     match User::get_by_id(&id, &state.dbpool).await? {
         Some(target) => {
             if target.is_infradmin() {
-                return Ok((StatusCode::FORBIDDEN, DELADMIN).into_response());
+                return Err(AuthError::CannotDeleteInfradmin)?;
             }
             if target.clearance >= u.clearance {
-                return Ok((StatusCode::FORBIDDEN, DELCLEAR).into_response());
+                return Err(AuthError::InsufficientClearance)?;
             }
             target.destroy(&state.dbpool).await?;
             Ok(StatusCode::NO_CONTENT.into_response())
         }
-        None => Ok((StatusCode::BAD_REQUEST, "No such user found.").into_response()),
+        None => Err(OmniError::NotFoundError("user")),
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct ChangePassword {
     password: String,
 }
+#[utoipa::path(
+    patch, path = "/users/{id}/change-password", tag = "users",
+    params(("id" = Uuid, Path, description = "User id")),
+    request_body = ChangePassword,
+    responses(
+        (status = 200, description = "Password updated", body = String),
+        (status = 400, response = crate::router::openapi::ProblemResponse),
+        (status = 401, response = crate::router::openapi::ProblemResponse),
+        (status = 403, response = crate::router::openapi::ProblemResponse),
+        (status = 404, response = crate::router::openapi::ProblemResponse),
+    )
+)]
 async fn change_password(
-    headers: HeaderMap,
-    cookies: Cookies,
+    actor: User,
     Path(id): Path<Uuid>,
     State(state): State<SharedState>,
     Json(pass): Json<ChangePassword>,
 ) -> Result<Response, OmniError> {
-    let actor = User::authenticate(&headers, cookies, &state.dbpool).await?;
     let target = match User::get_by_id(&id, &state.dbpool).await? {
         Some(u) => u,
-        None => return Ok((StatusCode::BAD_REQUEST, "No such user found.").into_response()),
+        None => return Err(OmniError::NotFoundError("user")),
     };
 
     if target.id == actor.id {
-        if !actor.has_permission(UA::UsersChangeOwnPasswordPermission) {
-            return Ok(StatusCode::FORBIDDEN.into_response());
-        }
+        actor.require_permission(UA::UsersChangeOwnPasswordPermission)?;
     } else {
-        if !actor.has_permission(UA::UsersManagePasswordsPermission) {
-            return Ok(StatusCode::FORBIDDEN.into_response());
-        }
+        actor.require_permission(UA::UsersManagePasswordsPermission)?;
         if actor.clearance <= target.clearance {
-            return Ok(StatusCode::FORBIDDEN.into_response());
+            return Err(AuthError::InsufficientClearance)?;
         }
     }
 
@@ -197,6 +306,12 @@ async fn change_password(
     Ok((StatusCode::OK, "Password updated.").into_response())
 }
 
+#[utoipa::path(
+    get, path = "/users/user-attributes", tag = "users", security(()),
+    responses(
+        (status = 200, body = Vec<UA>),
+    )
+)]
 async fn all_user_attributes() -> Response {
     Json(UA::VARIANTS).into_response()
 }
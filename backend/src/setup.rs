@@ -100,15 +100,11 @@ pub mod servertest {
             let cl = reqwest::Client::new();
             let mut iter = 1;
             loop {
-                match cl.get("http://localhost:2025/").send().await {
-                    Ok(resp) => match resp.status().is_success() {
-                        true => {
-                            info!("Health check passed.");
-                            break;
-                        }
-                        false => (),
-                    },
-                    Err(_) => (),
+                if let Ok(resp) = cl.get("http://localhost:2025/").send().await {
+                    if resp.status().is_success() {
+                        info!("Health check passed.");
+                        break;
+                    }
                 };
                 match iter {
                     1..=10 => sleep(Duration::from_secs(1)).await,
@@ -1,17 +1,21 @@
-use crate::database;
+use crate::{database, quotes::card::CardCache};
 use serde::Serialize;
 use sqlx::PgPool;
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 use tokio::sync::{broadcast, RwLock};
+use utoipa::ToSchema;
 
 #[derive(Debug, Clone)]
 pub struct SharedState {
     pub dbpool: PgPool,
     pub sysinfo: Arc<RwLock<SystemInfo>>,
     pub syscast: broadcast::Sender<SystemInfo>,
+    /// "METHOD /path" => hits on unversioned compatibility aliases
+    pub deprecated_hits: Arc<RwLock<HashMap<String, u64>>>,
+    pub card_cache: Arc<RwLock<CardCache>>,
 }
 
-#[derive(Debug, Clone, Default, Serialize)]
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
 pub struct SystemInfo {
     pub cpu_used: f32,
     pub mem_used: u64,
@@ -21,10 +25,16 @@ pub struct SystemInfo {
 }
 
 pub async fn init() -> SharedState {
+    with_pool(database::establish_connections().await)
+}
+
+pub fn with_pool(dbpool: PgPool) -> SharedState {
     let (tx, _) = broadcast::channel::<SystemInfo>(1);
     SharedState {
-        dbpool: database::establish_connections().await,
+        dbpool,
         sysinfo: Arc::new(RwLock::new(SystemInfo::default())),
         syscast: tx,
+        deprecated_hits: Arc::new(RwLock::new(HashMap::new())),
+        card_cache: Arc::new(RwLock::new(CardCache::default())),
     }
 }
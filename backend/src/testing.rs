@@ -0,0 +1,151 @@
+//! Fixtures for tests that run against a database; `#[sqlx::test]` gives each test a fresh one.
+
+use std::sync::LazyLock;
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{header::AUTHORIZATION, Request, StatusCode},
+    Router,
+};
+use chrono::NaiveDateTime;
+use serde_json::Value;
+use sqlx::PgPool;
+use tower::ServiceExt;
+use uuid::Uuid;
+
+use crate::{
+    quotes::{authors::Author, moderation::QuoteStatus, Quote, QuoteLine},
+    router, state,
+    user::{
+        attributes::{default_attributes_u64, UserAttribute},
+        auth::{password::hash_password, session::Session},
+        User,
+    },
+};
+
+pub const PASSWORD: &str = "hunter22";
+static PASSWORD_HASH: LazyLock<String> = LazyLock::new(|| hash_password(PASSWORD).unwrap());
+
+/// A user with the default attributes plus `permissions`.
+pub async fn user(
+    handle: &str,
+    clearance: u8,
+    permissions: &[UserAttribute],
+    pool: &PgPool,
+) -> User {
+    let attributes = permissions
+        .iter()
+        .fold(default_attributes_u64(), |acc, p| acc | p.get_bit());
+    let id = Uuid::now_v7();
+    sqlx::query!(
+        r#"
+        INSERT INTO users (id, handle, clearance, attributes, joindate, password_hash)
+        VALUES ($1, $2, $3, $4, NOW(), $5)
+        "#,
+        id,
+        handle,
+        clearance as i16,
+        attributes as i64,
+        *PASSWORD_HASH
+    )
+    .execute(pool)
+    .await
+    .unwrap();
+    User::get_by_id(&id, pool).await.unwrap().unwrap()
+}
+
+/// `Authorization` header value for a fresh session of the user.
+pub async fn bearer(user: &User, pool: &PgPool) -> String {
+    let (_, token) = Session::create(&user.id, pool).await.unwrap();
+    format!("Bearer {token}")
+}
+
+pub async fn author(codename: &str, pool: &PgPool) -> Author {
+    let author = Author {
+        id: Uuid::now_v7(),
+        fullname: codename.to_uppercase(),
+        codename: codename.to_string(),
+        bio: None,
+        avatar: None,
+        aliases: vec![],
+        user_id: None,
+    };
+    Author::create(author, pool).await.unwrap()
+}
+
+pub async fn link(author: &Author, user: &User, pool: &PgPool) {
+    sqlx::query!(
+        "UPDATE authors SET user_id = $1 WHERE id = $2",
+        user.id,
+        author.id
+    )
+    .execute(pool)
+    .await
+    .unwrap();
+}
+
+/// A quote of the given clearance, one public line per `(author, content)`.
+pub fn quote(lines: &[(&Author, &str)], clearance: u8) -> Quote {
+    Quote {
+        id: Uuid::now_v7(),
+        clearance,
+        clearance_name: None,
+        groups: vec![],
+        tags: vec![],
+        status: QuoteStatus::Published,
+        submitted_by: None,
+        review_note: None,
+        deleted_at: None,
+        deleted_by: None,
+        reactions: Default::default(),
+        context: None,
+        source: None,
+        timestamp: NaiveDateTime::default(),
+        authors: lines.iter().map(|(a, _)| (a.id, (*a).clone())).collect(),
+        lines: lines
+            .iter()
+            .map(|(a, content)| QuoteLine {
+                id: Uuid::now_v7(),
+                content: content.to_string(),
+                author_id: Some(a.id),
+                clearance: 0,
+                redacted: false,
+            })
+            .collect(),
+    }
+}
+
+pub async fn save(quote: Quote, submitter: &User, pool: &PgPool) -> Quote {
+    Quote::create(quote, submitter, pool).await.unwrap()
+}
+
+/// Sends the request, authenticated as `auth` if given; returns the status and JSON body.
+pub async fn send(
+    app: &Router,
+    method: &str,
+    uri: &str,
+    auth: Option<&str>,
+    body: Option<Value>,
+) -> (StatusCode, Value) {
+    let mut req = Request::builder().method(method).uri(uri);
+    if let Some(auth) = auth {
+        req = req.header(AUTHORIZATION, auth);
+    }
+    let req = match body {
+        Some(body) => req
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string())),
+        None => req.body(Body::empty()),
+    };
+    let res = app.clone().oneshot(req.unwrap()).await.unwrap();
+    let status = res.status();
+    let bytes = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    (
+        status,
+        serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+    )
+}
+
+pub fn app(pool: &PgPool) -> Router {
+    router::init(state::with_pool(pool.clone()))
+}
@@ -1,8 +1,9 @@
 use serde::Serialize;
-use strum::VariantArray;
+use strum::{IntoStaticStr, VariantArray};
+use utoipa::ToSchema;
 use UserAttribute as A;
 
-#[derive(VariantArray, Serialize)]
+#[derive(Clone, Copy, VariantArray, IntoStaticStr, Serialize, ToSchema)]
 pub enum UserAttribute {
     TheEverythingPermission,
     UsersInspectPermission,
@@ -14,13 +15,18 @@ pub enum UserAttribute {
     UsersManageAttributesPermission,
     UsersManualCreatePermission,
     UsersDeletePermission,
+    UsersManageGroupsPermission,
     LogsInspectPermission,
     AuthorsInspectPermission,
     AuthorsCreatePermission,
     AuthorsModifyPermission,
+    AuthorsManageLinksPermission,
     AuthorsDeletePermission,
     QuotesCreatePermission,
     QuotesDeletePermission,
+    QuotesReviewPermission,
+    QuotesPurgePermission,
+    QuotesModerateCommentsPermission,
 
     DisplayCoquetteAvatar,
     DisplayProfileCardFlower,
@@ -57,18 +63,23 @@ impl UserAttribute {
             A::UsersManageAttributesPermission => 7,
             A::UsersManualCreatePermission => 8,
             A::UsersDeletePermission => 9,
-            // 0b1 << 10-15
+            A::UsersManageGroupsPermission => 10,
+            // 0b1 << 11-15
             A::LogsInspectPermission => 16,
             // 0b1 << 17-19
             A::AuthorsInspectPermission => 20,
             A::AuthorsCreatePermission => 21,
             A::AuthorsModifyPermission => 22,
-            // 0b1 << 23-24
+            A::AuthorsManageLinksPermission => 23,
+            // 0b1 << 24
             A::AuthorsDeletePermission => 25,
             // 0b1 << 26-31
             A::QuotesCreatePermission => 32,
             A::QuotesDeletePermission => 33,
-            // 0b1 << 34-60
+            A::QuotesReviewPermission => 34,
+            A::QuotesPurgePermission => 35,
+            A::QuotesModerateCommentsPermission => 36,
+            // 0b1 << 37-60
             A::DisplayCoquetteAvatar => 61,
             A::DisplayProfileCardFlower => 62,
             // 0b1 << 63
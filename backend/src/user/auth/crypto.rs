@@ -37,7 +37,7 @@ pub fn generate_token() -> String {
     let mut bytes = [0u8; TOKEN_LENGTH];
     rng.fill(&mut bytes);
 
-    BASE64_URL_SAFE_NO_PAD.encode(&bytes)
+    BASE64_URL_SAFE_NO_PAD.encode(bytes)
 }
 
 pub fn hash_token(token: &str) -> String {
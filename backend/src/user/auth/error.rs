@@ -9,6 +9,27 @@ pub enum AuthError {
     #[error("No credentials provided")]
     NoCredentials,
 
+    #[error("Missing required permission: {0}")]
+    MissingPermission(&'static str),
+    #[error("Insufficient clearance")]
+    InsufficientClearance,
+    #[error("Only the infrastructure administrator may do this")]
+    InfradminOnly,
+    #[error("Only the user linked to this author may do this")]
+    NotLinkedUser,
+    #[error("Only members of the group may do this")]
+    NotGroupMember,
+    #[error("Only the user who submitted this quote may do this")]
+    NotSubmitter,
+    #[error("Only the user who wrote this comment may do this")]
+    NotCommenter,
+    #[error("Only a comment moderator may delete a comment that has replies")]
+    CommentHasReplies,
+    #[error("Only the owner of this collection may do this")]
+    NotCollectionOwner,
+    #[error("Only whoever created this share link, or submitted the quote, may do this")]
+    NotShareLinkManager,
+
     #[error("Non-ASCII characters found in AUTHORIZATION header")]
     NonAsciiHeaderCharacters,
     #[error("Base64 Basic Auth header is missing login/password colon separator")]
@@ -19,6 +40,15 @@ pub enum AuthError {
     UnsupportedHeaderAuthScheme,
     #[error("Can only clear sessions via Bearer token requests")]
     ClearSessionBearerOnly,
+    #[error("Please provide one token at a time")]
+    ClearSessionTooManyTokens,
+    #[error("Cannot delete the infrastructure administrator")]
+    CannotDeleteInfradmin,
+
+    #[error("Cookie-authenticated request is missing an Origin or Referer header")]
+    CsrfMissingOrigin,
+    #[error("Cookie-authenticated request came from a disallowed origin")]
+    CsrfOriginMismatch,
 }
 
 impl AuthError {
@@ -27,11 +57,51 @@ impl AuthError {
         use StatusCode as C;
         match self {
             E::InvalidCredentials | E::NoCredentials | E::SessionExpired => C::UNAUTHORIZED,
+            E::MissingPermission(_)
+            | E::InsufficientClearance
+            | E::InfradminOnly
+            | E::NotLinkedUser
+            | E::NotGroupMember
+            | E::NotSubmitter
+            | E::NotCommenter
+            | E::CommentHasReplies
+            | E::NotCollectionOwner
+            | E::NotShareLinkManager
+            | E::CannotDeleteInfradmin => C::FORBIDDEN,
             E::NonAsciiHeaderCharacters
             | E::NoBasicAuthColonSplit
             | E::BadHeaderAuthSchemeData
             | E::UnsupportedHeaderAuthScheme
-            | E::ClearSessionBearerOnly => C::BAD_REQUEST,
+            | E::ClearSessionBearerOnly
+            | E::ClearSessionTooManyTokens => C::BAD_REQUEST,
+            E::CsrfMissingOrigin | E::CsrfOriginMismatch => C::FORBIDDEN,
+        }
+    }
+    pub fn code(&self) -> &'static str {
+        use AuthError as E;
+        match self {
+            E::SessionExpired => "session_expired",
+            E::InvalidCredentials => "invalid_credentials",
+            E::NoCredentials => "no_credentials",
+            E::MissingPermission(_) => "missing_permission",
+            E::InsufficientClearance => "insufficient_clearance",
+            E::InfradminOnly => "infradmin_only",
+            E::NotLinkedUser => "not_linked_user",
+            E::NotGroupMember => "not_group_member",
+            E::NotSubmitter => "not_submitter",
+            E::NotCommenter => "not_commenter",
+            E::CommentHasReplies => "comment_has_replies",
+            E::NotCollectionOwner => "not_collection_owner",
+            E::NotShareLinkManager => "not_share_link_manager",
+            E::NonAsciiHeaderCharacters => "non_ascii_header_characters",
+            E::NoBasicAuthColonSplit => "no_basic_auth_colon_split",
+            E::BadHeaderAuthSchemeData => "bad_header_auth_scheme_data",
+            E::UnsupportedHeaderAuthScheme => "unsupported_header_auth_scheme",
+            E::ClearSessionBearerOnly => "clear_session_bearer_only",
+            E::ClearSessionTooManyTokens => "clear_session_too_many_tokens",
+            E::CannotDeleteInfradmin => "cannot_delete_infradmin",
+            E::CsrfMissingOrigin => "csrf_missing_origin",
+            E::CsrfOriginMismatch => "csrf_origin_mismatch",
         }
     }
 }
@@ -0,0 +1,50 @@
+use axum::{
+    extract::{FromRequestParts, OptionalFromRequestParts},
+    http::{header::AUTHORIZATION, request::Parts},
+};
+use tower_cookies::Cookies;
+
+use crate::{omnierror::OmniError, state::SharedState, user::User};
+
+use super::SESSION_COOKIE_NAME;
+
+impl FromRequestParts<SharedState> for User {
+    type Rejection = OmniError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &SharedState,
+    ) -> Result<Self, Self::Rejection> {
+        let cookies = extract_cookies(parts, state).await?;
+        User::authenticate(&parts.headers, cookies, &state.dbpool).await
+    }
+}
+
+/// `Option<User>` resolves to `None` only when no credentials were sent at all;
+/// invalid credentials are still rejected.
+impl OptionalFromRequestParts<SharedState> for User {
+    type Rejection = OmniError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &SharedState,
+    ) -> Result<Option<Self>, Self::Rejection> {
+        let cookies = extract_cookies(parts, state).await?;
+        let has_cookie = cookies
+            .get(SESSION_COOKIE_NAME)
+            .is_some_and(|c| !c.value().is_empty());
+        if !has_cookie && !parts.headers.contains_key(AUTHORIZATION) {
+            return Ok(None);
+        }
+        Ok(Some(
+            User::authenticate(&parts.headers, cookies, &state.dbpool).await?,
+        ))
+    }
+}
+
+async fn extract_cookies(parts: &mut Parts, state: &SharedState) -> Result<Cookies, OmniError> {
+    match Cookies::from_request_parts(parts, state).await {
+        Ok(cookies) => Ok(cookies),
+        Err((_, msg)) => Err(OmniError::CookieExtractionError(msg)),
+    }
+}
@@ -0,0 +1,173 @@
+use std::{marker::PhantomData, ops::Deref};
+
+use axum::{extract::FromRequestParts, http::request::Parts};
+
+use crate::{
+    omnierror::OmniError,
+    state::SharedState,
+    user::{attributes::UserAttribute, User},
+};
+
+use super::error::AuthError;
+
+/// A check an authenticated user has to pass before a handler runs.
+pub trait Guard: Send + Sync + 'static {
+    fn permits(user: &User) -> bool;
+    fn denial() -> AuthError;
+}
+
+/// Extracts an authenticated `User` and rejects the request unless the guard permits them.
+/// Usage: `u: Require<QuotesCreatePermission>`; derefs to the `User`.
+pub struct Require<G: Guard>(pub User, PhantomData<G>);
+
+impl<G: Guard> Deref for Require<G> {
+    type Target = User;
+    fn deref(&self) -> &User {
+        &self.0
+    }
+}
+
+impl<G: Guard> FromRequestParts<SharedState> for Require<G> {
+    type Rejection = OmniError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &SharedState,
+    ) -> Result<Self, Self::Rejection> {
+        let user = User::from_request_parts(parts, state).await?;
+        match G::permits(&user) {
+            true => Ok(Require(user, PhantomData)),
+            false => Err(G::denial())?,
+        }
+    }
+}
+
+impl User {
+    pub fn require_permission(&self, attr: UserAttribute) -> Result<(), AuthError> {
+        match self.has_permission(attr) {
+            true => Ok(()),
+            false => Err(AuthError::MissingPermission(attr.into())),
+        }
+    }
+}
+
+pub struct Infradmin;
+impl Guard for Infradmin {
+    fn permits(user: &User) -> bool {
+        user.is_infradmin()
+    }
+    fn denial() -> AuthError {
+        AuthError::InfradminOnly
+    }
+}
+
+macro_rules! permission_guards {
+    ($($perm:ident),* $(,)?) => {
+        $(
+            pub struct $perm;
+            impl Guard for $perm {
+                fn permits(user: &User) -> bool {
+                    user.has_permission(UserAttribute::$perm)
+                }
+                fn denial() -> AuthError {
+                    AuthError::MissingPermission(UserAttribute::$perm.into())
+                }
+            }
+        )*
+    };
+}
+
+permission_guards!(
+    TheEverythingPermission,
+    UsersInspectPermission,
+    UsersManualCreatePermission,
+    UsersDeletePermission,
+    UsersManageClearancesPermission,
+    UsersManageGroupsPermission,
+    LogsInspectPermission,
+    AuthorsInspectPermission,
+    AuthorsCreatePermission,
+    AuthorsModifyPermission,
+    AuthorsManageLinksPermission,
+    AuthorsDeletePermission,
+    QuotesCreatePermission,
+    QuotesDeletePermission,
+    QuotesReviewPermission,
+    QuotesPurgePermission,
+);
+
+#[cfg(test)]
+mod tests {
+    use axum::{http::StatusCode, routing::get, Json, Router};
+    use base64::{prelude::BASE64_STANDARD, Engine};
+    use sqlx::PgPool;
+    use tower_cookies::CookieManagerLayer;
+
+    use crate::{state, testing};
+
+    use super::*;
+    use UserAttribute as UA;
+
+    fn app(pool: &PgPool) -> Router {
+        Router::new()
+            .route("/me", get(|u: User| async move { Json(u.handle) }))
+            .route(
+                "/create",
+                get(|u: Require<QuotesCreatePermission>| async move { Json(u.handle.clone()) }),
+            )
+            .route("/infra", get(|_: Require<Infradmin>| async {}))
+            .with_state(state::with_pool(pool.clone()))
+            .layer(CookieManagerLayer::new())
+    }
+
+    #[sqlx::test]
+    async fn credentials_are_required_and_checked(pool: PgPool) {
+        let app = app(&pool);
+        let alice = testing::user("alice", 1, &[], &pool).await;
+        let basic = |password: &str| {
+            format!(
+                "Basic {}",
+                BASE64_STANDARD.encode(format!("alice:{password}"))
+            )
+        };
+
+        let (status, body) = testing::send(&app, "GET", "/me", None, None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["code"], "no_credentials");
+        let (status, body) = testing::send(&app, "GET", "/me", Some("Bearer nope"), None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["code"], "session_expired");
+        let (status, body) = testing::send(&app, "GET", "/me", Some(&basic("wrong")), None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["code"], "invalid_credentials");
+
+        let bearer = testing::bearer(&alice, &pool).await;
+        let (status, body) = testing::send(&app, "GET", "/me", Some(&bearer), None).await;
+        assert_eq!((status, body.as_str()), (StatusCode::OK, Some("alice")));
+        let basic = basic(testing::PASSWORD);
+        let (status, body) = testing::send(&app, "GET", "/me", Some(&basic), None).await;
+        assert_eq!((status, body.as_str()), (StatusCode::OK, Some("alice")));
+    }
+
+    #[sqlx::test]
+    async fn guards_check_permissions(pool: PgPool) {
+        let app = app(&pool);
+        let plain = testing::user("plain", 1, &[], &pool).await;
+        let creator = testing::user("creator", 1, &[UA::QuotesCreatePermission], &pool).await;
+        let all = testing::user("all", 1, &[UA::TheEverythingPermission], &pool).await;
+        let plain = testing::bearer(&plain, &pool).await;
+        let creator = testing::bearer(&creator, &pool).await;
+        let everything = testing::bearer(&all, &pool).await;
+
+        let (status, body) = testing::send(&app, "GET", "/create", Some(&plain), None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["code"], "missing_permission");
+        for auth in [&creator, &everything] {
+            let (status, _) = testing::send(&app, "GET", "/create", Some(auth), None).await;
+            assert_eq!(status, StatusCode::OK);
+        }
+        let (status, body) = testing::send(&app, "GET", "/infra", Some(&everything), None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["code"], "infradmin_only");
+    }
+}
@@ -4,6 +4,8 @@ use tower_cookies::cookie::time::Duration as CookieDuration;
 pub mod cookie;
 pub mod crypto;
 pub mod error;
+pub mod extractor;
+pub mod guard;
 pub mod password;
 pub mod session;
 pub mod userimpl;
@@ -1,6 +1,7 @@
 use chrono::{DateTime, Utc};
 use serde::Serialize;
 use sqlx::PgPool;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 use crate::{
@@ -13,7 +14,7 @@ use crate::{
 
 use super::error::AuthError;
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct Session {
     pub id: Uuid,
     pub user_id: Uuid,
@@ -27,23 +28,6 @@ impl Session {
         self.expiry <= Utc::now()
     }
 
-    pub async fn get_by_id(id: &Uuid, pool: &PgPool) -> Result<Session, OmniError> {
-        match sqlx::query_as!(
-            Session,
-            "SELECT id, user_id, issued, expiry, last_access FROM sessions WHERE id = $1",
-            id
-        )
-        .fetch_optional(pool)
-        .await
-        {
-            Ok(s) => match s {
-                Some(s) => Ok(s),
-                // infosec: don't leak session info
-                None => Err(AuthError::SessionExpired)?,
-            },
-            Err(e) => return Err(e)?,
-        }
-    }
     pub async fn get_by_token(token: &str, pool: &PgPool) -> Result<Session, OmniError> {
         let hashed_token = hash_token(token);
         match sqlx::query_as!(
@@ -59,7 +43,7 @@ impl Session {
                 // infosec: don't leak session info
                 None => Err(AuthError::SessionExpired)?,
             },
-            Err(e) => return Err(e)?,
+            Err(e) => Err(e)?,
         }
     }
     pub async fn get_all(pool: &PgPool) -> Result<Vec<Session>, OmniError> {
@@ -71,7 +55,7 @@ impl Session {
         .await
         {
             Ok(s) => Ok(s),
-            Err(e) => return Err(e)?,
+            Err(e) => Err(e)?,
         }
     }
     /// Ok(..) returns both the Session and the unhashed token as a String in a tuple
@@ -93,7 +77,7 @@ impl Session {
         .await
         {
             Ok(s) => Ok((s, token)),
-            Err(e) => return Err(e)?,
+            Err(e) => Err(e)?,
         }
     }
     pub async fn destroy(self, pool: &PgPool) -> Result<(), OmniError> {
@@ -102,7 +86,7 @@ impl Session {
             .await
         {
             Ok(_) => Ok(()),
-            Err(e) => return Err(e)?,
+            Err(e) => Err(e)?,
         }
     }
 
@@ -124,7 +108,7 @@ impl Session {
                 last_access,
                 ..self
             }),
-            Err(e) => return Err(e)?,
+            Err(e) => Err(e)?,
         }
     }
 }
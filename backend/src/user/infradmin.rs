@@ -15,6 +15,7 @@ impl User {
             id: Uuid::max(),
             handle: "admin".to_string(),
             clearance: 255,
+            clearance_name: None,
             attributes: UserAttribute::TheEverythingPermission.get_bit(),
             joindate: chrono::Utc::now(),
         }
@@ -40,8 +41,7 @@ pub async fn guarantee_infradmin_exists(pool: &PgPool) {
                     info!("Handle: admin; Password: {passw}");
                     info!("Please change these credentials as soon as possible.");
                 }
-                Err(e) => {
-                    let err = OmniError::from(e);
+                Err(err) => {
                     error!("Could not create infradmin!");
                     error!("{err}");
                     panic!();
@@ -1,28 +1,29 @@
 use attributes::{default_attributes_u64, UserAttribute};
 use chrono::{DateTime, Utc};
 use serde::Serialize;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 pub mod attributes;
 pub mod auth;
 pub mod infradmin;
+pub mod notifications;
 pub mod patch;
 pub mod queries;
 pub mod validity;
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, ToSchema)]
 pub struct User {
     pub id: Uuid,
     pub handle: String,
     pub clearance: u8,
+    /// Name of the clearance level, if defined
+    pub clearance_name: Option<String>,
     attributes: u64,
     pub joindate: DateTime<Utc>,
 }
 
 impl User {
-    pub fn has_attribute(&self, attr: UserAttribute) -> bool {
-        self.attributes & attr.get_bit() != 0
-    }
     pub fn has_permission(&self, attr: UserAttribute) -> bool {
         (self.attributes & attr.get_bit() != 0)
             || (self.attributes & UserAttribute::TheEverythingPermission.get_bit() != 0)
@@ -34,6 +35,7 @@ impl User {
             id: Uuid::now_v7(),
             handle,
             clearance: 1,
+            clearance_name: None,
             attributes: default_attributes_u64(),
             joindate: Utc::now(),
         }
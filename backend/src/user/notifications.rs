@@ -0,0 +1,146 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::{PgExecutor, PgPool, Postgres, Transaction};
+use strum::AsRefStr;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    omnierror::OmniError,
+    pagination::{Page, PageQuery},
+};
+
+#[derive(Serialize, ToSchema)]
+pub struct Notification {
+    pub id: Uuid,
+    pub kind: String,
+    pub quote_id: Option<Uuid>,
+    pub details: Value,
+    pub created: DateTime<Utc>,
+    pub read_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum NotificationKind {
+    /// A quote with a line by the user's author was added
+    Quoted,
+    ClaimApproved,
+    ClaimRejected,
+    FlagResolved,
+    /// A reviewer acted on a quote the user submitted
+    SubmissionApproved,
+    SubmissionRejected,
+    ChangesRequested,
+    /// Someone mentioned the user in a comment
+    Mentioned,
+}
+
+impl Notification {
+    /// Takes any executor so that notifications are only sent if the change they describe commits.
+    pub async fn send<'e, E: PgExecutor<'e>>(
+        user_id: &Uuid,
+        kind: NotificationKind,
+        quote_id: Option<Uuid>,
+        details: Value,
+        executor: E,
+    ) -> Result<(), OmniError> {
+        match sqlx::query!(
+            "INSERT INTO notifications (id, user_id, kind, quote_id, details) VALUES ($1, $2, $3, $4, $5)",
+            Uuid::now_v7(),
+            user_id,
+            kind.as_ref(),
+            quote_id,
+            details
+        )
+        .execute(executor)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e)?,
+        }
+    }
+    /// Notifies every user linked to an author with a line in the quote.
+    pub async fn send_quoted(
+        quote_id: &Uuid,
+        tr: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), OmniError> {
+        let linked = sqlx::query_scalar!(
+            r#"
+            SELECT DISTINCT authors.user_id AS "user_id!" FROM lines
+            INNER JOIN authors ON authors.id = lines.author_id
+            WHERE lines.quote_id = $1 AND authors.user_id IS NOT NULL
+            "#,
+            quote_id
+        )
+        .fetch_all(&mut **tr)
+        .await?;
+        for user_id in linked {
+            Notification::send(
+                &user_id,
+                NotificationKind::Quoted,
+                Some(*quote_id),
+                Value::Null,
+                &mut **tr,
+            )
+            .await?;
+        }
+        Ok(())
+    }
+    pub async fn get_page(
+        user_id: &Uuid,
+        unread_only: bool,
+        page: &PageQuery,
+        pool: &PgPool,
+    ) -> Result<Page<Notification>, OmniError> {
+        let total = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) AS "total!" FROM notifications
+            WHERE user_id = $1 AND (NOT $2 OR read_at IS NULL)
+            "#,
+            user_id,
+            unread_only
+        )
+        .fetch_one(pool)
+        .await?;
+        let items = sqlx::query_as!(
+            Notification,
+            r#"
+            SELECT id, kind, quote_id, details, created, read_at
+            FROM notifications
+            WHERE user_id = $1 AND (NOT $2 OR read_at IS NULL)
+            ORDER BY created DESC LIMIT $3 OFFSET $4
+            "#,
+            user_id,
+            unread_only,
+            page.limit(),
+            page.offset()
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(Page::new(items, page, total))
+    }
+    /// Marks one notification of the user as read, or all of them when `id` is `None`.
+    /// Returns how many notifications were affected.
+    pub async fn mark_read(
+        user_id: &Uuid,
+        id: Option<Uuid>,
+        pool: &PgPool,
+    ) -> Result<u64, OmniError> {
+        match sqlx::query!(
+            r#"
+            UPDATE notifications SET read_at = NOW()
+            WHERE user_id = $1 AND read_at IS NULL AND ($2::uuid IS NULL OR id = $2)
+            "#,
+            user_id,
+            id
+        )
+        .execute(pool)
+        .await
+        {
+            Ok(res) => Ok(res.rows_affected()),
+            Err(e) => Err(e)?,
+        }
+    }
+}
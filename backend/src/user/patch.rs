@@ -1,11 +1,12 @@
 use serde::Deserialize;
 use sqlx::PgPool;
+use utoipa::ToSchema;
 
-use crate::omnierror::OmniError;
+use crate::{clearance::ClearanceLevel, omnierror::OmniError};
 
 use super::{auth::password::hash_password, User};
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 #[serde(deny_unknown_fields)]
 pub struct UserPatch {
     pub handle: Option<String>,
@@ -23,6 +24,8 @@ impl User {
             user.handle = handle;
         }
         if let Some(clearance) = patch.clearance {
+            ClearanceLevel::ensure_defined(&[clearance], pool).await?;
+            user.clearance_name = ClearanceLevel::name_of(clearance, pool).await?;
             user.clearance = clearance;
         }
         // if let Some(attributes) = patch.attributes {
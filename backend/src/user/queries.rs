@@ -8,7 +8,12 @@ use super::{auth::password::hash_password, User};
 impl User {
     pub async fn get_by_id(id: &Uuid, pool: &PgPool) -> Result<Option<User>, OmniError> {
         match sqlx::query!(
-            "SELECT id, handle, clearance, attributes, joindate FROM users WHERE id = $1",
+            r#"
+            SELECT
+                id, handle, clearance, attributes, joindate,
+                (SELECT name FROM clearance_levels WHERE level = users.clearance) AS clearance_name
+            FROM users WHERE id = $1
+            "#,
             id
         )
         .fetch_optional(pool)
@@ -18,6 +23,7 @@ impl User {
                 id: res.id,
                 handle: res.handle,
                 clearance: res.clearance as u8,
+                clearance_name: res.clearance_name,
                 attributes: res.attributes as u64,
                 joindate: res.joindate,
             })),
@@ -27,7 +33,12 @@ impl User {
     }
     pub async fn get_by_handle(handle: &str, pool: &PgPool) -> Result<Option<User>, OmniError> {
         match sqlx::query!(
-            "SELECT id, handle, clearance, attributes, joindate FROM users WHERE handle = $1",
+            r#"
+            SELECT
+                id, handle, clearance, attributes, joindate,
+                (SELECT name FROM clearance_levels WHERE level = users.clearance) AS clearance_name
+            FROM users WHERE handle = $1
+            "#,
             handle
         )
         .fetch_optional(pool)
@@ -37,6 +48,7 @@ impl User {
                 id: res.id,
                 handle: res.handle,
                 clearance: res.clearance as u8,
+                clearance_name: res.clearance_name,
                 attributes: res.attributes as u64,
                 joindate: res.joindate,
             })),
@@ -45,9 +57,16 @@ impl User {
         }
     }
     pub async fn get_all(pool: &PgPool) -> Result<Vec<User>, OmniError> {
-        match sqlx::query!("SELECT id, handle, clearance, attributes, joindate FROM users")
-            .fetch_all(pool)
-            .await
+        match sqlx::query!(
+            r#"
+            SELECT
+                id, handle, clearance, attributes, joindate,
+                (SELECT name FROM clearance_levels WHERE level = users.clearance) AS clearance_name
+            FROM users
+            "#
+        )
+        .fetch_all(pool)
+        .await
         {
             Ok(res) => Ok(res
                 .into_iter()
@@ -55,6 +74,36 @@ impl User {
                     id: row.id,
                     handle: row.handle,
                     clearance: row.clearance as u8,
+                    clearance_name: row.clearance_name,
+                    attributes: row.attributes as u64,
+                    joindate: row.joindate,
+                })
+                .collect()),
+            Err(err) => Err(err)?,
+        }
+    }
+    pub async fn get_in_group(group_id: &Uuid, pool: &PgPool) -> Result<Vec<User>, OmniError> {
+        match sqlx::query!(
+            r#"
+            SELECT
+                id, handle, clearance, attributes, joindate,
+                (SELECT name FROM clearance_levels WHERE level = users.clearance) AS clearance_name
+            FROM users
+            WHERE id IN (SELECT user_id FROM group_members WHERE group_id = $1)
+            ORDER BY handle
+            "#,
+            group_id
+        )
+        .fetch_all(pool)
+        .await
+        {
+            Ok(res) => Ok(res
+                .into_iter()
+                .map(|row| User {
+                    id: row.id,
+                    handle: row.handle,
+                    clearance: row.clearance as u8,
+                    clearance_name: row.clearance_name,
                     attributes: row.attributes as u64,
                     joindate: row.joindate,
                 })
@@ -81,12 +130,145 @@ impl User {
         }
     }
     pub async fn destroy(self, pool: &PgPool) -> Result<(), OmniError> {
-        match sqlx::query!("DELETE FROM users WHERE id = $1", self.id)
-            .execute(pool)
-            .await
-        {
-            Ok(_) => Ok(()),
-            Err(err) => Err(err)?,
+        let mut tr = pool.begin().await?;
+        let destroyed = async {
+            sqlx::query!("DELETE FROM group_members WHERE user_id = $1", self.id)
+                .execute(&mut *tr)
+                .await?;
+            sqlx::query!(
+                r#"
+                DELETE FROM quote_comments WHERE user_id = $1 OR parent_id IN (
+                    SELECT id FROM quote_comments WHERE user_id = $1 AND parent_id IS NULL
+                )
+                "#,
+                self.id
+            )
+            .execute(&mut *tr)
+            .await?;
+            sqlx::query!(
+                "UPDATE quote_comments SET mentions = array_remove(mentions, $1) WHERE $1 = ANY(mentions)",
+                self.id
+            )
+            .execute(&mut *tr)
+            .await?;
+            for query in [
+                sqlx::query!(
+                    r#"
+                    DELETE FROM collection_items WHERE collection_id IN (
+                        SELECT id FROM collections WHERE owner_id = $1
+                    )
+                    "#,
+                    self.id
+                ),
+                sqlx::query!(
+                    r#"
+                    DELETE FROM collection_shares WHERE user_id = $1 OR collection_id IN (
+                        SELECT id FROM collections WHERE owner_id = $1
+                    )
+                    "#,
+                    self.id
+                ),
+                sqlx::query!("DELETE FROM collections WHERE owner_id = $1", self.id),
+                sqlx::query!("DELETE FROM quote_favourites WHERE user_id = $1", self.id),
+                sqlx::query!(
+                    r#"
+                    DELETE FROM quote_share_link_views WHERE link_id IN (
+                        SELECT id FROM quote_share_links WHERE created_by = $1
+                    )
+                    "#,
+                    self.id
+                ),
+                sqlx::query!("DELETE FROM quote_share_links WHERE created_by = $1", self.id),
+                sqlx::query!("DELETE FROM feed_tokens WHERE user_id = $1", self.id),
+                sqlx::query!("DELETE FROM notifications WHERE user_id = $1", self.id),
+                sqlx::query!("DELETE FROM author_claims WHERE user_id = $1", self.id),
+                sqlx::query!(
+                    "UPDATE author_claims SET decided_by = NULL WHERE decided_by = $1",
+                    self.id
+                ),
+                sqlx::query!("DELETE FROM quote_flags WHERE user_id = $1", self.id),
+                sqlx::query!(
+                    "UPDATE quote_flags SET resolved_by = NULL WHERE resolved_by = $1",
+                    self.id
+                ),
+                // the author stays, it just no longer belongs to anyone
+                sqlx::query!("UPDATE authors SET user_id = NULL WHERE user_id = $1", self.id),
+                // their submissions stay, just without a submitter
+                sqlx::query!(
+                    "UPDATE quotes SET submitted_by = NULL WHERE submitted_by = $1",
+                    self.id
+                ),
+                sqlx::query!(
+                    "UPDATE quotes SET deleted_by = NULL WHERE deleted_by = $1",
+                    self.id
+                ),
+            ] {
+                query.execute(&mut *tr).await?;
+            }
+            sqlx::query!("DELETE FROM quote_reactions WHERE user_id = $1", self.id)
+                .execute(&mut *tr)
+                .await?;
+            sqlx::query!("DELETE FROM users WHERE id = $1", self.id)
+                .execute(&mut *tr)
+                .await?;
+            Ok::<(), OmniError>(())
+        }
+        .await;
+        match destroyed {
+            Ok(()) => Ok(tr.commit().await?),
+            Err(e) => {
+                tr.rollback().await?;
+                Err(e)
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        quotes::{
+            authors::Author,
+            flags::{FlagKind, FlagResolution, NewQuoteFlag, QuoteFlag},
+            Quote,
+        },
+        testing,
+    };
+
+    #[sqlx::test]
+    async fn destroying_a_user_keeps_their_author_and_quotes(pool: PgPool) {
+        let admin = testing::user("admin", 1, &[], &pool).await;
+        let quoted = testing::user("quoted", 0, &[], &pool).await;
+        let author = testing::author("jk", &pool).await;
+        let claimed = testing::author("jan", &pool).await;
+        claimed.claim(&quoted, &pool).await.unwrap();
+        testing::link(&author, &quoted, &pool).await;
+        let quote = testing::save(testing::quote(&[(&author, "hi")], 0), &quoted, &pool).await;
+        let quote = Quote::get_by_id(&quote.id, &pool).await.unwrap().unwrap();
+        let flag = NewQuoteFlag {
+            kind: FlagKind::Hide,
+            reason: None,
+        };
+        QuoteFlag::create(&quote, flag, &quoted, &pool)
+            .await
+            .unwrap()
+            .resolve(
+                FlagResolution {
+                    accept: false,
+                    clearance: None,
+                },
+                &admin,
+                &pool,
+            )
+            .await
+            .unwrap();
+
+        quoted.destroy(&pool).await.unwrap();
+
+        let author = Author::get_by_id(&author.id, &pool).await.unwrap().unwrap();
+        assert_eq!(author.user_id, None);
+        let quote = Quote::get_by_id(&quote.id, &pool).await.unwrap().unwrap();
+        assert_eq!(quote.submitted_by, None);
+    }
+}
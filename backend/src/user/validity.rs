@@ -20,6 +20,29 @@ pub enum ValidityError {
     PasswordLengthInvalid,
 }
 
+impl ValidityError {
+    pub fn code(&self) -> &'static str {
+        use ValidityError as VA;
+        match self {
+            VA::HandleLengthInvalid => "handle_length_invalid",
+            VA::HandleInvalidChars => "handle_invalid_chars",
+            VA::HandleLeadingTrailingSpecialChars => "handle_leading_trailing_special_chars",
+            VA::HandleConsecutiveSpecialChars => "handle_consecutive_special_chars",
+            VA::PasswordLengthInvalid => "password_length_invalid",
+        }
+    }
+    pub fn field(&self) -> &'static str {
+        use ValidityError as VA;
+        match self {
+            VA::HandleLengthInvalid
+            | VA::HandleInvalidChars
+            | VA::HandleLeadingTrailingSpecialChars
+            | VA::HandleConsecutiveSpecialChars => "handle",
+            VA::PasswordLengthInvalid => "password",
+        }
+    }
+}
+
 impl User {
     pub fn is_valid_handle(handle: &str) -> Result<(), ValidityError> {
         use ValidityError as VA;
@@ -1,14 +1,57 @@
 use std::time::Duration;
 
+use chrono::Utc;
 use sysinfo::System;
 use tokio::{spawn, time::sleep};
-use tracing::info;
+use tracing::{error, info, warn};
 
-use crate::state::{SharedState, SystemInfo};
+use crate::{
+    quotes::Quote,
+    state::{SharedState, SystemInfo},
+};
+
+const TRASH_RETENTION_DAYS_DEFAULT: i64 = 30;
+const TRASH_PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
 
 pub fn init(state: SharedState) {
     info!("Spawning thread workers...");
+    let trash_state = state.clone();
     spawn(async { system_health_diagnostics(state).await });
+    spawn(async { trash_purge(trash_state).await });
+}
+
+/// Read from `TRASH_RETENTION_DAYS`; quotes are purged once they have been trashed this long.
+fn trash_retention_days() -> i64 {
+    match std::env::var("TRASH_RETENTION_DAYS") {
+        Ok(days) => match days.parse::<i64>() {
+            Ok(days) if days >= 0 => days,
+            _ => {
+                warn!(
+                    "TRASH_RETENTION_DAYS is not a valid number of days, defaulting to {}.",
+                    TRASH_RETENTION_DAYS_DEFAULT
+                );
+                TRASH_RETENTION_DAYS_DEFAULT
+            }
+        },
+        Err(_) => TRASH_RETENTION_DAYS_DEFAULT,
+    }
+}
+
+async fn trash_purge(state: SharedState) {
+    let retention = chrono::Duration::days(trash_retention_days());
+    info!(
+        "Trash purge thread worker ready! Keeping trashed quotes for {} days.",
+        retention.num_days()
+    );
+    loop {
+        let cutoff = Utc::now() - retention;
+        match Quote::purge_trashed_before(cutoff, &state.dbpool).await {
+            Ok(0) => (),
+            Ok(count) => info!("Purged {} quotes from the trash.", count),
+            Err(e) => error!("Could not purge the trash: {}", e),
+        }
+        sleep(TRASH_PURGE_INTERVAL).await;
+    }
 }
 
 async fn system_health_diagnostics(state: SharedState) {
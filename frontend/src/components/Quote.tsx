@@ -6,7 +6,8 @@ type QuoteData = {
   lines: {
     id: string;
     content: string;
-    author_id: string;
+    author_id: string | null;
+    redacted?: boolean;
   }[];
   authors: {
     [key: string]: {
@@ -18,11 +19,12 @@ type QuoteData = {
   context?: string;
   timestamp: string;
   clearance: number;
+  clearance_name?: string | null;
   //
   likes?: number;
 };
 
-const ClearanceLevel = (props: { level: number }) => {
+const ClearanceLevel = (props: { level: number; name?: string | null }) => {
   const color = `hsl(${((255 - props.level) / 255) * 100}, 45%, 50%)`;
   return (
     <div className="rounded-full px-3 flex flex-row justify-center items-center gap-2 py-1">
@@ -32,7 +34,7 @@ const ClearanceLevel = (props: { level: number }) => {
           color: color,
         }}
       />
-      {props.level}
+      {props.name ?? props.level}
     </div>
   );
 };
@@ -64,12 +66,18 @@ const Quote = (props: { data: QuoteData }) => {
           <div key={`${line.id}/${index}`} className="mb-2">
             <span className="flex flex-row gap-2 relative">
               <LucideQuote className="scale-[.65] scale-y-[.50] mt-[6px] absolute opacity-[.3]" />
-              <p className="font-fancy text-2xl ml-6">{line.content}</p>
+              <p
+                className={`font-fancy text-2xl ml-6 ${line.redacted ? "opacity-50 italic" : ""}`}
+              >
+                {line.content}
+              </p>
             </span>
             {showAuthor && (
               <p className="text-sm italic ml-3 flex flex-row gap-[6px]">
                 <span>{"—"}</span>
-                {props.data.authors[line.author_id].codename}
+                {line.author_id === null
+                  ? "???"
+                  : props.data.authors[line.author_id].codename}
               </p>
             )}
           </div>
@@ -78,7 +86,10 @@ const Quote = (props: { data: QuoteData }) => {
       <div className="flex flex-row mt-6 text-sm items-center">
         {props.data.timestamp.replace("T", " ")}
         <span className="ml-2">{"⋅"}</span>
-        <ClearanceLevel level={props.data.clearance} />
+        <ClearanceLevel
+          level={props.data.clearance}
+          name={props.data.clearance_name}
+        />
         {/* <span>{"⋅"}</span> */}
         {/* <LikesCounter likesnumber={props.data.likes || 0} /> */}
         {props.data.context && <span className="mr-2">{"⋅"}</span>}
@@ -11,8 +11,8 @@ export const qfetch = async (
 ) => {
   const input =
     process.env.NODE_ENV === "production"
-      ? `${process.env["NEXT_PUBLIC_API_URL"]}${path}`
-      : `http://localhost:2025${path}`;
+      ? `${process.env["NEXT_PUBLIC_API_URL"]}/v1${path}`
+      : `http://localhost:2025/v1${path}`;
   return fetch(input, {
     credentials: "include",
     ...init,